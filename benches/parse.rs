@@ -0,0 +1,42 @@
+//! Compares parsing a request into an owned `HttpRequest` against borrowing
+//! it as an `HttpRequestRef`. Run with `cargo bench --bench parse`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use martian::web::{HttpRequest, HttpRequestRef};
+
+const ITERATIONS: u32 = 100_000;
+
+const RAW_REQUEST: &str = "POST /users/42?fields=name&fields=email&verbose=true HTTP/1.1\r\n\
+    Host: localhost:8080\r\n\
+    User-Agent: curl/8.0.1\r\n\
+    Accept: application/json\r\n\
+    Accept-Encoding: gzip, deflate\r\n\
+    Content-Type: application/json\r\n\
+    Content-Length: 27\r\n\
+    \r\n\
+    {\"name\":\"martian\",\"age\":1}";
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let owned = time(|| {
+        black_box(HttpRequest::parse(black_box(RAW_REQUEST)).unwrap());
+    });
+    let borrowed = time(|| {
+        black_box(HttpRequestRef::parse(black_box(RAW_REQUEST)).unwrap());
+    });
+    let params = time(|| {
+        let request = HttpRequestRef::parse(black_box(RAW_REQUEST)).unwrap();
+        black_box(request.query_pairs().count());
+    });
+    println!("HttpRequest::parse            {:>8?}/iter", owned);
+    println!("HttpRequestRef::parse         {:>8?}/iter", borrowed);
+    println!("HttpRequestRef + query_pairs  {:>8?}/iter", params);
+}
@@ -0,0 +1,165 @@
+//! Attribute macros binding a handler to the route it is written for, used
+//! through the `macros` feature of `martian` rather than on their own.
+//!
+//! An attribute such as `#[get("/users/{id}")]` leaves the function as it is,
+//! adding alongside it a function of the same visibility which binds it, and
+//! [`routes!`] gathers the routes of handlers by name for `Server::route`.
+//!
+//! [`routes!`]: ./macro.routes.html
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Error, ItemFn, LitStr, Path, Token};
+
+/// The uri of a route, and the name given to it if any.
+struct RouteArgs {
+    uri: LitStr,
+    name: Option<LitStr>,
+}
+
+impl Parse for RouteArgs {
+    fn parse(input: ParseStream) -> syn::Result<RouteArgs> {
+        let uri = input.parse()?;
+        let mut name = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key != "name" {
+                return Err(Error::new(key.span(), "expected `name = \"...\"`"));
+            }
+            input.parse::<Token![=]>()?;
+            name = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(RouteArgs { uri, name })
+    }
+}
+
+/// The function binding the route of the handler of the name.
+fn binding_ident(handler: &Ident) -> Ident {
+    format_ident!("__martian_route_{}", handler)
+}
+
+fn route(method: &str, args: TokenStream, item: TokenStream) -> TokenStream {
+    let RouteArgs { uri, name } = parse_macro_input!(args as RouteArgs);
+    let handler = parse_macro_input!(item as ItemFn);
+    if !handler.sig.generics.params.is_empty() {
+        return Error::new_spanned(&handler.sig.generics, "a handler may not be generic")
+            .to_compile_error()
+            .into();
+    }
+    let vis = &handler.vis;
+    let ident = &handler.sig.ident;
+    let binding = binding_ident(ident);
+    let method = Ident::new(method, Span::call_site());
+    let to = match (&handler.sig.asyncness, name) {
+        (Some(_), None) => quote!(to_async(#uri, #ident)),
+        (Some(_), Some(name)) => {
+            return Error::new_spanned(name, "an async handler may not be named")
+                .to_compile_error()
+                .into();
+        }
+        (None, None) => quote!(to(#uri, #ident)),
+        (None, Some(name)) => quote!(to_named(#name, #uri, #ident)),
+    };
+    let expanded = quote! {
+        #handler
+
+        #[doc(hidden)]
+        #vis fn #binding() -> ::martian::server::Binding {
+            ::martian::server::Route::bind(::martian::web::HttpMethod::#method).#to
+        }
+    };
+    expanded.into()
+}
+
+/// Binds the function to `GET` requests of the uri, which is written as it
+/// is for `Binding::to`. The route may be named, as for `Binding::to_named`,
+/// with `#[get("/users/{id}", name = "user_detail")]`.
+#[proc_macro_attribute]
+pub fn get(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Get", args, item)
+}
+
+/// Same as [`get`], for `HEAD` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn head(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Head", args, item)
+}
+
+/// Same as [`get`], for `POST` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn post(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Post", args, item)
+}
+
+/// Same as [`get`], for `PUT` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn put(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Put", args, item)
+}
+
+/// Same as [`get`], for `PATCH` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn patch(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Patch", args, item)
+}
+
+/// Same as [`get`], for `DELETE` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn delete(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Delete", args, item)
+}
+
+/// Same as [`get`], for `OPTIONS` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn options(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Options", args, item)
+}
+
+/// Same as [`get`], for `TRACE` requests.
+///
+/// [`get`]: ./attr.get.html
+#[proc_macro_attribute]
+pub fn trace(args: TokenStream, item: TokenStream) -> TokenStream {
+    route("Trace", args, item)
+}
+
+/// The routes of the handlers, each given by its path as it would be called,
+/// as a `FnOnce() -> Binding` to be handed to `Server::route`.
+#[proc_macro]
+pub fn routes(input: TokenStream) -> TokenStream {
+    let handlers = parse_macro_input!(input with Punctuated::<Path, Token![,]>::parse_terminated);
+    let mut bindings = handlers.into_iter().map(|mut handler| {
+        if let Some(last) = handler.segments.last_mut() {
+            last.ident = binding_ident(&last.ident);
+        }
+        quote!(#handler())
+    });
+    let first = match bindings.next() {
+        Some(first) => first,
+        None => {
+            return Error::new(Span::call_site(), "expected at least one handler")
+                .to_compile_error()
+                .into();
+        }
+    };
+    let expanded = quote! {
+        || #first #(.and(#bindings))*
+    };
+    expanded.into()
+}
@@ -0,0 +1,285 @@
+//! A small blocking client for making requests to other http servers, or to
+//! a `martian` server itself, such as for health checks and webhooks. Every
+//! request is made over its own connection, which is closed once the
+//! response has been read, unless the client keeps a pool of connections to
+//! reuse.
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use crate::web::{
+    chunked, is_chunked, Body, HttpMethod, HttpRequest, HttpResponse, HttpVersion, State,
+};
+
+use self::pool::Pool;
+
+pub use self::pool::PoolOptions;
+
+mod pool;
+
+/// Sends an [`HttpRequest`] to the server named in a url and parses the
+/// [`HttpResponse`] it replies with. Only plain `http://` urls are supported.
+///
+/// # Examples:
+/// ```no_run
+/// use martian::client::HttpClient;
+/// use martian::web::StatusCode;
+/// let client = HttpClient::default();
+/// let response = client.get("http://localhost:8080/hello").unwrap();
+/// assert_eq!(response.status_code, StatusCode::Ok);
+/// ```
+///
+/// [`HttpRequest`]: ../web/struct.HttpRequest.html
+/// [`HttpResponse`]: ../web/struct.HttpResponse.html
+#[derive(Default, Debug, Clone)]
+pub struct HttpClient {
+    /// Shared between clones, so that they reuse the same connections.
+    pool: Option<Arc<Pool>>,
+}
+
+impl HttpClient {
+    /// A client keeping the connections it opens alive, to be reused by any
+    /// later request to the same host and port, rather than opening one for
+    /// each request. Clones of the client share its connections.
+    ///
+    /// A request sent over a connection the server closed while it was idle
+    /// is sent again over a new one, as long as its method is idempotent.
+    /// Any other fails, as the server may have acted on it before closing.
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// use martian::client::{HttpClient, PoolOptions};
+    /// use std::time::Duration;
+    /// let client = HttpClient::with_pool(PoolOptions {
+    ///     idle_timeout: Duration::from_secs(4),
+    ///     max_connections_per_host: Some(16),
+    ///     ..PoolOptions::default()
+    /// });
+    /// for _ in 0..100 {
+    ///     client.get("http://users.internal:8080/health").unwrap();
+    /// }
+    /// ```
+    pub fn with_pool(options: PoolOptions) -> HttpClient {
+        HttpClient {
+            pool: Some(Arc::new(Pool::new(options))),
+        }
+    }
+
+    pub fn get(&self, url: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Get, url, None)
+    }
+
+    pub fn post(&self, url: &str, body: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Post, url, Some(body))
+    }
+
+    pub fn head(&self, url: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Head, url, None)
+    }
+
+    pub fn put(&self, url: &str, body: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Put, url, Some(body))
+    }
+
+    pub fn patch(&self, url: &str, body: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Patch, url, Some(body))
+    }
+
+    pub fn delete(&self, url: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Delete, url, None)
+    }
+
+    pub fn options(&self, url: &str) -> io::Result<HttpResponse> {
+        self.request(HttpMethod::Options, url, None)
+    }
+
+    /// Makes a request with any [`HttpMethod`], `get`, `post` and the like
+    /// are shorthands for this.
+    ///
+    /// # Returns:
+    /// The parsed response, or an `Err` if the url is not a valid `http://`
+    /// url, the connection fails, or the response can not be parsed.
+    ///
+    /// [`HttpMethod`]: ../web/enum.HttpMethod.html
+    pub fn request(
+        &self,
+        http_method: HttpMethod,
+        url: &str,
+        body: Option<&str>,
+    ) -> io::Result<HttpResponse> {
+        let (authority, uri) = split_url(url)?;
+        let mut headers = HashMap::new();
+        headers.insert("Host".into(), authority.into());
+        let request = HttpRequest {
+            http_method,
+            uri: uri.into(),
+            http_version: HttpVersion::Http1_1,
+            headers: Some(headers),
+            body: body.map(|body| body.as_bytes().to_vec()),
+            path_params: HashMap::new(),
+            state: State::default(),
+            extensions: State::default(),
+            connection: None,
+        };
+        self.send(request)
+    }
+
+    /// Sends the request as it is to the server of its `Host`, or of its uri
+    /// when in absolute form. Unless the client is pooled, the server is
+    /// asked to close the connection after the response, as long as the
+    /// request does not already say otherwise.
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// use martian::client::HttpClient;
+    /// use martian::web::HttpRequest;
+    /// let request = HttpRequest::from(
+    ///     "POST /hooks/deploy HTTP/1.1\r\nHost: ci.internal:8080\r\nContent-Length: 2\r\n\r\n{}",
+    /// );
+    /// let response = HttpClient::default().send(request).unwrap();
+    /// ```
+    ///
+    /// # Returns:
+    /// The parsed response, or an `Err` if the request has no host, the
+    /// connection fails, or the response can not be parsed.
+    pub fn send(&self, mut request: HttpRequest) -> io::Result<HttpResponse> {
+        let address = match request.host() {
+            Some(host) if host.ends_with(']') || !host.contains(':') => format!("{}:80", host),
+            Some(host) => host.to_string(),
+            None => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "The request has no host to send it to",
+                ))
+            }
+        };
+        let pool = match &self.pool {
+            Some(pool) => pool,
+            None => {
+                if request.header("Connection").is_none() {
+                    let headers = request.headers.get_or_insert_with(HashMap::new);
+                    headers.insert("Connection".into(), "close".into());
+                }
+                let stream = TcpStream::connect(address)?;
+                return exchange(&stream, &request).map(|(response, _)| response);
+            }
+        };
+        let kept_alive = !request
+            .header("Connection")
+            .is_some_and(|connection| connection.eq_ignore_ascii_case("close"));
+        loop {
+            let (stream, reused) = pool.checkout(&address)?;
+            match exchange(&stream, &request) {
+                Ok((response, reusable)) => {
+                    pool.checkin(&address, Some(stream).filter(|_| reusable && kept_alive));
+                    return Ok(response);
+                }
+                Err(e) => {
+                    pool.checkin(&address, None);
+                    if !(reused && request.http_method.is_idempotent() && is_stale(&e)) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes the request to the connection and reads its response off of it,
+/// along with whether the connection can be used again.
+fn exchange(stream: &TcpStream, request: &HttpRequest) -> io::Result<(HttpResponse, bool)> {
+    let mut writer = stream;
+    writer.write_all(&request.to_bytes())?;
+    read_response(&mut BufReader::new(stream), &request.http_method)
+}
+
+/// Reads a single response off of the reader, leaving it at the end of the
+/// body, along with whether the connection is kept alive after it. A body
+/// framed by neither its length nor chunking is read until the connection
+/// is closed.
+///
+/// # Returns:
+/// An `Err` of kind `ConnectionAborted` if the connection is closed before
+/// any of the response, or `InvalidData` if it can not be parsed.
+fn read_response<R: BufRead>(
+    reader: &mut R,
+    http_method: &HttpMethod,
+) -> io::Result<(HttpResponse, bool)> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if reader.read_until(b'\n', &mut head)? == 0 {
+            return Err(match head.is_empty() {
+                true => io::Error::new(ErrorKind::ConnectionAborted, "No response was sent"),
+                false => ErrorKind::UnexpectedEof.into(),
+            });
+        }
+    }
+    let invalid = |e| io::Error::new(ErrorKind::InvalidData, e);
+    let mut response = HttpResponse::parse_bytes_to(&head, &HttpMethod::Head).map_err(invalid)?;
+    let mut reusable = keeps_alive(&response);
+    let code = response.status_code.code();
+    let body = if *http_method == HttpMethod::Head || code < 200 || code == 204 || code == 304 {
+        Vec::new()
+    } else if response.header("Transfer-Encoding").is_some_and(is_chunked) {
+        chunked::decode(&mut *reader).map_err(invalid)?
+    } else if let Some(length) = response.header("Content-Length") {
+        let length = length
+            .parse()
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid Content-Length"))?;
+        // Grown as the body arrives, rather than trusting the length up front.
+        let mut body = Vec::new();
+        if (reader.take(length).read_to_end(&mut body)? as u64) < length {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        body
+    } else {
+        reusable = false;
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        body
+    };
+    if !body.is_empty() {
+        response.body = Body::Bytes(body);
+    }
+    Ok((response, reusable))
+}
+
+/// Whether the server keeps the connection open after the response, the
+/// default from HTTP/1.1 on.
+fn keeps_alive(response: &HttpResponse) -> bool {
+    let connection = response.header("Connection").unwrap_or_default();
+    let has = |option: &str| {
+        connection
+            .split(',')
+            .any(|value| value.trim().eq_ignore_ascii_case(option))
+    };
+    match response.http_version {
+        HttpVersion::Http0_9 | HttpVersion::Http1_0 => has("keep-alive"),
+        HttpVersion::Http1_1 | HttpVersion::Http2 => !has("close"),
+    }
+}
+
+/// Whether the request failed for the connection having been closed by the
+/// server while it sat idle, as it may at any time, rather than for the
+/// request itself.
+fn is_stale(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::BrokenPipe
+    )
+}
+
+/// Splits a url into the authority to connect to and the uri to request.
+fn split_url(url: &str) -> io::Result<(&str, &str)> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        io::Error::new(ErrorKind::InvalidInput, "Only http:// urls are supported")
+    })?;
+    Ok(match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    })
+}
+
+#[cfg(test)]
+mod tests;
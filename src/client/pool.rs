@@ -0,0 +1,111 @@
+//! Connections kept open after their response, per host, for an
+//! [`HttpClient`] to reuse.
+//!
+//! [`HttpClient`]: ./struct.HttpClient.html
+
+use std::collections::HashMap;
+use std::io;
+use std::net::TcpStream;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// How many connections an [`HttpClient`] keeps to each host, and for how
+/// long, see [`HttpClient::with_pool`]. A host is told apart by the address
+/// connected to, its name and port.
+///
+/// [`HttpClient`]: ./struct.HttpClient.html
+/// [`HttpClient::with_pool`]: ./struct.HttpClient.html#method.with_pool
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct PoolOptions {
+    /// The most connections kept open to a host while unused, 8 by default.
+    /// Any returned beyond it is closed.
+    pub max_idle_per_host: usize,
+    /// How long a connection is kept unused before it is closed, 30 seconds
+    /// by default. Best kept below how long the server keeps it alive.
+    pub idle_timeout: Duration,
+    /// The most connections open to a host at once, used or not, unlimited
+    /// by default. A request finding every one of them in use waits for one
+    /// to be returned.
+    pub max_connections_per_host: Option<usize>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> PoolOptions {
+        PoolOptions {
+            max_idle_per_host: 8,
+            idle_timeout: Duration::from_secs(30),
+            max_connections_per_host: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Pool {
+    options: PoolOptions,
+    hosts: Mutex<HashMap<String, Host>>,
+    /// Notified whenever a connection is returned or closed.
+    returned: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Host {
+    /// The most recently returned last.
+    idle: Vec<(TcpStream, Instant)>,
+    /// Every connection open to the host, idle ones included.
+    open: usize,
+}
+
+impl Pool {
+    pub(super) fn new(options: PoolOptions) -> Pool {
+        Pool {
+            options,
+            hosts: Mutex::new(HashMap::new()),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// A connection to the address, along with whether it was idle in the
+    /// pool rather than newly opened. Idle connections past their timeout
+    /// are closed along the way.
+    ///
+    /// # Returns:
+    /// An `Err` if a new connection could not be opened.
+    pub(super) fn checkout(&self, address: &str) -> io::Result<(TcpStream, bool)> {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let host = hosts.entry(address.into()).or_default();
+            let idle = host.idle.len();
+            let idle_timeout = self.options.idle_timeout;
+            host.idle
+                .retain(|(_, returned)| returned.elapsed() < idle_timeout);
+            host.open -= idle - host.idle.len();
+            if let Some((stream, _)) = host.idle.pop() {
+                return Ok((stream, true));
+            }
+            let max = self.options.max_connections_per_host.unwrap_or(usize::MAX);
+            if host.open < max {
+                host.open += 1;
+                break;
+            }
+            hosts = self.returned.wait(hosts).unwrap_or_else(|e| e.into_inner());
+        }
+        drop(hosts);
+        TcpStream::connect(address)
+            .map(|stream| (stream, false))
+            .inspect_err(|_| self.checkin(address, None))
+    }
+
+    /// Returns a connection checked out to the address, to be kept idle if
+    /// there is room for it, or `None` for one which was closed.
+    pub(super) fn checkin(&self, address: &str, stream: Option<TcpStream>) {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        let host = hosts.entry(address.into()).or_default();
+        match stream {
+            Some(stream) if host.idle.len() < self.options.max_idle_per_host => {
+                host.idle.push((stream, Instant::now()));
+            }
+            _ => host.open -= 1,
+        }
+        self.returned.notify_one();
+    }
+}
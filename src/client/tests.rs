@@ -0,0 +1,257 @@
+use crate::client::{read_response, split_url, HttpClient, PoolOptions};
+use crate::server::{HttpServer, Route, Server};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, HttpVersion, StatusCode};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+fn test_hello(request: HttpRequest) -> HttpResponse {
+    HttpResponse {
+        http_version: HttpVersion::Http1_1,
+        status_code: StatusCode::Ok,
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Bytes(format!("hello {}", request.body_text().unwrap()).into_bytes()),
+    }
+}
+
+#[test]
+fn should_split_authority_and_uri_when_url_has_path() {
+    let actual_split = split_url("http://localhost:8080/hello?greet=world").unwrap();
+    assert_eq!(actual_split, ("localhost:8080", "/hello?greet=world"));
+}
+
+#[test]
+fn should_default_uri_to_root_when_url_has_no_path() {
+    assert_eq!(split_url("http://localhost").unwrap(), ("localhost", "/"));
+}
+
+#[test]
+fn should_have_an_error_result_when_url_is_not_http() {
+    assert!(split_url("https://localhost/").is_err());
+}
+
+#[test]
+fn should_keep_unknown_status_code_when_server_replies_with_one() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        stream
+            .write_all(b"HTTP/1.1 418 I'm a teapot\r\nContent-Length: 5\r\n\r\nshort")
+            .unwrap();
+    });
+    let actual_response = HttpClient::default().get(&url).unwrap();
+    assert_eq!(actual_response.status_code, StatusCode::Other(418));
+    assert_eq!(actual_response.body, Body::Bytes(b"short".to_vec()));
+}
+
+#[test]
+fn should_receive_response_from_listening_server() {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    thread::spawn(move || {
+        let mut server = Server::default();
+        server
+            .route(|| Route::bind(HttpMethod::Post).to("/hello", test_hello))
+            .unwrap();
+        server.listen(address).unwrap();
+    });
+    let url = format!("http://{}/hello", address);
+    let client = HttpClient::default();
+    let actual_response = (0..50)
+        .find_map(|_| {
+            thread::sleep(Duration::from_millis(10));
+            client.post(&url, "world").ok()
+        })
+        .unwrap();
+    assert_eq!(actual_response.status_code, StatusCode::Ok);
+    assert_eq!(actual_response.body, Body::Bytes(b"hello world".to_vec()));
+}
+
+#[test]
+fn should_send_request_to_its_host_when_sent_as_is() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post).to("/hooks", |request: HttpRequest| {
+                let connection = request.header("Connection").unwrap_or_default();
+                format!("{} {}", connection, request.body_text().unwrap())
+            })
+        })
+        .unwrap();
+    let test_server = HttpServer::spawn_for_test(server).unwrap();
+    let raw_request = format!(
+        "POST /hooks HTTP/1.1\r\nHost: {}\r\nContent-Length: 7\r\n\r\ndeploy!",
+        test_server.addr()
+    );
+    let response = HttpClient::default()
+        .send(HttpRequest::from(raw_request.as_str()))
+        .unwrap();
+    assert_eq!(response.body, Body::Bytes(b"close deploy!".to_vec()));
+}
+
+#[test]
+fn should_have_an_error_result_when_request_has_no_host() {
+    let request = HttpRequest::from("GET / HTTP/1.1\r\n\r\n");
+    let error = HttpClient::default().send(request).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn should_read_no_body_when_response_is_to_head_request() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "hello"))
+        .unwrap();
+    let test_server = HttpServer::spawn_for_test(server).unwrap();
+    let response = HttpClient::default().head(&test_server.url("/")).unwrap();
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.header("Content-Length"), Some("5"));
+    assert_eq!(response.body, Body::Empty);
+}
+
+/// Answers with the port of the client, a `Server` without workers keeping
+/// no connection alive.
+fn peer_port_server() -> Server {
+    let mut server = Server::with_workers(2);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/port", |request: HttpRequest| {
+                request.connection.unwrap().peer_addr.port().to_string()
+            })
+        })
+        .unwrap();
+    server
+}
+
+#[test]
+fn should_reuse_connection_when_pooled() {
+    let test_server = HttpServer::spawn_for_test(peer_port_server()).unwrap();
+    let client = HttpClient::with_pool(PoolOptions::default());
+    let url = test_server.url("/port");
+    let first = client.get(&url).unwrap();
+    let second = client.clone().get(&url).unwrap();
+    assert_eq!(first.body, second.body);
+    let unpooled = HttpClient::default();
+    assert_ne!(
+        unpooled.get(&url).unwrap().body,
+        unpooled.get(&url).unwrap().body
+    );
+}
+
+#[test]
+fn should_open_new_connection_when_idle_one_timed_out() {
+    let test_server = HttpServer::spawn_for_test(peer_port_server()).unwrap();
+    let client = HttpClient::with_pool(PoolOptions {
+        idle_timeout: Duration::ZERO,
+        ..PoolOptions::default()
+    });
+    let url = test_server.url("/port");
+    assert_ne!(
+        client.get(&url).unwrap().body,
+        client.get(&url).unwrap().body
+    );
+}
+
+#[test]
+fn should_share_one_connection_when_limited_to_one_per_host() {
+    let test_server = HttpServer::spawn_for_test(peer_port_server()).unwrap();
+    let client = HttpClient::with_pool(PoolOptions {
+        max_connections_per_host: Some(1),
+        ..PoolOptions::default()
+    });
+    let url = test_server.url("/port");
+    let ports = thread::scope(|scope| {
+        let requests = (0..4)
+            .map(|_| scope.spawn(|| client.get(&url).unwrap().body))
+            .collect::<Vec<_>>();
+        requests
+            .into_iter()
+            .map(|request| request.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    assert!(ports.iter().all(|port| *port == ports[0]));
+}
+
+#[test]
+fn should_send_again_when_idle_connection_was_closed_by_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for (i, stream) in listener.incoming().take(2).enumerate() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n{}", i);
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    let client = HttpClient::with_pool(PoolOptions::default());
+    assert_eq!(client.get(&url).unwrap().body, Body::from("0"));
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(client.get(&url).unwrap().body, Body::from("1"));
+}
+
+#[test]
+fn should_not_send_post_again_when_idle_connection_was_closed_by_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        listener
+    });
+    let client = HttpClient::with_pool(PoolOptions::default());
+    assert_eq!(client.post(&url, "").unwrap().status_code, StatusCode::Ok);
+    let listener = server.join().unwrap();
+    assert!(client.post(&url, "again").is_err());
+    listener.set_nonblocking(true).unwrap();
+    let accepted = listener.accept().map(|_| ());
+    assert_eq!(accepted.unwrap_err().kind(), ErrorKind::WouldBlock);
+}
+
+#[test]
+fn should_fail_without_allocating_length_up_front_when_body_is_short() {
+    let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 1152921504606846976\r\n\r\nshort";
+    let error = read_response(&mut &raw[..], &HttpMethod::Get).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn should_read_responses_one_at_a_time_when_framed() {
+    let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n\
+        HTTP/1.1 200 OK\r\nContent-Length: 3\r\nConnection: close\r\n\r\nbye\
+        HTTP/1.0 200 OK\r\n\r\nrest";
+    let mut reader = &raw[..];
+    let (response, reusable) = read_response(&mut reader, &HttpMethod::Get).unwrap();
+    assert_eq!((response.body, reusable), (Body::from("hi"), true));
+    let (response, reusable) = read_response(&mut reader, &HttpMethod::Get).unwrap();
+    assert_eq!((response.body, reusable), (Body::from("bye"), false));
+    let (response, reusable) = read_response(&mut reader, &HttpMethod::Get).unwrap();
+    assert_eq!((response.body, reusable), (Body::from("rest"), false));
+    let error = read_response(&mut reader, &HttpMethod::Get).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::ConnectionAborted);
+}
@@ -1,2 +1,4 @@
+pub mod client;
 pub mod server;
+pub mod test;
 pub mod web;
@@ -0,0 +1,355 @@
+//! What is known about a request once the [`Server`] is done with it, handed
+//! to the hook registered through [`Server::on_request_complete`] or written
+//! out by the [`AccessLog`] middleware.
+//!
+//! [`Server`]: ../struct.Server.html
+//! [`Server::on_request_complete`]: ../struct.Server.html#method.on_request_complete
+//! [`AccessLog`]: ../struct.AccessLog.html
+
+use std::fmt;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::web::date::{civil_from_days, MONTHS};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, HttpVersion, StatusCode};
+
+use super::{Middleware, Next};
+
+/// A single entry of an access log.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RequestLog {
+    pub http_method: HttpMethod,
+    /// The path of the request uri, without its query.
+    pub path: String,
+    pub http_version: HttpVersion,
+    pub status_code: StatusCode,
+    /// When the request started being handled.
+    pub time: SystemTime,
+    /// How long the request took to handle, not including writing the
+    /// response out.
+    pub elapsed: Duration,
+    /// The length of the response body, `None` when it is streamed.
+    pub body_size: Option<usize>,
+    /// The address of the client, `None` when the request did not arrive over
+    /// a listener.
+    pub peer_addr: Option<SocketAddr>,
+    /// The `Referer` header of the request.
+    pub referer: Option<String>,
+    /// The `User-Agent` header of the request.
+    pub user_agent: Option<String>,
+}
+
+impl RequestLog {
+    /// Formats the entry as a line of the
+    /// [Common Log Format](https://httpd.apache.org/docs/current/logs.html#common),
+    /// with times in UTC.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::RequestLog;
+    /// use martian::web::{HttpMethod, HttpVersion, StatusCode};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// let request_log = RequestLog {
+    ///     http_method: HttpMethod::Get,
+    ///     path: "/hello".into(),
+    ///     http_version: HttpVersion::Http1_1,
+    ///     status_code: StatusCode::Ok,
+    ///     time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+    ///     elapsed: Duration::from_millis(3),
+    ///     body_size: Some(5),
+    ///     peer_addr: Some("127.0.0.1:54321".parse().unwrap()),
+    ///     referer: None,
+    ///     user_agent: None,
+    /// };
+    /// assert_eq!(
+    ///     request_log.common_log_format(),
+    ///     "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /hello HTTP/1.1\" 200 5"
+    /// );
+    /// ```
+    pub fn common_log_format(&self) -> String {
+        format!(
+            "{} - - [{}] \"{} {} {}\" {} {}",
+            self.peer_addr
+                .map_or("-".into(), |peer_addr| peer_addr.ip().to_string()),
+            format_time(self.time),
+            self.http_method.as_str(),
+            self.path,
+            self.http_version,
+            self.status_code.code(),
+            self.body_size
+                .map_or("-".into(), |body_size| body_size.to_string()),
+        )
+    }
+
+    /// Formats the entry as a line of the
+    /// [Combined Log Format](https://httpd.apache.org/docs/current/logs.html#combined),
+    /// the [`common_log_format`] followed by the referer and user agent.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::RequestLog;
+    /// use martian::web::{HttpMethod, HttpVersion, StatusCode};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// let request_log = RequestLog {
+    ///     http_method: HttpMethod::Get,
+    ///     path: "/hello".into(),
+    ///     http_version: HttpVersion::Http1_1,
+    ///     status_code: StatusCode::Ok,
+    ///     time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+    ///     elapsed: Duration::from_millis(3),
+    ///     body_size: Some(5),
+    ///     peer_addr: Some("127.0.0.1:54321".parse().unwrap()),
+    ///     referer: None,
+    ///     user_agent: Some("curl/8.0".into()),
+    /// };
+    /// assert_eq!(
+    ///     request_log.combined_log_format(),
+    ///     "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /hello HTTP/1.1\" 200 5 \"-\" \"curl/8.0\""
+    /// );
+    /// ```
+    ///
+    /// [`common_log_format`]: ./struct.RequestLog.html#method.common_log_format
+    pub fn combined_log_format(&self) -> String {
+        format!(
+            "{} \"{}\" \"{}\"",
+            self.common_log_format(),
+            escape(self.referer.as_deref().unwrap_or("-")),
+            escape(self.user_agent.as_deref().unwrap_or("-")),
+        )
+    }
+
+    /// Formats the entry as `key=value` pairs, as understood by most log
+    /// collectors, with its time in RFC 3339 and how long it took in
+    /// milliseconds. Values which are not known are left out.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::RequestLog;
+    /// use martian::web::{HttpMethod, HttpVersion, StatusCode};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// let request_log = RequestLog {
+    ///     http_method: HttpMethod::Get,
+    ///     path: "/hello".into(),
+    ///     http_version: HttpVersion::Http1_1,
+    ///     status_code: StatusCode::Ok,
+    ///     time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+    ///     elapsed: Duration::from_micros(3_250),
+    ///     body_size: Some(5),
+    ///     peer_addr: Some("127.0.0.1:54321".parse().unwrap()),
+    ///     referer: None,
+    ///     user_agent: None,
+    /// };
+    /// assert_eq!(
+    ///     request_log.structured_log_format(),
+    ///     "time=2000-10-10T13:55:36Z method=GET path=\"/hello\" status=200 bytes=5 \
+    ///      latency_ms=3.250 remote_addr=127.0.0.1:54321"
+    /// );
+    /// ```
+    pub fn structured_log_format(&self) -> String {
+        let mut line = format!(
+            "time={} method={} path=\"{}\" status={}",
+            format_rfc3339(self.time),
+            self.http_method.as_str(),
+            escape(&self.path),
+            self.status_code.code(),
+        );
+        if let Some(body_size) = self.body_size {
+            line.push_str(&format!(" bytes={}", body_size));
+        }
+        line.push_str(&format!(
+            " latency_ms={:.3}",
+            self.elapsed.as_secs_f64() * 1_000.0
+        ));
+        if let Some(peer_addr) = self.peer_addr {
+            line.push_str(&format!(" remote_addr={}", peer_addr));
+        }
+        if let Some(referer) = &self.referer {
+            line.push_str(&format!(" referer=\"{}\"", escape(referer)));
+        }
+        if let Some(user_agent) = &self.user_agent {
+            line.push_str(&format!(" user_agent=\"{}\"", escape(user_agent)));
+        }
+        line
+    }
+
+    /// Formats the entry as a line of the [`LogFormat`].
+    ///
+    /// [`LogFormat`]: ./enum.LogFormat.html
+    pub fn format(&self, log_format: LogFormat) -> String {
+        match log_format {
+            LogFormat::Common => self.common_log_format(),
+            LogFormat::Combined => self.combined_log_format(),
+            LogFormat::Structured => self.structured_log_format(),
+        }
+    }
+}
+
+/// How each request is written out by an [`AccessLog`].
+///
+/// [`AccessLog`]: ./struct.AccessLog.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum LogFormat {
+    /// See [`RequestLog::common_log_format`].
+    ///
+    /// [`RequestLog::common_log_format`]: ./struct.RequestLog.html#method.common_log_format
+    #[default]
+    Common,
+    /// See [`RequestLog::combined_log_format`].
+    ///
+    /// [`RequestLog::combined_log_format`]: ./struct.RequestLog.html#method.combined_log_format
+    Combined,
+    /// See [`RequestLog::structured_log_format`].
+    ///
+    /// [`RequestLog::structured_log_format`]: ./struct.RequestLog.html#method.structured_log_format
+    Structured,
+}
+
+/// [`Middleware`] writing a line to its target for every request it wraps,
+/// in the [`LogFormat`] asked for. Wrapped around the whole [`Server`]
+/// through [`Server::wrap`], it logs every request, those answered with a
+/// 404 or after a panic included. A line which can not be written is
+/// dropped, the request is answered all the same.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{AccessLog, LogFormat, Server};
+/// use std::io;
+/// let mut server = Server::default();
+/// server.wrap(AccessLog::new(io::stderr()).with_format(LogFormat::Combined));
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`LogFormat`]: ./enum.LogFormat.html
+/// [`Server`]: ./struct.Server.html
+/// [`Server::wrap`]: ./struct.Server.html#method.wrap
+pub struct AccessLog {
+    target: Mutex<Box<dyn Write + Send>>,
+    log_format: LogFormat,
+}
+
+impl AccessLog {
+    /// Writes each request to the target in the Common Log Format, the lines
+    /// of requests served in parallel never interleaving.
+    pub fn new<W: Write + Send + 'static>(target: W) -> AccessLog {
+        AccessLog {
+            target: Mutex::new(Box::new(target)),
+            log_format: LogFormat::default(),
+        }
+    }
+
+    pub fn with_format(mut self, log_format: LogFormat) -> AccessLog {
+        self.log_format = log_format;
+        self
+    }
+}
+
+impl Middleware for AccessLog {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let pending_log = PendingLog::start(&request);
+        let response = next.run(request);
+        let line = pending_log.complete(&response).format(self.log_format);
+        // A target left poisoned by a panicking write is still written to.
+        let mut target = self.target.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(target, "{}", line).and_then(|_| target.flush());
+        response
+    }
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("log_format", &self.log_format)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The [`RequestLog`] of a request still being handled, completed by its
+/// response.
+///
+/// [`RequestLog`]: ./struct.RequestLog.html
+pub(in crate::server) struct PendingLog {
+    request_log: RequestLog,
+    started: Instant,
+}
+
+impl PendingLog {
+    pub(in crate::server) fn start(request: &HttpRequest) -> PendingLog {
+        PendingLog {
+            request_log: RequestLog {
+                http_method: request.http_method.clone(),
+                path: request.uri.path().to_string(),
+                http_version: request.http_version,
+                status_code: StatusCode::Ok,
+                time: SystemTime::now(),
+                elapsed: Duration::ZERO,
+                body_size: None,
+                peer_addr: request
+                    .connection
+                    .as_ref()
+                    .map(|connection| connection.peer_addr),
+                referer: request.header("Referer").map(String::from),
+                user_agent: request.header("User-Agent").map(String::from),
+            },
+            started: Instant::now(),
+        }
+    }
+
+    pub(in crate::server) fn complete(self, response: &HttpResponse) -> RequestLog {
+        RequestLog {
+            status_code: response.status_code,
+            elapsed: self.started.elapsed(),
+            body_size: match &response.body {
+                Body::Empty => Some(0),
+                Body::Bytes(bytes) => Some(bytes.len()),
+                Body::Stream(_) => response.stream_len().map(|len| len as usize),
+                Body::Upgrade(_) => None,
+            },
+            ..self.request_log
+        }
+    }
+}
+
+/// Escapes the quotes and backslashes of a value written within quotes.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Formats a time as `2000-10-10T13:55:36Z`.
+fn format_rfc3339(time: SystemTime) -> String {
+    let secs = unix_secs(time);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Formats a time as `10/Oct/2000:13:55:36 +0000`.
+fn format_time(time: SystemTime) -> String {
+    let secs = unix_secs(time);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3_600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,122 @@
+use crate::server::tests::TestStream;
+use crate::server::{AccessLog, LogFormat, RequestLog, Route, Server};
+use crate::web::{ConnectionInfo, HttpMethod, HttpVersion, StatusCode};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// A target whose lines can still be read once handed to an `AccessLog`.
+#[derive(Clone, Default)]
+struct SharedTarget(Arc<Mutex<Vec<u8>>>);
+
+impl SharedTarget {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl Write for SharedTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn serve_logged(log_format: LogFormat, raw_requests: &[&str]) -> Vec<String> {
+    let target = SharedTarget::default();
+    let mut server = Server::default();
+    server.wrap(AccessLog::new(target.clone()).with_format(log_format));
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", |_| "hello")
+                .to("/panic", |_| -> &str { panic!("Broken") })
+        })
+        .unwrap();
+    let connection_info = ConnectionInfo {
+        peer_addr: "10.0.0.1:4000".parse().unwrap(),
+        local_addr: "10.0.0.2:80".parse().unwrap(),
+        is_tls: false,
+        peer_identity: None,
+        server_name: None,
+    };
+    for raw_request in raw_requests {
+        server
+            .serve(
+                &mut TestStream::of(raw_request),
+                Some(connection_info.clone()),
+                None,
+            )
+            .unwrap();
+    }
+    target.lines()
+}
+
+fn request_log() -> RequestLog {
+    RequestLog {
+        http_method: HttpMethod::Post,
+        path: "/say".into(),
+        http_version: HttpVersion::Http1_0,
+        status_code: StatusCode::Created,
+        time: UNIX_EPOCH + Duration::from_secs(951_782_400),
+        elapsed: Duration::from_millis(12),
+        body_size: None,
+        peer_addr: None,
+        referer: Some("http://example.com/\"quoted\"".into()),
+        user_agent: Some("agent\\1".into()),
+    }
+}
+
+#[test]
+fn should_write_line_per_request_when_wrapped_around_server() {
+    let lines = serve_logged(
+        LogFormat::Combined,
+        &[
+            "GET /?greet=world HTTP/1.1\r\nReferer: http://example.com/\r\nUser-Agent: curl/8.0\r\n\r\n",
+            "GET /missing HTTP/1.1\r\n\r\n",
+            "GET /panic HTTP/1.0\r\n\r\n",
+        ],
+    );
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("10.0.0.1 - - ["));
+    assert!(lines[0].ends_with("] \"GET / HTTP/1.1\" 200 5 \"http://example.com/\" \"curl/8.0\""));
+    assert!(lines[1].ends_with("] \"GET /missing HTTP/1.1\" 404 0 \"-\" \"-\""));
+    assert!(lines[2].contains("] \"GET /panic HTTP/1.0\" 500 "));
+}
+
+#[test]
+fn should_write_common_log_format_when_no_format_given() {
+    let lines = serve_logged(LogFormat::default(), &["GET / HTTP/1.1\r\n\r\n"]);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with("] \"GET / HTTP/1.1\" 200 5"));
+}
+
+#[test]
+fn should_write_latency_and_remote_addr_when_structured() {
+    let lines = serve_logged(LogFormat::Structured, &["GET / HTTP/1.1\r\n\r\n"]);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].starts_with("time="));
+    assert!(lines[0].contains(" method=GET path=\"/\" status=200 bytes=5 latency_ms="));
+    assert!(lines[0].ends_with(" remote_addr=10.0.0.1:4000"));
+}
+
+#[test]
+fn should_escape_quotes_when_formatting_headers() {
+    assert_eq!(
+        request_log().combined_log_format(),
+        "- - - [29/Feb/2000:00:00:00 +0000] \"POST /say HTTP/1.0\" 201 - \
+         \"http://example.com/\\\"quoted\\\"\" \"agent\\\\1\""
+    );
+    assert_eq!(
+        request_log().format(LogFormat::Structured),
+        "time=2000-02-29T00:00:00Z method=POST path=\"/say\" status=201 latency_ms=12.000 \
+         referer=\"http://example.com/\\\"quoted\\\"\" user_agent=\"agent\\\\1\""
+    );
+}
@@ -0,0 +1,584 @@
+//! Serving connections on an async runtime, with the `async` feature. Every
+//! connection is a task rather than a thread of its own, so one left idle
+//! between requests holds nothing up, and routes bound through
+//! [`Binding::to_async`] are awaited on the runtime. Any other route is
+//! handed to a blocking thread of the runtime, as it may block for as long as
+//! it likes. Which runtime is up to the [`AsyncRuntime`] served on.
+//!
+//! [`Binding::to_async`]: ../struct.Binding.html#method.to_async
+//! [`AsyncRuntime`]: ../trait.AsyncRuntime.html
+
+use std::future::{self, Future};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{self, TcpListener, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use crate::web::{
+    Body, ConnectionInfo, HttpMethod, HttpRequest, HttpResponse, HttpVersion, MartianError,
+    StatusCode,
+};
+
+use super::connection::{self, Framing, Limits, Next, ReadError};
+use super::load_shed;
+#[cfg(feature = "log")]
+use super::logging;
+use super::{mount, AsyncCallback, DefaultHeaders, Handler, Lifecycle, Server};
+
+pub(in crate::server) use self::runtime::block_on;
+#[cfg(feature = "async-std")]
+pub use self::runtime::AsyncStd;
+#[cfg(feature = "smol")]
+pub use self::runtime::Smol;
+#[cfg(feature = "tokio")]
+pub use self::runtime::Tokio;
+pub use self::runtime::{AsyncRuntime, BoxFuture};
+
+mod runtime;
+
+/// How much of a connection is read in at a time.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// What becomes of a connection once a request has been served on it.
+enum Served {
+    KeepAlive(bool),
+    /// Taken over by the response, once its head is written.
+    Upgrade(HttpResponse),
+}
+
+impl Server {
+    /// Same as [`listen`], but serves every connection as a task on the
+    /// [`AsyncRuntime`] rather than on a thread of its own. It is to be
+    /// awaited on that same runtime, and the `Server` is moved in, as its
+    /// tasks may outlive any borrow of it. Connections are kept alive as the
+    /// [`KeepAlive`] allows, without any workers, and neither TLS nor HTTP/2
+    /// is spoken. The listener is bound with the [`SocketOptions`].
+    ///
+    /// Only an `async` route which is the first to see the request is
+    /// awaited on the runtime. A request going through any [`Middleware`] of
+    /// the `Server` or route, a mounted `Server` or a virtual host, or ending
+    /// at a route which is not `async`, is handled on a blocking thread of
+    /// the runtime instead, the same as [`listen`] would handle it. That
+    /// thread blocks on any `async` route the request ends up at, taking up
+    /// a thread per request, though never holding up the runtime.
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// # #[cfg(feature = "tokio")]
+    /// # {
+    /// use martian::server::{Route, Server, Tokio};
+    /// use martian::web::{HttpMethod, HttpRequest};
+    /// use std::time::Duration;
+    /// async fn slow(_: HttpRequest) -> &'static str {
+    ///     tokio::time::sleep(Duration::from_secs(1)).await;
+    ///     "done"
+    /// }
+    /// let mut server = Server::default();
+    /// server
+    ///     .route(|| Route::bind(HttpMethod::Get).to_async("/slow", slow))
+    ///     .unwrap();
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    /// runtime
+    ///     .block_on(server.listen_async(Tokio, "127.0.0.1:8080"))
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// [`listen`]: ./struct.Server.html#method.listen
+    /// [`AsyncRuntime`]: ./trait.AsyncRuntime.html
+    /// [`KeepAlive`]: ./struct.KeepAlive.html
+    /// [`SocketOptions`]: ./struct.SocketOptions.html
+    /// [`Middleware`]: ./trait.Middleware.html
+    pub async fn listen_async<R, A>(self, runtime: R, addr: A) -> io::Result<()>
+    where
+        R: AsyncRuntime,
+        A: ToSocketAddrs,
+    {
+        let listener = self.socket_options.bind(addr)?;
+        self.serve_async(runtime, listener).await
+    }
+
+    /// Same as [`listen_async`], but over a listener which is already bound.
+    ///
+    /// [`listen_async`]: ./struct.Server.html#method.listen_async
+    pub async fn serve_async<R: AsyncRuntime>(
+        self,
+        runtime: R,
+        listener: TcpListener,
+    ) -> io::Result<()> {
+        listener.set_nonblocking(true)?;
+        let listener = runtime.listener(listener)?;
+        self.health.set_lifecycle(Lifecycle::Serving);
+        let server = Arc::new(self);
+        loop {
+            // A single bad connection has no bearing on the ones after it.
+            let stream = match runtime.accept(&listener).await {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            if server.socket_options.nodelay {
+                let _ = runtime.set_nodelay(&stream);
+            }
+            let connection = Buffered::new(runtime.clone(), stream);
+            let server = Arc::clone(&server);
+            runtime.spawn(Box::pin(async move {
+                let _ = catch_unwind(server.serve_stream_async(connection)).await;
+            }));
+        }
+    }
+
+    /// Serves the requests of the connection in turn, the same as [`serve`]
+    /// does on a thread.
+    ///
+    /// [`serve`]: ./struct.Server.html#method.serve
+    async fn serve_stream_async<R: AsyncRuntime>(
+        self: Arc<Self>,
+        mut connection: Buffered<R>,
+    ) -> io::Result<()> {
+        let (peer_addr, local_addr) = connection.runtime.addrs(&connection.stream)?;
+        #[cfg(feature = "log")]
+        logging::accepted(peer_addr);
+        let connection_info = ConnectionInfo {
+            peer_addr,
+            local_addr,
+            is_tls: false,
+            peer_identity: None,
+            server_name: None,
+        };
+        let accepted = Instant::now();
+        let max_requests = self.keep_alive.max_requests.max(1);
+        for served in 1..=max_requests {
+            let timeout = match served {
+                1 => self.timeouts.read,
+                _ => Some(self.keep_alive.timeout),
+            };
+            // Anything but the start of another request ends the connection
+            // quietly, be it closed by the client or left idle.
+            let runtime = connection.runtime.clone();
+            match within(&runtime, timeout, connection.fill_buf()).await {
+                Some(Ok(buffer)) if !buffer.is_empty() => {}
+                _ => break,
+            }
+            let started = match served {
+                1 => accepted,
+                _ => Instant::now(),
+            };
+            let deadline = self.timeouts.read.map(|read| started + read);
+            let may_keep_alive = served < max_requests;
+            match self
+                .serve_request_async(
+                    &mut connection,
+                    connection_info.clone(),
+                    deadline,
+                    may_keep_alive,
+                )
+                .await?
+            {
+                Served::KeepAlive(true) => {}
+                Served::KeepAlive(false) => break,
+                Served::Upgrade(response) => return hand_over(connection, response).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a single request off of the connection and writes its response,
+    /// answering those which can not be read by the deadline, or at all, as
+    /// [`serve_request`] does.
+    ///
+    /// [`serve_request`]: ./struct.Server.html#method.serve_request
+    async fn serve_request_async<R: AsyncRuntime>(
+        self: &Arc<Self>,
+        connection: &mut Buffered<R>,
+        connection_info: ConnectionInfo,
+        deadline: Option<Instant>,
+        may_keep_alive: bool,
+    ) -> io::Result<Served> {
+        let runtime = connection.runtime.clone();
+        let read = read_request(connection, &self.limits);
+        let time_left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let mut request = match within(&runtime, time_left, read).await {
+            Some(Ok(request)) => request,
+            Some(Err(ReadError::Rejected(status_code))) => {
+                return close_with(connection, status_code, &self.default_headers).await;
+            }
+            Some(Err(ReadError::Io(e))) => return Err(e),
+            None => {
+                let status_code = StatusCode::RequestTimeout;
+                return close_with(connection, status_code, &self.default_headers).await;
+            }
+        };
+        request.connection = Some(connection_info);
+        let is_head = request.http_method == HttpMethod::Head;
+        let http_version = request.http_version;
+        let keep_alive = may_keep_alive && connection::wants_keep_alive(&request);
+        let mut response = self.handle_async(&runtime, request).await;
+        self.default_headers.stamp(&mut response);
+        if matches!(response.body, Body::Upgrade(_)) {
+            return Ok(Served::Upgrade(response));
+        }
+        response.http_version = response.http_version.min(http_version);
+        let keep_alive = connection::set_keep_alive(&mut response, keep_alive);
+        write_response(connection, response, is_head).await?;
+        Ok(Served::KeepAlive(keep_alive))
+    }
+
+    /// Awaits the `async` route matching the request on the runtime, handing
+    /// any other request to a blocking thread to be handled there.
+    async fn handle_async<R: AsyncRuntime>(
+        self: &Arc<Self>,
+        runtime: &R,
+        mut request: HttpRequest,
+    ) -> HttpResponse {
+        let callback = match self.async_route(&mut request) {
+            Some(callback) => callback,
+            None => {
+                let server = Arc::clone(self);
+                return runtime.spawn_blocking(move || server.handle(request)).await;
+            }
+        };
+        let tracked = self.track(&request);
+        let response = match self.enter() {
+            Some(_permit) => self.invoke_async(runtime, callback, request).await,
+            None => load_shed::overloaded(),
+        };
+        self.report(tracked, &response);
+        response
+    }
+
+    /// The callback of the route matching the request, as long as it is an
+    /// `async` one and nothing else is to see the request first, be it
+    /// middleware, a mounted `Server` or a trailing slash policy. The request
+    /// is given its path params and the state.
+    fn async_route(&self, request: &mut HttpRequest) -> Option<AsyncCallback> {
+        let is_mounted = self
+            .mounts
+            .iter()
+            .any(|(prefix, _)| mount::strip_prefix(prefix, request.uri.as_str()).is_some());
+        if !self.middleware.is_empty() || is_mounted || self.virtual_host(request).is_some() {
+            return None;
+        }
+        self.normalize(request);
+        let (route, path_params) =
+            self.find_route(request, &request.http_method, request.uri.path())?;
+        let callback = match &route.handler {
+            Handler::Async(callback) if route.middleware.is_empty() => Arc::clone(callback),
+            _ => return None,
+        };
+        #[cfg(feature = "log")]
+        logging::routed(request, route.pattern.uri());
+        request.path_params = path_params;
+        request.state = self.state.clone();
+        Some(callback)
+    }
+
+    /// Awaits the callback, given up on once the handler timeout passes. A
+    /// callback which panics or times out is answered the same as [`invoke`]
+    /// answers it.
+    ///
+    /// [`invoke`]: ./struct.Server.html#method.invoke
+    async fn invoke_async<R: AsyncRuntime>(
+        &self,
+        runtime: &R,
+        callback: AsyncCallback,
+        request: HttpRequest,
+    ) -> HttpResponse {
+        // The request is only kept around when there is a hook to hand it to.
+        let hook_request = self.panic_hook.map(|hook| (hook, request.clone()));
+        let invoked = catch_unwind(async move { callback(request).await });
+        match within(runtime, self.timeouts.handler, invoked).await {
+            Some(Ok(response)) => response,
+            Some(Err(payload)) => self.recover(hook_request, payload),
+            None => self.fail(MartianError::new(
+                StatusCode::ServiceUnavailable,
+                "Handler timed out",
+            )),
+        }
+    }
+}
+
+/// The stream of a connection, read through a buffer so that no more of it
+/// is taken than a request goes on for.
+struct Buffered<R: AsyncRuntime> {
+    runtime: R,
+    stream: R::Stream,
+    buffer: Vec<u8>,
+    position: usize,
+    filled: usize,
+}
+
+impl<R: AsyncRuntime> Buffered<R> {
+    fn new(runtime: R, stream: R::Stream) -> Buffered<R> {
+        Buffered {
+            runtime,
+            stream,
+            buffer: vec![0; BUFFER_SIZE],
+            position: 0,
+            filled: 0,
+        }
+    }
+
+    /// Whatever is buffered, reading more in once it has all been consumed.
+    /// Empty once the stream is closed.
+    async fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.filled {
+            self.filled = self
+                .runtime
+                .read(&mut self.stream, &mut self.buffer)
+                .await?;
+            self.position = 0;
+        }
+        Ok(&self.buffer[self.position..self.filled])
+    }
+
+    /// Writes to `writer` up to and including the delimiter, reading no more
+    /// than `limit` bytes, or fewer when the stream is closed.
+    ///
+    /// # Returns:
+    /// How many bytes were read.
+    async fn read_until<W: Write>(
+        &mut self,
+        delimiter: Option<u8>,
+        writer: &mut W,
+        limit: u64,
+    ) -> io::Result<u64> {
+        let mut read = 0;
+        while read < limit {
+            let available = self.fill_buf().await?;
+            if available.is_empty() {
+                break;
+            }
+            let available = &available[..(limit - read).min(available.len() as u64) as usize];
+            let found = delimiter.and_then(|delimiter| {
+                available
+                    .iter()
+                    .position(|byte| *byte == delimiter)
+                    .map(|index| index + 1)
+            });
+            let taken = found.unwrap_or(available.len());
+            writer.write_all(&available[..taken])?;
+            self.position += taken;
+            read += taken as u64;
+            if found.is_some() {
+                break;
+            }
+        }
+        Ok(read)
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.runtime.write_all(&mut self.stream, buf).await
+    }
+}
+
+/// Awaits the future for as long as the timeout allows, `None` if it was not
+/// done in time.
+async fn within<R, F>(runtime: &R, timeout: Option<Duration>, future: F) -> Option<F::Output>
+where
+    R: AsyncRuntime,
+    F: Future,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return Some(future.await),
+    };
+    let mut future = Box::pin(future);
+    let mut sleep = runtime.sleep(timeout);
+    future::poll_fn(|context| match future.as_mut().poll(context) {
+        Poll::Ready(output) => Poll::Ready(Some(output)),
+        Poll::Pending => sleep.as_mut().poll(context).map(|_| None),
+    })
+    .await
+}
+
+/// Awaits the future, catching a panic while it is polled rather than letting
+/// it unwind through the runtime.
+async fn catch_unwind<F: Future>(future: F) -> std::thread::Result<F::Output> {
+    let mut future = Box::pin(future);
+    // Nothing borrowed is observed after a panic, the future is dropped
+    // straight after.
+    future::poll_fn(|context| {
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(context))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    })
+    .await
+}
+
+/// Answers with the status and no body, closing the connection after.
+async fn close_with<R: AsyncRuntime>(
+    connection: &mut Buffered<R>,
+    status_code: StatusCode,
+    default_headers: &DefaultHeaders,
+) -> io::Result<Served> {
+    #[cfg(feature = "log")]
+    logging::rejected(status_code);
+    let mut response = HttpResponse::new(status_code);
+    default_headers.stamp(&mut response);
+    connection::set_keep_alive(&mut response, false);
+    write_response(connection, response, false).await?;
+    Ok(Served::KeepAlive(false))
+}
+
+/// Writes the response out as [`HttpResponse::write_to`] would. A streamed
+/// body may block while it is read, so each chunk of it is read on a
+/// blocking thread.
+///
+/// [`HttpResponse::write_to`]: ../web/struct.HttpResponse.html#method.write_to
+async fn write_response<R: AsyncRuntime>(
+    connection: &mut Buffered<R>,
+    mut response: HttpResponse,
+    is_head: bool,
+) -> io::Result<()> {
+    let mut head = Vec::new();
+    let stream_len = response.stream_len();
+    let mut body = match mem::take(&mut response.body) {
+        Body::Stream(body) if !is_head && response.status_code.allows_body() => body,
+        body => {
+            response.body = body;
+            match is_head {
+                true => response.write_head_to(&mut head)?,
+                false => response.write_to(&mut head)?,
+            }
+            return connection.write_all(&head).await;
+        }
+    };
+    let is_chunked = stream_len.is_none() && response.http_version >= HttpVersion::Http1_1;
+    if let Some(len) = stream_len {
+        body = Box::new(body.take(len));
+    }
+    let mut written = 0;
+    // The head is the same for any stream, only its framing is written out.
+    response.body = Body::Stream(Box::new(io::empty()));
+    response.write_head_to(&mut head)?;
+    connection.write_all(&head).await?;
+    loop {
+        let (returned, chunk) = connection
+            .runtime
+            .spawn_blocking(move || {
+                let mut chunk = vec![0; BUFFER_SIZE];
+                let read = body.read(&mut chunk).map(|read| {
+                    chunk.truncate(read);
+                    chunk
+                });
+                (body, read)
+            })
+            .await;
+        body = returned;
+        let chunk = chunk?;
+        if !is_chunked {
+            written += chunk.len() as u64;
+            match chunk.is_empty() {
+                true if written < stream_len.unwrap_or_default() => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The body ended short of its Content-Length",
+                    ))
+                }
+                true => return Ok(()),
+                false => connection.write_all(&chunk).await?,
+            }
+            continue;
+        }
+        // An empty chunk is the one terminating the body.
+        connection
+            .write_all(format!("{:X}\r\n", chunk.len()).as_bytes())
+            .await?;
+        connection.write_all(&chunk).await?;
+        connection.write_all(b"\r\n").await?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Takes the connection off of the runtime and hands it over to the upgrade
+/// of the response on a blocking thread, as a WebSocket is read and written
+/// synchronously.
+async fn hand_over<R: AsyncRuntime>(
+    connection: Buffered<R>,
+    response: HttpResponse,
+) -> io::Result<()> {
+    let buffered = connection.buffer[connection.position..connection.filled].to_vec();
+    let runtime = connection.runtime;
+    let stream = runtime.detach(connection.stream)?;
+    runtime
+        .spawn_blocking(move || {
+            let socket = stream.try_clone()?;
+            let buffered = io::Cursor::new(buffered);
+            let mut reader = io::BufReader::new(Detached { buffered, stream });
+            super::hand_over(&mut reader, Some(&socket), response).map(|_| ())
+        })
+        .await
+}
+
+/// A connection taken off of the runtime, reading whatever had already been
+/// buffered of it first.
+struct Detached {
+    buffered: io::Cursor<Vec<u8>>,
+    stream: net::TcpStream,
+}
+
+impl Read for Detached {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.buffered.read(buf)? {
+            0 => self.stream.read(buf),
+            read => Ok(read),
+        }
+    }
+}
+
+impl Write for Detached {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Reads a single request as its [`Framing`] asks for it, the same as
+/// [`connection::read_request`] does, without blocking the runtime while
+/// waiting on the client.
+///
+/// [`Framing`]: ../connection/struct.Framing.html
+/// [`connection::read_request`]: ../connection/fn.read_request.html
+async fn read_request<R: AsyncRuntime>(
+    connection: &mut Buffered<R>,
+    limits: &Limits,
+) -> Result<HttpRequest, ReadError> {
+    let mut framing = Framing::new(limits);
+    loop {
+        match framing.next() {
+            Next::Line(limit, status_code) => {
+                let mut line = Vec::new();
+                let max_read = limit as u64 + 2;
+                let read = connection
+                    .read_until(Some(b'\n'), &mut line, max_read)
+                    .await?;
+                connection::check_line(&line, read == max_read, status_code)?;
+                framing.line_read(&line)?;
+            }
+            Next::Body(length) => {
+                if connection
+                    .read_until(None, framing.body_mut(), length)
+                    .await?
+                    < length
+                {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                framing.body_read();
+            }
+            Next::Done => return framing.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
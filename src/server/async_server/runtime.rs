@@ -0,0 +1,316 @@
+//! The async runtimes a `Server` can be served on, each behind a feature of
+//! its own name. Only what serving needs of a runtime is asked of it, so any
+//! other can be plugged in by implementing [`AsyncRuntime`].
+//!
+//! [`AsyncRuntime`]: ../trait.AsyncRuntime.html
+
+use std::future::Future;
+use std::io;
+use std::net::{self, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// A future boxed up so that it can be handed to and from a runtime.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// What a [`Server`] needs of an async runtime for serving on it through
+/// [`Server::listen_async`]. It is implemented by [`Tokio`], [`AsyncStd`]
+/// and [`Smol`], each behind the feature of the same name.
+///
+/// [`Server`]: ./struct.Server.html
+/// [`Server::listen_async`]: ./struct.Server.html#method.listen_async
+/// [`Tokio`]: ./struct.Tokio.html
+/// [`AsyncStd`]: ./struct.AsyncStd.html
+/// [`Smol`]: ./struct.Smol.html
+pub trait AsyncRuntime: Clone + Send + Sync + 'static {
+    type Listener: Send + Sync + 'static;
+    type Stream: Send + 'static;
+
+    /// Takes over a listener, already set to not block, so that connections
+    /// are accepted through the runtime.
+    fn listener(&self, listener: net::TcpListener) -> io::Result<Self::Listener>;
+
+    fn accept<'a>(&self, listener: &'a Self::Listener) -> BoxFuture<'a, io::Result<Self::Stream>>;
+
+    /// The address of the client and the local one the stream is on.
+    fn addrs(&self, stream: &Self::Stream) -> io::Result<(SocketAddr, SocketAddr)>;
+
+    /// Sets `TCP_NODELAY` on the stream, as the [`SocketOptions`] may ask.
+    ///
+    /// [`SocketOptions`]: ./struct.SocketOptions.html
+    fn set_nodelay(&self, stream: &Self::Stream) -> io::Result<()>;
+
+    fn read<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a mut [u8],
+    ) -> BoxFuture<'a, io::Result<usize>>;
+
+    fn write_all<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a [u8],
+    ) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Takes the stream off of the runtime, blocking once again.
+    fn detach(&self, stream: Self::Stream) -> io::Result<net::TcpStream>;
+
+    /// Runs the future as a task of its own, detached from the caller.
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+
+    /// Runs the function on a thread where it may block, without holding up
+    /// any task.
+    fn spawn_blocking<T, F>(&self, f: F) -> BoxFuture<'static, T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static;
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// Serves on the [tokio](https://tokio.rs) runtime the listen is awaited
+/// on, which needs both its IO and time drivers enabled.
+#[cfg(feature = "tokio")]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Tokio;
+
+#[cfg(feature = "tokio")]
+impl AsyncRuntime for Tokio {
+    type Listener = tokio::net::TcpListener;
+    type Stream = tokio::net::TcpStream;
+
+    fn listener(&self, listener: net::TcpListener) -> io::Result<Self::Listener> {
+        tokio::net::TcpListener::from_std(listener)
+    }
+
+    fn accept<'a>(&self, listener: &'a Self::Listener) -> BoxFuture<'a, io::Result<Self::Stream>> {
+        Box::pin(async move { Ok(listener.accept().await?.0) })
+    }
+
+    fn addrs(&self, stream: &Self::Stream) -> io::Result<(SocketAddr, SocketAddr)> {
+        Ok((stream.peer_addr()?, stream.local_addr()?))
+    }
+
+    fn set_nodelay(&self, stream: &Self::Stream) -> io::Result<()> {
+        stream.set_nodelay(true)
+    }
+
+    fn read<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a mut [u8],
+    ) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(tokio::io::AsyncReadExt::read(stream, buf))
+    }
+
+    fn write_all<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a [u8],
+    ) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(tokio::io::AsyncWriteExt::write_all(stream, buf))
+    }
+
+    fn detach(&self, stream: Self::Stream) -> io::Result<net::TcpStream> {
+        let stream = stream.into_std()?;
+        stream.set_nonblocking(false)?;
+        Ok(stream)
+    }
+
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        tokio::spawn(future);
+    }
+
+    fn spawn_blocking<T, F>(&self, f: F) -> BoxFuture<'static, T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let task = tokio::task::spawn_blocking(f);
+        // Only a panic is passed on, as the runtime is shutting down once a
+        // blocking task is cancelled.
+        Box::pin(async move {
+            match task.await {
+                Ok(output) => output,
+                Err(error) => std::panic::resume_unwind(error.into_panic()),
+            }
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Serves on the [async-std](https://async.rs) runtime.
+#[cfg(feature = "async-std")]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct AsyncStd;
+
+#[cfg(feature = "async-std")]
+impl AsyncRuntime for AsyncStd {
+    type Listener = async_std::net::TcpListener;
+    type Stream = async_std::net::TcpStream;
+
+    fn listener(&self, listener: net::TcpListener) -> io::Result<Self::Listener> {
+        Ok(listener.into())
+    }
+
+    fn accept<'a>(&self, listener: &'a Self::Listener) -> BoxFuture<'a, io::Result<Self::Stream>> {
+        Box::pin(async move { Ok(listener.accept().await?.0) })
+    }
+
+    fn addrs(&self, stream: &Self::Stream) -> io::Result<(SocketAddr, SocketAddr)> {
+        Ok((stream.peer_addr()?, stream.local_addr()?))
+    }
+
+    fn set_nodelay(&self, stream: &Self::Stream) -> io::Result<()> {
+        stream.set_nodelay(true)
+    }
+
+    fn read<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a mut [u8],
+    ) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(async_std::io::ReadExt::read(stream, buf))
+    }
+
+    fn write_all<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a [u8],
+    ) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async_std::io::WriteExt::write_all(stream, buf))
+    }
+
+    fn detach(&self, stream: Self::Stream) -> io::Result<net::TcpStream> {
+        std::convert::TryFrom::try_from(stream)
+    }
+
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        async_std::task::spawn(future);
+    }
+
+    fn spawn_blocking<T, F>(&self, f: F) -> BoxFuture<'static, T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        Box::pin(async_std::task::spawn_blocking(f))
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+/// Serves on the global executor of [smol](https://github.com/smol-rs/smol),
+/// running on as many threads as `SMOL_THREADS` asks for.
+#[cfg(feature = "smol")]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Smol;
+
+#[cfg(feature = "smol")]
+impl AsyncRuntime for Smol {
+    type Listener = smol::net::TcpListener;
+    type Stream = smol::net::TcpStream;
+
+    fn listener(&self, listener: net::TcpListener) -> io::Result<Self::Listener> {
+        std::convert::TryFrom::try_from(listener)
+    }
+
+    fn accept<'a>(&self, listener: &'a Self::Listener) -> BoxFuture<'a, io::Result<Self::Stream>> {
+        Box::pin(async move { Ok(listener.accept().await?.0) })
+    }
+
+    fn addrs(&self, stream: &Self::Stream) -> io::Result<(SocketAddr, SocketAddr)> {
+        Ok((stream.peer_addr()?, stream.local_addr()?))
+    }
+
+    fn set_nodelay(&self, stream: &Self::Stream) -> io::Result<()> {
+        stream.set_nodelay(true)
+    }
+
+    fn read<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a mut [u8],
+    ) -> BoxFuture<'a, io::Result<usize>> {
+        Box::pin(smol::io::AsyncReadExt::read(stream, buf))
+    }
+
+    fn write_all<'a>(
+        &self,
+        stream: &'a mut Self::Stream,
+        buf: &'a [u8],
+    ) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(smol::io::AsyncWriteExt::write_all(stream, buf))
+    }
+
+    fn detach(&self, stream: Self::Stream) -> io::Result<net::TcpStream> {
+        let stream = Arc::<smol::Async<net::TcpStream>>::from(stream);
+        let stream = Arc::try_unwrap(stream)
+            .map_err(|_| io::Error::other("Stream is still shared"))?
+            .into_inner()?;
+        stream.set_nonblocking(false)?;
+        Ok(stream)
+    }
+
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        smol::spawn(future).detach();
+    }
+
+    fn spawn_blocking<T, F>(&self, f: F) -> BoxFuture<'static, T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        Box::pin(smol::unblock(f))
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            smol::Timer::after(duration).await;
+        })
+    }
+}
+
+/// Blocks the calling thread until the future is done, polling it whenever
+/// it is woken. With the `tokio` feature, a future is polled on a tokio
+/// runtime of its own when not called from one, as tokio futures can not be
+/// polled off of one.
+///
+/// # Panics:
+/// If called from within a tokio task rather than a blocking thread.
+pub(in crate::server) fn block_on<F: Future>(future: F) -> F::Output {
+    #[cfg(feature = "tokio")]
+    if tokio::runtime::Handle::try_current().is_err() {
+        return tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Runtime could not be started")
+            .block_on(future);
+    }
+    let mut future = Box::pin(future);
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Wakes a thread blocked on a future.
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
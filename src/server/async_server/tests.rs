@@ -0,0 +1,242 @@
+use crate::server::tests::TestStream;
+use crate::server::{Route, Server};
+use crate::web::{HttpMethod, HttpRequest};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::io::{Read, Write};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+async fn user(request: HttpRequest) -> String {
+    format!("user {}", request.path_param("id").unwrap_or_default())
+}
+
+fn user_server() -> Server {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to_async("/users/{id}", user))
+        .unwrap();
+    server
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+fn bind() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    (listener, address)
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+fn get(address: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path).unwrap();
+    let mut raw_response = String::new();
+    stream.read_to_string(&mut raw_response).unwrap();
+    raw_response
+}
+
+#[test]
+fn should_block_on_async_route_when_served_on_a_thread() {
+    let mut stream = TestStream::of("GET /users/3 HTTP/1.1\r\n\r\n");
+    user_server().serve(&mut stream, None, None).unwrap();
+    assert!(String::from_utf8_lossy(&stream.output).ends_with("user 3"));
+}
+
+#[cfg(feature = "async-std")]
+#[test]
+fn should_await_async_route_when_served_on_async_std() {
+    let (listener, address) = bind();
+    async_std::task::spawn(user_server().serve_async(crate::server::AsyncStd, listener));
+    assert!(get(address, "/users/5").ends_with("\r\n\r\nuser 5"));
+}
+
+#[cfg(feature = "smol")]
+#[test]
+fn should_await_async_route_when_served_on_smol() {
+    let (listener, address) = bind();
+    smol::spawn(user_server().serve_async(crate::server::Smol, listener)).detach();
+    assert!(get(address, "/users/6").ends_with("\r\n\r\nuser 6"));
+}
+
+#[cfg(feature = "tokio")]
+mod on_tokio {
+    use super::{bind, get, user_server};
+    use crate::server::tests::{small_limits_server, trickle_request};
+    use crate::server::{Next, Route, Server, Timeouts, Tokio};
+    use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, StatusCode};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tokio::runtime::{Builder, Runtime};
+
+    /// Serves on a runtime with a single worker thread, so that requests are
+    /// only served alongside each other when nothing blocks it.
+    fn listen_async(server: Server) -> (Runtime, SocketAddr) {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        let (listener, address) = bind();
+        runtime.spawn(server.serve_async(Tokio, listener));
+        (runtime, address)
+    }
+
+    #[test]
+    fn should_await_async_route_when_listening_async() {
+        let (_runtime, address) = listen_async(user_server());
+        let raw_response = get(address, "/users/7");
+        assert!(raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(raw_response.ends_with("\r\n\r\nuser 7"));
+    }
+
+    #[test]
+    fn should_serve_other_connections_when_async_route_is_waiting() {
+        let mut server = Server::default();
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get)
+                    .to_async("/slow", |_| async {
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        "slow"
+                    })
+                    .to("/fast", |_| "fast")
+            })
+            .unwrap();
+        let (_runtime, address) = listen_async(server);
+        let slow = thread::spawn(move || get(address, "/slow"));
+        thread::sleep(Duration::from_millis(50));
+        let started = Instant::now();
+        assert!(get(address, "/fast").ends_with("fast"));
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(slow.join().unwrap().ends_with("slow"));
+    }
+
+    #[test]
+    fn should_block_a_thread_of_its_own_on_async_route_when_behind_middleware() {
+        let mut server = Server::default();
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get)
+                    .to_async("/slow", |_| async {
+                        thread::sleep(Duration::from_millis(500));
+                        "slow"
+                    })
+                    .to_async("/fast", |_| async { "fast" })
+            })
+            .unwrap();
+        server.wrap(|request: HttpRequest, next: Next| {
+            let mut response = next.run(request);
+            response.headers.insert("X-Wrapped".into(), "yes".into());
+            response
+        });
+        let (_runtime, address) = listen_async(server);
+        let slow = thread::spawn(move || get(address, "/slow"));
+        thread::sleep(Duration::from_millis(50));
+        let started = Instant::now();
+        let fast = get(address, "/fast");
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(fast.contains("X-Wrapped: yes\r\n"));
+        assert!(fast.ends_with("\r\n\r\nfast"));
+        assert!(slow.join().unwrap().ends_with("slow"));
+    }
+
+    #[test]
+    fn should_keep_connection_alive_when_listening_async() {
+        let (_runtime, address) = listen_async(user_server());
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(
+                b"GET /users/1 HTTP/1.1\r\n\r\nGET /users/2 HTTP/1.1\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut raw_response = String::new();
+        stream.read_to_string(&mut raw_response).unwrap();
+        assert_eq!(raw_response.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(raw_response.contains("user 1"));
+        assert!(raw_response.ends_with("user 2"));
+    }
+
+    #[test]
+    fn should_answer_with_500_when_async_route_panics() {
+        let mut server = Server::default();
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get).to_async("/", |_| async {
+                    if true {
+                        panic!("Broken");
+                    }
+                    HttpResponse::new(StatusCode::Ok)
+                })
+            })
+            .unwrap();
+        let (_runtime, address) = listen_async(server);
+        assert!(get(address, "/").starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    }
+
+    #[test]
+    fn should_hold_request_to_limits_when_listening_async() {
+        let (_runtime, address) = listen_async(small_limits_server());
+        let send = |raw_request: &[u8]| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(raw_request).unwrap();
+            let mut raw_response = String::new();
+            stream.read_to_string(&mut raw_response).unwrap();
+            raw_response
+        };
+        let chunked = send(
+            b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n12345\r\n5\r\n67890\r\n0\r\n\r\n",
+        );
+        assert!(chunked.starts_with("HTTP/1.1 413 "));
+        let headers = send(b"POST /echo HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n");
+        assert!(headers.starts_with("HTTP/1.1 431 "));
+        let within =
+            send(b"POST /echo HTTP/1.1\r\nConnection: close\r\nContent-Length: 8\r\n\r\n12345678");
+        assert!(within.ends_with("\r\n\r\n12345678"));
+    }
+
+    #[test]
+    fn should_answer_with_408_when_client_trickles_request_past_read_timeout() {
+        let mut server = user_server();
+        server.timeouts(Timeouts {
+            read: Some(Duration::from_millis(100)),
+            ..Timeouts::default()
+        });
+        let (_runtime, address) = listen_async(server);
+        assert!(trickle_request(address).starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    }
+
+    #[test]
+    fn should_answer_with_503_when_async_route_times_out() {
+        let mut server = Server::default();
+        server.timeouts(Timeouts {
+            handler: Some(Duration::from_millis(50)),
+            ..Timeouts::default()
+        });
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get).to_async("/", |_| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "late"
+                })
+            })
+            .unwrap();
+        let (_runtime, address) = listen_async(server);
+        assert!(get(address, "/").starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    }
+
+    #[test]
+    fn should_stream_body_as_chunks_when_listening_async() {
+        let mut server = Server::default();
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get).to("/", |_| HttpResponse {
+                    body: Body::Stream(Box::new(&b"streamed"[..])),
+                    ..HttpResponse::new(StatusCode::Ok)
+                })
+            })
+            .unwrap();
+        let (_runtime, address) = listen_async(server);
+        assert!(get(address, "/").ends_with("\r\n\r\n8\r\nstreamed\r\n0\r\n\r\n"));
+    }
+}
@@ -0,0 +1,277 @@
+//! Validating JSON Web Tokens, see
+//! [RFC 7519](https://www.rfc-editor.org/rfc/rfc7519), signed with either
+//! `HS256` or `RS256`.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ring::{hmac, signature};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::web::encoding::base64url_decode;
+use crate::web::{HttpRequest, HttpResponse};
+
+use super::{bearer_challenge, bearer_token, unauthorized};
+use crate::server::{Middleware, Next};
+
+/// [`Middleware`] authenticating requests with a JSON Web Token, sent through
+/// the `Bearer` scheme. A token is only valid when its signature is one of
+/// the key, made with the algorithm of the key, and when it is within its
+/// `exp` and `nbf`, as well as for the audience and of the issuer, when
+/// either are asked for. The [`Claims`] of a valid token are the principal.
+///
+/// The `exp` and `nbf` claims are only checked when a token has them, so a
+/// token without `exp` never expires.
+///
+/// # Examples:
+/// ```
+/// use martian::server::auth::{Claims, JwtAuth};
+/// use martian::server::{Route, Server};
+/// use martian::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+/// use std::time::Duration;
+/// let mut server = Server::default();
+/// server.route(|| {
+///     Route::bind(HttpMethod::Delete)
+///         .to("/users/{id}", |request: HttpRequest| {
+///             let claims = request.extensions.get::<Claims>().unwrap();
+///             match claims.contains("roles", "admin") {
+///                 true => HttpResponse::new(StatusCode::NoContent),
+///                 false => HttpResponse::new(StatusCode::Forbidden),
+///             }
+///         })
+///         .with(
+///             JwtAuth::hs256(b"a secret only the issuer knows")
+///                 .issuer("https://auth.example.com")
+///                 .audience("users-api")
+///                 .leeway(Duration::from_secs(30)),
+///         )
+/// })
+/// .unwrap();
+/// ```
+///
+/// [`Middleware`]: ../trait.Middleware.html
+/// [`Claims`]: ./struct.Claims.html
+#[derive(Debug)]
+pub struct JwtAuth {
+    realm: String,
+    key: Key,
+    audience: Option<String>,
+    issuer: Option<String>,
+    leeway: Duration,
+}
+
+#[derive(Debug)]
+enum Key {
+    Hs256(hmac::Key),
+    /// The DER of an `RSAPublicKey`.
+    Rs256(Vec<u8>),
+}
+
+impl Key {
+    fn algorithm(&self) -> &'static str {
+        match self {
+            Key::Hs256(_) => "HS256",
+            Key::Rs256(_) => "RS256",
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Key::Hs256(key) => hmac::verify(key, message, signature).is_ok(),
+            Key::Rs256(public_key) => signature::UnparsedPublicKey::new(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                public_key,
+            )
+            .verify(message, signature)
+            .is_ok(),
+        }
+    }
+}
+
+impl JwtAuth {
+    /// Accepts tokens signed with `HS256`, an HMAC of the shared secret.
+    pub fn hs256(secret: &[u8]) -> JwtAuth {
+        JwtAuth::with_key(Key::Hs256(hmac::Key::new(hmac::HMAC_SHA256, secret)))
+    }
+
+    /// Accepts tokens signed with `RS256` by the private key of the public
+    /// key, given as the DER of a PKCS#1 `RSAPublicKey`, such as
+    /// `openssl rsa -pubin -in public.pem -RSAPublicKey_out -outform DER`
+    /// writes.
+    pub fn rs256(public_key: &[u8]) -> JwtAuth {
+        JwtAuth::with_key(Key::Rs256(public_key.to_vec()))
+    }
+
+    fn with_key(key: Key) -> JwtAuth {
+        JwtAuth {
+            realm: "api".into(),
+            key,
+            audience: None,
+            issuer: None,
+            leeway: Duration::ZERO,
+        }
+    }
+
+    /// The realm rejected requests are asked for a token to, `api` unless
+    /// given.
+    pub fn realm(mut self, realm: &str) -> JwtAuth {
+        self.realm = realm.into();
+        self
+    }
+
+    /// Only accepts tokens whose `aud` is, or lists, the audience.
+    pub fn audience(mut self, audience: &str) -> JwtAuth {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Only accepts tokens whose `iss` is the issuer.
+    pub fn issuer(mut self, issuer: &str) -> JwtAuth {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// How far the clock of the issuer may be off from ours, stretching the
+    /// `exp` and `nbf` of tokens by as much.
+    pub fn leeway(mut self, leeway: Duration) -> JwtAuth {
+        self.leeway = leeway;
+        self
+    }
+
+    /// The claims of the token, if it is valid.
+    pub fn validate(&self, token: &str) -> Option<Claims> {
+        let (message, signature) = token.rsplit_once('.')?;
+        let (header, payload) = message.split_once('.')?;
+        if payload.contains('.') {
+            return None;
+        }
+        let header = decode_object(header)?;
+        if header.get("alg").and_then(Value::as_str) != Some(self.key.algorithm()) {
+            return None;
+        }
+        if !self
+            .key
+            .verify(message.as_bytes(), &base64url_decode(signature)?)
+        {
+            return None;
+        }
+        let claims = Claims {
+            claims: decode_object(payload)?,
+        };
+        match self.is_current(&claims) && self.is_intended(&claims) {
+            true => Some(claims),
+            false => None,
+        }
+    }
+
+    /// Whether it is past the `nbf` of the claims and not yet their `exp`.
+    fn is_current(&self, claims: &Claims) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let leeway = self.leeway.as_secs_f64();
+        // A time which is not a number can not be trusted to have passed.
+        let is_expired = match claims.get("exp").map(Value::as_f64) {
+            Some(Some(exp)) => now >= exp + leeway,
+            Some(None) => true,
+            None => false,
+        };
+        let is_early = match claims.get("nbf").map(Value::as_f64) {
+            Some(Some(nbf)) => now + leeway < nbf,
+            Some(None) => true,
+            None => false,
+        };
+        !is_expired && !is_early
+    }
+
+    /// Whether the claims are for the audience and of the issuer asked for.
+    fn is_intended(&self, claims: &Claims) -> bool {
+        let is_for_audience = match &self.audience {
+            Some(audience) => claims.contains_exactly("aud", audience),
+            None => true,
+        };
+        let is_of_issuer = match &self.issuer {
+            Some(issuer) => claims.get("iss").and_then(Value::as_str) == Some(issuer),
+            None => true,
+        };
+        is_for_audience && is_of_issuer
+    }
+}
+
+impl Middleware for JwtAuth {
+    fn handle(&self, mut request: HttpRequest, next: Next) -> HttpResponse {
+        let token = bearer_token(&request);
+        match token.and_then(|token| self.validate(token)) {
+            Some(claims) => {
+                request.extensions.insert(claims);
+                next.run(request)
+            }
+            None => unauthorized(bearer_challenge(&self.realm, token.is_some())),
+        }
+    }
+}
+
+/// The claims of a valid token, as [`JwtAuth`] puts them in the
+/// [`extensions`] of its request.
+///
+/// [`JwtAuth`]: ./struct.JwtAuth.html
+/// [`extensions`]: ../../web/struct.HttpRequest.html#structfield.extensions
+#[derive(PartialEq, Debug, Clone)]
+pub struct Claims {
+    claims: Map<String, Value>,
+}
+
+impl Claims {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.claims.get(name)
+    }
+
+    /// Who the token is about, its `sub`.
+    pub fn subject(&self) -> Option<&str> {
+        self.get("sub").and_then(Value::as_str)
+    }
+
+    /// Whether the claim is the value, lists it, or holds it among other
+    /// values separated by spaces, as `scope` does.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::auth::{Claims, JwtAuth};
+    /// fn can_write(claims: &Claims) -> bool {
+    ///     claims.contains("roles", "editor") || claims.contains("scope", "items:write")
+    /// }
+    /// ```
+    pub fn contains(&self, name: &str, value: &str) -> bool {
+        match self.get(name) {
+            Some(Value::String(values)) => values.split(' ').any(|v| v == value),
+            Some(Value::Array(_)) => self.contains_exactly(name, value),
+            _ => false,
+        }
+    }
+
+    /// Whether the claim is the value, or lists it.
+    fn contains_exactly(&self, name: &str, value: &str) -> bool {
+        match self.get(name) {
+            Some(Value::String(v)) => v == value,
+            Some(Value::Array(values)) => values.iter().any(|v| v.as_str() == Some(value)),
+            _ => false,
+        }
+    }
+
+    /// The claims as a type of their own, such as one naming the claims an
+    /// issuer adds.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_value(Value::Object(self.claims.clone()))
+    }
+}
+
+fn decode_object(segment: &str) -> Option<Map<String, Value>> {
+    match serde_json::from_slice(&base64url_decode(segment)?).ok()? {
+        Value::Object(object) => Some(object),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
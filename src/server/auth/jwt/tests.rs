@@ -0,0 +1,201 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ring::{hmac, rand, signature};
+use serde_json::{json, Value};
+
+use crate::server::auth::{Claims, JwtAuth};
+use crate::server::tests::fixture;
+use crate::server::{Route, Server};
+use crate::test::TestClient;
+use crate::web::encoding::{base64_decode, base64url_encode};
+use crate::web::{Body, HttpMethod, HttpRequest, StatusCode};
+
+const SECRET: &[u8] = b"a secret only the issuer knows";
+
+fn unsigned(algorithm: &str, claims: &Value) -> String {
+    let header = json!({ "alg": algorithm, "typ": "JWT" });
+    format!(
+        "{}.{}",
+        base64url_encode(header.to_string().as_bytes()),
+        base64url_encode(claims.to_string().as_bytes())
+    )
+}
+
+fn hs256(claims: &Value) -> String {
+    let message = unsigned("HS256", claims);
+    let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET);
+    let tag = hmac::sign(&key, message.as_bytes());
+    format!("{}.{}", message, base64url_encode(tag.as_ref()))
+}
+
+fn rsa_key_pair() -> signature::RsaKeyPair {
+    let pem = std::fs::read_to_string(fixture("jwt-key.pem")).unwrap();
+    let base64 = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+    signature::RsaKeyPair::from_pkcs8(&base64_decode(&base64).unwrap()).unwrap()
+}
+
+fn rs256(key_pair: &signature::RsaKeyPair, claims: &Value) -> String {
+    let message = unsigned("RS256", claims);
+    let mut signature = vec![0; key_pair.public().modulus_len()];
+    key_pair
+        .sign(
+            &signature::RSA_PKCS1_SHA256,
+            &rand::SystemRandom::new(),
+            message.as_bytes(),
+            &mut signature,
+        )
+        .unwrap();
+    format!("{}.{}", message, base64url_encode(&signature))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn restricted() -> JwtAuth {
+    JwtAuth::hs256(SECRET)
+        .issuer("https://auth.example.com")
+        .audience("users-api")
+}
+
+#[test]
+fn should_accept_token_when_hs256_signature_and_claims_are_valid() {
+    let claims = restricted().validate(&hs256(&json!({
+        "sub": "ada",
+        "iss": "https://auth.example.com",
+        "aud": ["billing-api", "users-api"],
+        "exp": now() + 60,
+        "nbf": now() - 60,
+        "roles": ["admin"],
+    })));
+    let claims = claims.unwrap();
+    assert_eq!(claims.subject(), Some("ada"));
+    assert!(claims.contains("roles", "admin"));
+    assert!(!claims.contains("roles", "guest"));
+}
+
+#[test]
+fn should_accept_token_when_rs256_signature_is_valid() {
+    let key_pair = rsa_key_pair();
+    let jwt_auth = JwtAuth::rs256(key_pair.public().as_ref());
+    let claims = jwt_auth.validate(&rs256(&key_pair, &json!({ "sub": "ada" })));
+    assert_eq!(claims.unwrap().subject(), Some("ada"));
+}
+
+#[test]
+fn should_reject_token_when_signature_is_not_of_key() {
+    let token = hs256(&json!({ "sub": "ada" }));
+    assert!(JwtAuth::hs256(b"another secret").validate(&token).is_none());
+    let (message, _) = token.rsplit_once('.').unwrap();
+    let tampered = format!("{}.{}", message, base64url_encode(b"forged"));
+    assert!(JwtAuth::hs256(SECRET).validate(&tampered).is_none());
+}
+
+#[test]
+fn should_reject_token_when_algorithm_is_not_of_key() {
+    let key_pair = rsa_key_pair();
+    let public_key = key_pair.public().as_ref().to_vec();
+    // Signed with the public key as an HMAC secret, which anyone could do.
+    let message = unsigned("HS256", &json!({ "sub": "mallory" }));
+    let tag = hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, &public_key),
+        message.as_bytes(),
+    );
+    let token = format!("{}.{}", message, base64url_encode(tag.as_ref()));
+    assert!(JwtAuth::rs256(&public_key).validate(&token).is_none());
+    let none = format!("{}.", unsigned("none", &json!({ "sub": "mallory" })));
+    assert!(JwtAuth::hs256(SECRET).validate(&none).is_none());
+}
+
+#[test]
+fn should_reject_token_when_expired_or_not_yet_valid() {
+    let jwt_auth = JwtAuth::hs256(SECRET);
+    for claims in &[
+        json!({ "exp": now() - 10 }),
+        json!({ "nbf": now() + 60 }),
+        json!({ "exp": "tomorrow" }),
+    ] {
+        assert!(jwt_auth.validate(&hs256(claims)).is_none(), "{}", claims);
+    }
+    let lenient = JwtAuth::hs256(SECRET).leeway(Duration::from_secs(30));
+    assert!(lenient
+        .validate(&hs256(&json!({ "exp": now() - 10 })))
+        .is_some());
+}
+
+#[test]
+fn should_reject_token_when_audience_or_issuer_is_wrong() {
+    for claims in &[
+        json!({ "iss": "https://auth.example.com", "aud": "billing-api" }),
+        json!({ "iss": "https://auth.example.com" }),
+        json!({ "iss": "https://evil.example.com", "aud": "users-api" }),
+    ] {
+        assert!(
+            restricted().validate(&hs256(claims)).is_none(),
+            "{}",
+            claims
+        );
+    }
+}
+
+#[test]
+fn should_reject_token_when_malformed() {
+    let jwt_auth = JwtAuth::hs256(SECRET);
+    let token = hs256(&json!({ "sub": "ada" }));
+    for malformed in &["", "a.b", "not.base64!.at-all", &format!("{}.extra", token)] {
+        assert!(jwt_auth.validate(malformed).is_none(), "{}", malformed);
+    }
+}
+
+#[test]
+fn should_pass_claims_on_when_request_has_valid_token() {
+    let mut server = Server::default();
+    server.wrap(JwtAuth::hs256(SECRET).realm("users"));
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/me", |request: HttpRequest| {
+                let claims = request.extensions.get::<Claims>().unwrap();
+                claims.subject().unwrap_or_default().to_string()
+            })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let get = |authorization: &str| {
+        client
+            .request(HttpMethod::Get, "/me")
+            .header("Authorization", authorization)
+            .send()
+    };
+    let response = get(&format!("Bearer {}", hs256(&json!({ "sub": "ada" }))));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, Body::from("ada"));
+    let response = get("Bearer not.a.token");
+    assert_eq!(response.status_code, StatusCode::Unauthorized);
+    assert_eq!(
+        response.headers.get("WWW-Authenticate").map(String::as_str),
+        Some("Bearer realm=\"users\", error=\"invalid_token\"")
+    );
+}
+
+#[test]
+fn should_deserialize_claims_when_asked_for_a_type() {
+    #[derive(serde::Deserialize)]
+    struct Custom {
+        sub: String,
+        tenant: u32,
+    }
+    let claims = JwtAuth::hs256(SECRET)
+        .validate(&hs256(
+            &json!({ "sub": "ada", "tenant": 7, "scope": "items:read items:write" }),
+        ))
+        .unwrap();
+    assert!(claims.contains("scope", "items:write"));
+    let custom = claims.deserialize::<Custom>().unwrap();
+    assert_eq!((custom.sub.as_str(), custom.tenant), ("ada", 7));
+}
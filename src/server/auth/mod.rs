@@ -0,0 +1,188 @@
+//! Authenticating requests through their `Authorization` header, before they
+//! reach a callback. A request which can not be authenticated is answered
+//! with a 401 asking for credentials through `WWW-Authenticate`. One which
+//! can is passed on with whoever it was authenticated as, the principal, in
+//! its [`extensions`].
+//!
+//! # Examples:
+//! ```
+//! use martian::server::auth::BasicAuth;
+//! use martian::server::{Route, Server};
+//! use martian::web::{HttpMethod, HttpRequest};
+//! struct User {
+//!     name: String,
+//! }
+//! let mut server = Server::default();
+//! server.route(|| {
+//!     Route::bind(HttpMethod::Get)
+//!         .to("/me", |request: HttpRequest| {
+//!             request.extensions.get::<User>().unwrap().name.clone()
+//!         })
+//!         .with(BasicAuth::new("admin", |user: &str, password: &str| {
+//!             match (user, password) {
+//!                 ("ada", "lovelace") => Some(User { name: user.into() }),
+//!                 _ => None,
+//!             }
+//!         }))
+//! })
+//! .unwrap();
+//! ```
+//!
+//! [`extensions`]: ../../web/struct.HttpRequest.html#structfield.extensions
+
+use crate::web::{Authorization, HttpRequest, HttpResponse, StatusCode};
+
+use super::{Middleware, Next};
+
+#[cfg(feature = "jwt")]
+pub use self::jwt::{Claims, JwtAuth};
+
+#[cfg(feature = "jwt")]
+mod jwt;
+
+/// [`Middleware`] authenticating requests with a user and password, sent
+/// through the `Basic` scheme, as checked by the verifier. Whatever it
+/// returns for the credentials is the principal, with `None` rejecting them.
+///
+/// The credentials are only encoded, not encrypted, so they are best only
+/// ever sent over TLS.
+///
+/// [`Middleware`]: ../trait.Middleware.html
+#[derive(Debug, Clone)]
+pub struct BasicAuth<F> {
+    realm: String,
+    verify: F,
+}
+
+impl<F, P> BasicAuth<F>
+where
+    F: Fn(&str, &str) -> Option<P> + Send + Sync,
+    P: Send + Sync + 'static,
+{
+    /// Asks for credentials to the realm, which browsers show when prompting
+    /// for them.
+    pub fn new(realm: &str, verify: F) -> BasicAuth<F> {
+        BasicAuth {
+            realm: realm.into(),
+            verify,
+        }
+    }
+
+    fn authenticate(&self, request: &HttpRequest) -> Option<P> {
+        match request.authorization()? {
+            Authorization::Basic { user, password } => (self.verify)(&user, &password),
+            _ => None,
+        }
+    }
+}
+
+impl<F, P> Middleware for BasicAuth<F>
+where
+    F: Fn(&str, &str) -> Option<P> + Send + Sync,
+    P: Send + Sync + 'static,
+{
+    fn handle(&self, mut request: HttpRequest, next: Next) -> HttpResponse {
+        match self.authenticate(&request) {
+            Some(principal) => {
+                request.extensions.insert(principal);
+                next.run(request)
+            }
+            None => unauthorized(format!(
+                "Basic realm=\"{}\", charset=\"UTF-8\"",
+                escape(&self.realm)
+            )),
+        }
+    }
+}
+
+/// [`Middleware`] authenticating requests with a token, sent through the
+/// `Bearer` scheme, as checked by the validator. Whatever it returns for
+/// the token is the principal, with `None` rejecting it.
+///
+/// # Examples:
+/// ```
+/// use martian::server::auth::BearerAuth;
+/// use martian::server::Server;
+/// let mut server = Server::default();
+/// server.wrap(BearerAuth::new("api", |token: &str| match token {
+///     "s3cr3t" => Some("service-account".to_string()),
+///     _ => None,
+/// }));
+/// ```
+///
+/// [`Middleware`]: ../trait.Middleware.html
+#[derive(Debug, Clone)]
+pub struct BearerAuth<F> {
+    realm: String,
+    validate: F,
+}
+
+impl<F, P> BearerAuth<F>
+where
+    F: Fn(&str) -> Option<P> + Send + Sync,
+    P: Send + Sync + 'static,
+{
+    pub fn new(realm: &str, validate: F) -> BearerAuth<F> {
+        BearerAuth {
+            realm: realm.into(),
+            validate,
+        }
+    }
+}
+
+impl<F, P> Middleware for BearerAuth<F>
+where
+    F: Fn(&str) -> Option<P> + Send + Sync,
+    P: Send + Sync + 'static,
+{
+    fn handle(&self, mut request: HttpRequest, next: Next) -> HttpResponse {
+        let token = bearer_token(&request);
+        match token.and_then(|token| (self.validate)(token)) {
+            Some(principal) => {
+                request.extensions.insert(principal);
+                next.run(request)
+            }
+            // A token was sent, it just was not any good.
+            None => unauthorized(bearer_challenge(&self.realm, token.is_some())),
+        }
+    }
+}
+
+/// The `WWW-Authenticate` of a `Bearer` scheme, telling the client whether
+/// the token it sent is no good, as opposed to missing, see
+/// [RFC 6750](https://www.rfc-editor.org/rfc/rfc6750#section-3).
+pub(in crate::server) fn bearer_challenge(realm: &str, is_invalid: bool) -> String {
+    match is_invalid {
+        true => format!(
+            "Bearer realm=\"{}\", error=\"invalid_token\"",
+            escape(realm)
+        ),
+        false => format!("Bearer realm=\"{}\"", escape(realm)),
+    }
+}
+
+/// The token of the `Authorization` header, as long as it is of the `Bearer`
+/// scheme.
+pub(in crate::server) fn bearer_token(request: &HttpRequest) -> Option<&str> {
+    match request.authorization()? {
+        Authorization::Bearer(token) => Some(token),
+        _ => None,
+    }
+}
+
+/// A 401 asking for credentials through the challenge.
+pub(in crate::server) fn unauthorized(challenge: String) -> HttpResponse {
+    let mut response = HttpResponse::new(StatusCode::Unauthorized);
+    response
+        .headers
+        .insert("WWW-Authenticate".into(), challenge);
+    response
+}
+
+/// Escapes the quotes and backslashes of a value written within quotes.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,106 @@
+use crate::server::auth::{BasicAuth, BearerAuth};
+use crate::server::{Route, Server};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{Body, HttpMethod, HttpRequest, StatusCode};
+
+#[derive(PartialEq, Debug)]
+struct User(String);
+
+fn auth_client<F>(with: F) -> TestClient
+where
+    F: FnOnce(&mut Server),
+{
+    let mut server = Server::default();
+    with(&mut server);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/me", |request: HttpRequest| {
+                request.extensions.get::<User>().unwrap().0.clone()
+            })
+        })
+        .unwrap();
+    TestClient::new(server)
+}
+
+fn basic_client() -> TestClient {
+    auth_client(|server| {
+        server.wrap(BasicAuth::new(
+            "admin \"area\"",
+            |user: &str, password: &str| match (user, password) {
+                ("ada", "love:lace") => Some(User(user.into())),
+                _ => None,
+            },
+        ))
+    })
+}
+
+fn bearer_client() -> TestClient {
+    auth_client(|server| {
+        server.wrap(BearerAuth::new("api", |token: &str| match token {
+            "s3cr3t" => Some(User("service".into())),
+            _ => None,
+        }))
+    })
+}
+
+fn get(client: &TestClient, authorization: Option<&str>) -> TestResponse {
+    let request = client.request(HttpMethod::Get, "/me");
+    match authorization {
+        Some(authorization) => request.header("Authorization", authorization).send(),
+        None => request.send(),
+    }
+}
+
+#[test]
+fn should_pass_principal_on_when_basic_credentials_are_verified() {
+    // "ada:love:lace", the password holding a colon of its own.
+    let response = get(&basic_client(), Some("basic YWRhOmxvdmU6bGFjZQ=="));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, Body::from("ada"));
+}
+
+#[test]
+fn should_ask_for_credentials_when_basic_credentials_are_missing_or_wrong() {
+    let client = basic_client();
+    // "ada:babbage", "not base64" and a token of another scheme.
+    for authorization in &[
+        None,
+        Some("Basic YWRhOmJhYmJhZ2U="),
+        Some("Basic %%"),
+        Some("Bearer s3cr3t"),
+    ] {
+        let response = get(&client, *authorization);
+        assert_eq!(response.status_code, StatusCode::Unauthorized);
+        assert_eq!(
+            response.headers.get("WWW-Authenticate").map(String::as_str),
+            Some("Basic realm=\"admin \\\"area\\\"\", charset=\"UTF-8\"")
+        );
+    }
+}
+
+#[test]
+fn should_pass_principal_on_when_bearer_token_is_valid() {
+    let response = get(&bearer_client(), Some("Bearer s3cr3t"));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, Body::from("service"));
+}
+
+#[test]
+fn should_ask_for_token_when_bearer_token_is_missing() {
+    let response = get(&bearer_client(), None);
+    assert_eq!(response.status_code, StatusCode::Unauthorized);
+    assert_eq!(
+        response.headers.get("WWW-Authenticate").map(String::as_str),
+        Some("Bearer realm=\"api\"")
+    );
+}
+
+#[test]
+fn should_reject_token_as_invalid_when_bearer_token_is_not_valid() {
+    let response = get(&bearer_client(), Some("Bearer guessed"));
+    assert_eq!(response.status_code, StatusCode::Unauthorized);
+    assert_eq!(
+        response.headers.get("WWW-Authenticate").map(String::as_str),
+        Some("Bearer realm=\"api\", error=\"invalid_token\"")
+    );
+}
@@ -0,0 +1,354 @@
+//! Compressing the bodies of responses for the clients which accept them
+//! compressed, as negotiated through `Accept-Encoding`, and decompressing
+//! those of requests sent compressed.
+
+use std::io::{self, Read, Write};
+
+#[cfg(feature = "gzip")]
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+#[cfg(feature = "deflate")]
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+
+use crate::web::negotiation::{self, Negotiable};
+use crate::web::{header_value, Body, HttpRequest, HttpResponse, Mime, StatusCode};
+
+use super::{Middleware, Next};
+
+/// The codings a body may be compressed with, in the order they are
+/// preferred in when a client accepts several as much.
+const CODINGS: &[Coding] = &[
+    #[cfg(feature = "gzip")]
+    Coding::Gzip,
+    #[cfg(feature = "deflate")]
+    Coding::Deflate,
+];
+
+/// [`Middleware`] compressing the body of a response with a coding the
+/// client accepts, setting its `Content-Encoding` and adding
+/// `Accept-Encoding` to its `Vary`. `gzip` and `deflate` are each behind a
+/// feature of the same name.
+///
+/// Only a body in memory is compressed, as long as it is at least the
+/// [`min_size`], which by default is 1 KiB, below which compressing saves
+/// next to nothing. Neither is a body which is compressed already, going by
+/// its `Content-Type` or `Content-Encoding`, nor one of a response with
+/// `Cache-Control: no-transform`.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Compression, Server};
+/// let mut server = Server::default();
+/// server.wrap(Compression::new().min_size(512));
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`min_size`]: ./struct.Compression.html#method.min_size
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Compression {
+    min_size: usize,
+}
+
+impl Default for Compression {
+    fn default() -> Compression {
+        Compression { min_size: 1024 }
+    }
+}
+
+impl Compression {
+    pub fn new() -> Compression {
+        Compression::default()
+    }
+
+    /// The smallest body compressed, in bytes.
+    pub fn min_size(mut self, min_size: usize) -> Compression {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Whether the response could be compressed, whichever codings the
+    /// client accepts.
+    fn is_compressible(&self, response: &HttpResponse) -> bool {
+        let is_large_enough = match &response.body {
+            Body::Bytes(bytes) => bytes.len() >= self.min_size,
+            _ => false,
+        };
+        let headers = &response.headers;
+        let is_no_transform = header_value(headers, "Cache-Control").is_some_and(|cache_control| {
+            cache_control
+                .split(',')
+                .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+        });
+        is_large_enough
+            && response.status_code != StatusCode::PartialContent
+            && header_value(headers, "Content-Encoding").is_none()
+            && !header_value(headers, "Content-Type").is_some_and(is_compressed)
+            && !is_no_transform
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let coding = request.header("Accept-Encoding").and_then(negotiate);
+        let mut response = next.run(request);
+        if !self.is_compressible(&response) {
+            return response;
+        }
+        negotiation::add_vary(&mut response.headers, "Accept-Encoding");
+        let (coding, bytes) = match (coding, &response.body) {
+            (Some(coding), Body::Bytes(bytes)) => (coding, bytes),
+            _ => return response,
+        };
+        response.body = match coding.encode(bytes) {
+            Ok(encoded) => Body::Bytes(encoded),
+            Err(_) => return response,
+        };
+        response
+            .headers
+            .insert("Content-Encoding".into(), coding.as_str().into());
+        // The compressed body is no longer the same bytes a strong validator
+        // promises.
+        if let Some(etag) = response.headers.get_mut("ETag") {
+            if !etag.starts_with("W/") {
+                etag.insert_str(0, "W/");
+            }
+        }
+        response
+    }
+}
+
+/// [`Middleware`] decompressing the body of a request sent with a
+/// `Content-Encoding`, before any callback sees it, the coding being taken
+/// off of its headers along with the `Content-Length` following the body.
+/// `gzip` and `deflate` are each behind a feature of the same name, a
+/// request compressed any other way being answered with a 415.
+///
+/// A body inflating to more than the [`max_size`], by default 10 MiB, is
+/// answered with a 413 as soon as it gets there, so that a small request can
+/// not inflate to fill the memory. One which is not what its coding says is
+/// answered with a 400.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Decompression, Server};
+/// let mut server = Server::default();
+/// server.wrap(Decompression::new().max_size(64 * 1024 * 1024));
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`max_size`]: ./struct.Decompression.html#method.max_size
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Decompression {
+    max_size: usize,
+}
+
+impl Default for Decompression {
+    fn default() -> Decompression {
+        Decompression {
+            max_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl Decompression {
+    pub fn new() -> Decompression {
+        Decompression::default()
+    }
+
+    /// The largest a body may inflate to, in bytes.
+    pub fn max_size(mut self, max_size: usize) -> Decompression {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The body undone of every coding of the `Content-Encoding`, the last
+    /// applied being the first undone.
+    fn decode(&self, content_encoding: &str, body: Vec<u8>) -> Result<Vec<u8>, HttpResponse> {
+        let mut decoded = body;
+        for name in content_encoding.rsplit(',').map(str::trim) {
+            if name.is_empty() || name.eq_ignore_ascii_case("identity") {
+                continue;
+            }
+            let coding = CODINGS
+                .iter()
+                .find(|coding| coding.as_str().eq_ignore_ascii_case(name))
+                .ok_or_else(|| HttpResponse::new(StatusCode::UnsupportedMediaType))?;
+            decoded = coding
+                .decode(&decoded, self.max_size)
+                .map_err(|error| match error {
+                    InflateError::Malformed => {
+                        HttpResponse::bad_request("Malformed compressed body")
+                    }
+                    InflateError::TooLarge => HttpResponse::new(StatusCode::ContentTooLarge),
+                })?;
+        }
+        Ok(decoded)
+    }
+}
+
+impl Middleware for Decompression {
+    fn handle(&self, mut request: HttpRequest, next: Next) -> HttpResponse {
+        let headers = match &mut request.headers {
+            Some(headers) => headers,
+            None => return next.run(request),
+        };
+        let content_encoding = match headers
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case("Content-Encoding"))
+            .cloned()
+        {
+            Some(key) => headers.remove(&key).unwrap_or_default(),
+            None => return next.run(request),
+        };
+        let body = request.body.take().unwrap_or_default();
+        let decoded = match self.decode(&content_encoding, body) {
+            Ok(decoded) => decoded,
+            Err(response) => return response,
+        };
+        if let Some(key) = headers
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case("Content-Length"))
+            .cloned()
+        {
+            headers.insert(key, decoded.len().to_string());
+        }
+        request.body = match decoded.is_empty() {
+            true => None,
+            false => Some(decoded),
+        };
+        next.run(request)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Coding {
+    /// DEFLATE in the gzip format, see
+    /// [RFC 1952](https://www.rfc-editor.org/rfc/rfc1952).
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// DEFLATE in the zlib format, see
+    /// [RFC 1950](https://www.rfc-editor.org/rfc/rfc1950), which is what
+    /// HTTP means by `deflate`.
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+impl Coding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Coding::Gzip => "gzip",
+            #[cfg(feature = "deflate")]
+            Coding::Deflate => "deflate",
+        }
+    }
+
+    /// The bytes in the coding, compressed at the default level.
+    ///
+    /// # Returns:
+    /// An `Err` only if the bytes could not be compressed.
+    fn encode(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Coding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "deflate")]
+            Coding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+impl Coding {
+    /// Undoes the coding, inflating to no more than the most bytes allowed.
+    fn decode(&self, bytes: &[u8], max_size: usize) -> Result<Vec<u8>, InflateError> {
+        match self {
+            // Members may follow one another, inflating to their bytes one
+            // after another.
+            #[cfg(feature = "gzip")]
+            Coding::Gzip => inflate(MultiGzDecoder::new(bytes), max_size),
+            #[cfg(feature = "deflate")]
+            Coding::Deflate => {
+                let mut decoder = ZlibDecoder::new(bytes);
+                let inflated = inflate(&mut decoder, max_size)?;
+                // Nothing may follow the stream.
+                match decoder.total_in() == bytes.len() as u64 {
+                    true => Ok(inflated),
+                    false => Err(InflateError::Malformed),
+                }
+            }
+        }
+    }
+}
+
+/// Why a body could not be undone of its coding.
+#[derive(PartialEq, Eq, Debug)]
+enum InflateError {
+    Malformed,
+    /// Inflating would take more than the most bytes allowed.
+    TooLarge,
+}
+
+/// Reads the decoder to its end, failing rather than inflating to more than
+/// the most bytes allowed, as a small body may inflate to a great many.
+fn inflate<R: Read>(decoder: R, max_size: usize) -> Result<Vec<u8>, InflateError> {
+    let mut inflated = Vec::new();
+    // A byte past the most allowed tells a body over it from one right at it.
+    decoder
+        .take((max_size as u64).saturating_add(1))
+        .read_to_end(&mut inflated)
+        .map_err(|_| InflateError::Malformed)?;
+    match inflated.len() > max_size {
+        true => Err(InflateError::TooLarge),
+        false => Ok(inflated),
+    }
+}
+
+/// The coding the client most prefers out of those it accepts, through a
+/// coding of its own or `*`, with a non-zero `q`.
+fn negotiate(accept_encoding: &str) -> Option<Coding> {
+    let names = CODINGS.iter().map(Coding::as_str).collect::<Vec<_>>();
+    let name = Negotiable::Encoding.preferred(Some(accept_encoding), &names)?;
+    CODINGS
+        .iter()
+        .copied()
+        .find(|coding| coding.as_str() == name)
+}
+
+/// Whether the `Content-Type` is of a format which is compressed already,
+/// which compressing again would only make larger.
+fn is_compressed(content_type: &str) -> bool {
+    match Mime::from(content_type) {
+        Ok(mime) => matches!(
+            mime,
+            Mime::ApplicationZip
+                | Mime::ApplicationGzip
+                | Mime::ImagePng
+                | Mime::ImageJpeg
+                | Mime::ImageGif
+                | Mime::ImageWebp
+                | Mime::ImageAvif
+                | Mime::FontWoff
+                | Mime::FontWoff2
+                | Mime::AudioMpeg
+                | Mime::AudioOgg
+                | Mime::VideoMp4
+                | Mime::VideoWebm
+        ),
+        Err(essence) => {
+            let essence = essence.to_ascii_lowercase();
+            ["image/", "audio/", "video/"]
+                .iter()
+                .any(|prefix| essence.starts_with(prefix))
+                && !essence.ends_with("+xml")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,369 @@
+use crate::server::compression::{Coding, InflateError, CODINGS};
+use crate::server::{Compression, Decompression, Route, Server};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+fn sample_text(len: usize) -> Vec<u8> {
+    let words = [
+        "martian ",
+        "server ",
+        "request ",
+        "response ",
+        "route ",
+        "body ",
+    ];
+    let mut text = Vec::with_capacity(len);
+    let mut seed = 7u32;
+    while text.len() < len {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        text.extend_from_slice(words[(seed >> 16) as usize % words.len()].as_bytes());
+    }
+    text.truncate(len);
+    text
+}
+
+#[test]
+fn should_decode_back_to_input_when_encoded() {
+    let mut noise = Vec::new();
+    let mut seed = 1u32;
+    for _ in 0..5000 {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        noise.push((seed >> 24) as u8);
+    }
+    let far_repeat = [noise.clone(), sample_text(40_000), noise.clone()].concat();
+    for coding in CODINGS {
+        for input in [
+            Vec::new(),
+            b"a".to_vec(),
+            vec![b'z'; 1000],
+            sample_text(10_000),
+            noise.clone(),
+            far_repeat.clone(),
+        ] {
+            let encoded = coding.encode(&input).unwrap();
+            assert_eq!(
+                coding.decode(&encoded, usize::MAX),
+                Ok(input.clone()),
+                "{:?} of {} bytes",
+                coding,
+                input.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn should_shrink_text_when_encoded() {
+    let text = sample_text(10_000);
+    for coding in CODINGS {
+        assert!(coding.encode(&text).unwrap().len() < text.len() / 3);
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_frame_in_gzip_format_when_coding_is_gzip() {
+    let text = sample_text(2000);
+    let encoded = Coding::Gzip.encode(&text).unwrap();
+    assert_eq!(&encoded[..3], &[0x1f, 0x8b, 8]);
+    assert_eq!(encoded[encoded.len() - 4..], 2000u32.to_le_bytes());
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn should_frame_in_zlib_format_when_coding_is_deflate() {
+    let text = sample_text(2000);
+    let encoded = Coding::Deflate.encode(&text).unwrap();
+    assert_eq!(encoded[0] & 0x0f, 8);
+    assert_eq!((u16::from(encoded[0]) << 8 | u16::from(encoded[1])) % 31, 0);
+}
+
+#[cfg(all(feature = "gzip", feature = "deflate"))]
+#[test]
+fn should_negotiate_most_preferred_coding_when_client_accepts_several() {
+    use crate::server::compression::negotiate;
+    assert_eq!(negotiate("gzip, deflate, br"), Some(Coding::Gzip));
+    assert_eq!(
+        negotiate("deflate;q=0.9, gzip;q=0.5"),
+        Some(Coding::Deflate)
+    );
+    assert_eq!(negotiate("GZIP;q=0, *"), Some(Coding::Deflate));
+    assert_eq!(negotiate("br, identity"), None);
+    assert_eq!(negotiate("*;q=0"), None);
+}
+
+fn compressed_client() -> TestClient {
+    let mut server = Server::default();
+    server.wrap(Compression::new());
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/text", |_| {
+                    let mut response =
+                        HttpResponse::text(&String::from_utf8(sample_text(4000)).unwrap());
+                    response.headers.insert("ETag".into(), "\"v1\"".into());
+                    response.headers.insert("Vary".into(), "Origin".into());
+                    response
+                })
+                .to("/short", |_| "short")
+                .to("/png", |_| {
+                    let mut response = HttpResponse::ok_with_body(vec![0; 4000]);
+                    response
+                        .headers
+                        .insert("Content-Type".into(), "image/png".into());
+                    response
+                })
+                .to("/no-transform", |_| {
+                    let mut response = HttpResponse::text(&"a".repeat(4000));
+                    response
+                        .headers
+                        .insert("Cache-Control".into(), "public, no-transform".into());
+                    response
+                })
+        })
+        .unwrap();
+    TestClient::new(server)
+}
+
+fn get(client: &TestClient, path: &str, accept_encoding: &str) -> TestResponse {
+    client
+        .request(HttpMethod::Get, path)
+        .header("Accept-Encoding", accept_encoding)
+        .send()
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_compress_body_when_client_accepts_coding() {
+    let response = get(&compressed_client(), "/text", "gzip");
+    assert_eq!(response.status_code, StatusCode::Ok);
+    let header = |name: &str| response.headers.get(name).map(String::as_str);
+    assert_eq!(header("Content-Encoding"), Some("gzip"));
+    assert_eq!(header("Vary"), Some("Origin, Accept-Encoding"));
+    assert_eq!(header("ETag"), Some("W/\"v1\""));
+    let encoded = match &response.body {
+        Body::Bytes(bytes) => bytes,
+        _ => panic!("a body in memory"),
+    };
+    assert_eq!(
+        Coding::Gzip.decode(encoded, usize::MAX),
+        Ok(sample_text(4000))
+    );
+}
+
+#[test]
+fn should_only_vary_when_client_accepts_no_coding() {
+    let response = get(&compressed_client(), "/text", "br");
+    assert!(!response.headers.contains_key("Content-Encoding"));
+    assert_eq!(
+        response.headers.get("Vary").map(String::as_str),
+        Some("Origin, Accept-Encoding")
+    );
+    assert_eq!(
+        response.body,
+        Body::from(String::from_utf8(sample_text(4000)).unwrap().as_str())
+    );
+}
+
+#[test]
+fn should_leave_body_as_is_when_it_is_not_worth_compressing() {
+    let client = compressed_client();
+    for path in &["/short", "/png", "/no-transform"] {
+        let response = get(&client, path, "gzip, deflate");
+        assert!(
+            !response.headers.contains_key("Content-Encoding"),
+            "{}",
+            path
+        );
+        assert!(!response.headers.contains_key("Vary"), "{}", path);
+    }
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn should_inflate_stored_and_dynamic_blocks_when_written_by_zlib() {
+    // What zlib writes at level 9 for text long enough to be worth a block
+    // of dynamic codes, and at level 0, a stored block.
+    let text = [
+        "the quick brown fox jumps over the lazy dog; ".repeat(3),
+        "pack my box with five dozen liquor jugs".into(),
+    ]
+    .concat();
+    let dynamic = [
+        0x78, 0xda, 0xb5, 0xcb, 0xc9, 0x11, 0x80, 0x20, 0x10, 0x44, 0xd1, 0x54, 0x3a, 0x0f, 0xa3,
+        0x01, 0x65, 0x53, 0x60, 0xd8, 0x11, 0xa3, 0x77, 0xca, 0x1c, 0x3c, 0x76, 0xfd, 0xd7, 0xcd,
+        0x2a, 0xe4, 0xee, 0xf6, 0x0b, 0xb2, 0xd0, 0x8c, 0xd0, 0x74, 0xe3, 0xec, 0x21, 0x55, 0xd0,
+        0x50, 0x05, 0x8d, 0xb3, 0x17, 0xcf, 0xc2, 0x41, 0x66, 0xfb, 0xd6, 0x3f, 0x38, 0x09, 0x76,
+        0x61, 0x41, 0x32, 0x9a, 0xae, 0x59, 0x68, 0x37, 0x14, 0xa7, 0x47, 0x45, 0x78, 0x97, 0x3b,
+        0x15, 0xfe, 0x9a, 0xfa, 0x02, 0xb6, 0x48, 0x3f, 0x86,
+    ];
+    let stored = [
+        0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o', 0x06, 0x2c, 0x02,
+        0x15,
+    ];
+    let decode = |encoded: &[u8]| Coding::Deflate.decode(encoded, usize::MAX);
+    assert_eq!(decode(&dynamic), Ok(text.into_bytes()));
+    assert_eq!(decode(&stored), Ok(b"hello".to_vec()));
+}
+
+#[test]
+fn should_stop_inflating_when_over_max_size() {
+    for coding in CODINGS {
+        let encoded = coding.encode(&vec![0; 1_000_000]).unwrap();
+        assert!(encoded.len() < 10_000);
+        assert_eq!(
+            coding.decode(&encoded, 64 * 1024),
+            Err(InflateError::TooLarge)
+        );
+        assert!(coding.decode(&encoded, 1_000_000).is_ok());
+    }
+}
+
+#[test]
+fn should_fail_to_inflate_when_malformed() {
+    for coding in CODINGS {
+        let encoded = coding.encode(&sample_text(1000)).unwrap();
+        let trailing = [&encoded[..], b"?"].concat();
+        let mut corrupted = encoded.clone();
+        let last = corrupted.len() - 5;
+        corrupted[last] ^= 1;
+        for malformed in [
+            &[][..],
+            &encoded[..encoded.len() / 2],
+            &trailing,
+            &corrupted,
+            b"plain text",
+        ] {
+            assert_eq!(
+                coding.decode(malformed, usize::MAX),
+                Err(InflateError::Malformed),
+                "{:?}",
+                coding
+            );
+        }
+    }
+}
+
+fn decompressed_client(max_size: usize) -> TestClient {
+    let mut server = Server::default();
+    server.wrap(Decompression::new().max_size(max_size));
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post).to("/echo", |request: HttpRequest| {
+                let mut response = HttpResponse::ok_with_body(request.body_bytes().to_vec());
+                let content_length = request.header("Content-Length").unwrap_or("none");
+                let content_encoding = request.header("Content-Encoding").unwrap_or("none");
+                response
+                    .headers
+                    .insert("X-Content-Length".into(), content_length.into());
+                response
+                    .headers
+                    .insert("X-Content-Encoding".into(), content_encoding.into());
+                response
+            })
+        })
+        .unwrap();
+    TestClient::new(server)
+}
+
+fn post(client: &TestClient, content_encoding: &str, body: &[u8]) -> TestResponse {
+    client
+        .request(HttpMethod::Post, "/echo")
+        .header("Content-Encoding", content_encoding)
+        .body(body)
+        .send()
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_decompress_body_when_request_is_gzipped() {
+    let text = sample_text(5000);
+    let response = post(
+        &decompressed_client(1 << 20),
+        "gzip",
+        &Coding::Gzip.encode(&text).unwrap(),
+    );
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, Body::Bytes(text));
+    let header = |name: &str| response.headers.get(name).map(String::as_str);
+    assert_eq!(header("X-Content-Length"), Some("5000"));
+    assert_eq!(header("X-Content-Encoding"), Some("none"));
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_decompress_every_member_when_gzip_members_are_concatenated() {
+    // `gzip.compress(b"hi", mtime=0)` with `FNAME` set to "a", followed by
+    // a member of `Coding::Gzip`.
+    let named = [
+        0x1f, 0x8b, 8, 0x08, 0, 0, 0, 0, 0, 0xff, b'a', 0, 0xcb, 0xc8, 0x04, 0x00, 0xac, 0x2a,
+        0x93, 0xd8, 0x02, 0x00, 0x00, 0x00,
+    ];
+    let body = [&named[..], &Coding::Gzip.encode(b" there").unwrap()].concat();
+    let response = post(&decompressed_client(1 << 20), "gzip", &body);
+    assert_eq!(response.body, Body::from("hi there"));
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn should_decompress_body_when_request_is_deflated() {
+    let text = sample_text(5000);
+    let response = post(
+        &decompressed_client(1 << 20),
+        "Deflate",
+        &Coding::Deflate.encode(&text).unwrap(),
+    );
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, Body::Bytes(text));
+}
+
+#[cfg(all(feature = "gzip", feature = "deflate"))]
+#[test]
+fn should_decompress_in_reverse_order_when_several_codings_were_applied() {
+    let text = sample_text(3000);
+    let body = Coding::Gzip
+        .encode(&Coding::Deflate.encode(&text).unwrap())
+        .unwrap();
+    let response = post(
+        &decompressed_client(1 << 20),
+        "deflate, identity, gzip",
+        &body,
+    );
+    assert_eq!(response.body, Body::Bytes(text));
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_answer_content_too_large_when_body_inflates_over_max_size() {
+    let bomb = Coding::Gzip.encode(&vec![0; 1 << 20]).unwrap();
+    let response = post(&decompressed_client(64 * 1024), "gzip", &bomb);
+    assert_eq!(response.status_code, StatusCode::ContentTooLarge);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn should_answer_bad_request_when_body_is_not_of_its_coding() {
+    let client = decompressed_client(1 << 20);
+    let mut corrupted = Coding::Gzip.encode(&sample_text(2000)).unwrap();
+    let last = corrupted.len() - 5;
+    corrupted[last] ^= 1;
+    for body in [&b"plain text"[..], &corrupted] {
+        let response = post(&client, "gzip", body);
+        assert_eq!(response.status_code, StatusCode::BadRequest);
+    }
+}
+
+#[test]
+fn should_answer_unsupported_media_type_when_coding_is_unknown() {
+    let response = post(&decompressed_client(1 << 20), "br", b"compressed");
+    assert_eq!(response.status_code, StatusCode::UnsupportedMediaType);
+}
+
+#[test]
+fn should_pass_body_as_is_when_request_has_no_coding() {
+    let client = decompressed_client(1 << 20);
+    let response = client.post("/echo", "hello");
+    assert_eq!(response.body, Body::from("hello"));
+    let response = post(&client, "identity", b"hello");
+    assert_eq!(response.body, Body::from("hello"));
+}
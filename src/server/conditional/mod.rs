@@ -0,0 +1,157 @@
+//! Answering a `GET` or `HEAD` for something the client has a fresh copy of
+//! already, going by the validators it sends along, with a 304 rather than
+//! the whole of it again. See
+//! [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-13.1).
+
+use std::collections::HashMap;
+
+use crate::web::date::parse_http_date;
+use crate::web::{header_value, Body, HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+use super::{Middleware, Next};
+
+/// The headers a 304 keeps of the response it stands in for.
+const KEPT_HEADERS: [&str; 7] = [
+    "Cache-Control",
+    "Content-Location",
+    "Date",
+    "ETag",
+    "Expires",
+    "Last-Modified",
+    "Vary",
+];
+
+/// [`Middleware`] giving every 200 to a `GET` or `HEAD` with a body in memory
+/// an `ETag` hashed from that body, unless it has one already, and answering
+/// with a 304 when the request shows the client has it already. That is when
+/// its `If-None-Match` names the `ETag` of the response, or without one, when
+/// its `If-Modified-Since` is no earlier than the `Last-Modified`.
+///
+/// The `ETag` is strong, promising the very same bytes, unless made
+/// [`weak`], only promising the same meaning, such as for a page which
+/// differs in little more than a timestamp it is rendered with.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{ConditionalGet, Server};
+/// let mut server = Server::default();
+/// server.wrap(ConditionalGet::new());
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`weak`]: ./struct.ConditionalGet.html#method.weak
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct ConditionalGet {
+    weak: bool,
+}
+
+impl ConditionalGet {
+    pub fn new() -> ConditionalGet {
+        ConditionalGet::default()
+    }
+
+    /// Gives responses weak `ETag`s, `W/"..."`, in place of strong ones.
+    pub fn weak(mut self) -> ConditionalGet {
+        self.weak = true;
+        self
+    }
+}
+
+impl Middleware for ConditionalGet {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let is_get = matches!(request.http_method, HttpMethod::Get | HttpMethod::Head);
+        let if_none_match = request.header("If-None-Match").map(String::from);
+        let if_modified_since = request.header("If-Modified-Since").map(String::from);
+        let mut response = next.run(request);
+        if !is_get || response.status_code != StatusCode::Ok {
+            return response;
+        }
+        if header_value(&response.headers, "ETag").is_none() {
+            if let Body::Bytes(bytes) = &response.body {
+                let etag = format!("\"{:016x}\"", fnv1a(bytes));
+                let etag = match self.weak {
+                    true => format!("W/{}", etag),
+                    false => etag,
+                };
+                response.headers.insert("ETag".into(), etag);
+            }
+        }
+        let is_not_modified = is_not_modified(
+            if_none_match.as_deref(),
+            if_modified_since.as_deref(),
+            &response.headers,
+        );
+        match is_not_modified {
+            true => not_modified(response),
+            false => response,
+        }
+    }
+}
+
+/// Whether the client has what the response would give it already, going by
+/// the `If-None-Match` and `If-Modified-Since` of its request, the latter only
+/// counting without the former.
+pub(in crate::server) fn is_fresh(
+    request: &HttpRequest,
+    headers: &HashMap<String, String>,
+) -> bool {
+    is_not_modified(
+        request.header("If-None-Match"),
+        request.header("If-Modified-Since"),
+        headers,
+    )
+}
+
+fn is_not_modified(
+    if_none_match: Option<&str>,
+    if_modified_since: Option<&str>,
+    headers: &HashMap<String, String>,
+) -> bool {
+    if let Some(if_none_match) = if_none_match {
+        let etag = header_value(headers, "ETag").map(opaque_tag);
+        return if_none_match.split(',').map(str::trim).any(|tag| {
+            // Compared weakly, as a client may well have been given a
+            // weakened `ETag`, such as along with a compressed body.
+            tag == "*" || etag.is_some_and(|etag| opaque_tag(tag) == etag)
+        });
+    }
+    let last_modified = header_value(headers, "Last-Modified").and_then(parse_http_date);
+    let if_modified_since = if_modified_since.and_then(parse_http_date);
+    match (last_modified, if_modified_since) {
+        (Some(last_modified), Some(if_modified_since)) => last_modified <= if_modified_since,
+        _ => false,
+    }
+}
+
+/// The 304 standing in for the response, without its body.
+pub(in crate::server) fn not_modified(response: HttpResponse) -> HttpResponse {
+    let mut not_modified = HttpResponse::new(StatusCode::NotModified);
+    not_modified.headers = response
+        .headers
+        .into_iter()
+        .filter(|(name, _)| {
+            KEPT_HEADERS
+                .iter()
+                .any(|kept| kept.eq_ignore_ascii_case(name))
+        })
+        .collect();
+    not_modified.cookies = response.cookies;
+    not_modified
+}
+
+/// The tag without the `W/` a weak one starts with.
+fn opaque_tag(etag: &str) -> &str {
+    etag.strip_prefix("W/").unwrap_or(etag)
+}
+
+/// The 64 bit [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// hash of the bytes, which is quick and, unlike that of `std`, the same
+/// across builds, so that an `ETag` outlives a restart.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,150 @@
+use crate::server::{ConditionalGet, Route, Server};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, SetCookie, StatusCode};
+
+fn conditional_server(conditional_get: ConditionalGet) -> Server {
+    let mut server = Server::default();
+    server.wrap(conditional_get);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/page", |_| {
+                    let mut response = HttpResponse::html("<p>page</p>");
+                    response
+                        .headers
+                        .insert("Cache-Control".into(), "no-cache".into());
+                    response.set_cookie(SetCookie::new("seen", "1"));
+                    response
+                })
+                .to("/dated", |_| {
+                    let mut response = HttpResponse::ok_with_body(Body::Empty);
+                    response.headers.insert(
+                        "Last-Modified".into(),
+                        "Sun, 06 Nov 1994 08:49:37 GMT".into(),
+                    );
+                    response
+                })
+                .to("/tagged", |_| {
+                    let mut response = HttpResponse::text("tagged");
+                    response.headers.insert("ETag".into(), "\"v2\"".into());
+                    response
+                })
+                .to("/missing", |_| HttpResponse::not_found())
+        })
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/page", |_| HttpResponse::text("posted")))
+        .unwrap();
+    server
+}
+
+fn request(client: &TestClient, request_line: &str, headers: &str) -> TestResponse {
+    client.send(HttpRequest::from(
+        format!("{} HTTP/1.1\r\n{}\r\n", request_line, headers).as_str(),
+    ))
+}
+
+fn etag(response: &HttpResponse) -> Option<&str> {
+    response.headers.get("ETag").map(String::as_str)
+}
+
+#[test]
+fn should_tag_response_with_hash_of_body_when_it_has_no_etag() {
+    let client = TestClient::new(conditional_server(ConditionalGet::new()));
+    let response = request(&client, "GET /page", "");
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(etag(&response), Some("\"2240319afaa29efd\""));
+    assert_eq!(etag(&request(&client, "GET /page", "")), etag(&response));
+    assert_eq!(etag(&request(&client, "GET /tagged", "")), Some("\"v2\""));
+    let weak_client = TestClient::new(conditional_server(ConditionalGet::new().weak()));
+    assert_eq!(
+        etag(&request(&weak_client, "GET /page", "")),
+        Some("W/\"2240319afaa29efd\"")
+    );
+}
+
+#[test]
+fn should_answer_not_modified_when_if_none_match_names_etag() {
+    let client = TestClient::new(conditional_server(ConditionalGet::new()));
+    for if_none_match in [
+        "\"2240319afaa29efd\"",
+        "W/\"2240319afaa29efd\"",
+        "\"old\", \"2240319afaa29efd\"",
+        "*",
+    ] {
+        let response = request(
+            &client,
+            "GET /page",
+            &format!("If-None-Match: {}\r\n", if_none_match),
+        );
+        assert_eq!(
+            response.status_code,
+            StatusCode::NotModified,
+            "{}",
+            if_none_match
+        );
+        assert_eq!(response.body, Body::Empty);
+        assert_eq!(etag(&response), Some("\"2240319afaa29efd\""));
+        assert_eq!(
+            response.headers.get("Cache-Control").map(String::as_str),
+            Some("no-cache")
+        );
+        assert!(!response.headers.contains_key("Content-Type"));
+        assert_eq!(response.cookies.len(), 1);
+    }
+    let response = request(&client, "HEAD /tagged", "If-None-Match: \"v2\"\r\n");
+    assert_eq!(response.status_code, StatusCode::NotModified);
+}
+
+#[test]
+fn should_answer_in_full_when_if_none_match_names_other_etags() {
+    let client = TestClient::new(conditional_server(ConditionalGet::new()));
+    let response = request(
+        &client,
+        "GET /tagged",
+        "If-None-Match: \"v1\", W/\"v3\"\r\n",
+    );
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, Body::from("tagged"));
+}
+
+#[test]
+fn should_answer_not_modified_when_unmodified_since_if_modified_since() {
+    let client = TestClient::new(conditional_server(ConditionalGet::new()));
+    let modified = |if_modified_since: &str| {
+        request(
+            &client,
+            "GET /dated",
+            &format!("If-Modified-Since: {}\r\n", if_modified_since),
+        )
+        .status_code
+    };
+    assert_eq!(
+        modified("Sun, 06 Nov 1994 08:49:37 GMT"),
+        StatusCode::NotModified
+    );
+    assert_eq!(
+        modified("Sun Nov 20 00:00:00 1994"),
+        StatusCode::NotModified
+    );
+    assert_eq!(modified("Sun, 06 Nov 1994 08:49:36 GMT"), StatusCode::Ok);
+    assert_eq!(modified("not a date"), StatusCode::Ok);
+    // Nothing to compare against without a `Last-Modified`.
+    let response = request(
+        &client,
+        "GET /page",
+        "If-Modified-Since: Sun, 06 Nov 2094 08:49:37 GMT\r\n",
+    );
+    assert_eq!(response.status_code, StatusCode::Ok);
+}
+
+#[test]
+fn should_leave_response_as_is_when_request_is_not_get_or_response_not_ok() {
+    let client = TestClient::new(conditional_server(ConditionalGet::new()));
+    let posted = request(&client, "POST /page", "If-None-Match: *\r\n");
+    assert_eq!(posted.status_code, StatusCode::Ok);
+    assert_eq!(etag(&posted), None);
+    let missing = request(&client, "GET /missing", "If-None-Match: *\r\n");
+    assert_eq!(missing.status_code, StatusCode::NotFound);
+    assert_eq!(etag(&missing), None);
+}
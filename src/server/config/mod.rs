@@ -0,0 +1,378 @@
+//! Every setting of putting a [`Server`] on the network gathered in one
+//! place, checked before any of it is used, and read from the environment or
+//! a TOML file with the `config` feature.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "config")]
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr};
+#[cfg(feature = "config")]
+use std::path::Path;
+use std::time::Duration;
+#[cfg(feature = "config")]
+use std::{env, fs};
+
+#[cfg(feature = "tls")]
+use super::TlsConfig;
+use super::{KeepAlive, Limits, Timeouts};
+
+#[cfg(feature = "config")]
+mod toml;
+
+/// How an [`HttpServer`] is put on the network and how its [`Server`]
+/// treats connections, see [`HttpServer::from_config`]. Anything left out
+/// is as it is by default for each, the port being `8080` of every
+/// interface.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{HttpServer, Server, ServerConfig};
+/// use std::net::Ipv4Addr;
+/// let config = ServerConfig {
+///     port: 0,
+///     bind_address: Ipv4Addr::LOCALHOST.into(),
+///     workers: 4,
+///     ..ServerConfig::default()
+/// };
+/// let http_server = HttpServer::from_config(config, Server::default()).unwrap();
+/// assert_eq!(http_server.addr().ip(), Ipv4Addr::LOCALHOST);
+/// ```
+///
+/// [`HttpServer`]: ./struct.HttpServer.html
+/// [`Server`]: ./struct.Server.html
+/// [`HttpServer::from_config`]: ./struct.HttpServer.html#method.from_config
+#[derive(PartialEq, Debug, Clone)]
+pub struct ServerConfig {
+    /// Bound to only once started, `0` being any free port.
+    pub port: u16,
+    pub bind_address: IpAddr,
+    /// See [`Server::with_workers`].
+    ///
+    /// [`Server::with_workers`]: ./struct.Server.html#method.with_workers
+    pub workers: usize,
+    pub timeouts: Timeouts,
+    pub keep_alive: KeepAlive,
+    pub limits: Limits,
+    /// Serves HTTPS rather than HTTP, see [`HttpServer::with_tls`].
+    ///
+    /// [`HttpServer::with_tls`]: ./struct.HttpServer.html#method.with_tls
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+}
+
+impl ServerConfig {
+    /// Checks that every setting can be served with.
+    ///
+    /// # Returns:
+    /// An `Err` naming the first setting found to be zero where it can not
+    /// be, be it a timeout, a limit, or the requests of a connection kept
+    /// alive, or a TLS certificate or key not given a path.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            if tls.cert_pem_path.as_os_str().is_empty() {
+                return Err(ConfigError::Missing("tls.cert_pem_path"));
+            }
+            if tls.key_pem_path.as_os_str().is_empty() {
+                return Err(ConfigError::Missing("tls.key_pem_path"));
+            }
+        }
+        let timeouts = [
+            ("timeouts.read", self.timeouts.read),
+            ("timeouts.handler", self.timeouts.handler),
+            ("keep_alive.timeout", Some(self.keep_alive.timeout)),
+        ];
+        let limits = [
+            (
+                "timeouts.max_handler_threads",
+                Some(self.timeouts.max_handler_threads),
+            ),
+            (
+                "keep_alive.max_requests",
+                Some(self.keep_alive.max_requests),
+            ),
+            (
+                "limits.max_request_line",
+                Some(self.limits.max_request_line),
+            ),
+            (
+                "limits.max_header_bytes",
+                Some(self.limits.max_header_bytes),
+            ),
+            ("limits.max_headers", Some(self.limits.max_headers)),
+            ("limits.max_in_flight", self.limits.max_in_flight),
+        ];
+        let zero = timeouts
+            .iter()
+            .find(|(_, timeout)| *timeout == Some(Duration::ZERO))
+            .map(|(setting, _)| setting)
+            .or_else(|| {
+                limits
+                    .iter()
+                    .find(|(_, limit)| *limit == Some(0))
+                    .map(|(setting, _)| setting)
+            });
+        match zero {
+            Some(setting) => Err(ConfigError::Zero(setting)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Every setting read from the environment or a TOML file, as named in a
+/// TOML file.
+#[cfg(feature = "config")]
+const SETTINGS: [&str; 15] = [
+    "port",
+    "bind_address",
+    "workers",
+    "timeouts.read",
+    "timeouts.handler",
+    "timeouts.max_handler_threads",
+    "keep_alive.timeout",
+    "keep_alive.max_requests",
+    "limits.max_request_line",
+    "limits.max_header_bytes",
+    "limits.max_headers",
+    "limits.max_body",
+    "limits.max_body_in_memory",
+    "limits.spool_dir",
+    "limits.max_in_flight",
+];
+
+#[cfg(all(feature = "config", feature = "tls"))]
+const TLS_SETTINGS: &[&str] = &["tls.cert_pem_path", "tls.key_pem_path"];
+#[cfg(all(feature = "config", not(feature = "tls")))]
+const TLS_SETTINGS: &[&str] = &[];
+
+#[cfg(feature = "config")]
+impl ServerConfig {
+    /// The config of the `MARTIAN_` variables of the environment, each
+    /// setting named as it is in a TOML file, in upper case with a `_` in
+    /// place of any `.`, such as `MARTIAN_PORT`, `MARTIAN_WORKERS` and
+    /// `MARTIAN_LIMITS_MAX_BODY`. Any setting not in the environment is left
+    /// as it is by default.
+    ///
+    /// A timeout is a number of seconds, which may have a fraction, and a
+    /// setting which may be left unset, such as `MARTIAN_TIMEOUTS_HANDLER`,
+    /// is unset by `none`.
+    ///
+    /// # Returns:
+    /// An `Err` if a variable can not be read as its setting, or if the
+    /// config is not valid, see [`validate`].
+    ///
+    /// [`validate`]: ./struct.ServerConfig.html#method.validate
+    pub fn from_env() -> Result<ServerConfig, ConfigError> {
+        ServerConfig::from_vars(|name| env::var(name).ok())
+    }
+
+    /// The config of the TOML document, each setting named by its path in
+    /// the `ServerConfig`, any not given being left as it is by default. The
+    /// values are as for [`from_env`], a number of seconds or `"none"` for
+    /// a timeout. Settings may be given under tables, inline or not, or by
+    /// dotted keys alike, though none of them can be an array or a date.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::ServerConfig;
+    /// use std::time::Duration;
+    /// let config = ServerConfig::from_toml_str(
+    ///     r#"
+    ///     port = 9090
+    ///     workers = 8
+    ///
+    ///     [timeouts]
+    ///     read = 10
+    ///     handler = 2.5
+    ///
+    ///     [limits]
+    ///     max_body = 1_048_576
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(config.port, 9090);
+    /// assert_eq!(config.timeouts.handler, Some(Duration::from_millis(2500)));
+    /// ```
+    ///
+    /// # Returns:
+    /// An `Err` if the document is not TOML, if it has a setting unknown or
+    /// which can not be read, or if the config is not valid, see
+    /// [`validate`].
+    ///
+    /// [`from_env`]: ./struct.ServerConfig.html#method.from_env
+    /// [`validate`]: ./struct.ServerConfig.html#method.validate
+    pub fn from_toml_str(text: &str) -> Result<ServerConfig, ConfigError> {
+        let mut config = ServerConfig::default();
+        for (key, value) in toml::parse(text)? {
+            if !ServerConfig::settings().any(|setting| setting == key) {
+                return Err(ConfigError::Unknown(key));
+            }
+            if config.set(&key, &value).is_none() {
+                return Err(ConfigError::Invalid {
+                    setting: key,
+                    value,
+                });
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Same as [`from_toml_str`], for the contents of the file.
+    ///
+    /// # Returns:
+    /// An `Err` if the file can not be read, or of kind `InvalidData` with
+    /// the [`ConfigError`] if its config can not be.
+    ///
+    /// [`from_toml_str`]: ./struct.ServerConfig.html#method.from_toml_str
+    /// [`ConfigError`]: ./enum.ConfigError.html
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> io::Result<ServerConfig> {
+        let text = fs::read_to_string(path)?;
+        ServerConfig::from_toml_str(&text).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    fn from_vars<F>(var: F) -> Result<ServerConfig, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut config = ServerConfig::default();
+        for setting in ServerConfig::settings() {
+            let name = format!("MARTIAN_{}", setting.to_ascii_uppercase().replace('.', "_"));
+            if let Some(value) = var(&name) {
+                if config.set(setting, &value).is_none() {
+                    return Err(ConfigError::Invalid {
+                        setting: name,
+                        value,
+                    });
+                }
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn settings() -> impl Iterator<Item = &'static str> {
+        SETTINGS.iter().chain(TLS_SETTINGS).copied()
+    }
+
+    /// Sets the setting to the value, unless it can not be read as such.
+    fn set(&mut self, setting: &str, value: &str) -> Option<()> {
+        match setting {
+            "port" => self.port = value.parse().ok()?,
+            "bind_address" => self.bind_address = value.parse().ok()?,
+            "workers" => self.workers = value.parse().ok()?,
+            "timeouts.read" => self.timeouts.read = optional(value, duration)?,
+            "timeouts.handler" => self.timeouts.handler = optional(value, duration)?,
+            "timeouts.max_handler_threads" => {
+                self.timeouts.max_handler_threads = value.parse().ok()?
+            }
+            "keep_alive.timeout" => self.keep_alive.timeout = duration(value)?,
+            "keep_alive.max_requests" => self.keep_alive.max_requests = value.parse().ok()?,
+            "limits.max_request_line" => self.limits.max_request_line = value.parse().ok()?,
+            "limits.max_header_bytes" => self.limits.max_header_bytes = value.parse().ok()?,
+            "limits.max_headers" => self.limits.max_headers = value.parse().ok()?,
+            "limits.max_body" => self.limits.max_body = value.parse().ok()?,
+            "limits.max_body_in_memory" => self.limits.max_body_in_memory = value.parse().ok()?,
+            "limits.spool_dir" => self.limits.spool_dir = Some(value.into()),
+            "limits.max_in_flight" => {
+                self.limits.max_in_flight = optional(value, |value| value.parse().ok())?
+            }
+            #[cfg(feature = "tls")]
+            "tls.cert_pem_path" => self.tls_config().cert_pem_path = value.into(),
+            #[cfg(feature = "tls")]
+            "tls.key_pem_path" => self.tls_config().key_pem_path = value.into(),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// The TLS config, without a certificate or key until they are set.
+    #[cfg(feature = "tls")]
+    fn tls_config(&mut self) -> &mut TlsConfig {
+        self.tls.get_or_insert_with(|| TlsConfig {
+            cert_pem_path: Default::default(),
+            key_pem_path: Default::default(),
+            hosts: Vec::new(),
+            client_auth: Default::default(),
+        })
+    }
+}
+
+/// `None` for `none`, or else the value read as the setting.
+#[cfg(feature = "config")]
+fn optional<T, F>(value: &str, read: F) -> Option<Option<T>>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    match value {
+        "none" => Some(None),
+        value => read(value).map(Some),
+    }
+}
+
+/// A number of seconds, which may have a fraction.
+#[cfg(feature = "config")]
+fn duration(value: &str) -> Option<Duration> {
+    Duration::try_from_secs_f64(value.parse().ok()?).ok()
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            port: 8080,
+            bind_address: Ipv4Addr::UNSPECIFIED.into(),
+            workers: 0,
+            timeouts: Timeouts::default(),
+            keep_alive: KeepAlive::default(),
+            limits: Limits::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+/// Why a [`ServerConfig`] can not be served with.
+///
+/// [`ServerConfig`]: ./struct.ServerConfig.html
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum ConfigError {
+    /// The setting, named by its path in the [`ServerConfig`] such as
+    /// `limits.max_headers`, is zero, which would turn every request away.
+    ///
+    /// [`ServerConfig`]: ./struct.ServerConfig.html
+    Zero(&'static str),
+    /// The setting, as for [`Zero`], has to be given along with another.
+    ///
+    /// [`Zero`]: ./enum.ConfigError.html#variant.Zero
+    Missing(&'static str),
+    /// The value of the setting, named as it was where it was read from,
+    /// can not be read as that setting.
+    Invalid { setting: String, value: String },
+    /// A TOML document has a setting of no such name.
+    Unknown(String),
+    /// The line of a TOML document, counting from 1, is not TOML, or has a
+    /// key given already.
+    Syntax(usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Zero(setting) => write!(f, "{} must be more than zero", setting),
+            ConfigError::Missing(setting) => write!(f, "{} must be given", setting),
+            ConfigError::Invalid { setting, value } => {
+                write!(f, "{} can not be {:?}", setting, value)
+            }
+            ConfigError::Unknown(setting) => write!(f, "there is no setting {}", setting),
+            ConfigError::Syntax(line) => write!(f, "line {} is not valid TOML", line),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,240 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use crate::server::{ConfigError, HttpServer, KeepAlive, Limits, Server, ServerConfig, Timeouts};
+
+#[test]
+fn should_be_valid_when_left_as_default() {
+    assert_eq!(ServerConfig::default().validate(), Ok(()));
+}
+
+#[test]
+fn should_name_setting_when_it_is_zero() {
+    let config = ServerConfig {
+        timeouts: Timeouts {
+            handler: Some(Duration::ZERO),
+            ..Timeouts::default()
+        },
+        ..ServerConfig::default()
+    };
+    assert_eq!(
+        config.validate(),
+        Err(ConfigError::Zero("timeouts.handler"))
+    );
+    let config = ServerConfig {
+        limits: Limits {
+            max_in_flight: Some(0),
+            ..Limits::default()
+        },
+        ..ServerConfig::default()
+    };
+    let error = config.validate().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "limits.max_in_flight must be more than zero"
+    );
+    let config = ServerConfig {
+        keep_alive: KeepAlive {
+            max_requests: 0,
+            ..KeepAlive::default()
+        },
+        ..ServerConfig::default()
+    };
+    assert_eq!(
+        config.validate(),
+        Err(ConfigError::Zero("keep_alive.max_requests"))
+    );
+}
+
+#[test]
+fn should_listen_on_configured_address_when_built() {
+    let http_server = HttpServer::builder()
+        .bind_address(Ipv4Addr::LOCALHOST.into())
+        .port(9000)
+        .workers(3)
+        .build(Server::default())
+        .unwrap();
+    assert_eq!(
+        http_server.addrs(),
+        vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 9000))]
+    );
+}
+
+#[test]
+fn should_not_build_when_config_is_invalid() {
+    let built = HttpServer::builder()
+        .config(ServerConfig {
+            port: 0,
+            ..ServerConfig::default()
+        })
+        .keep_alive(KeepAlive {
+            timeout: Duration::ZERO,
+            ..KeepAlive::default()
+        })
+        .build(Server::default());
+    assert_eq!(built.err(), Some(ConfigError::Zero("keep_alive.timeout")));
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_read_settings_when_in_environment() {
+    let vars = [
+        ("MARTIAN_PORT", "9090"),
+        ("MARTIAN_BIND_ADDRESS", "::1"),
+        ("MARTIAN_TIMEOUTS_READ", "none"),
+        ("MARTIAN_KEEP_ALIVE_TIMEOUT", "0.25"),
+        ("MARTIAN_LIMITS_MAX_IN_FLIGHT", "64"),
+        ("PORT", "1"),
+    ];
+    let config = ServerConfig::from_vars(|name| {
+        vars.iter()
+            .find(|(var, _)| *var == name)
+            .map(|(_, value)| value.to_string())
+    })
+    .unwrap();
+    assert_eq!(
+        config,
+        ServerConfig {
+            port: 9090,
+            bind_address: std::net::Ipv6Addr::LOCALHOST.into(),
+            timeouts: Timeouts {
+                read: None,
+                ..Timeouts::default()
+            },
+            keep_alive: KeepAlive {
+                timeout: Duration::from_millis(250),
+                ..KeepAlive::default()
+            },
+            limits: Limits {
+                max_in_flight: Some(64),
+                ..Limits::default()
+            },
+            ..ServerConfig::default()
+        }
+    );
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_name_variable_when_it_can_not_be_read() {
+    let config = ServerConfig::from_vars(|name| match name {
+        "MARTIAN_WORKERS" => Some("many".into()),
+        _ => None,
+    });
+    assert_eq!(
+        config,
+        Err(ConfigError::Invalid {
+            setting: "MARTIAN_WORKERS".into(),
+            value: "many".into(),
+        })
+    );
+    let config = ServerConfig::from_vars(|name| match name {
+        "MARTIAN_LIMITS_MAX_HEADERS" => Some("0".into()),
+        _ => None,
+    });
+    assert_eq!(config, Err(ConfigError::Zero("limits.max_headers")));
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_read_tables_and_dotted_keys_when_parsing_toml() {
+    let config = ServerConfig::from_toml_str(
+        r#"
+        # Tuned for the staging cluster.
+        bind_address = "\u0031\u0032\u0037.0.0.1" # loopback only
+        keep_alive.max_requests = 10
+
+        [ limits ]
+        max_body = 2_048
+        max_body_in_memory = 1_024
+        spool_dir = "/var/spool/martian"
+        max_in_flight = 'none'
+
+        [timeouts]
+        handler = 1.5
+        max_handler_threads = 4
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.bind_address, Ipv4Addr::LOCALHOST);
+    assert_eq!(config.keep_alive.max_requests, 10);
+    assert_eq!(config.limits.max_body, 2048);
+    assert_eq!(config.limits.max_body_in_memory, 1024);
+    assert_eq!(config.limits.spool_dir, Some("/var/spool/martian".into()));
+    assert_eq!(config.limits.max_in_flight, None);
+    assert_eq!(config.timeouts.handler, Some(Duration::from_millis(1500)));
+    assert_eq!(config.timeouts.max_handler_threads, 4);
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_not_read_toml_when_it_is_not_understood() {
+    let cases = [
+        ("port = 80\nport = 81", ConfigError::Syntax(2)),
+        ("[limits\nmax_body = 1", ConfigError::Syntax(1)),
+        ("workers = \"4", ConfigError::Syntax(1)),
+        ("prot = 80", ConfigError::Unknown("prot".into())),
+        (
+            "[timeouts]\nread = -1",
+            ConfigError::Invalid {
+                setting: "timeouts.read".into(),
+                value: "-1".into(),
+            },
+        ),
+        ("port = \"80\\q\"", ConfigError::Syntax(1)),
+    ];
+    for (toml, error) in cases {
+        assert_eq!(ServerConfig::from_toml_str(toml), Err(error), "{}", toml);
+    }
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_read_inline_tables_when_parsing_toml() {
+    let config = ServerConfig::from_toml_str(
+        "limits = { max_body = 2_048 }\nkeep_alive = { timeout = 0.5, max_requests = 3 }",
+    )
+    .unwrap();
+    assert_eq!(config.limits.max_body, 2048);
+    assert_eq!(config.keep_alive.timeout, Duration::from_millis(500));
+    assert_eq!(config.keep_alive.max_requests, 3);
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_name_setting_when_toml_value_is_array_or_date() {
+    let cases = [
+        ("workers = [4]", "workers", "[4]"),
+        ("[limits]\nmax_body = [1, 2]", "limits.max_body", "[1, 2]"),
+        ("port = 1979-05-27", "port", "1979-05-27"),
+    ];
+    for (toml, setting, value) in cases {
+        assert_eq!(
+            ServerConfig::from_toml_str(toml),
+            Err(ConfigError::Invalid {
+                setting: setting.into(),
+                value: value.into(),
+            }),
+            "{}",
+            toml
+        );
+    }
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn should_fail_with_invalid_data_when_toml_file_is_invalid() {
+    let path = std::env::temp_dir().join(format!("martian-config-{}.toml", std::process::id()));
+    std::fs::write(&path, "port = 70000").unwrap();
+    let error = ServerConfig::from_toml_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "port can not be \"70000\"");
+}
+
+#[cfg(all(feature = "config", feature = "tls"))]
+#[test]
+fn should_need_key_when_certificate_is_given() {
+    let config = ServerConfig::from_toml_str("tls.cert_pem_path = \"cert.pem\"");
+    assert_eq!(config, Err(ConfigError::Missing("tls.key_pem_path")));
+}
@@ -0,0 +1,62 @@
+//! Reading the settings of a [TOML](https://toml.io/en/v1.0.0) document as
+//! they would be written in the environment.
+
+use ::toml::{Table, Value};
+
+use super::ConfigError;
+
+/// Every setting of the document, named by its keys joined by a `.` through
+/// any tables, dotted keys or inline tables it is in, with its value as it
+/// would be written in the environment. A string is as it is, without its
+/// quotes.
+///
+/// # Returns:
+/// An `Err` of the line of the first thing which is not TOML, or of a key
+/// given twice, or `Invalid` for a value no setting can be, such as an array
+/// or a date.
+pub(super) fn parse(text: &str) -> Result<Vec<(String, String)>, ConfigError> {
+    let table = text.parse::<Table>().map_err(|e| {
+        let start = e.span().map_or(0, |span| span.start);
+        ConfigError::Syntax(text[..start].matches('\n').count() + 1)
+    })?;
+    let mut entries = Vec::new();
+    flatten("", table, &mut entries)?;
+    Ok(entries)
+}
+
+fn flatten(
+    prefix: &str,
+    table: Table,
+    entries: &mut Vec<(String, String)>,
+) -> Result<(), ConfigError> {
+    for (key, value) in table {
+        let key = match prefix {
+            "" => key,
+            prefix => format!("{}.{}", prefix, key),
+        };
+        let value = match value {
+            Value::Table(table) => {
+                flatten(&key, table, entries)?;
+                continue;
+            }
+            Value::String(string) => string,
+            Value::Integer(integer) => integer.to_string(),
+            Value::Float(float) => float.to_string(),
+            Value::Boolean(boolean) => boolean.to_string(),
+            Value::Datetime(datetime) => {
+                return Err(ConfigError::Invalid {
+                    setting: key,
+                    value: datetime.to_string(),
+                })
+            }
+            array @ Value::Array(_) => {
+                return Err(ConfigError::Invalid {
+                    setting: key,
+                    value: array.to_string(),
+                })
+            }
+        };
+        entries.push((key, value));
+    }
+    Ok(())
+}
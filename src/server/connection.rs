@@ -0,0 +1,585 @@
+//! Reading requests off of a connection. Only as much is read as the
+//! request declares through its headers, so the connection is left positioned
+//! at the start of whatever follows, be it the next request on a connection
+//! kept alive.
+
+use std::env;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::web::{
+    chunked, multipart, Body, HttpRequest, HttpRequestRef, HttpResponse, HttpVersion, PeerIdentity,
+    Spool, Spooled, StatusCode,
+};
+
+/// The longest line giving the size of a chunk, extensions included.
+const MAX_CHUNK_LINE: usize = 1024;
+
+/// How long, and for how many requests, a connection is kept open for the
+/// requests following its first. Only a `Server` with workers keeps any
+/// connection alive, one without closing each after its first response so
+/// that its single thread is never left waiting on an idle client.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{KeepAlive, Server};
+/// use std::time::Duration;
+/// let mut server = Server::with_workers(8);
+/// server.keep_alive(KeepAlive {
+///     timeout: Duration::from_secs(15),
+///     max_requests: 1000,
+/// });
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct KeepAlive {
+    /// How long a read off of the connection may wait before it is closed.
+    pub timeout: Duration,
+    /// How many requests are served over one connection, the response to the
+    /// last one asking the client to close it. `1` keeps none alive.
+    pub max_requests: usize,
+}
+
+impl Default for KeepAlive {
+    fn default() -> KeepAlive {
+        KeepAlive {
+            timeout: Duration::from_secs(5),
+            max_requests: 100,
+        }
+    }
+}
+
+/// Anything a request can be read from and its response written to, be it a
+/// plain `TcpStream` or one wrapped in TLS.
+pub(in crate::server) trait Connection: Read + Write {
+    fn is_tls(&self) -> bool {
+        false
+    }
+
+    /// Who the client proved to be through its certificate, once the
+    /// handshake is done.
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        None
+    }
+
+    /// The hostname the client asked for through SNI during the handshake.
+    fn server_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the client chose HTTP/2 through ALPN during the handshake.
+    #[cfg(feature = "http2")]
+    fn is_http2(&self) -> bool {
+        false
+    }
+
+    /// Whether all that has been read off of the socket has been read out of
+    /// the connection too, so that the next request is only noticed through
+    /// the socket.
+    #[cfg(feature = "mio")]
+    fn is_drained(&mut self) -> bool {
+        true
+    }
+
+    /// Called once the response has been written, before the connection is
+    /// dropped.
+    fn close(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Connection for TcpStream {}
+
+#[cfg(feature = "tls")]
+impl Connection for super::tls::TlsStream {
+    fn is_tls(&self) -> bool {
+        true
+    }
+
+    fn peer_identity(&self) -> Option<PeerIdentity> {
+        super::tls::peer_identity(&self.conn)
+    }
+
+    fn server_name(&self) -> Option<String> {
+        self.conn.server_name().map(String::from)
+    }
+
+    #[cfg(feature = "http2")]
+    fn is_http2(&self) -> bool {
+        self.conn.alpn_protocol() == Some(&b"h2"[..])
+    }
+
+    /// Records already read off of the socket may hold the next request.
+    #[cfg(feature = "mio")]
+    fn is_drained(&mut self) -> bool {
+        self.conn
+            .process_new_packets()
+            .is_ok_and(|io_state| io_state.plaintext_bytes_to_read() == 0)
+    }
+
+    /// Lets the client know the response is complete, rather than leaving it
+    /// to guess whether the connection was cut short.
+    fn close(&mut self) -> io::Result<()> {
+        self.conn.send_close_notify();
+        self.flush()
+    }
+}
+
+/// The connection as it is handed over once upgraded, reading through the
+/// buffer so that nothing the client sent straight after the request is lost.
+pub(in crate::server) struct Upgraded<'a, S>(pub(in crate::server) &'a mut BufReader<S>);
+
+impl<S: Read> Read for Upgraded<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<S: Write> Write for Upgraded<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.get_mut().flush()
+    }
+}
+
+/// The most of a request the `Server` reads in from a client, rejecting a
+/// request going over any of them with a 414, 431 or 413 as fits, rather
+/// than holding all of it in memory, along with the most requests it handles
+/// at once.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Limits, Server};
+/// let mut server = Server::default();
+/// server.limits(Limits {
+///     max_body: 64 * 1024 * 1024,
+///     ..Limits::default()
+/// });
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Limits {
+    /// The longest request line, 8 KiB by default. Answered with a 414.
+    pub max_request_line: usize,
+    /// The most bytes of headers, 64 KiB by default. Answered with a 431.
+    pub max_header_bytes: usize,
+    /// The most headers, 100 by default. Answered with a 431.
+    pub max_headers: usize,
+    /// The largest body, not counting any chunked framing, 10 MiB by
+    /// default. Answered with a 413.
+    pub max_body: u64,
+    /// The largest multipart body held in memory, 1 MiB by default. One
+    /// larger, as an upload may well be, is written to a temporary file as it
+    /// is read in instead, for its handler to read back through
+    /// [`HttpRequest::body_reader`]. It is still held to `max_body`.
+    ///
+    /// [`HttpRequest::body_reader`]: ../web/struct.HttpRequest.html#method.body_reader
+    pub max_body_in_memory: u64,
+    /// The directory a body too large to be held in memory is written to,
+    /// that of `std::env::temp_dir` by default. Its file is only readable by
+    /// the user the `Server` runs as, and removed once done with.
+    pub spool_dir: Option<PathBuf>,
+    /// The most requests handled at once, across every route, unlimited by
+    /// default. Answered with a 503 right away, rather than queueing up
+    /// behind those being handled.
+    pub max_in_flight: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_request_line: 8 * 1024,
+            max_header_bytes: 64 * 1024,
+            max_headers: 100,
+            max_body: 10 * 1024 * 1024,
+            max_body_in_memory: 1024 * 1024,
+            spool_dir: None,
+            max_in_flight: None,
+        }
+    }
+}
+
+/// Whether the client asks for the connection to be kept open after the
+/// response, the default from HTTP/1.1 on.
+pub(in crate::server) fn wants_keep_alive(request: &HttpRequest) -> bool {
+    let has_option = |option: &str| {
+        request.header("Connection").is_some_and(|connection| {
+            connection
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case(option))
+        })
+    };
+    match request.http_version >= HttpVersion::Http1_1 {
+        true => !has_option("close"),
+        false => has_option("keep-alive"),
+    }
+}
+
+/// Lets the client know whether the connection is kept open after the
+/// response, unless the response already asks for it to be closed or has a
+/// body only ended by closing it.
+///
+/// # Returns:
+/// Whether the connection is kept open.
+pub(in crate::server) fn set_keep_alive(response: &mut HttpResponse, keep_alive: bool) -> bool {
+    let is_closing = response.headers.iter().any(|(key, value)| {
+        key.eq_ignore_ascii_case("Connection") && value.eq_ignore_ascii_case("close")
+    });
+    let is_delimited_by_close = response.http_version < HttpVersion::Http1_1
+        && matches!(response.body, Body::Stream(_))
+        && response.stream_len().is_none()
+        && response.status_code.allows_body();
+    if is_closing || is_delimited_by_close {
+        return false;
+    }
+    let is_persistent_by_default = response.http_version >= HttpVersion::Http1_1;
+    if keep_alive != is_persistent_by_default {
+        let connection = if keep_alive { "keep-alive" } else { "close" };
+        response
+            .headers
+            .insert("Connection".into(), connection.into());
+    }
+    keep_alive
+}
+
+/// Why a request could not be read off of the connection.
+pub(in crate::server) enum ReadError {
+    Io(io::Error),
+    /// Part of the request is over its [`Limits`], to be answered with the
+    /// status rather than read any further.
+    ///
+    /// [`Limits`]: ./struct.Limits.html
+    Rejected(StatusCode),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(io_error: io::Error) -> ReadError {
+        ReadError::Io(io_error)
+    }
+}
+
+/// Reads through the buffer of a connection, each read off of the socket
+/// under it only waiting for whatever is left until the deadline. A client
+/// trickling a request in a byte at a time is timed out all the same, rather
+/// than the wait starting over with every byte.
+pub(in crate::server) struct UntilDeadline<'r, R> {
+    reader: &'r mut BufReader<R>,
+    socket: Option<&'r TcpStream>,
+    deadline: Option<Instant>,
+}
+
+impl<'r, R: Read> UntilDeadline<'r, R> {
+    pub(in crate::server) fn new(
+        reader: &'r mut BufReader<R>,
+        socket: Option<&'r TcpStream>,
+        deadline: Option<Instant>,
+    ) -> UntilDeadline<'r, R> {
+        UntilDeadline {
+            reader,
+            socket,
+            deadline,
+        }
+    }
+
+    /// Sets the socket to time out at the deadline, once the buffer has run
+    /// dry and it is about to be read off of.
+    fn wait(&self) -> io::Result<()> {
+        let deadline = match self.deadline {
+            Some(deadline) if self.reader.buffer().is_empty() => deadline,
+            _ => return Ok(()),
+        };
+        let time_left = deadline.saturating_duration_since(Instant::now());
+        if time_left.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        match self.socket {
+            Some(socket) => socket.set_read_timeout(Some(time_left)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<R: Read> Read for UntilDeadline<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.wait()?;
+        self.reader.read(buf)
+    }
+}
+
+impl<R: Read> BufRead for UntilDeadline<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.wait()?;
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.reader.consume(amount)
+    }
+}
+
+/// What is to be read of a request next, as worked out by its [`Framing`].
+///
+/// [`Framing`]: ./struct.Framing.html
+pub(in crate::server) enum Next {
+    /// A line, rejected with the status once longer than the limit, not
+    /// counting its line ending.
+    Line(usize, StatusCode),
+    /// That many bytes of the body.
+    Body(u64),
+    /// Nothing more, the request has been read in whole.
+    Done,
+}
+
+/// How far along the request a [`Framing`] is.
+///
+/// [`Framing`]: ./struct.Framing.html
+#[derive(Clone, Copy)]
+enum Stage {
+    RequestLine,
+    Headers,
+    /// The body of the length given by `Content-Length`.
+    Sized(u64),
+    /// The line giving the size of the next chunk.
+    ChunkSize,
+    /// The data of a chunk of the size.
+    ChunkData(u64),
+    /// The line ending following the data of a chunk.
+    ChunkEnd,
+    Trailers,
+    Done,
+}
+
+/// Works out how a request is framed as it is read in, its head up to the
+/// blank line and then a body framed by either `Content-Length` or
+/// `Transfer-Encoding: chunked`. Nothing past the [`Limits`] is asked for.
+/// Every way the `Server` reads requests goes through it, be it blocking or
+/// not, leaving each only to do the reading.
+///
+/// [`Limits`]: ./struct.Limits.html
+pub(in crate::server) struct Framing<'l> {
+    limits: &'l Limits,
+    stage: Stage,
+    head: Vec<u8>,
+    body: Spool,
+    content_length: Option<u64>,
+    is_chunked: bool,
+    is_multipart: bool,
+    headers: usize,
+    /// The bytes of the headers, or of the trailers once past the body.
+    header_bytes: usize,
+}
+
+impl<'l> Framing<'l> {
+    pub(in crate::server) fn new(limits: &'l Limits) -> Framing<'l> {
+        Framing {
+            limits,
+            stage: Stage::RequestLine,
+            head: Vec::new(),
+            body: Spool::default(),
+            content_length: None,
+            is_chunked: false,
+            is_multipart: false,
+            headers: 0,
+            header_bytes: 0,
+        }
+    }
+
+    pub(in crate::server) fn next(&self) -> Next {
+        let header_budget = self
+            .limits
+            .max_header_bytes
+            .saturating_sub(self.header_bytes);
+        match self.stage {
+            Stage::RequestLine => Next::Line(self.limits.max_request_line, StatusCode::UriTooLong),
+            Stage::Headers | Stage::Trailers => {
+                Next::Line(header_budget, StatusCode::RequestHeaderFieldsTooLarge)
+            }
+            Stage::ChunkSize => Next::Line(MAX_CHUNK_LINE, StatusCode::BadRequest),
+            Stage::ChunkEnd => Next::Line(0, StatusCode::BadRequest),
+            Stage::Sized(length) | Stage::ChunkData(length) => Next::Body(length),
+            Stage::Done => Next::Done,
+        }
+    }
+
+    /// Takes in the line asked for, line ending and all.
+    pub(in crate::server) fn line_read(&mut self, line: &[u8]) -> Result<(), ReadError> {
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim_end_matches(&['\r', '\n'][..]);
+        self.stage = match self.stage {
+            Stage::RequestLine => {
+                self.head.extend_from_slice(line);
+                Stage::Headers
+            }
+            Stage::Headers => {
+                self.head.extend_from_slice(line);
+                match text.is_empty() {
+                    true => self.body_stage()?,
+                    false => {
+                        self.header_read(text)?;
+                        self.header_bytes += line.len();
+                        Stage::Headers
+                    }
+                }
+            }
+            Stage::ChunkSize => match chunked::chunk_size(text) {
+                Ok(0) => {
+                    self.header_bytes = 0;
+                    Stage::Trailers
+                }
+                Ok(size) if self.body.len() + size as u64 > self.limits.max_body => {
+                    return Err(ReadError::Rejected(StatusCode::ContentTooLarge));
+                }
+                Ok(size) => Stage::ChunkData(size as u64),
+                Err(_) => return Err(ReadError::Rejected(StatusCode::BadRequest)),
+            },
+            Stage::ChunkEnd if text.is_empty() => Stage::ChunkSize,
+            Stage::ChunkEnd => return Err(ReadError::Rejected(StatusCode::BadRequest)),
+            Stage::Trailers if text.is_empty() => Stage::Done,
+            Stage::Trailers => {
+                self.header_bytes += line.len();
+                Stage::Trailers
+            }
+            stage => stage,
+        };
+        Ok(())
+    }
+
+    /// Where the bytes of the body asked for go, to be followed by a call to
+    /// [`body_read`] once they have all been read.
+    ///
+    /// [`body_read`]: ./struct.Framing.html#method.body_read
+    pub(in crate::server) fn body_mut(&mut self) -> &mut Spool {
+        &mut self.body
+    }
+
+    pub(in crate::server) fn body_read(&mut self) {
+        self.stage = match self.stage {
+            Stage::Sized(_) => Stage::Done,
+            Stage::ChunkData(_) => Stage::ChunkEnd,
+            stage => stage,
+        };
+    }
+
+    /// The request read in, a malformed one being rejected with a 400. A
+    /// body written to a temporary file is kept in its extensions.
+    pub(in crate::server) fn finish(self) -> Result<HttpRequest, ReadError> {
+        let head = self.head.strip_suffix(b"\r\n\r\n").unwrap_or(&self.head);
+        let mut request = HttpRequestRef::parse_head(head)
+            .map_err(|_| ReadError::Rejected(StatusCode::BadRequest))?
+            .to_owned();
+        match self.body.finish()? {
+            Spooled::Memory(bytes) => request.body = Some(bytes).filter(|body| !body.is_empty()),
+            Spooled::File(spooled) => request.extensions.insert(spooled),
+        }
+        Ok(request)
+    }
+
+    /// Takes note of how the request is framed, rejecting any way of framing
+    /// it which could be read as another by whoever passed it on, such as a
+    /// malformed or repeated `Content-Length` or a transfer coding other
+    /// than chunked.
+    fn header_read(&mut self, header: &str) -> Result<(), ReadError> {
+        self.headers += 1;
+        if self.headers > self.limits.max_headers {
+            return Err(ReadError::Rejected(StatusCode::RequestHeaderFieldsTooLarge));
+        }
+        let bad_request = Err(ReadError::Rejected(StatusCode::BadRequest));
+        if let Some((key, value)) = header.split_once(':') {
+            let value = value.trim();
+            if key.eq_ignore_ascii_case("Content-Length") {
+                let is_digits = !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit());
+                match value.parse().ok().filter(|_| is_digits) {
+                    Some(length) if self.content_length.unwrap_or(length) == length => {
+                        self.content_length = Some(length);
+                    }
+                    _ => return bad_request,
+                }
+            } else if key.eq_ignore_ascii_case("Transfer-Encoding") {
+                if self.is_chunked || !value.eq_ignore_ascii_case("chunked") {
+                    return bad_request;
+                }
+                self.is_chunked = true;
+            } else if key.eq_ignore_ascii_case("Content-Type") {
+                self.is_multipart = multipart::boundary(value).is_some();
+            }
+        }
+        Ok(())
+    }
+
+    /// What follows the head, as its framing headers have it, readying the
+    /// body to be written to a temporary file should it be a large multipart
+    /// one.
+    fn body_stage(&mut self) -> Result<Stage, ReadError> {
+        if self.is_multipart {
+            let dir = self.limits.spool_dir.clone().unwrap_or_else(env::temp_dir);
+            self.body = Spool::spilling(self.limits.max_body_in_memory, dir);
+        }
+        match (self.is_chunked, self.content_length.unwrap_or(0)) {
+            (true, _) if self.content_length.is_some() => {
+                Err(ReadError::Rejected(StatusCode::BadRequest))
+            }
+            (true, _) => Ok(Stage::ChunkSize),
+            (false, 0) => Ok(Stage::Done),
+            (false, length) if length > self.limits.max_body => {
+                Err(ReadError::Rejected(StatusCode::ContentTooLarge))
+            }
+            (false, length) => Ok(Stage::Sized(length)),
+        }
+    }
+}
+
+/// Reads a single request, as its [`Framing`] asks for it.
+///
+/// [`Framing`]: ./struct.Framing.html
+pub(in crate::server) fn read_request<R: BufRead>(
+    reader: &mut R,
+    limits: &Limits,
+) -> Result<HttpRequest, ReadError> {
+    let mut framing = Framing::new(limits);
+    loop {
+        match framing.next() {
+            Next::Line(limit, status_code) => {
+                let line = read_line(reader, limit, status_code)?;
+                framing.line_read(&line)?;
+            }
+            Next::Body(length) => {
+                if io::copy(&mut reader.take(length), framing.body_mut())? < length {
+                    return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+                }
+                framing.body_read();
+            }
+            Next::Done => return framing.finish(),
+        }
+    }
+}
+
+/// Reads a line, line ending and all. A line longer than `limit`, not
+/// counting its line ending, is rejected with the status.
+fn read_line<R: BufRead>(
+    reader: &mut R,
+    limit: usize,
+    status_code: StatusCode,
+) -> Result<Vec<u8>, ReadError> {
+    let mut line = Vec::new();
+    let max_read = limit as u64 + 2;
+    let read = reader.take(max_read).read_until(b'\n', &mut line)?;
+    check_line(&line, read as u64 == max_read, status_code)?;
+    Ok(line)
+}
+
+/// Checks a line was read whole, `at_limit` when reading it stopped at the
+/// most it could be.
+pub(in crate::server) fn check_line(
+    line: &[u8],
+    at_limit: bool,
+    status_code: StatusCode,
+) -> Result<(), ReadError> {
+    match (line.last(), at_limit) {
+        (Some(b'\n'), _) => Ok(()),
+        (Some(_), true) => Err(ReadError::Rejected(status_code)),
+        _ => Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+    }
+}
@@ -0,0 +1,218 @@
+//! Letting the scripts of other origins make requests, as browsers only do
+//! once told through the `Access-Control-*` headers that they may.
+
+use std::time::Duration;
+
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+use super::{Middleware, Next};
+
+/// [`Middleware`] answering CORS preflight requests by itself and adding the
+/// CORS headers to the responses of the origins it allows. Nothing is allowed
+/// until asked for, aside from the `GET`, `HEAD` and `POST` methods.
+///
+/// A preflight request, an `OPTIONS` with an `Access-Control-Request-Method`,
+/// is answered with a 204 listing what is allowed, or a 403 when its origin
+/// is not allowed. Any other request is passed on, its response only being
+/// given the headers when its origin is allowed, so that the browser keeps
+/// the response from a script of any other.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Cors, Server};
+/// use martian::web::HttpMethod;
+/// use std::time::Duration;
+/// let mut server = Server::default();
+/// server.wrap(
+///     Cors::new()
+///         .allow_origin("https://app.example.com")
+///         .allow_methods(&[HttpMethod::Get, HttpMethod::Put, HttpMethod::Delete])
+///         .allow_headers(&["Authorization", "Content-Type"])
+///         .allow_credentials()
+///         .max_age(Duration::from_secs(600)),
+/// );
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cors {
+    /// `None` allows any origin.
+    origins: Option<Vec<String>>,
+    methods: Vec<HttpMethod>,
+    /// `None` allows whichever headers a preflight request asks for.
+    headers: Option<Vec<String>>,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Default for Cors {
+    fn default() -> Cors {
+        Cors {
+            origins: Some(Vec::new()),
+            methods: vec![HttpMethod::Get, HttpMethod::Head, HttpMethod::Post],
+            headers: Some(Vec::new()),
+            exposed_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+impl Cors {
+    pub fn new() -> Cors {
+        Cors::default()
+    }
+
+    /// Allows requests from any origin, whichever headers they ask for.
+    pub fn permissive() -> Cors {
+        Cors::new().allow_any_origin().allow_any_header()
+    }
+
+    /// Allows requests from the origin, such as `https://example.com`, on
+    /// top of those already allowed.
+    pub fn allow_origin(mut self, origin: &str) -> Cors {
+        if let Some(origins) = &mut self.origins {
+            origins.push(origin.trim_end_matches('/').into());
+        }
+        self
+    }
+
+    pub fn allow_any_origin(mut self) -> Cors {
+        self.origins = None;
+        self
+    }
+
+    /// The methods listed to a preflight request, in place of `GET`, `HEAD`
+    /// and `POST`.
+    pub fn allow_methods(mut self, methods: &[HttpMethod]) -> Cors {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// The headers a request may be sent with, on top of those a browser
+    /// always allows, such as `Accept`.
+    pub fn allow_headers(mut self, headers: &[&str]) -> Cors {
+        self.headers = Some(headers.iter().map(|header| header.to_string()).collect());
+        self
+    }
+
+    pub fn allow_any_header(mut self) -> Cors {
+        self.headers = None;
+        self
+    }
+
+    /// The headers of a response a script may read, on top of those it
+    /// always can, such as `Content-Type`.
+    pub fn expose_headers(mut self, headers: &[&str]) -> Cors {
+        self.exposed_headers = headers.iter().map(|header| header.to_string()).collect();
+        self
+    }
+
+    /// Allows requests made with cookies or other credentials. The origin of
+    /// the request is then always echoed back rather than allowing any with
+    /// `*`, which browsers refuse credentials for.
+    pub fn allow_credentials(mut self) -> Cors {
+        self.credentials = true;
+        self
+    }
+
+    /// How long a browser may cache the answer to a preflight request.
+    pub fn max_age(mut self, max_age: Duration) -> Cors {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        match &self.origins {
+            Some(origins) => origins.iter().any(|allowed| allowed == origin),
+            None => true,
+        }
+    }
+
+    /// Answers a preflight request from an allowed origin.
+    fn preflight(&self, request: &HttpRequest, origin: &str) -> HttpResponse {
+        let mut response = HttpResponse::new(StatusCode::NoContent);
+        self.allow(&mut response, origin);
+        let methods = self
+            .methods
+            .iter()
+            .map(HttpMethod::as_str)
+            .collect::<Vec<_>>();
+        response
+            .headers
+            .insert("Access-Control-Allow-Methods".into(), methods.join(", "));
+        let headers = match &self.headers {
+            Some(headers) => headers.join(", "),
+            None => request
+                .header("Access-Control-Request-Headers")
+                .unwrap_or_default()
+                .into(),
+        };
+        if !headers.is_empty() {
+            response
+                .headers
+                .insert("Access-Control-Allow-Headers".into(), headers);
+        }
+        if let Some(max_age) = self.max_age {
+            response.headers.insert(
+                "Access-Control-Max-Age".into(),
+                max_age.as_secs().to_string(),
+            );
+        }
+        response
+    }
+
+    /// Adds the headers allowing the origin to the response.
+    fn allow(&self, response: &mut HttpResponse, origin: &str) {
+        let allowed_origin = match self.origins.is_none() && !self.credentials {
+            true => "*",
+            false => origin,
+        };
+        let headers = &mut response.headers;
+        headers.insert("Access-Control-Allow-Origin".into(), allowed_origin.into());
+        if self.credentials {
+            headers.insert("Access-Control-Allow-Credentials".into(), "true".into());
+        }
+        if allowed_origin != "*" {
+            let vary = match headers.get("Vary") {
+                Some(vary) => format!("{}, Origin", vary),
+                None => "Origin".into(),
+            };
+            headers.insert("Vary".into(), vary);
+        }
+    }
+}
+
+impl Middleware for Cors {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let origin = match request.header("Origin") {
+            Some(origin) => origin.to_string(),
+            // Not a cross-origin request at all.
+            None => return next.run(request),
+        };
+        let is_preflight = request.http_method == HttpMethod::Options
+            && request.header("Access-Control-Request-Method").is_some();
+        let is_allowed = self.allows(&origin);
+        if is_preflight {
+            return match is_allowed {
+                true => self.preflight(&request, &origin),
+                false => HttpResponse::new(StatusCode::Forbidden),
+            };
+        }
+        let mut response = next.run(request);
+        if is_allowed {
+            self.allow(&mut response, &origin);
+            if !self.exposed_headers.is_empty() {
+                response.headers.insert(
+                    "Access-Control-Expose-Headers".into(),
+                    self.exposed_headers.join(", "),
+                );
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,113 @@
+use crate::server::{Cors, Route, Server};
+use crate::test::TestClient;
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+use std::time::Duration;
+
+fn cors_client(cors: Cors) -> TestClient {
+    let mut server = Server::default();
+    server.wrap(cors);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/items", |_| {
+                let mut response = HttpResponse::text("items");
+                response.headers.insert("X-Total".into(), "1".into());
+                response
+            })
+        })
+        .unwrap();
+    TestClient::new(server)
+}
+
+fn restricted() -> Cors {
+    Cors::new()
+        .allow_origin("https://app.example.com/")
+        .allow_methods(&[HttpMethod::Get, HttpMethod::Delete])
+        .allow_headers(&["Authorization"])
+        .expose_headers(&["X-Total"])
+        .allow_credentials()
+        .max_age(Duration::from_secs(600))
+}
+
+#[test]
+fn should_answer_preflight_when_origin_is_allowed() {
+    let response = cors_client(restricted()).send(HttpRequest::from(
+        "OPTIONS /items HTTP/1.1\r\nOrigin: https://app.example.com\r\n\
+         Access-Control-Request-Method: DELETE\r\n\r\n",
+    ));
+    assert_eq!(response.status_code, StatusCode::NoContent);
+    let header = |name: &str| response.headers.get(name).map(String::as_str);
+    assert_eq!(
+        header("Access-Control-Allow-Origin"),
+        Some("https://app.example.com")
+    );
+    assert_eq!(header("Access-Control-Allow-Methods"), Some("GET, DELETE"));
+    assert_eq!(
+        header("Access-Control-Allow-Headers"),
+        Some("Authorization")
+    );
+    assert_eq!(header("Access-Control-Allow-Credentials"), Some("true"));
+    assert_eq!(header("Access-Control-Max-Age"), Some("600"));
+    assert_eq!(header("Vary"), Some("Origin"));
+}
+
+#[test]
+fn should_forbid_preflight_when_origin_is_not_allowed() {
+    let response = cors_client(restricted()).send(HttpRequest::from(
+        "OPTIONS /items HTTP/1.1\r\nOrigin: https://evil.example.com\r\n\
+         Access-Control-Request-Method: GET\r\n\r\n",
+    ));
+    assert_eq!(response.status_code, StatusCode::Forbidden);
+    assert!(!response.headers.contains_key("Access-Control-Allow-Origin"));
+}
+
+#[test]
+fn should_add_headers_to_response_when_origin_is_allowed() {
+    let client = cors_client(restricted());
+    let response = client.send(HttpRequest::from(
+        "GET /items HTTP/1.1\r\nOrigin: https://app.example.com\r\n\r\n",
+    ));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(
+        response.headers.get("Access-Control-Allow-Origin"),
+        Some(&"https://app.example.com".to_string())
+    );
+    assert_eq!(
+        response.headers.get("Access-Control-Expose-Headers"),
+        Some(&"X-Total".to_string())
+    );
+    let response = client.send(HttpRequest::from(
+        "GET /items HTTP/1.1\r\nOrigin: https://evil.example.com\r\n\r\n",
+    ));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert!(!response.headers.contains_key("Access-Control-Allow-Origin"));
+}
+
+#[test]
+fn should_allow_any_origin_and_requested_headers_when_permissive() {
+    let client = cors_client(Cors::permissive());
+    let response = client.send(HttpRequest::from(
+        "OPTIONS /items HTTP/1.1\r\nOrigin: https://any.example.com\r\n\
+         Access-Control-Request-Method: GET\r\nAccess-Control-Request-Headers: X-Custom\r\n\r\n",
+    ));
+    assert_eq!(
+        response.headers.get("Access-Control-Allow-Origin"),
+        Some(&"*".to_string())
+    );
+    assert_eq!(
+        response.headers.get("Access-Control-Allow-Headers"),
+        Some(&"X-Custom".to_string())
+    );
+    assert!(!response.headers.contains_key("Vary"));
+}
+
+#[test]
+fn should_pass_request_through_when_not_cross_origin() {
+    let response =
+        cors_client(restricted()).send(HttpRequest::from("OPTIONS /items HTTP/1.1\r\n\r\n"));
+    assert_eq!(response.status_code, StatusCode::NoContent);
+    assert_eq!(
+        response.headers.get("Allow"),
+        Some(&"GET, HEAD, OPTIONS".to_string())
+    );
+    assert!(!response.headers.contains_key("Access-Control-Allow-Origin"));
+}
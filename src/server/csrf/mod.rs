@@ -0,0 +1,202 @@
+//! Keeping other sites from making requests on behalf of a user, through a
+//! token only pages of this one can read, see the
+//! [double-submit cookie](https://cheatsheetseries.owasp.org/cheatsheets/Cross-Site_Request_Forgery_Prevention_Cheat_Sheet.html#alternative-using-a-double-submit-cookie-pattern)
+//! pattern.
+
+use crate::web::encoding::{base64url_encode, escape_html};
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, SameSite, SetCookie, StatusCode};
+
+use super::{Middleware, Next};
+
+/// [`Middleware`] rejecting `POST`, `PUT`, `PATCH` and `DELETE` requests with
+/// a 403 unless they send back the token of their cookie, either through a
+/// header or a form field. A browser sends the cookie along with requests
+/// made from any site, but only pages of this one can read it to send the
+/// token as well.
+///
+/// A request without the cookie is given a new token, set as the cookie on
+/// its response. Every request passed on has its [`CsrfToken`] in its
+/// [`extensions`], for the callback to embed into its page.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Csrf, CsrfToken, Route, Server};
+/// use martian::web::{HttpMethod, HttpRequest, HttpResponse};
+/// let mut server = Server::default();
+/// server.wrap(Csrf::new().secure());
+/// server
+///     .route(|| {
+///         Route::bind(HttpMethod::Get).to("/profile", |request: HttpRequest| {
+///             let token = request.extensions.get::<CsrfToken>().unwrap();
+///             HttpResponse::html(&format!(
+///                 "<form method=\"post\">{}<button>Save</button></form>",
+///                 token.hidden_input()
+///             ))
+///         })
+///     })
+///     .unwrap();
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`CsrfToken`]: ./struct.CsrfToken.html
+/// [`extensions`]: ../web/struct.HttpRequest.html#structfield.extensions
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Csrf {
+    cookie_name: String,
+    header_name: String,
+    field_name: String,
+    secure: bool,
+}
+
+impl Default for Csrf {
+    fn default() -> Csrf {
+        Csrf {
+            cookie_name: "csrf_token".into(),
+            header_name: "X-CSRF-Token".into(),
+            field_name: "csrf_token".into(),
+            secure: false,
+        }
+    }
+}
+
+impl Csrf {
+    pub fn new() -> Csrf {
+        Csrf::default()
+    }
+
+    /// The cookie holding the token, `csrf_token` unless given.
+    pub fn cookie_name(mut self, cookie_name: &str) -> Csrf {
+        self.cookie_name = cookie_name.into();
+        self
+    }
+
+    /// The header a script sends the token back through, `X-CSRF-Token`
+    /// unless given.
+    pub fn header_name(mut self, header_name: &str) -> Csrf {
+        self.header_name = header_name.into();
+        self
+    }
+
+    /// The form field a form sends the token back through, `csrf_token`
+    /// unless given.
+    pub fn field_name(mut self, field_name: &str) -> Csrf {
+        self.field_name = field_name.into();
+        self
+    }
+
+    /// Only sets the cookie to be sent back over https.
+    pub fn secure(mut self) -> Csrf {
+        self.secure = true;
+        self
+    }
+
+    /// The token the request sent back, if any.
+    fn submitted_token(&self, request: &HttpRequest) -> Option<String> {
+        if let Some(token) = request.header(&self.header_name) {
+            return Some(token.into());
+        }
+        request.form()?.get(&self.field_name).map(String::from)
+    }
+
+    fn cookie(&self, token: &str) -> SetCookie {
+        // Not `HttpOnly`, so that scripts of the site can read it.
+        let cookie = SetCookie::new(&self.cookie_name, token)
+            .path("/")
+            .same_site(SameSite::Lax);
+        match self.secure {
+            true => cookie.secure(),
+            false => cookie,
+        }
+    }
+}
+
+impl Middleware for Csrf {
+    fn handle(&self, mut request: HttpRequest, next: Next) -> HttpResponse {
+        let cookie_token = request
+            .cookie(&self.cookie_name)
+            .filter(|token| !token.is_empty())
+            .map(String::from);
+        let is_state_changing = matches!(
+            request.http_method,
+            HttpMethod::Post | HttpMethod::Put | HttpMethod::Patch | HttpMethod::Delete
+        );
+        if is_state_changing {
+            let is_matching = match (&cookie_token, self.submitted_token(&request)) {
+                (Some(cookie_token), Some(submitted)) => {
+                    is_equal(cookie_token.as_bytes(), submitted.as_bytes())
+                }
+                _ => false,
+            };
+            if !is_matching {
+                return HttpResponse::new(StatusCode::Forbidden);
+            }
+        }
+        let (value, is_new) = match cookie_token {
+            Some(token) => (token, false),
+            None => match generate_token() {
+                Some(token) => (token, true),
+                None => return HttpResponse::new(StatusCode::InternalServerError),
+            },
+        };
+        request.extensions.insert(CsrfToken {
+            value: value.clone(),
+            header_name: self.header_name.clone(),
+            field_name: self.field_name.clone(),
+        });
+        let mut response = next.run(request);
+        if is_new {
+            response.set_cookie(self.cookie(&value));
+        }
+        response
+    }
+}
+
+/// The token of a request passed on by [`Csrf`], as found in its
+/// [`extensions`], to be embedded into the page a callback answers with so
+/// that it can send the token back.
+///
+/// [`Csrf`]: ./struct.Csrf.html
+/// [`extensions`]: ../web/struct.HttpRequest.html#structfield.extensions
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CsrfToken {
+    value: String,
+    header_name: String,
+    field_name: String,
+}
+
+impl CsrfToken {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The hidden `<input>` sending the token back along with a form.
+    pub fn hidden_input(&self) -> String {
+        format!(
+            "<input type=\"hidden\" name=\"{}\" value=\"{}\">",
+            escape_html(&self.field_name),
+            escape_html(&self.value)
+        )
+    }
+
+    /// The name and value of the header sending the token back, such as
+    /// along with a `fetch` of a script.
+    pub fn header(&self) -> (&str, &str) {
+        (&self.header_name, &self.value)
+    }
+}
+
+/// 32 random bytes, as base64url.
+fn generate_token() -> Option<String> {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes).ok()?;
+    Some(base64url_encode(&bytes))
+}
+
+/// Compares the bytes in a time only depending on their lengths, so that
+/// the time taken gives no hint of how much of a guess was right.
+fn is_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,108 @@
+use crate::server::{Csrf, CsrfToken, Route, Server};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{Body, HttpMethod, HttpRequest, StatusCode};
+
+fn csrf_client(csrf: Csrf) -> TestClient {
+    let mut server = Server::default();
+    server.wrap(csrf);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/form", |request: HttpRequest| {
+                let token = request.extensions.get::<CsrfToken>().unwrap();
+                token.hidden_input()
+            })
+        })
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/form", |_| "saved"))
+        .unwrap();
+    TestClient::new(server)
+}
+
+fn request(raw_request: &str) -> TestResponse {
+    csrf_client(Csrf::new()).send(HttpRequest::from(raw_request))
+}
+
+#[test]
+fn should_set_new_token_cookie_when_request_has_none() {
+    let response =
+        csrf_client(Csrf::new().secure()).send(HttpRequest::from("GET /form HTTP/1.1\r\n\r\n"));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.cookies.len(), 1);
+    let cookie = &response.cookies[0];
+    assert_eq!(cookie.name, "csrf_token");
+    assert_eq!(cookie.value.len(), 43);
+    assert!(cookie.secure && !cookie.http_only);
+    assert_eq!(
+        response.body,
+        Body::from(
+            format!(
+                "<input type=\"hidden\" name=\"csrf_token\" value=\"{}\">",
+                cookie.value
+            )
+            .as_str()
+        )
+    );
+}
+
+#[test]
+fn should_keep_token_when_request_has_cookie() {
+    let response = request("GET /form HTTP/1.1\r\nCookie: csrf_token=abc\r\n\r\n");
+    assert!(response.cookies.is_empty());
+    assert_eq!(
+        response.body,
+        Body::from("<input type=\"hidden\" name=\"csrf_token\" value=\"abc\">")
+    );
+}
+
+#[test]
+fn should_pass_request_on_when_token_is_sent_back() {
+    let through_header =
+        request("POST /form HTTP/1.1\r\nCookie: csrf_token=abc\r\nX-CSRF-Token: abc\r\n\r\n");
+    assert_eq!(through_header.status_code, StatusCode::Ok);
+    let through_form = request(
+        "POST /form HTTP/1.1\r\nCookie: csrf_token=abc\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\n\
+         Content-Length: 23\r\n\r\nname=ada&csrf_token=abc",
+    );
+    assert_eq!(through_form.status_code, StatusCode::Ok);
+    assert_eq!(through_form.body, Body::from("saved"));
+}
+
+#[test]
+fn should_reject_state_changing_request_when_token_is_missing_or_wrong() {
+    for raw_request in &[
+        "POST /form HTTP/1.1\r\nCookie: csrf_token=abc\r\n\r\n",
+        "POST /form HTTP/1.1\r\nX-CSRF-Token: abc\r\n\r\n",
+        "POST /form HTTP/1.1\r\nCookie: csrf_token=abc\r\nX-CSRF-Token: abd\r\n\r\n",
+        "POST /form HTTP/1.1\r\nCookie: csrf_token=\r\nX-CSRF-Token: \r\n\r\n",
+    ] {
+        assert_eq!(
+            request(raw_request).status_code,
+            StatusCode::Forbidden,
+            "{}",
+            raw_request
+        );
+    }
+}
+
+#[test]
+fn should_use_configured_names_when_given() {
+    let client = csrf_client(
+        Csrf::new()
+            .cookie_name("xsrf")
+            .header_name("X-XSRF-Token")
+            .field_name("_token"),
+    );
+    let response = client.send(HttpRequest::from(
+        "POST /form HTTP/1.1\r\nCookie: xsrf=abc\r\nX-XSRF-Token: abc\r\n\r\n",
+    ));
+    assert_eq!(response.status_code, StatusCode::Ok);
+    let response = client.send(HttpRequest::from(
+        "GET /form HTTP/1.1\r\nCookie: xsrf=abc\r\n\r\n",
+    ));
+    assert_eq!(
+        response.body,
+        Body::from("<input type=\"hidden\" name=\"_token\" value=\"abc\">")
+    );
+}
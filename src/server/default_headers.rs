@@ -0,0 +1,85 @@
+//! The headers the `Server` adds to every response it writes out, unless the
+//! response has them already.
+
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::web::date::format_http_date;
+use crate::web::HttpResponse;
+
+/// The headers the [`Server`] stamps every response it writes out with. A
+/// header the response has already, in any case, is left as it is. Responses
+/// handled through [`Server::handle`] are not stamped, only those written to
+/// a connection.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{DefaultHeaders, Server};
+/// let mut server = Server::default();
+/// server.default_headers(DefaultHeaders {
+///     server: None,
+///     ..DefaultHeaders::default()
+/// });
+/// ```
+///
+/// [`Server`]: ./struct.Server.html
+/// [`Server::handle`]: ./struct.Server.html#method.handle
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct DefaultHeaders {
+    /// Whether to add a `Date` of when the response is written, as is done
+    /// by default. The date is formatted at most once a second per thread.
+    pub date: bool,
+    /// The `Server` to add, `martian/` followed by the version of the crate
+    /// by default, or none at all.
+    pub server: Option<String>,
+}
+
+impl DefaultHeaders {
+    /// Leaves responses as they are.
+    pub fn none() -> DefaultHeaders {
+        DefaultHeaders {
+            date: false,
+            server: None,
+        }
+    }
+
+    pub(in crate::server) fn stamp(&self, response: &mut HttpResponse) {
+        if self.date && response.header("Date").is_none() {
+            response.headers.insert("Date".into(), now());
+        }
+        if let Some(server) = &self.server {
+            if response.header("Server").is_none() {
+                response.headers.insert("Server".into(), server.clone());
+            }
+        }
+    }
+}
+
+impl Default for DefaultHeaders {
+    fn default() -> DefaultHeaders {
+        DefaultHeaders {
+            date: true,
+            server: Some(concat!("martian/", env!("CARGO_PKG_VERSION")).into()),
+        }
+    }
+}
+
+thread_local! {
+    /// The second last formatted and its date.
+    static DATE: RefCell<(u64, String)> = const { RefCell::new((0, String::new())) };
+}
+
+/// The current date as written in a `Date` header.
+fn now() -> String {
+    let now = SystemTime::now();
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    DATE.with(|date| {
+        let mut date = date.borrow_mut();
+        if date.0 != secs || date.1.is_empty() {
+            *date = (secs, format_http_date(now));
+        }
+        date.1.clone()
+    })
+}
@@ -0,0 +1,262 @@
+//! Serving with the `mio` feature, see [`IoModel::EventLoop`]. A single
+//! thread polls every connection left idle, handing one over to the workers
+//! once its next request starts coming in. A worker serves requests off of
+//! the connection for as long as they follow one another, parking it back
+//! with the poller once nothing more is buffered.
+//!
+//! [`IoModel::EventLoop`]: ../enum.IoModel.html#variant.EventLoop
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use mio::{Events, Interest, Poll, Token, Waker};
+
+use crate::web::ConnectionInfo;
+
+use super::connection::Connection;
+use super::Server;
+
+/// Wakes the poller for whatever has been sent to it.
+const WAKER: Token = Token(usize::MAX);
+
+/// A connection waiting on its next request.
+struct Parked<C> {
+    state: State<C>,
+    /// Kept for setting the timeouts of reads through the connection.
+    socket: TcpStream,
+    /// Registered with the poller while the connection is idle.
+    source: mio::net::TcpStream,
+    connection_info: Option<ConnectionInfo>,
+    /// How many requests have been served on the connection so far.
+    served: usize,
+}
+
+enum State<C> {
+    /// Only connected once its first request starts coming in, so that a
+    /// TLS handshake is done by a worker.
+    Accepted(TcpStream),
+    Connected(C),
+}
+
+enum ToPoller<C> {
+    Park(Parked<C>),
+    /// The streams have run out, so only connections yet to send their first
+    /// request are still waited on.
+    Stop,
+}
+
+impl Server {
+    /// Serves every stream through the event loop, returning once the
+    /// streams run out and the connections accepted have sent their first
+    /// request or timed out waiting for it.
+    pub(in crate::server) fn run_event_loop<C, F>(
+        &self,
+        streams: impl Iterator<Item = TcpStream>,
+        connect: F,
+    ) -> io::Result<()>
+    where
+        C: Connection + Send,
+        F: Fn(TcpStream) -> io::Result<C> + Sync,
+    {
+        let poll = Poll::new()?;
+        let waker = Waker::new(poll.registry(), WAKER)?;
+        let (to_poller, parked) = mpsc::channel();
+        let (to_workers, ready) = mpsc::channel();
+        let ready = Mutex::new(ready);
+        let (ready, waker, connect) = (&ready, &waker, &connect);
+        thread::scope(|scope| {
+            scope.spawn(move || self.poll_idle(poll, parked, to_workers));
+            for _ in 0..self.workers.max(1) {
+                let to_poller = to_poller.clone();
+                scope.spawn(move || loop {
+                    // The lock is let go of before serving, so the other
+                    // workers can take the next connection in the meantime.
+                    let next = match ready.lock() {
+                        Ok(ready) => ready.recv(),
+                        Err(_) => break,
+                    };
+                    let parked = match next {
+                        Ok(parked) => parked,
+                        // The poller is gone and the queue is drained.
+                        Err(_) => break,
+                    };
+                    if let Some(parked) = self.serve_parked(parked, connect) {
+                        if to_poller.send(ToPoller::Park(parked)).is_ok() {
+                            let _ = waker.wake();
+                        }
+                    }
+                });
+            }
+            for stream in streams {
+                // A single bad connection has no bearing on the ones after it.
+                if let Ok(parked) = park(stream) {
+                    let _ = to_poller.send(ToPoller::Park(parked));
+                    let _ = waker.wake();
+                }
+            }
+            let _ = to_poller.send(ToPoller::Stop);
+            let _ = waker.wake();
+        });
+        Ok(())
+    }
+
+    /// Waits on the parked connections, handing each over to the workers
+    /// once it is readable. Those left idle for longer than the [`Timeouts`]
+    /// allow for a first request, or the [`KeepAlive`] for any after it, are
+    /// closed.
+    ///
+    /// [`Timeouts`]: ./struct.Timeouts.html
+    /// [`KeepAlive`]: ./struct.KeepAlive.html
+    fn poll_idle<C>(
+        &self,
+        mut poll: Poll,
+        parked: Receiver<ToPoller<C>>,
+        to_workers: Sender<Parked<C>>,
+    ) {
+        let mut idle = HashMap::<Token, (Parked<C>, Option<Instant>)>::new();
+        let mut next_token = 0;
+        let mut is_stopping = false;
+        let mut events = Events::with_capacity(1024);
+        loop {
+            let now = Instant::now();
+            idle.retain(|_, (parked, deadline)| {
+                let is_expired = deadline.is_some_and(|deadline| deadline <= now);
+                // Only a first request is still waited on once stopping.
+                let is_kept = !(is_expired || is_stopping && parked.served > 0);
+                if !is_kept {
+                    let _ = poll.registry().deregister(&mut parked.source);
+                }
+                is_kept
+            });
+            if is_stopping && idle.is_empty() {
+                return;
+            }
+            let timeout = idle
+                .values()
+                .filter_map(|(_, deadline)| *deadline)
+                .min()
+                .map(|deadline| deadline.saturating_duration_since(now));
+            match poll.poll(&mut events, timeout) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return,
+            }
+            for event in events.iter() {
+                if let Some((mut parked, _)) = idle.remove(&event.token()) {
+                    let _ = poll.registry().deregister(&mut parked.source);
+                    if to_workers.send(parked).is_err() {
+                        return;
+                    }
+                }
+            }
+            for message in parked.try_iter() {
+                let mut parked = match message {
+                    ToPoller::Park(parked) => parked,
+                    ToPoller::Stop => {
+                        is_stopping = true;
+                        continue;
+                    }
+                };
+                let token = Token(next_token);
+                next_token = (next_token + 1) % WAKER.0;
+                let timeout = match parked.served {
+                    0 => self.timeouts.read,
+                    _ => Some(self.keep_alive.timeout),
+                };
+                let registry = poll.registry();
+                if registry
+                    .register(&mut parked.source, token, Interest::READABLE)
+                    .is_ok()
+                {
+                    idle.insert(token, (parked, timeout.map(|timeout| now + timeout)));
+                }
+            }
+        }
+    }
+
+    /// Serves the requests coming in on the connection for as long as they
+    /// follow one another, the same as [`serve`] would.
+    ///
+    /// # Returns:
+    /// The connection, to be parked once more when it is kept alive.
+    ///
+    /// [`serve`]: ./struct.Server.html#method.serve
+    fn serve_parked<C, F>(&self, mut parked: Parked<C>, connect: &F) -> Option<Parked<C>>
+    where
+        C: Connection,
+        F: Fn(TcpStream) -> io::Result<C>,
+    {
+        let mut connection = match parked.state {
+            State::Accepted(stream) => connect(stream).ok()?,
+            State::Connected(connection) => connection,
+        };
+        if let Some(connection_info) = &mut parked.connection_info {
+            connection_info.is_tls = connection.is_tls();
+        }
+        let max_requests = self.keep_alive.max_requests.max(1);
+        let socket = Some(&parked.socket);
+        let mut reader = BufReader::new(&mut connection);
+        let is_kept = loop {
+            let _ = parked.socket.set_read_timeout(self.timeouts.read);
+            let started = Instant::now();
+            // Anything but the start of another request ends the connection
+            // quietly, be it closed by the client or left idle.
+            if reader.fill_buf().map_or(true, |buffer| buffer.is_empty()) {
+                break false;
+            }
+            parked.served += 1;
+            let served = parked.served;
+            let connection_info = &mut parked.connection_info;
+            match self.serve_next(
+                &mut reader,
+                connection_info,
+                socket,
+                served,
+                started,
+                max_requests,
+            ) {
+                Ok(true) if reader.buffer().is_empty() && reader.get_mut().is_drained() => {
+                    break true
+                }
+                Ok(true) => {}
+                Ok(false) | Err(_) => break false,
+            }
+        };
+        if !is_kept {
+            let _ = connection.close();
+            return None;
+        }
+        parked.state = State::Connected(connection);
+        Some(parked)
+    }
+}
+
+/// A connection freshly accepted, yet to be connected.
+fn park<C>(stream: TcpStream) -> io::Result<Parked<C>> {
+    let connection_info = stream
+        .peer_addr()
+        .and_then(|peer_addr| Ok((peer_addr, stream.local_addr()?)))
+        .ok()
+        .map(|(peer_addr, local_addr)| ConnectionInfo {
+            peer_addr,
+            local_addr,
+            is_tls: false,
+            peer_identity: None,
+            server_name: None,
+        });
+    Ok(Parked {
+        socket: stream.try_clone()?,
+        source: mio::net::TcpStream::from_std(stream.try_clone()?),
+        state: State::Accepted(stream),
+        connection_info,
+        served: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,89 @@
+use crate::server::{IoModel, KeepAlive, Route, Server, Shutdown};
+use crate::web::HttpMethod;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Serves through the event loop on a single worker until the returned
+/// `Shutdown` is triggered.
+fn listen(keep_alive: KeepAlive) -> (SocketAddr, Shutdown) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let trigger = shutdown.clone();
+    thread::spawn(move || {
+        let mut server = Server::with_workers(1);
+        server.io_model(IoModel::EventLoop);
+        server.keep_alive(keep_alive);
+        server
+            .route(|| Route::bind(HttpMethod::Get).to("/", |_| "hello"))
+            .unwrap();
+        server
+            .accept(shutdown.incoming(&listener).unwrap(), Ok)
+            .unwrap();
+    });
+    (address, trigger)
+}
+
+/// Reads a single response with a `Content-Length`, leaving the connection
+/// open.
+fn read_response(stream: &mut TcpStream) -> String {
+    let mut raw_response = Vec::new();
+    let mut byte = [0];
+    while !raw_response.ends_with(b"hello") {
+        assert_eq!(stream.read(&mut byte).unwrap(), 1);
+        raw_response.push(byte[0]);
+    }
+    String::from_utf8(raw_response).unwrap()
+}
+
+#[test]
+fn should_serve_other_connections_when_single_worker_has_idle_ones_kept_alive() {
+    let (address, shutdown) = listen(KeepAlive::default());
+    let started = Instant::now();
+    let mut idle = (0..8)
+        .map(|_| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            assert!(read_response(&mut stream).starts_with("HTTP/1.1 200 OK\r\n"));
+            stream
+        })
+        .collect::<Vec<_>>();
+    // Each idle connection is still served once it sends another request.
+    for stream in &mut idle {
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert!(read_response(stream).starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+    // Well within the keep alive timeout any of them would hold a worker for.
+    assert!(started.elapsed() < Duration::from_secs(2));
+    shutdown.trigger();
+}
+
+#[test]
+fn should_serve_pipelined_requests_when_sent_together() {
+    let (address, shutdown) = listen(KeepAlive::default());
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut raw_response = String::new();
+    stream.read_to_string(&mut raw_response).unwrap();
+    assert_eq!(raw_response.matches("HTTP/1.1 200 OK").count(), 2);
+    shutdown.trigger();
+}
+
+#[test]
+fn should_close_connection_when_idle_for_longer_than_keep_alive_timeout() {
+    let (address, shutdown) = listen(KeepAlive {
+        timeout: Duration::from_millis(100),
+        max_requests: 100,
+    });
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    read_response(&mut stream);
+    let started = Instant::now();
+    assert_eq!(stream.read(&mut [0]).unwrap(), 0);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    shutdown.trigger();
+}
@@ -0,0 +1,169 @@
+//! Pulling typed values off of a request before a handler sees it, so that
+//! the handler takes what it needs as arguments rather than the whole
+//! [`HttpRequest`].
+//!
+//! [`HttpRequest`]: ../../web/struct.HttpRequest.html
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(any(feature = "serde", feature = "json"))]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+#[cfg(feature = "json")]
+use crate::web::StatusCode;
+use crate::web::{HttpRequest, HttpResponse, IntoResponse, ParamError, QueryParams};
+
+/// A value taken off of a request, as an argument of a handler bound through
+/// [`Binding::to_typed`]. A [`ParamError`] is answered with a 400 describing
+/// it, without the handler being called.
+///
+/// # Examples:
+/// ```
+/// use martian::server::FromRequest;
+/// use martian::web::{HttpRequest, ParamError};
+/// struct UserAgent(String);
+/// impl FromRequest for UserAgent {
+///     fn from_request(request: &HttpRequest) -> Result<UserAgent, ParamError> {
+///         request
+///             .headers
+///             .iter()
+///             .flatten()
+///             .find(|(key, _)| key.eq_ignore_ascii_case("User-Agent"))
+///             .map(|(_, value)| UserAgent(value.clone()))
+///             .ok_or_else(|| ParamError::Missing("User-Agent".into()))
+///     }
+/// }
+/// ```
+///
+/// [`Binding::to_typed`]: ../struct.Binding.html#method.to_typed
+/// [`ParamError`]: ../../web/enum.ParamError.html
+pub trait FromRequest: Sized {
+    fn from_request(request: &HttpRequest) -> Result<Self, ParamError>;
+}
+
+impl FromRequest for HttpRequest {
+    fn from_request(request: &HttpRequest) -> Result<HttpRequest, ParamError> {
+        Ok(request.clone())
+    }
+}
+
+impl FromRequest for QueryParams {
+    fn from_request(request: &HttpRequest) -> Result<QueryParams, ParamError> {
+        Ok(request.params().clone())
+    }
+}
+
+/// Never fails, being `None` when the value could not be taken.
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(request: &HttpRequest) -> Result<Option<T>, ParamError> {
+        Ok(T::from_request(request).ok())
+    }
+}
+
+/// The one path param captured by the route, parsed into `T`. For a route
+/// capturing more than one, use [`HttpRequest::param`] instead. On a route
+/// capturing none or more than one, it fails with [`ParamError::Unfit`],
+/// answered with a 500.
+///
+/// [`HttpRequest::param`]: ../../web/struct.HttpRequest.html#method.param
+/// [`ParamError::Unfit`]: ../../web/enum.ParamError.html#variant.Unfit
+#[derive(PartialEq, Debug, Clone)]
+pub struct PathParam<T>(pub T);
+
+impl<T> FromRequest for PathParam<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn from_request(request: &HttpRequest) -> Result<PathParam<T>, ParamError> {
+        let mut names = request.path_params.keys();
+        match (names.next(), names.next()) {
+            (Some(name), None) => request.param(name).map(PathParam),
+            _ => Err(ParamError::Unfit(format!(
+                "PathParam needs exactly one path param to be captured, but {} were for {}",
+                request.path_params.len(),
+                request.uri.path()
+            ))),
+        }
+    }
+}
+
+/// All of the query params deserialized into `T`, see
+/// [`HttpRequest::params_as`].
+///
+/// [`HttpRequest::params_as`]: ../../web/struct.HttpRequest.html#method.params_as
+#[cfg(feature = "serde")]
+#[derive(PartialEq, Debug, Clone)]
+pub struct Query<T>(pub T);
+
+#[cfg(feature = "serde")]
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    fn from_request(request: &HttpRequest) -> Result<Query<T>, ParamError> {
+        request.params_as().map(Query)
+    }
+}
+
+/// The body deserialized from JSON into `T`, see [`HttpRequest::json`]. As
+/// what a handler returns, it is a 200 with `T` serialized as its body.
+///
+/// [`HttpRequest::json`]: ../../web/struct.HttpRequest.html#method.json
+#[cfg(feature = "json")]
+#[derive(PartialEq, Debug, Clone)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(request: &HttpRequest) -> Result<Json<T>, ParamError> {
+        request
+            .json()
+            .map(Json)
+            .map_err(|e| ParamError::InvalidBody(e.to_string()))
+    }
+}
+
+/// A value failing to serialize is answered with a 500.
+#[cfg(feature = "json")]
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::json(&self.0)
+            .unwrap_or_else(|_| HttpResponse::new(StatusCode::InternalServerError))
+    }
+}
+
+/// A handler whose every argument is [`FromRequest`], up to six of them. Any
+/// `Fn` of such arguments returning something [`IntoResponse`] is a
+/// `TypedHandler`, `Args` being the `fn` type of its signature.
+///
+/// [`FromRequest`]: ./trait.FromRequest.html
+/// [`IntoResponse`]: ../../web/trait.IntoResponse.html
+pub trait TypedHandler<Args>: Send + Sync + 'static {
+    fn call(&self, request: HttpRequest) -> Result<HttpResponse, ParamError>;
+}
+
+macro_rules! typed_handler {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg,)*> TypedHandler<fn($($arg),*) -> R> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($arg: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, request: HttpRequest) -> Result<HttpResponse, ParamError> {
+                $(let $arg = $arg::from_request(&request)?;)*
+                Ok(self($($arg),*).into_response())
+            }
+        }
+    };
+}
+
+typed_handler!();
+typed_handler!(A);
+typed_handler!(A, B);
+typed_handler!(A, B, C);
+typed_handler!(A, B, C, D);
+typed_handler!(A, B, C, D, E);
+typed_handler!(A, B, C, D, E, G);
@@ -0,0 +1,98 @@
+//! Predicates a request has to satisfy, on top of its method and path, for a
+//! route to be picked for it.
+
+use std::fmt;
+
+use crate::web::{HttpRequest, Mime};
+
+/// Decides whether a request may be routed to the route it guards, see
+/// [`Binding::guard`]. A request any guard of a route turns away is matched
+/// against the next best route instead, as though the route were not bound.
+///
+/// Any `Fn(&HttpRequest) -> bool` closure is a `Guard`.
+///
+/// # Examples:
+/// ```
+/// use martian::server::Guard;
+/// use martian::web::HttpRequest;
+/// struct Mobile;
+/// impl Guard for Mobile {
+///     fn check(&self, request: &HttpRequest) -> bool {
+///         request
+///             .header("User-Agent")
+///             .is_some_and(|user_agent| user_agent.contains("Mobile"))
+///     }
+/// }
+/// ```
+///
+/// [`Binding::guard`]: ./struct.Binding.html#method.guard
+pub trait Guard: Send + Sync {
+    fn check(&self, request: &HttpRequest) -> bool;
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&HttpRequest) -> bool + Send + Sync,
+{
+    fn check(&self, request: &HttpRequest) -> bool {
+        self(request)
+    }
+}
+
+impl fmt::Debug for dyn Guard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Guard(..)")
+    }
+}
+
+/// [`Guard`] letting through a request with the header, whatever its value.
+///
+/// [`Guard`]: ./trait.Guard.html
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct HeaderPresent<N>(pub N);
+
+impl<N: AsRef<str> + Send + Sync> Guard for HeaderPresent<N> {
+    fn check(&self, request: &HttpRequest) -> bool {
+        request.header(self.0.as_ref()).is_some()
+    }
+}
+
+/// [`Guard`] letting through a request with the header set to exactly the
+/// value, the name of the header being matched ignoring case.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{HeaderEquals, Route};
+/// use martian::web::HttpMethod;
+/// Route::bind(HttpMethod::Post)
+///     .to("/hook", |_| "received")
+///     .guard(HeaderEquals("X-Token", "s3cret"));
+/// ```
+///
+/// [`Guard`]: ./trait.Guard.html
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct HeaderEquals<N, V>(pub N, pub V);
+
+impl<N, V> Guard for HeaderEquals<N, V>
+where
+    N: AsRef<str> + Send + Sync,
+    V: AsRef<str> + Send + Sync,
+{
+    fn check(&self, request: &HttpRequest) -> bool {
+        request.header(self.0.as_ref()) == Some(self.1.as_ref())
+    }
+}
+
+/// [`Guard`] letting through a request whose body is of the type, going by
+/// its `Content-Type`, see [`HttpRequest::content_type`].
+///
+/// [`Guard`]: ./trait.Guard.html
+/// [`HttpRequest::content_type`]: ../web/struct.HttpRequest.html#method.content_type
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ContentTypeIs(pub Mime);
+
+impl Guard for ContentTypeIs {
+    fn check(&self, request: &HttpRequest) -> bool {
+        request.content_type() == Some(self.0)
+    }
+}
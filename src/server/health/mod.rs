@@ -0,0 +1,154 @@
+//! Answering the probes of an orchestrator such as Kubernetes, telling it
+//! whether the [`Server`] is alive and whether it is ready for requests.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::web::{HttpMethod, HttpResponse, StatusCode};
+
+use super::{Route, RouteConflict, Server};
+
+type HealthCheck = Box<dyn Fn() -> Result<(), String> + Send + Sync>;
+
+/// Where a [`Server`] is at in its life, as [`Server::lifecycle`] has it. It
+/// is only ready for requests while `Serving`.
+///
+/// [`Server`]: ./struct.Server.html
+/// [`Server::lifecycle`]: ./struct.Server.html#method.lifecycle
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Lifecycle {
+    /// Not yet accepting connections.
+    Starting,
+    /// Accepting connections.
+    Serving,
+    /// No longer accepting connections, such as once shut down, but possibly
+    /// still serving those accepted before.
+    Draining,
+}
+
+impl Lifecycle {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Lifecycle::Starting => "starting",
+            Lifecycle::Serving => "serving",
+            Lifecycle::Draining => "draining",
+        }
+    }
+}
+
+/// The lifecycle and health checks of a `Server`, shared with its health
+/// routes so that checks registered after them are still run.
+#[derive(Default)]
+pub(in crate::server) struct Health {
+    /// A `Lifecycle`, as its index.
+    lifecycle: AtomicU8,
+    checks: RwLock<Vec<(String, HealthCheck)>>,
+}
+
+impl Health {
+    pub(in crate::server) fn lifecycle(&self) -> Lifecycle {
+        match self.lifecycle.load(Ordering::SeqCst) {
+            0 => Lifecycle::Starting,
+            1 => Lifecycle::Serving,
+            _ => Lifecycle::Draining,
+        }
+    }
+
+    pub(in crate::server) fn set_lifecycle(&self, lifecycle: Lifecycle) {
+        self.lifecycle.store(lifecycle as u8, Ordering::SeqCst);
+    }
+
+    /// A 200 while serving with every check passing, or a 503, listing the
+    /// lifecycle and the outcome of each check either way.
+    fn readiness(&self) -> HttpResponse {
+        let lifecycle = self.lifecycle();
+        let mut is_ready = lifecycle == Lifecycle::Serving;
+        let mut body = lifecycle.as_str().to_string();
+        let checks = self.checks.read().unwrap_or_else(|e| e.into_inner());
+        for (name, check) in checks.iter() {
+            let outcome = match check() {
+                Ok(()) => "ok".into(),
+                Err(reason) => {
+                    is_ready = false;
+                    reason
+                }
+            };
+            body.push_str(&format!("\n{}: {}", name, outcome));
+        }
+        let mut response = HttpResponse::text(&body);
+        if !is_ready {
+            response.status_code = StatusCode::ServiceUnavailable;
+        }
+        response
+    }
+}
+
+impl Server {
+    /// Binds `GET` routes answering the liveness and readiness probes of an
+    /// orchestrator. The liveness route always answers with a 200, as the
+    /// `Server` answering at all is all it tells. The readiness route answers
+    /// with a 200 only while the `Server` is [`Lifecycle::Serving`] and every
+    /// check registered through [`health_check`] passes, and with a 503
+    /// otherwise, such as while draining connections after a shutdown. Its
+    /// body lists the lifecycle and the outcome of every check.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Server;
+    /// let mut server = Server::default();
+    /// server.enable_health("/healthz", "/readyz").unwrap();
+    /// server.health_check("disk", || match std::env::temp_dir().exists() {
+    ///     true => Ok(()),
+    ///     false => Err("temp dir is gone".into()),
+    /// });
+    /// ```
+    ///
+    /// # Returns:
+    /// A [`RouteConflict`] if either uri is already bound.
+    ///
+    /// [`Lifecycle::Serving`]: ./enum.Lifecycle.html#variant.Serving
+    /// [`health_check`]: ./struct.Server.html#method.health_check
+    /// [`RouteConflict`]: ./struct.RouteConflict.html
+    pub fn enable_health(
+        &mut self,
+        health_uri: &str,
+        ready_uri: &str,
+    ) -> Result<(), RouteConflict> {
+        let health = Arc::clone(&self.health);
+        self.route(|| {
+            Route::bind(HttpMethod::Get)
+                .to(health_uri, |_| "ok")
+                .to(ready_uri, move |_| health.readiness())
+        })
+    }
+
+    /// Registers a check run on every request to the readiness route of
+    /// [`enable_health`], the `Server` only being ready while it returns
+    /// `Ok`. The reason of an `Err` is listed under the name of the check.
+    ///
+    /// [`enable_health`]: ./struct.Server.html#method.enable_health
+    pub fn health_check<F>(&mut self, name: &str, check: F)
+    where
+        F: Fn() -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.health
+            .checks
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((name.into(), Box::new(check)));
+    }
+
+    /// Where the `Server` is at in its life. A `Server` is `Starting` until
+    /// it is first listened with, and `Draining` once it stops accepting
+    /// connections, such as once its [`Shutdown`] is triggered.
+    ///
+    /// [`Shutdown`]: ./struct.Shutdown.html
+    pub fn lifecycle(&self) -> Lifecycle {
+        self.health.lifecycle()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,59 @@
+use crate::server::{Lifecycle, Server, Shutdown};
+use crate::test::TestClient;
+use crate::web::StatusCode;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn should_be_alive_but_not_ready_when_starting() {
+    let mut server = Server::default();
+    server.enable_health("/healthz", "/readyz").unwrap();
+    assert_eq!(server.lifecycle(), Lifecycle::Starting);
+    let client = TestClient::new(server);
+    client.get("/healthz").assert_status(StatusCode::Ok);
+    client
+        .get("/readyz")
+        .assert_status(StatusCode::ServiceUnavailable)
+        .assert_body("starting");
+}
+
+#[test]
+fn should_be_ready_when_serving_until_a_check_fails() {
+    let is_healthy = Arc::new(AtomicBool::new(true));
+    let check = Arc::clone(&is_healthy);
+    let mut server = Server::with_workers(1);
+    server.enable_health("/healthz", "/readyz").unwrap();
+    server.health_check("database", move || match check.load(Ordering::SeqCst) {
+        true => Ok(()),
+        false => Err("connection refused".into()),
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let trigger = shutdown.clone();
+    let get_ready = || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /readyz HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut raw_response = String::new();
+        stream.read_to_string(&mut raw_response).unwrap();
+        raw_response
+    };
+    thread::scope(|scope| {
+        let serving = scope.spawn(|| server.accept(shutdown.incoming(&listener)?, Ok));
+        let raw_response = get_ready();
+        assert!(raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(raw_response.ends_with("\r\n\r\nserving\ndatabase: ok"));
+        is_healthy.store(false, Ordering::SeqCst);
+        let raw_response = get_ready();
+        assert!(raw_response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(raw_response.ends_with("\r\n\r\nserving\ndatabase: connection refused"));
+        trigger.trigger();
+        serving.join().unwrap().unwrap();
+    });
+    assert_eq!(server.lifecycle(), Lifecycle::Draining);
+}
@@ -0,0 +1,131 @@
+//! The frames everything on an HTTP/2 connection is sent in, see RFC 7540
+//! section 4. Each is a 9 byte header, giving its length, type, flags and
+//! stream, followed by its payload.
+
+use std::io::{self, Read, Write};
+
+pub(super) const DATA: u8 = 0x0;
+pub(super) const HEADERS: u8 = 0x1;
+pub(super) const PRIORITY: u8 = 0x2;
+pub(super) const RST_STREAM: u8 = 0x3;
+pub(super) const SETTINGS: u8 = 0x4;
+pub(super) const PUSH_PROMISE: u8 = 0x5;
+pub(super) const PING: u8 = 0x6;
+pub(super) const GOAWAY: u8 = 0x7;
+pub(super) const WINDOW_UPDATE: u8 = 0x8;
+pub(super) const CONTINUATION: u8 = 0x9;
+
+pub(super) const END_STREAM: u8 = 0x1;
+/// The same bit as `END_STREAM`, on `SETTINGS` and `PING` frames.
+pub(super) const ACK: u8 = 0x1;
+pub(super) const END_HEADERS: u8 = 0x4;
+pub(super) const PADDED: u8 = 0x8;
+pub(super) const PRIORITY_FLAG: u8 = 0x20;
+
+/// The largest frame either side may send until told otherwise.
+pub(super) const DEFAULT_MAX_FRAME_SIZE: usize = 16_384;
+
+#[derive(PartialEq, Eq, Debug)]
+pub(super) struct Frame {
+    pub(super) kind: u8,
+    pub(super) flags: u8,
+    pub(super) stream_id: u32,
+    pub(super) payload: Vec<u8>,
+}
+
+impl Frame {
+    pub(super) fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// The payload without any padding, or priority of a `HEADERS` frame.
+    ///
+    /// # Returns:
+    /// `None` if the padding is longer than the payload.
+    pub(super) fn content(&self) -> Option<&[u8]> {
+        let mut content = &self.payload[..];
+        if self.has_flag(PADDED) {
+            let (&padding, rest) = content.split_first()?;
+            content = rest.get(..rest.len().checked_sub(padding as usize)?)?;
+        }
+        if self.kind == HEADERS && self.has_flag(PRIORITY_FLAG) {
+            content = content.get(5..)?;
+        }
+        Some(content)
+    }
+}
+
+/// Why a frame could not be read.
+#[derive(Debug)]
+pub(super) enum ReadError {
+    Io(io::Error),
+    /// A frame larger than the most the server said it takes.
+    TooLarge,
+}
+
+pub(super) fn read_frame<R: Read>(reader: &mut R, max_size: usize) -> Result<Frame, ReadError> {
+    let mut header = [0; 9];
+    reader.read_exact(&mut header).map_err(ReadError::Io)?;
+    let length = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    if length > max_size {
+        return Err(ReadError::TooLarge);
+    }
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload).map_err(ReadError::Io)?;
+    Ok(Frame {
+        kind: header[3],
+        flags: header[4],
+        // The reserved bit is ignored.
+        stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+        payload,
+    })
+}
+
+/// Writes the frame in a single write, so that it is not split up on the
+/// wire any more than it has to be.
+pub(super) fn write_frame<W: Write>(
+    writer: &mut W,
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(9 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+    frame.push(kind);
+    frame.push(flags);
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)
+}
+
+/// The settings in the payload of a `SETTINGS` frame, each an identifier and
+/// its value.
+///
+/// # Returns:
+/// `None` if the payload is not made up of whole settings.
+pub(super) fn settings(payload: &[u8]) -> Option<Vec<(u16, u32)>> {
+    if !payload.len().is_multiple_of(6) {
+        return None;
+    }
+    let settings = payload
+        .chunks(6)
+        .map(|setting| {
+            let identifier = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+            (identifier, value)
+        })
+        .collect();
+    Some(settings)
+}
+
+pub(super) fn settings_payload(settings: &[(u16, u32)]) -> Vec<u8> {
+    settings
+        .iter()
+        .flat_map(|(identifier, value)| {
+            let mut setting = identifier.to_be_bytes().to_vec();
+            setting.extend_from_slice(&value.to_be_bytes());
+            setting
+        })
+        .collect()
+}
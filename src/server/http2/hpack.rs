@@ -0,0 +1,281 @@
+//! HPACK, the compression of the header blocks of HTTP/2, see RFC 7541.
+//! Header blocks are decoded in full, the dynamic table of the client
+//! included. Those written back never add to the dynamic table, so that
+//! there is nothing to keep in step with the client.
+
+use std::collections::VecDeque;
+
+use super::huffman;
+
+/// A header block which can not be decompressed, after which the dynamic
+/// table is out of step with the client and the connection is unusable.
+#[derive(PartialEq, Eq, Debug)]
+pub(super) struct CompressionError;
+
+/// The headers every HPACK encoder and decoder knows of, indexed from `1`.
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// How much of the dynamic table an entry takes up, more than its name and
+/// value for the overhead of storing it.
+const ENTRY_OVERHEAD: usize = 32;
+
+/// Decodes the header blocks of a single connection, in the order they were
+/// sent in, keeping its dynamic table in step with that of the client.
+#[derive(Debug)]
+pub(super) struct Decoder {
+    /// Newest entry first, being the first to be indexed.
+    table: VecDeque<(String, String)>,
+    table_size: usize,
+    max_table_size: usize,
+    /// The most the client may resize the table to, as given in the
+    /// settings of the server.
+    table_size_limit: usize,
+}
+
+impl Decoder {
+    pub(super) fn new(table_size_limit: usize) -> Decoder {
+        Decoder {
+            table: VecDeque::new(),
+            table_size: 0,
+            max_table_size: table_size_limit,
+            table_size_limit,
+        }
+    }
+
+    /// Decodes a header block into its headers, in the order they appear in.
+    pub(super) fn decode(
+        &mut self,
+        block: &[u8],
+    ) -> Result<Vec<(String, String)>, CompressionError> {
+        let mut headers = Vec::new();
+        let mut rest = block;
+        while let Some(&first) = rest.first() {
+            if first & 0x80 != 0 {
+                let (index, after) = decode_integer(rest, 7)?;
+                headers.push(self.entry(index)?);
+                rest = after;
+            } else if first & 0xc0 == 0x40 {
+                let (header, after) = self.decode_literal(rest, 6)?;
+                self.insert(header.clone());
+                headers.push(header);
+                rest = after;
+            } else if first & 0xe0 == 0x20 {
+                let (max_table_size, after) = decode_integer(rest, 5)?;
+                if max_table_size > self.table_size_limit {
+                    return Err(CompressionError);
+                }
+                self.max_table_size = max_table_size;
+                self.evict();
+                rest = after;
+            } else {
+                // Without indexing, or never indexed, which to a server that
+                // does not forward headers is the same.
+                let (header, after) = self.decode_literal(rest, 4)?;
+                headers.push(header);
+                rest = after;
+            }
+        }
+        Ok(headers)
+    }
+
+    /// A literal header, its name either indexed or a literal too.
+    fn decode_literal<'a>(
+        &self,
+        block: &'a [u8],
+        prefix_bits: u8,
+    ) -> Result<((String, String), &'a [u8]), CompressionError> {
+        let (index, rest) = decode_integer(block, prefix_bits)?;
+        let (name, rest) = match index {
+            0 => decode_string(rest)?,
+            _ => (self.entry(index)?.0, rest),
+        };
+        let (value, rest) = decode_string(rest)?;
+        Ok(((name, value), rest))
+    }
+
+    /// The header at the index, the static table coming before the dynamic
+    /// one.
+    fn entry(&self, index: usize) -> Result<(String, String), CompressionError> {
+        match index {
+            0 => Err(CompressionError),
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Ok((name.into(), value.into()))
+            }
+            _ => self.table.get(index - 62).cloned().ok_or(CompressionError),
+        }
+    }
+
+    fn insert(&mut self, header: (String, String)) {
+        self.table_size += entry_size(&header);
+        self.table.push_front(header);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.table_size > self.max_table_size {
+            match self.table.pop_back() {
+                Some(header) => self.table_size -= entry_size(&header),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Encodes a header block, each header being a literal which is not indexed
+/// unless it is in the static table.
+pub(super) fn encode<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in headers {
+        let exact = STATIC_TABLE
+            .iter()
+            .position(|entry| *entry == (name, value));
+        let named = STATIC_TABLE
+            .iter()
+            .position(|(static_name, _)| *static_name == name);
+        match (exact, named) {
+            (Some(i), _) => encode_integer(&mut block, 0x80, 7, i + 1),
+            (None, Some(i)) => {
+                encode_integer(&mut block, 0, 4, i + 1);
+                encode_string(&mut block, value);
+            }
+            (None, None) => {
+                block.push(0);
+                encode_string(&mut block, name);
+                encode_string(&mut block, value);
+            }
+        }
+    }
+    block
+}
+
+fn entry_size((name, value): &(String, String)) -> usize {
+    name.len() + value.len() + ENTRY_OVERHEAD
+}
+
+/// Decodes an integer starting in the low `prefix_bits` of the first byte,
+/// returning it along with what follows it.
+pub(super) fn decode_integer(
+    block: &[u8],
+    prefix_bits: u8,
+) -> Result<(usize, &[u8]), CompressionError> {
+    let (first, mut rest) = block.split_first().ok_or(CompressionError)?;
+    let max_prefix = (1usize << prefix_bits) - 1;
+    let mut value = usize::from(*first) & max_prefix;
+    if value < max_prefix {
+        return Ok((value, rest));
+    }
+    let mut shift = 0;
+    loop {
+        let (byte, after) = rest.split_first().ok_or(CompressionError)?;
+        rest = after;
+        // Anything needing more than 28 bits is more than any sane block.
+        if shift > 21 {
+            return Err(CompressionError);
+        }
+        value += usize::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok((value, rest));
+        }
+    }
+}
+
+fn decode_string(block: &[u8]) -> Result<(String, &[u8]), CompressionError> {
+    let is_huffman = block.first().ok_or(CompressionError)? & 0x80 != 0;
+    let (length, rest) = decode_integer(block, 7)?;
+    if rest.len() < length {
+        return Err(CompressionError);
+    }
+    let (encoded, rest) = rest.split_at(length);
+    let decoded = match is_huffman {
+        true => huffman::decode(encoded)?,
+        false => encoded.to_vec(),
+    };
+    Ok((String::from_utf8_lossy(&decoded).into_owned(), rest))
+}
+
+/// Encodes the integer into the low `prefix_bits` of a byte starting out as
+/// `flags`, and as many bytes after it as it needs.
+fn encode_integer(block: &mut Vec<u8>, flags: u8, prefix_bits: u8, value: usize) {
+    let max_prefix = (1usize << prefix_bits) - 1;
+    if value < max_prefix {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | max_prefix as u8);
+    let mut rest = value - max_prefix;
+    while rest >= 0x80 {
+        block.push(rest as u8 & 0x7f | 0x80);
+        rest >>= 7;
+    }
+    block.push(rest as u8);
+}
+
+/// Encodes the string as is, without Huffman coding it.
+fn encode_string(block: &mut Vec<u8>, string: &str) {
+    encode_integer(block, 0, 7, string.len());
+    block.extend_from_slice(string.as_bytes());
+}
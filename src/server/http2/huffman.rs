@@ -0,0 +1,70 @@
+//! The Huffman code HPACK compresses strings with, see RFC 7541 Appendix B.
+//! The code is canonical, so it is given by how many codes there are of each
+//! length and the symbols in the order of their codes.
+
+use super::hpack::CompressionError;
+
+/// How many codes there are of each length in bits.
+const CODES_OF_LENGTH: [u16; 31] = [
+    0, 0, 0, 0, 0, 10, 26, 32, 6, 0, 5, 3, 2, 6, 2, 3, 0, 0, 0, 3, 8, 13, 26, 29, 12, 4, 15, 19,
+    29, 0, 4,
+];
+
+/// The symbols in the order of their codes, `256` being the end of string.
+const SYMBOLS: [u16; 257] = [
+    48, 49, 50, 97, 99, 101, 105, 111, 115, 116, 32, 37, 45, 46, 47, 51, 52, 53, 54, 55, 56, 57,
+    61, 65, 95, 98, 100, 102, 103, 104, 108, 109, 110, 112, 114, 117, 58, 66, 67, 68, 69, 70, 71,
+    72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 89, 106, 107, 113, 118, 119,
+    120, 121, 122, 38, 42, 44, 59, 88, 90, 33, 34, 40, 41, 63, 39, 43, 124, 35, 62, 0, 36, 64, 91,
+    93, 126, 94, 125, 60, 96, 123, 92, 195, 208, 128, 130, 131, 162, 184, 194, 224, 226, 153, 161,
+    167, 172, 176, 177, 179, 209, 216, 217, 227, 229, 230, 129, 132, 133, 134, 136, 146, 154, 156,
+    160, 163, 164, 169, 170, 173, 178, 181, 185, 186, 187, 189, 190, 196, 198, 228, 232, 233, 1,
+    135, 137, 138, 139, 140, 141, 143, 147, 149, 150, 151, 152, 155, 157, 158, 165, 166, 168, 174,
+    175, 180, 182, 183, 188, 191, 197, 231, 239, 9, 142, 144, 145, 148, 159, 171, 206, 215, 225,
+    236, 237, 199, 207, 234, 235, 192, 193, 200, 201, 202, 205, 210, 213, 218, 219, 238, 240, 242,
+    243, 255, 203, 204, 211, 212, 214, 221, 222, 223, 241, 244, 245, 246, 247, 248, 250, 251, 252,
+    253, 254, 2, 3, 4, 5, 6, 7, 8, 11, 12, 14, 15, 16, 17, 18, 19, 20, 21, 23, 24, 25, 26, 27, 28,
+    29, 30, 31, 127, 220, 249, 10, 13, 22, 256,
+];
+
+const END_OF_STRING: u16 = 256;
+
+/// Decodes a Huffman encoded string, which is padded out to a whole byte with
+/// the most significant bits of the end of string code, all of them `1`.
+///
+/// # Returns:
+/// An `Err` if the string has the end of string code in it, or padding which
+/// is longer than 7 bits or not all `1`.
+pub(super) fn decode(encoded: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 8 / 5);
+    // The code read so far, its length and where codes of that length start.
+    let (mut code, mut length, mut first, mut index) = (0u32, 0usize, 0u32, 0usize);
+    let mut all_ones = true;
+    for byte in encoded {
+        for shift in (0..8).rev() {
+            let bit = u32::from(byte >> shift & 1);
+            code = code << 1 | bit;
+            all_ones &= bit == 1;
+            length += 1;
+            let count = u32::from(*CODES_OF_LENGTH.get(length).ok_or(CompressionError)?);
+            if code < first + count {
+                match SYMBOLS[index + (code - first) as usize] {
+                    END_OF_STRING => return Err(CompressionError),
+                    symbol => decoded.push(symbol as u8),
+                }
+                code = 0;
+                length = 0;
+                first = 0;
+                index = 0;
+                all_ones = true;
+            } else {
+                index += count as usize;
+                first = (first + count) << 1;
+            }
+        }
+    }
+    match length <= 7 && all_ones {
+        true => Ok(decoded),
+        false => Err(CompressionError),
+    }
+}
@@ -0,0 +1,687 @@
+//! Serving HTTP/2, see RFC 7540, to clients negotiating it through ALPN over
+//! TLS, upgrading to it from HTTP/1.1 with `Upgrade: h2c`, or starting with
+//! it straight away on a cleartext connection. Only available with the
+//! `http2` feature.
+//!
+//! The requests of a connection are handled one after another on its own
+//! thread, the same as those of a kept alive HTTP/1.1 connection, even
+//! though the client may have any number of them open at once.
+
+mod frame;
+mod hpack;
+mod huffman;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufReader, Cursor, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use self::frame::{Frame, ReadError};
+use super::Server;
+use crate::web::encoding::base64url_decode;
+use crate::web::{
+    Body, ConnectionInfo, HttpMethod, HttpRequest, HttpResponse, HttpVersion, State, StatusCode,
+};
+
+/// What a client speaking HTTP/2 starts a connection with.
+pub(in crate::server) const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const MAX_CONCURRENT_STREAMS: usize = 100;
+const DEFAULT_WINDOW_SIZE: i64 = 65_535;
+const MAX_WINDOW_SIZE: i64 = (1 << 31) - 1;
+const HEADER_TABLE_SIZE: usize = 4_096;
+
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+const NO_ERROR: u32 = 0x0;
+const PROTOCOL_ERROR: u32 = 0x1;
+const INTERNAL_ERROR: u32 = 0x2;
+const FLOW_CONTROL_ERROR: u32 = 0x3;
+const FRAME_SIZE_ERROR: u32 = 0x6;
+const REFUSED_STREAM: u32 = 0x7;
+const COMPRESSION_ERROR: u32 = 0x9;
+const ENHANCE_YOUR_CALM: u32 = 0xb;
+
+/// Why a connection can not go on.
+enum Error {
+    Io(io::Error),
+    /// The client broke the protocol, which it is told of with a `GOAWAY` of
+    /// the error code.
+    Protocol(u32),
+}
+
+impl From<io::Error> for Error {
+    fn from(io_error: io::Error) -> Error {
+        Error::Io(io_error)
+    }
+}
+
+/// The settings an HTTP/1.1 request asking to be upgraded to HTTP/2 carries
+/// in its `HTTP2-Settings` header, `None` if it does not ask to be.
+pub(in crate::server) fn upgrade_settings(request: &HttpRequest) -> Option<Vec<u8>> {
+    let has_token = |header: &str, token: &str| {
+        request.header(header).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|listed| listed.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    let asks_to_upgrade = has_token("Upgrade", "h2c")
+        && has_token("Connection", "Upgrade")
+        && has_token("Connection", "HTTP2-Settings");
+    match asks_to_upgrade {
+        true => base64url_decode(request.header("HTTP2-Settings")?),
+        false => None,
+    }
+}
+
+/// Agrees to the upgrade of [`upgrade_settings`], after which the connection
+/// is served through [`serve`].
+///
+/// [`upgrade_settings`]: ./fn.upgrade_settings.html
+/// [`serve`]: ./fn.serve.html
+pub(in crate::server) fn switch_protocols<W: Write>(stream: &mut W) -> io::Result<()> {
+    let mut response = HttpResponse::new(StatusCode::SwitchingProtocols);
+    response
+        .headers
+        .insert("Connection".into(), "Upgrade".into());
+    response.headers.insert("Upgrade".into(), "h2c".into());
+    response.write_to(stream)
+}
+
+/// Serves the connection as HTTP/2 for as long as the client keeps it open,
+/// or until the `Server` has served as many requests on it as it keeps a
+/// connection alive for. A connection upgraded from HTTP/1.1 has the request
+/// it was upgraded by answered first, along with the settings it carried.
+pub(in crate::server) fn serve<S: Read + Write>(
+    server: &Server,
+    reader: &mut BufReader<S>,
+    connection_info: Option<ConnectionInfo>,
+    socket: Option<&TcpStream>,
+    upgraded: Option<(HttpRequest, Vec<u8>)>,
+) -> io::Result<()> {
+    let mut connection = Http2Connection {
+        server,
+        reader,
+        connection_info,
+        socket,
+        decoder: hpack::Decoder::new(HEADER_TABLE_SIZE),
+        receiving: HashMap::new(),
+        ready: VecDeque::new(),
+        continuation: None,
+        last_stream_id: 0,
+        send_window: DEFAULT_WINDOW_SIZE,
+        stream_send_windows: HashMap::new(),
+        initial_window_size: DEFAULT_WINDOW_SIZE,
+        max_frame_size: frame::DEFAULT_MAX_FRAME_SIZE,
+        served: 0,
+        going_away: false,
+        goaway_sent: false,
+    };
+    match connection.run(upgraded) {
+        Ok(()) => connection.go_away(NO_ERROR),
+        Err(Error::Protocol(error_code)) => connection.go_away(error_code),
+        Err(Error::Io(io_error)) => Err(io_error),
+    }
+}
+
+struct Http2Connection<'a, S: Read + Write> {
+    server: &'a Server,
+    reader: &'a mut BufReader<S>,
+    connection_info: Option<ConnectionInfo>,
+    socket: Option<&'a TcpStream>,
+    decoder: hpack::Decoder,
+    /// The streams whose request is still coming in.
+    receiving: HashMap<u32, Receiving>,
+    /// The requests which are all there, in the order they came in.
+    ready: VecDeque<(u32, HttpRequest)>,
+    /// A header block being continued in `CONTINUATION` frames, along with
+    /// its stream and whether it ends the stream.
+    continuation: Option<(u32, bool, Vec<u8>)>,
+    last_stream_id: u32,
+    /// How much more of the bodies of all responses the client takes.
+    send_window: i64,
+    /// How much more of the body of its response each stream takes, for
+    /// every stream which is not yet answered.
+    stream_send_windows: HashMap<u32, i64>,
+    initial_window_size: i64,
+    /// The largest frame the client takes.
+    max_frame_size: usize,
+    served: usize,
+    /// Whether either side is done with the connection, after which no new
+    /// streams are served.
+    going_away: bool,
+    goaway_sent: bool,
+}
+
+#[derive(Default)]
+struct Receiving {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl<'a, S: Read + Write> Http2Connection<'a, S> {
+    fn run(&mut self, upgraded: Option<(HttpRequest, Vec<u8>)>) -> Result<(), Error> {
+        let limits = &self.server.limits;
+        let settings = frame::settings_payload(&[
+            (
+                SETTINGS_MAX_CONCURRENT_STREAMS,
+                MAX_CONCURRENT_STREAMS as u32,
+            ),
+            (
+                SETTINGS_MAX_HEADER_LIST_SIZE,
+                limits.max_header_bytes.min(u32::MAX as usize) as u32,
+            ),
+        ]);
+        self.write_frame(frame::SETTINGS, 0, 0, &settings)?;
+        if let Some((mut request, settings)) = upgraded {
+            // Answered over HTTP/2, the same as the requests after it.
+            request.http_version = HttpVersion::Http2;
+            // Acknowledged by switching protocols, rather than a `SETTINGS`
+            // frame.
+            self.apply_settings(&settings)?;
+            self.last_stream_id = 1;
+            self.stream_send_windows.insert(1, self.initial_window_size);
+            self.ready.push_back((1, request));
+        }
+        self.reader.get_mut().flush()?;
+        self.set_read_timeout(self.server.timeouts.read);
+        let mut preface = [0; 24];
+        self.reader.read_exact(&mut preface)?;
+        if preface != PREFACE {
+            return Err(Error::Protocol(PROTOCOL_ERROR));
+        }
+        loop {
+            if let Some((stream_id, request)) = self.ready.pop_front() {
+                self.respond(stream_id, request)?;
+                continue;
+            }
+            let is_idle = self.receiving.is_empty() && self.continuation.is_none();
+            if self.going_away && is_idle {
+                return Ok(());
+            }
+            self.reader.get_mut().flush()?;
+            self.set_read_timeout(match is_idle {
+                true => Some(self.server.keep_alive.timeout),
+                false => self.server.timeouts.read,
+            });
+            match self.read_frame() {
+                Ok(frame) => self.on_frame(frame)?,
+                // Anything but the start of another request ends the
+                // connection quietly, be it closed by the client or left
+                // idle.
+                Err(Error::Io(_)) if is_idle => return Ok(()),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Frame, Error> {
+        frame::read_frame(self.reader, frame::DEFAULT_MAX_FRAME_SIZE).map_err(|e| match e {
+            ReadError::Io(io_error) => Error::Io(io_error),
+            ReadError::TooLarge => Error::Protocol(FRAME_SIZE_ERROR),
+        })
+    }
+
+    fn on_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        if let Some((stream_id, end_stream, mut block)) = self.continuation.take() {
+            if frame.kind != frame::CONTINUATION || frame.stream_id != stream_id {
+                return Err(Error::Protocol(PROTOCOL_ERROR));
+            }
+            block.extend_from_slice(&frame.payload);
+            return self.on_header_fragment(stream_id, end_stream, block, frame.flags);
+        }
+        match frame.kind {
+            frame::DATA => self.on_data(frame),
+            frame::HEADERS if frame.stream_id != 0 => {
+                let block = frame.content().ok_or(Error::Protocol(PROTOCOL_ERROR))?;
+                let end_stream = frame.has_flag(frame::END_STREAM);
+                self.on_header_fragment(frame.stream_id, end_stream, block.to_vec(), frame.flags)
+            }
+            frame::PRIORITY => Ok(()),
+            frame::RST_STREAM => {
+                self.close_stream(frame.stream_id);
+                self.ready
+                    .retain(|(stream_id, _)| *stream_id != frame.stream_id);
+                Ok(())
+            }
+            frame::SETTINGS if frame.stream_id == 0 => {
+                if !frame.has_flag(frame::ACK) {
+                    self.apply_settings(&frame.payload)?;
+                    self.write_frame(frame::SETTINGS, frame::ACK, 0, &[])?;
+                }
+                Ok(())
+            }
+            frame::PING if frame.payload.len() != 8 => Err(Error::Protocol(FRAME_SIZE_ERROR)),
+            frame::PING if !frame.has_flag(frame::ACK) => {
+                self.write_frame(frame::PING, frame::ACK, 0, &frame.payload)
+            }
+            frame::PING => Ok(()),
+            frame::GOAWAY => {
+                self.going_away = true;
+                Ok(())
+            }
+            frame::WINDOW_UPDATE => self.on_window_update(frame),
+            frame::HEADERS | frame::SETTINGS | frame::PUSH_PROMISE | frame::CONTINUATION => {
+                Err(Error::Protocol(PROTOCOL_ERROR))
+            }
+            // Frames of extensions this server does not know are ignored.
+            _ => Ok(()),
+        }
+    }
+
+    /// Decodes the header block once it is all there, holding on to it until
+    /// then.
+    fn on_header_fragment(
+        &mut self,
+        stream_id: u32,
+        end_stream: bool,
+        block: Vec<u8>,
+        flags: u8,
+    ) -> Result<(), Error> {
+        if flags & frame::END_HEADERS == 0 {
+            // Decoding needs the whole block, which is not held on to past
+            // the limit on headers.
+            if block.len() > self.server.limits.max_header_bytes {
+                return Err(Error::Protocol(ENHANCE_YOUR_CALM));
+            }
+            self.continuation = Some((stream_id, end_stream, block));
+            return Ok(());
+        }
+        // Decoded even when the stream is refused, to keep the dynamic table
+        // in step with the client.
+        let headers = self
+            .decoder
+            .decode(&block)
+            .map_err(|_| Error::Protocol(COMPRESSION_ERROR))?;
+        if self.receiving.contains_key(&stream_id) {
+            // Trailers, which end the stream and are dropped, the same as
+            // those of a chunked HTTP/1.1 body.
+            return match end_stream {
+                true => self.complete(stream_id),
+                false => Err(Error::Protocol(PROTOCOL_ERROR)),
+            };
+        }
+        if stream_id.is_multiple_of(2) {
+            return Err(Error::Protocol(PROTOCOL_ERROR));
+        }
+        if stream_id <= self.last_stream_id {
+            // The trailers of a stream already answered.
+            return Ok(());
+        }
+        self.last_stream_id = stream_id;
+        if self.going_away || self.receiving.len() + self.ready.len() >= MAX_CONCURRENT_STREAMS {
+            return self.reset(stream_id, REFUSED_STREAM);
+        }
+        self.stream_send_windows
+            .insert(stream_id, self.initial_window_size);
+        let limits = &self.server.limits;
+        let header_bytes = headers
+            .iter()
+            .map(|(name, value)| name.len() + value.len() + 32)
+            .sum::<usize>();
+        if header_bytes > limits.max_header_bytes || headers.len() > limits.max_headers {
+            return self.reject(
+                stream_id,
+                StatusCode::RequestHeaderFieldsTooLarge,
+                end_stream,
+            );
+        }
+        let receiving = Receiving {
+            headers,
+            ..Receiving::default()
+        };
+        self.receiving.insert(stream_id, receiving);
+        match end_stream {
+            true => self.complete(stream_id),
+            false => Ok(()),
+        }
+    }
+
+    fn on_data(&mut self, frame: Frame) -> Result<(), Error> {
+        let content = frame.content().ok_or(Error::Protocol(PROTOCOL_ERROR))?;
+        let end_stream = frame.has_flag(frame::END_STREAM);
+        // Given back straight away, a body being limited by the `Limits`
+        // rather than by flow control.
+        let increment = (frame.payload.len() as u32).to_be_bytes();
+        if !frame.payload.is_empty() {
+            self.write_frame(frame::WINDOW_UPDATE, 0, 0, &increment)?;
+        }
+        let receiving = match self.receiving.get_mut(&frame.stream_id) {
+            Some(receiving) => receiving,
+            // Whatever is still sent after a stream is answered or reset.
+            None => return Ok(()),
+        };
+        if (receiving.body.len() + content.len()) as u64 > self.server.limits.max_body {
+            self.receiving.remove(&frame.stream_id);
+            return self.reject(frame.stream_id, StatusCode::ContentTooLarge, end_stream);
+        }
+        receiving.body.extend_from_slice(content);
+        match end_stream {
+            true => self.complete(frame.stream_id),
+            false if !frame.payload.is_empty() => {
+                self.write_frame(frame::WINDOW_UPDATE, 0, frame.stream_id, &increment)
+            }
+            false => Ok(()),
+        }
+    }
+
+    fn on_window_update(&mut self, frame: Frame) -> Result<(), Error> {
+        let increment = match frame.payload[..] {
+            [a, b, c, d] => i64::from(u32::from_be_bytes([a, b, c, d]) & 0x7fff_ffff),
+            _ => return Err(Error::Protocol(FRAME_SIZE_ERROR)),
+        };
+        if frame.stream_id == 0 {
+            self.send_window += increment;
+            return match increment == 0 || self.send_window > MAX_WINDOW_SIZE {
+                true => Err(Error::Protocol(FLOW_CONTROL_ERROR)),
+                false => Ok(()),
+            };
+        }
+        match self.stream_send_windows.get_mut(&frame.stream_id) {
+            Some(window) if *window + increment > MAX_WINDOW_SIZE => {
+                self.reset(frame.stream_id, FLOW_CONTROL_ERROR)
+            }
+            Some(window) => {
+                *window += increment;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn apply_settings(&mut self, payload: &[u8]) -> Result<(), Error> {
+        let settings = frame::settings(payload).ok_or(Error::Protocol(FRAME_SIZE_ERROR))?;
+        for (identifier, value) in settings {
+            match identifier {
+                SETTINGS_INITIAL_WINDOW_SIZE => {
+                    let value = i64::from(value);
+                    let delta = value - self.initial_window_size;
+                    let windows = self.stream_send_windows.values_mut();
+                    if value > MAX_WINDOW_SIZE
+                        || windows.into_iter().any(|window| {
+                            *window += delta;
+                            *window > MAX_WINDOW_SIZE
+                        })
+                    {
+                        return Err(Error::Protocol(FLOW_CONTROL_ERROR));
+                    }
+                    self.initial_window_size = value;
+                }
+                SETTINGS_MAX_FRAME_SIZE => match value {
+                    16_384..=16_777_215 => self.max_frame_size = value as usize,
+                    _ => return Err(Error::Protocol(PROTOCOL_ERROR)),
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the request of the stream to be handled, now that all of it is
+    /// there.
+    fn complete(&mut self, stream_id: u32) -> Result<(), Error> {
+        let receiving = self.receiving.remove(&stream_id).unwrap_or_default();
+        match request_of(receiving, self.connection_info.clone()) {
+            Some(request) => {
+                self.ready.push_back((stream_id, request));
+                Ok(())
+            }
+            None => self.reject(stream_id, StatusCode::BadRequest, true),
+        }
+    }
+
+    /// Answers the stream with the status and no body straight away, without
+    /// it being handled. A stream the client is still sending on is reset,
+    /// as nothing more of it is needed.
+    fn reject(
+        &mut self,
+        stream_id: u32,
+        status_code: StatusCode,
+        ended: bool,
+    ) -> Result<(), Error> {
+        self.write_response(stream_id, HttpResponse::new(status_code), false)?;
+        match ended {
+            true => Ok(()),
+            false => self.reset(stream_id, NO_ERROR),
+        }
+    }
+
+    fn respond(&mut self, stream_id: u32, request: HttpRequest) -> Result<(), Error> {
+        let is_head = request.http_method == HttpMethod::Head;
+        let response = self.server.handle(request);
+        self.write_response(stream_id, response, is_head)?;
+        self.served += 1;
+        if self.served >= self.server.max_requests() {
+            self.go_away(NO_ERROR)?;
+        }
+        Ok(())
+    }
+
+    fn write_response(
+        &mut self,
+        stream_id: u32,
+        mut response: HttpResponse,
+        is_head: bool,
+    ) -> Result<(), Error> {
+        self.server.default_headers.stamp(&mut response);
+        let (head, body) = response_head(response, is_head);
+        let block = hpack::encode(head.iter().map(|(name, value)| (&name[..], &value[..])));
+        let mut fragments = block.chunks(self.max_frame_size).peekable();
+        let mut kind = frame::HEADERS;
+        let mut flags = match body {
+            Some(_) => 0,
+            None => frame::END_STREAM,
+        };
+        while let Some(fragment) = fragments.next() {
+            if fragments.peek().is_none() {
+                flags |= frame::END_HEADERS;
+            }
+            self.write_frame(kind, flags, stream_id, fragment)?;
+            kind = frame::CONTINUATION;
+            flags = 0;
+        }
+        if let Some(body) = body {
+            self.write_body(stream_id, body)?;
+        }
+        self.stream_send_windows.remove(&stream_id);
+        Ok(())
+    }
+
+    /// Writes the body in as large frames as the client takes, waiting on it
+    /// to take more once it has taken all it can.
+    fn write_body(&mut self, stream_id: u32, body: Body) -> Result<(), Error> {
+        let mut reader: Box<dyn Read + Send> = match body {
+            Body::Empty | Body::Upgrade(_) => Box::new(io::empty()),
+            Body::Bytes(bytes) => Box::new(Cursor::new(bytes)),
+            Body::Stream(reader) => reader,
+        };
+        let mut buffer = Vec::new();
+        loop {
+            let window = match self.wait_for_window(stream_id)? {
+                Some(window) => window,
+                // Reset by the client.
+                None => return Ok(()),
+            };
+            buffer.resize(window, 0);
+            let read = match reader.read(&mut buffer) {
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return self.reset(stream_id, INTERNAL_ERROR),
+            };
+            if read == 0 {
+                return self.write_frame(frame::DATA, frame::END_STREAM, stream_id, &[]);
+            }
+            self.send_window -= read as i64;
+            if let Some(stream_window) = self.stream_send_windows.get_mut(&stream_id) {
+                *stream_window -= read as i64;
+            }
+            self.write_frame(frame::DATA, 0, stream_id, &buffer[..read])?;
+            // Sent on straight away, as a body streamed out slowly is meant
+            // to be seen as it comes.
+            self.reader.get_mut().flush()?;
+        }
+    }
+
+    /// How much of the body can be sent in the next frame, reading frames
+    /// until the client takes any more. `None` once the stream is reset.
+    fn wait_for_window(&mut self, stream_id: u32) -> Result<Option<usize>, Error> {
+        loop {
+            let window = match self.stream_send_windows.get(&stream_id) {
+                Some(stream_window) => (*stream_window).min(self.send_window),
+                None => return Ok(None),
+            };
+            if window > 0 {
+                return Ok(Some((window as usize).min(self.max_frame_size)));
+            }
+            self.reader.get_mut().flush()?;
+            self.set_read_timeout(self.server.timeouts.read);
+            let frame = self.read_frame()?;
+            self.on_frame(frame)?;
+        }
+    }
+
+    fn reset(&mut self, stream_id: u32, error_code: u32) -> Result<(), Error> {
+        self.close_stream(stream_id);
+        self.write_frame(frame::RST_STREAM, 0, stream_id, &error_code.to_be_bytes())
+    }
+
+    fn close_stream(&mut self, stream_id: u32) {
+        self.receiving.remove(&stream_id);
+        self.stream_send_windows.remove(&stream_id);
+    }
+
+    /// Lets the client know no streams after the last one are served, the
+    /// first time only.
+    fn go_away(&mut self, error_code: u32) -> io::Result<()> {
+        self.going_away = true;
+        if self.goaway_sent {
+            return Ok(());
+        }
+        self.goaway_sent = true;
+        let mut payload = self.last_stream_id.to_be_bytes().to_vec();
+        payload.extend_from_slice(&error_code.to_be_bytes());
+        let stream = self.reader.get_mut();
+        frame::write_frame(stream, frame::GOAWAY, 0, 0, &payload)?;
+        stream.flush()
+    }
+
+    fn write_frame(
+        &mut self,
+        kind: u8,
+        flags: u8,
+        stream_id: u32,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        frame::write_frame(self.reader.get_mut(), kind, flags, stream_id, payload)?;
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) {
+        if let Some(socket) = self.socket {
+            let _ = socket.set_read_timeout(timeout);
+        }
+    }
+}
+
+/// The request of a stream, `None` if its pseudo headers are missing or
+/// malformed. Its headers are joined up as HTTP/1.1 would have them, with a
+/// `Host` taken from its authority.
+fn request_of(receiving: Receiving, connection: Option<ConnectionInfo>) -> Option<HttpRequest> {
+    let (mut method, mut path, mut authority) = (None, None, None);
+    let mut headers = HashMap::<String, String>::new();
+    for (name, value) in receiving.headers {
+        match name.as_str() {
+            ":method" => method = Some(value),
+            ":path" => path = Some(value),
+            ":authority" => authority = Some(value),
+            ":scheme" => {}
+            _ if name.starts_with(':') => return None,
+            _ => {
+                // Cookies may be split up to compress better.
+                let separator = match name.as_str() {
+                    "cookie" => "; ",
+                    _ => ", ",
+                };
+                headers
+                    .entry(name)
+                    .and_modify(|joined| {
+                        joined.push_str(separator);
+                        joined.push_str(&value);
+                    })
+                    .or_insert(value);
+            }
+        }
+    }
+    if let Some(authority) = authority {
+        headers.entry("host".into()).or_insert(authority);
+    }
+    Some(HttpRequest {
+        http_method: HttpMethod::from(&method?).ok()?,
+        uri: path.filter(|path| !path.is_empty())?.into(),
+        http_version: HttpVersion::Http2,
+        headers: Some(headers).filter(|headers| !headers.is_empty()),
+        body: Some(receiving.body).filter(|body| !body.is_empty()),
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection,
+    })
+}
+
+/// The headers of the response as HTTP/2 sends them, lowercase and without
+/// those only meaning something to a single HTTP/1.1 connection, along with
+/// its body unless there is none to send.
+fn response_head(response: HttpResponse, is_head: bool) -> (Vec<(String, String)>, Option<Body>) {
+    let mut head = vec![(
+        ":status".to_string(),
+        response.status_code.code().to_string(),
+    )];
+    head.extend(
+        response
+            .headers
+            .iter()
+            .filter(|(name, _)| !is_connection_specific(name))
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone())),
+    );
+    head.extend(
+        response
+            .cookies
+            .iter()
+            .map(|cookie| ("set-cookie".to_string(), cookie.to_string())),
+    );
+    if !response.status_code.allows_body() {
+        return (head, None);
+    }
+    // An upgrade has no meaning over HTTP/2, which leaves it without a body.
+    let content_length = match &response.body {
+        Body::Empty | Body::Upgrade(_) => Some(0),
+        Body::Bytes(bytes) => Some(bytes.len()),
+        Body::Stream(_) => response.stream_len().map(|len| len as usize),
+    };
+    if let Some(content_length) = content_length {
+        head.push(("content-length".into(), content_length.to_string()));
+    }
+    match (is_head, response.body) {
+        (true, _) | (_, Body::Empty) | (_, Body::Upgrade(_)) => (head, None),
+        (false, body) => (head, Some(body)),
+    }
+}
+
+fn is_connection_specific(name: &str) -> bool {
+    [
+        "Connection",
+        "Keep-Alive",
+        "Proxy-Connection",
+        "Transfer-Encoding",
+        "Upgrade",
+        "Content-Length",
+    ]
+    .iter()
+    .any(|specific| specific.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,259 @@
+use crate::server::http2::frame::{self, Frame};
+use crate::server::http2::{hpack, huffman, upgrade_settings, PREFACE};
+use crate::server::tests::TestStream;
+use crate::server::{Route, Server};
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, HttpVersion};
+use std::io::Cursor;
+
+/// The headers of a request, pseudo headers included, and its body.
+type TestRequest<'a> = (&'a [(&'a str, &'a str)], &'a [u8]);
+
+/// Everything a client sends to ask for the requests, each on a stream of its
+/// own.
+fn client_bytes(requests: &[TestRequest]) -> Vec<u8> {
+    let mut raw = PREFACE.to_vec();
+    frame::write_frame(&mut raw, frame::SETTINGS, 0, 0, &[]).unwrap();
+    for (i, (headers, body)) in requests.iter().enumerate() {
+        let stream_id = 2 * i as u32 + 1;
+        let block = hpack::encode(headers.iter().copied());
+        let flags = match body.is_empty() {
+            true => frame::END_HEADERS | frame::END_STREAM,
+            false => frame::END_HEADERS,
+        };
+        frame::write_frame(&mut raw, frame::HEADERS, flags, stream_id, &block).unwrap();
+        if !body.is_empty() {
+            frame::write_frame(&mut raw, frame::DATA, frame::END_STREAM, stream_id, body).unwrap();
+        }
+    }
+    raw
+}
+
+fn frames_of(raw: &[u8]) -> Vec<Frame> {
+    let mut reader = Cursor::new(raw);
+    let mut frames = Vec::new();
+    while (reader.position() as usize) < raw.len() {
+        frames.push(frame::read_frame(&mut reader, frame::DEFAULT_MAX_FRAME_SIZE).unwrap());
+    }
+    frames
+}
+
+/// The headers and body of the response on the stream.
+fn response_on(frames: &[Frame], stream_id: u32) -> (Vec<(String, String)>, Vec<u8>) {
+    let mut decoder = hpack::Decoder::new(4_096);
+    let mut headers = Vec::new();
+    let mut body = Vec::new();
+    for frame in frames {
+        match frame.kind {
+            frame::HEADERS => {
+                let decoded = decoder.decode(&frame.payload).unwrap();
+                if frame.stream_id == stream_id {
+                    headers = decoded;
+                }
+            }
+            frame::DATA if frame.stream_id == stream_id => body.extend_from_slice(&frame.payload),
+            _ => {}
+        }
+    }
+    (headers, body)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn test_server() -> Server {
+    let mut server = Server::with_workers(1);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/", |request: HttpRequest| {
+                assert_eq!(request.http_version, HttpVersion::Http2);
+                format!("hello {}", request.header("Host").unwrap_or("nobody"))
+            })
+        })
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", |request: HttpRequest| request.body))
+        .unwrap();
+    server
+}
+
+#[test]
+fn should_decode_header_block_of_rfc_7541_when_huffman_encoded() {
+    let block = [
+        0x82, 0x86, 0x84, 0x41, 0x8c, 0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90,
+        0xf4, 0xff,
+    ];
+    let mut decoder = hpack::Decoder::new(4_096);
+    let headers = decoder.decode(&block).unwrap();
+    let expected = [
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/"),
+        (":authority", "www.example.com"),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    assert_eq!(headers, expected);
+    // Added to the dynamic table, so that it is indexed from then on.
+    assert_eq!(
+        decoder.decode(&[0xbe]).unwrap(),
+        vec![(":authority".to_string(), "www.example.com".to_string())]
+    );
+}
+
+#[test]
+fn should_have_an_error_result_when_huffman_padding_is_not_all_ones() {
+    assert!(huffman::decode(&[
+        0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4, 0xfe
+    ])
+    .is_err());
+}
+
+#[test]
+fn should_round_trip_headers_through_encoder_and_decoder() {
+    let headers = [
+        (":status", "200"),
+        ("content-type", "text/plain"),
+        ("x-custom", "value"),
+    ];
+    let block = hpack::encode(headers.iter().copied());
+    let decoded = hpack::Decoder::new(4_096).decode(&block).unwrap();
+    let decoded: Vec<_> = decoded
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    assert_eq!(decoded, headers.to_vec());
+}
+
+#[test]
+fn should_serve_requests_on_their_streams_when_client_starts_with_preface() {
+    let get = [
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/"),
+        (":authority", "example.com"),
+    ];
+    let post = [(":method", "POST"), (":scheme", "http"), (":path", "/echo")];
+    let mut stream = TestStream::of(client_bytes(&[(&get, b""), (&post, b"posted")]));
+    test_server().serve(&mut stream, None, None).unwrap();
+    let frames = frames_of(&stream.output);
+    assert_eq!(frames[0].kind, frame::SETTINGS);
+    assert!(frames
+        .iter()
+        .any(|frame| frame.kind == frame::SETTINGS && frame.has_flag(frame::ACK)));
+
+    let (headers, body) = response_on(&frames, 1);
+    assert_eq!(header(&headers, ":status"), Some("200"));
+    assert_eq!(header(&headers, "content-length"), Some("17"));
+    assert_eq!(body, b"hello example.com");
+    let (headers, body) = response_on(&frames, 3);
+    assert_eq!(header(&headers, ":status"), Some("200"));
+    assert_eq!(body, b"posted");
+    assert_eq!(frames.last().unwrap().kind, frame::GOAWAY);
+}
+
+#[test]
+fn should_reset_stream_with_413_when_body_is_over_limit() {
+    let post = [(":method", "POST"), (":scheme", "http"), (":path", "/echo")];
+    let mut stream = TestStream::of(client_bytes(&[(&post, &[b'a'; 64])]));
+    let mut server = test_server();
+    server.limits(crate::server::Limits {
+        max_body: 16,
+        ..crate::server::Limits::default()
+    });
+    server.serve(&mut stream, None, None).unwrap();
+    let frames = frames_of(&stream.output);
+    let (headers, body) = response_on(&frames, 1);
+    assert_eq!(header(&headers, ":status"), Some("413"));
+    assert!(body.is_empty());
+}
+
+#[test]
+fn should_go_away_with_protocol_error_when_preface_is_wrong() {
+    let mut stream = TestStream::of(b"PRI * HTTP/2.0\r\n\r\nXX\r\n\r\n");
+    test_server().serve(&mut stream, None, None).unwrap();
+    let frames = frames_of(&stream.output);
+    let goaway = frames.last().unwrap();
+    assert_eq!(goaway.kind, frame::GOAWAY);
+    assert_eq!(goaway.payload[4..], 1u32.to_be_bytes());
+}
+
+#[test]
+fn should_answer_upgraded_request_over_http2_when_asking_for_h2c() {
+    let mut raw = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+        Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAAP__\r\n\r\n"
+        .to_vec();
+    raw.extend_from_slice(&client_bytes(&[]));
+    let mut stream = TestStream::of(raw);
+    test_server().serve(&mut stream, None, None).unwrap();
+
+    let switching = b"HTTP/1.1 101 Switching Protocols\r\n";
+    assert!(stream.output.starts_with(switching));
+    let head_end = stream
+        .output
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap()
+        + 4;
+    let head = String::from_utf8_lossy(&stream.output[..head_end]);
+    assert!(head.contains("Upgrade: h2c\r\n"));
+    let frames = frames_of(&stream.output[head_end..]);
+    let (headers, body) = response_on(&frames, 1);
+    assert_eq!(header(&headers, ":status"), Some("200"));
+    assert_eq!(body, b"hello example.com");
+}
+
+#[test]
+fn should_not_upgrade_when_http2_settings_is_missing() {
+    let request =
+        HttpRequest::from("GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n");
+    assert_eq!(upgrade_settings(&request), None);
+    let mut stream = TestStream::of("GET / HTTP/1.1\r\nHost: example.com\r\nUpgrade: h2c\r\n\r\n");
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| HttpResponse::text("http/1.1")))
+        .unwrap();
+    server.serve(&mut stream, None, None).unwrap();
+    assert!(stream.output.starts_with(b"HTTP/1.1 200 OK\r\n"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_speak_http2_when_chosen_through_alpn() {
+    use crate::server::tests::{fixture, listen_tls, tls_client};
+    use crate::server::{ClientAuth, TlsConfig};
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConnection, StreamOwned};
+    use std::convert::TryFrom;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    let address = listen_tls(TlsConfig {
+        cert_pem_path: fixture("cert.pem"),
+        key_pem_path: fixture("key.pem"),
+        hosts: Vec::new(),
+        client_auth: ClientAuth::None,
+    });
+    let mut client = (*tls_client(&fixture("cert.pem"), false)).clone();
+    client.alpn_protocols = vec![b"h2".to_vec()];
+    let server_name = ServerName::try_from("localhost").unwrap();
+    let connection = ClientConnection::new(Arc::new(client), server_name).unwrap();
+    let mut tls_stream = StreamOwned::new(connection, TcpStream::connect(address).unwrap());
+    let get = [(":method", "GET"), (":scheme", "https"), (":path", "/")];
+    let mut raw = client_bytes(&[(&get, b"")]);
+    // Told it is done with after its only request, for the server to close.
+    frame::write_frame(&mut raw, frame::GOAWAY, 0, 0, &[0; 8]).unwrap();
+    tls_stream.write_all(&raw).unwrap();
+    let mut raw_response = Vec::new();
+    let _ = tls_stream.read_to_end(&mut raw_response);
+
+    assert_eq!(tls_stream.conn.alpn_protocol(), Some(&b"h2"[..]));
+    let (headers, _) = response_on(&frames_of(&raw_response), 1);
+    assert_eq!(header(&headers, ":status"), Some("200"));
+}
@@ -0,0 +1,378 @@
+//! Putting a [`Server`] on the network, listening on a port of every
+//! interface of the machine or on any addresses given.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::test::TestServer;
+
+#[cfg(feature = "tls")]
+use super::{tls, TlsConfig};
+use super::{ConfigError, KeepAlive, Limits, Server, ServerConfig, Shutdown, Timeouts};
+
+/// A [`Server`] paired with the addresses it is to be started on.
+///
+/// # Examples:
+/// ```no_run
+/// use martian::server::{HttpServer, Route, Server};
+/// use martian::web::{HttpMethod, HttpRequest, HttpResponse};
+/// fn hello(_: HttpRequest) -> HttpResponse {
+///     HttpResponse::text("hello")
+/// }
+/// let mut server = Server::default();
+/// server
+///     .route(|| Route::bind(HttpMethod::Get).to("/", hello))
+///     .unwrap();
+/// HttpServer::of_port(8080, server).start().unwrap();
+/// ```
+///
+/// [`Server`]: ./struct.Server.html
+pub struct HttpServer {
+    server: Server,
+    /// Bound as soon as they are given, so that the port of one given as `0`
+    /// is known before starting.
+    listeners: Vec<TcpListener>,
+    /// Bound only once started, such as the port of every interface.
+    deferred_addr: Option<SocketAddr>,
+    shutdown: Shutdown,
+    #[cfg(all(unix, feature = "signals"))]
+    shutdown_on_signals: bool,
+    #[cfg(feature = "tls")]
+    tls_config: Option<TlsConfig>,
+}
+
+impl HttpServer {
+    /// Pairs the [`Server`] with the port it will listen on, on every
+    /// interface, once started.
+    ///
+    /// [`Server`]: ./struct.Server.html
+    pub fn of_port(port: u16, server: Server) -> HttpServer {
+        HttpServer {
+            deferred_addr: Some((Ipv4Addr::UNSPECIFIED, port).into()),
+            ..HttpServer::new(server)
+        }
+    }
+
+    /// Pairs the [`Server`] with the address of the [`ServerConfig`], to be
+    /// bound once started, having set every other setting of the config on
+    /// the `Server` in place of its own.
+    ///
+    /// # Returns:
+    /// An `Err` if the config is not valid, see [`ServerConfig::validate`].
+    ///
+    /// [`Server`]: ./struct.Server.html
+    /// [`ServerConfig`]: ./struct.ServerConfig.html
+    /// [`ServerConfig::validate`]: ./struct.ServerConfig.html#method.validate
+    pub fn from_config(
+        config: ServerConfig,
+        mut server: Server,
+    ) -> Result<HttpServer, ConfigError> {
+        config.validate()?;
+        server.workers = config.workers;
+        server.timeouts(config.timeouts);
+        server.keep_alive(config.keep_alive);
+        server.limits(config.limits);
+        Ok(HttpServer {
+            deferred_addr: Some((config.bind_address, config.port).into()),
+            #[cfg(feature = "tls")]
+            tls_config: config.tls,
+            ..HttpServer::new(server)
+        })
+    }
+
+    /// Serves the [`Server`] on a free port of the loopback interface, on a
+    /// thread of its own, until the [`TestServer`] returned is shut down or
+    /// dropped. The port is bound before this returns, so the server can be
+    /// connected to straight away.
+    ///
+    /// # Returns:
+    /// An `Err` if no port could be bound to.
+    ///
+    /// [`Server`]: ./struct.Server.html
+    /// [`TestServer`]: ../test/struct.TestServer.html
+    pub fn spawn_for_test(server: Server) -> io::Result<TestServer> {
+        let http_server = HttpServer::new(server).bind((Ipv4Addr::LOCALHOST, 0))?;
+        Ok(TestServer::spawn(http_server))
+    }
+
+    /// Builds up a [`ServerConfig`] one setting at a time, see
+    /// [`HttpServerBuilder`].
+    ///
+    /// [`ServerConfig`]: ./struct.ServerConfig.html
+    /// [`HttpServerBuilder`]: ./struct.HttpServerBuilder.html
+    pub fn builder() -> HttpServerBuilder {
+        HttpServerBuilder::default()
+    }
+
+    /// A [`Server`] not yet listening anywhere, see [`bind`].
+    ///
+    /// [`Server`]: ./struct.Server.html
+    /// [`bind`]: ./struct.HttpServer.html#method.bind
+    pub fn new(server: Server) -> HttpServer {
+        HttpServer {
+            server,
+            listeners: Vec::new(),
+            deferred_addr: None,
+            shutdown: Shutdown::new(),
+            #[cfg(all(unix, feature = "signals"))]
+            shutdown_on_signals: false,
+            #[cfg(feature = "tls")]
+            tls_config: None,
+        }
+    }
+
+    /// Binds to the address straight away, listening on it as well as any
+    /// bound before once started. An address with port `0` is given any free
+    /// port, found through [`addrs`]. It is bound with the [`SocketOptions`]
+    /// the [`Server`] has by then.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{HttpServer, Server};
+    /// use std::net::{Ipv4Addr, SocketAddr};
+    /// let http_server = HttpServer::new(Server::default())
+    ///     .bind("127.0.0.1:0")
+    ///     .unwrap()
+    ///     .bind_addr(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+    ///     .unwrap();
+    /// assert_eq!(http_server.addrs().len(), 2);
+    /// assert_ne!(http_server.port(), 0);
+    /// ```
+    ///
+    /// # Returns:
+    /// An `Err` if the address could not be bound to.
+    ///
+    /// [`addrs`]: ./struct.HttpServer.html#method.addrs
+    /// [`SocketOptions`]: ./struct.SocketOptions.html
+    /// [`Server`]: ./struct.Server.html
+    pub fn bind<A: ToSocketAddrs>(mut self, addr: A) -> io::Result<HttpServer> {
+        self.listeners.push(self.server.socket_options.bind(addr)?);
+        Ok(self)
+    }
+
+    /// Same as [`bind`], for an address already resolved.
+    ///
+    /// [`bind`]: ./struct.HttpServer.html#method.bind
+    pub fn bind_addr(self, addr: SocketAddr) -> io::Result<HttpServer> {
+        self.bind(addr)
+    }
+
+    /// Shuts down on `SIGINT` or `SIGTERM` once started, such as a Ctrl-C in
+    /// the terminal, see [`Shutdown::on_signals`].
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// use martian::server::{HttpServer, Server};
+    /// HttpServer::of_port(8080, Server::default())
+    ///     .shutdown_on_signals()
+    ///     .start()
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`Shutdown::on_signals`]: ./struct.Shutdown.html#method.on_signals
+    #[cfg(all(unix, feature = "signals"))]
+    pub fn shutdown_on_signals(mut self) -> HttpServer {
+        self.shutdown_on_signals = true;
+        self
+    }
+
+    /// Serves HTTPS on every address rather than HTTP, using the certificate
+    /// and key in the [`TlsConfig`], see [`Server::listen_tls`].
+    ///
+    /// # Examples:
+    /// ```no_run
+    /// use martian::server::{ClientAuth, HttpServer, Server, TlsConfig};
+    /// HttpServer::of_port(8443, Server::default())
+    ///     .with_tls(TlsConfig {
+    ///         cert_pem_path: "cert.pem".into(),
+    ///         key_pem_path: "key.pem".into(),
+    ///         hosts: Vec::new(),
+    ///         client_auth: ClientAuth::None,
+    ///     })
+    ///     .start()
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`TlsConfig`]: ./struct.TlsConfig.html
+    /// [`Server::listen_tls`]: ./struct.Server.html#method.listen_tls
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls_config: TlsConfig) -> HttpServer {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// The port of [`addr`].
+    ///
+    /// [`addr`]: ./struct.HttpServer.html#method.addr
+    pub fn port(&self) -> u16 {
+        self.addr().port()
+    }
+
+    /// The first of [`addrs`], or port `0` of every interface when there
+    /// are none.
+    ///
+    /// [`addrs`]: ./struct.HttpServer.html#method.addrs
+    pub fn addr(&self) -> SocketAddr {
+        self.addrs()
+            .first()
+            .copied()
+            .unwrap_or_else(|| (Ipv4Addr::UNSPECIFIED, 0).into())
+    }
+
+    /// Every address [`start`] listens on, those bound already being the
+    /// addresses actually bound to.
+    ///
+    /// [`start`]: ./struct.HttpServer.html#method.start
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .flat_map(|listener| listener.local_addr())
+            .chain(self.deferred_addr)
+            .collect()
+    }
+
+    /// The handle stopping this server once started, to be triggered from
+    /// another thread.
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Serves every connection made to any of [`addrs`], see
+    /// [`Server::listen_until`]. This blocks until [`shutdown`] is
+    /// triggered. The connections of every address are served by the same
+    /// workers of the [`Server`].
+    ///
+    /// # Returns:
+    /// An `Err` if the port of [`of_port`], or the address of
+    /// [`from_config`], could not be bound to, or if the
+    /// certificate or key of [`with_tls`] can not be loaded.
+    ///
+    /// [`addrs`]: ./struct.HttpServer.html#method.addrs
+    /// [`Server::listen_until`]: ./struct.Server.html#method.listen_until
+    /// [`shutdown`]: ./struct.HttpServer.html#method.shutdown
+    /// [`Server`]: ./struct.Server.html
+    /// [`of_port`]: ./struct.HttpServer.html#method.of_port
+    /// [`from_config`]: ./struct.HttpServer.html#method.from_config
+    /// [`with_tls`]: ./struct.HttpServer.html#method.with_tls
+    pub fn start(&self) -> io::Result<()> {
+        #[cfg(feature = "tls")]
+        let server_config = match &self.tls_config {
+            Some(tls_config) => Some(tls_config.server_config()?),
+            None => None,
+        };
+        let deferred_listener = match self.deferred_addr {
+            Some(addr) => Some(self.server.socket_options.bind(addr)?),
+            None => None,
+        };
+        #[cfg(all(unix, feature = "signals"))]
+        if self.shutdown_on_signals {
+            self.shutdown.on_signals()?;
+        }
+        let listeners = self.listeners.iter().chain(&deferred_listener);
+        let incoming = listeners
+            .map(|listener| self.shutdown.incoming(listener))
+            .collect::<io::Result<Vec<_>>>()?;
+        thread::scope(|scope| {
+            // A rendezvous, so that accepting holds off for as long as the
+            // workers are busy, as when listening on a single address.
+            let (sender, streams) = mpsc::sync_channel(0);
+            for incoming in incoming {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    for stream in incoming {
+                        let _ = sender.send(stream);
+                    }
+                });
+            }
+            drop(sender);
+            #[cfg(feature = "tls")]
+            if let Some(server_config) = &server_config {
+                return self.server.accept(streams.into_iter(), |stream| {
+                    tls::accept(server_config, stream)
+                });
+            }
+            self.server.accept(streams.into_iter(), Ok)
+        })
+    }
+}
+
+/// Builds the [`ServerConfig`] of an [`HttpServer`], each setting left out
+/// being as it is by default, see [`HttpServer::builder`].
+///
+/// # Examples:
+/// ```
+/// use martian::server::{HttpServer, Limits, Server};
+/// let http_server = HttpServer::builder()
+///     .port(8080)
+///     .workers(8)
+///     .limits(Limits {
+///         max_body: 1024 * 1024,
+///         ..Limits::default()
+///     })
+///     .build(Server::default())
+///     .unwrap();
+/// assert_eq!(http_server.port(), 8080);
+/// ```
+///
+/// [`ServerConfig`]: ./struct.ServerConfig.html
+/// [`HttpServer`]: ./struct.HttpServer.html
+/// [`HttpServer::builder`]: ./struct.HttpServer.html#method.builder
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct HttpServerBuilder {
+    config: ServerConfig,
+}
+
+impl HttpServerBuilder {
+    /// Starts over from the config, rather than from the defaults.
+    pub fn config(mut self, config: ServerConfig) -> HttpServerBuilder {
+        self.config = config;
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> HttpServerBuilder {
+        self.config.port = port;
+        self
+    }
+
+    pub fn bind_address(mut self, bind_address: IpAddr) -> HttpServerBuilder {
+        self.config.bind_address = bind_address;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> HttpServerBuilder {
+        self.config.workers = workers;
+        self
+    }
+
+    pub fn timeouts(mut self, timeouts: Timeouts) -> HttpServerBuilder {
+        self.config.timeouts = timeouts;
+        self
+    }
+
+    pub fn keep_alive(mut self, keep_alive: KeepAlive) -> HttpServerBuilder {
+        self.config.keep_alive = keep_alive;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> HttpServerBuilder {
+        self.config.limits = limits;
+        self
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls_config: TlsConfig) -> HttpServerBuilder {
+        self.config.tls = Some(tls_config);
+        self
+    }
+
+    /// Same as [`HttpServer::from_config`], for the config built.
+    ///
+    /// [`HttpServer::from_config`]: ./struct.HttpServer.html#method.from_config
+    pub fn build(self, server: Server) -> Result<HttpServer, ConfigError> {
+        HttpServer::from_config(self.config, server)
+    }
+}
@@ -0,0 +1,112 @@
+//! Answering the requests over a limit of requests in flight with a 503 right
+//! away, rather than queueing them up behind those already being handled, so
+//! that an overloaded `Server` stays quick for the ones it does take.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::web::{HttpRequest, HttpResponse, StatusCode};
+
+use super::{Middleware, Next};
+
+/// How many requests are being handled through one limit.
+#[derive(Debug, Default)]
+pub(in crate::server) struct Concurrency {
+    in_flight: AtomicUsize,
+}
+
+impl Concurrency {
+    /// Counts a request as in flight until the `Permit` is dropped.
+    ///
+    /// # Returns:
+    /// `None` when as many as the most allowed are already in flight.
+    pub(in crate::server) fn enter(&self, max_in_flight: usize) -> Option<Permit<'_>> {
+        self.count_in(max_in_flight)?;
+        Some(Permit { concurrency: self })
+    }
+
+    /// As [`enter`], the permit holding on to the count so that it can be
+    /// moved onto another thread.
+    ///
+    /// [`enter`]: ./struct.Concurrency.html#method.enter
+    pub(in crate::server) fn enter_owned(
+        self: &Arc<Self>,
+        max_in_flight: usize,
+    ) -> Option<OwnedPermit> {
+        self.count_in(max_in_flight)?;
+        Some(OwnedPermit {
+            concurrency: self.clone(),
+        })
+    }
+
+    fn count_in(&self, max_in_flight: usize) -> Option<()> {
+        self.in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_flight| {
+                match in_flight < max_in_flight {
+                    true => Some(in_flight + 1),
+                    false => None,
+                }
+            })
+            .ok()
+            .map(|_| ())
+    }
+}
+
+/// A request counted as in flight.
+pub(in crate::server) struct Permit<'a> {
+    concurrency: &'a Concurrency,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.concurrency.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A request counted as in flight, see [`Concurrency::enter_owned`].
+///
+/// [`Concurrency::enter_owned`]: ./struct.Concurrency.html#method.enter_owned
+pub(in crate::server) struct OwnedPermit {
+    concurrency: Arc<Concurrency>,
+}
+
+impl Drop for OwnedPermit {
+    fn drop(&mut self) {
+        self.concurrency.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The limit of a single route, see [`Binding::max_in_flight`].
+///
+/// [`Binding::max_in_flight`]: ../struct.Binding.html#method.max_in_flight
+#[derive(Debug)]
+pub(in crate::server) struct ConcurrencyLimit {
+    max_in_flight: usize,
+    concurrency: Concurrency,
+}
+
+impl ConcurrencyLimit {
+    pub(in crate::server) fn new(max_in_flight: usize) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            max_in_flight,
+            concurrency: Concurrency::default(),
+        }
+    }
+}
+
+impl Middleware for ConcurrencyLimit {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        match self.concurrency.enter(self.max_in_flight) {
+            Some(_permit) => next.run(request),
+            None => overloaded(),
+        }
+    }
+}
+
+/// The 503 a request over a limit is answered with.
+pub(in crate::server) fn overloaded() -> HttpResponse {
+    HttpResponse::new(StatusCode::ServiceUnavailable)
+}
+
+#[cfg(test)]
+mod tests;
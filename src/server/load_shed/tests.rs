@@ -0,0 +1,90 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::server::load_shed::Concurrency;
+use crate::server::{Limits, Route, Server};
+use crate::test::TestClient;
+use crate::web::{HttpMethod, HttpRequest, StatusCode};
+
+/// A client of a `Server` whose `/slow` route holds its request until
+/// released, having said it started on it.
+fn slow_client<F>(configure: F) -> (Arc<TestClient>, Receiver<()>, Sender<()>)
+where
+    F: FnOnce(&mut Server, Box<dyn Fn(HttpRequest) -> &'static str + Send + Sync>),
+{
+    let (started_tx, started_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let (started_tx, release_rx) = (Mutex::new(started_tx), Mutex::new(release_rx));
+    let slow = Box::new(move |_: HttpRequest| {
+        started_tx.lock().unwrap().send(()).unwrap();
+        release_rx.lock().unwrap().recv().unwrap();
+        "slow"
+    });
+    let mut server = Server::default();
+    configure(&mut server, slow);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/fast", |_| "fast"))
+        .unwrap();
+    (Arc::new(TestClient::new(server)), started_rx, release_tx)
+}
+
+fn get_in_background(client: &Arc<TestClient>, path: &'static str) -> JoinHandle<StatusCode> {
+    let client = Arc::clone(client);
+    thread::spawn(move || client.get(path).status_code)
+}
+
+#[test]
+fn should_shed_request_when_server_has_max_in_flight() {
+    let (client, started, release) = slow_client(|server, slow| {
+        server.limits(Limits {
+            max_in_flight: Some(1),
+            ..Limits::default()
+        });
+        server
+            .route(|| Route::bind(HttpMethod::Get).to("/slow", slow))
+            .unwrap();
+    });
+    let slow = get_in_background(&client, "/slow");
+    started.recv().unwrap();
+    assert_eq!(
+        client.get("/fast").status_code,
+        StatusCode::ServiceUnavailable
+    );
+    release.send(()).unwrap();
+    assert_eq!(slow.join().unwrap(), StatusCode::Ok);
+    assert_eq!(client.get("/fast").status_code, StatusCode::Ok);
+}
+
+#[test]
+fn should_shed_request_to_route_only_when_route_has_max_in_flight() {
+    let (client, started, release) = slow_client(|server, slow| {
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get)
+                    .to("/slow", slow)
+                    .max_in_flight(1)
+            })
+            .unwrap();
+    });
+    let slow = get_in_background(&client, "/slow");
+    started.recv().unwrap();
+    assert_eq!(
+        client.get("/slow").status_code,
+        StatusCode::ServiceUnavailable
+    );
+    assert_eq!(client.get("/fast").status_code, StatusCode::Ok);
+    release.send(()).unwrap();
+    assert_eq!(slow.join().unwrap(), StatusCode::Ok);
+}
+
+#[test]
+fn should_admit_request_again_when_permit_is_dropped() {
+    let concurrency = Concurrency::default();
+    let first = concurrency.enter(2);
+    let second = concurrency.enter(2);
+    assert!(first.is_some() && second.is_some());
+    assert!(concurrency.enter(2).is_none());
+    drop(first);
+    assert!(concurrency.enter(2).is_some());
+}
@@ -0,0 +1,103 @@
+//! Reporting what the [`Server`] does through [log](https://docs.rs/log),
+//! with the `log` feature, to whichever logger the application has set up.
+//! Every request handled is logged at `info` once answered, along with its
+//! `method`, `path`, `status`, `elapsed_ms` and `peer_addr` as key values.
+//! What happens on the way there is logged at `debug` and `trace`, and
+//! handlers which panic or time out at `error` and `warn`.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+
+use log::{debug, error, info, log_enabled, trace, warn, Level};
+
+use crate::web::{HttpRequest, MartianError, StatusCode};
+
+use super::RequestLog;
+
+pub(in crate::server) fn bound(listener: &TcpListener) {
+    if let Ok(local_addr) = listener.local_addr() {
+        debug!("Listening on {}", local_addr);
+    }
+}
+
+pub(in crate::server) fn accepted(peer_addr: SocketAddr) {
+    trace!("Accepted connection from {}", peer_addr);
+}
+
+/// A connection dropped before its first request, such as by a failed TLS
+/// handshake.
+pub(in crate::server) fn dropped(error: &io::Error) {
+    debug!("Dropped connection: {}", error);
+}
+
+/// A request answered straight away, without being parsed.
+pub(in crate::server) fn rejected(status_code: StatusCode) {
+    debug!(
+        "Rejected request with {} {}",
+        status_code.code(),
+        status_code.reason_phrase()
+    );
+}
+
+pub(in crate::server) fn routed(request: &HttpRequest, uri: &str) {
+    trace!(
+        "Routed {} {} to {}",
+        request.http_method.as_str(),
+        request.uri.path(),
+        uri
+    );
+}
+
+pub(in crate::server) fn unmatched(request: &HttpRequest) {
+    debug!(
+        "No route matches {} {}",
+        request.http_method.as_str(),
+        request.uri.path()
+    );
+}
+
+/// An error a request is answered with, a server error being logged as a
+/// warning.
+pub(in crate::server) fn failed(error: &MartianError) {
+    match error.status_code.is_server_error() {
+        true => warn!("Handler failed with {}", error),
+        false => debug!("Handler failed with {}", error),
+    }
+}
+
+pub(in crate::server) fn panicked(message: &str) {
+    error!("Handler panicked: {}", message);
+}
+
+/// Whether [`completed`] logs anything, so that the [`RequestLog`] is only
+/// kept when it does.
+///
+/// [`completed`]: ./fn.completed.html
+/// [`RequestLog`]: ../struct.RequestLog.html
+pub(in crate::server) fn is_completion_logged() -> bool {
+    log_enabled!(Level::Info)
+}
+
+pub(in crate::server) fn completed(request_log: &RequestLog) {
+    let peer_addr = request_log
+        .peer_addr
+        .map_or("-".into(), |peer_addr| peer_addr.to_string());
+    let elapsed_ms = request_log.elapsed.as_secs_f64() * 1_000.0;
+    info!(
+        method = request_log.http_method.as_str(),
+        path = request_log.path.as_str(),
+        status = request_log.status_code.code(),
+        elapsed_ms = elapsed_ms,
+        peer_addr = peer_addr.as_str();
+        "{} {} {} in {:.3}ms",
+        request_log.http_method.as_str(),
+        request_log.path,
+        request_log.status_code.code(),
+        elapsed_ms
+    );
+}
+
+#[cfg(test)]
+mod tests;
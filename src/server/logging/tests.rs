@@ -0,0 +1,119 @@
+use crate::server::tests::TestStream;
+use crate::server::{Route, Server};
+use crate::web::{HttpMethod, MartianError, StatusCode};
+use log::kv::Key;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::HashMap;
+use std::sync::{Mutex, Once};
+
+/// What is kept of a record, for checking what was logged.
+#[derive(Debug)]
+struct Captured {
+    level: Level,
+    message: String,
+    key_values: HashMap<&'static str, String>,
+}
+
+struct Capture(Mutex<Vec<Captured>>);
+
+static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let key_values = ["method", "path", "status", "elapsed_ms", "peer_addr"]
+            .iter()
+            .filter_map(|key| {
+                let value = record.key_values().get(Key::from_str(key))?;
+                Some((*key, value.to_string()))
+            })
+            .collect();
+        self.0.lock().unwrap().push(Captured {
+            level: record.level(),
+            message: record.args().to_string(),
+            key_values,
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Serves the request, returning every record logged about its path. Tests
+/// log alongside each other, so each is to request a path of its own.
+fn serve_logged(server: &Server, raw_request: &str, path: &str) -> Vec<Captured> {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+    });
+    server
+        .serve(&mut TestStream::of(raw_request), None, None)
+        .unwrap();
+    let mut captured = CAPTURE.0.lock().unwrap();
+    let (logged, others) = captured
+        .drain(..)
+        .partition(|captured: &Captured| captured.message.contains(path));
+    *captured = others;
+    logged
+}
+
+fn server() -> Server {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/logged/{id}", |_| "logged")
+                .to_fallible("/failing", |_| -> Result<&str, MartianError> {
+                    Err(MartianError::new(StatusCode::BadGateway, "upstream"))
+                })
+        })
+        .unwrap();
+    server
+}
+
+#[test]
+fn should_log_request_with_key_values_when_completed() {
+    let logged = serve_logged(&server(), "GET /logged/1 HTTP/1.1\r\n\r\n", "/logged/1");
+    let completed = logged
+        .iter()
+        .find(|captured| captured.level == Level::Info)
+        .unwrap();
+    assert!(completed.message.starts_with("GET /logged/1 200 in "));
+    assert_eq!(completed.key_values["method"], "GET");
+    assert_eq!(completed.key_values["path"], "/logged/1");
+    assert_eq!(completed.key_values["status"], "200");
+    assert_eq!(completed.key_values["peer_addr"], "-");
+    assert!(completed.key_values.contains_key("elapsed_ms"));
+}
+
+#[test]
+fn should_log_route_matched_when_routing() {
+    let logged = serve_logged(&server(), "GET /logged/2 HTTP/1.1\r\n\r\n", "/logged/2");
+    assert!(logged.iter().any(|captured| captured.level == Level::Trace
+        && captured.message == "Routed GET /logged/2 to /logged/{id}"));
+}
+
+#[test]
+fn should_log_unmatched_request_when_no_route_matches() {
+    let logged = serve_logged(&server(), "GET /unlogged HTTP/1.1\r\n\r\n", "/unlogged");
+    assert!(logged.iter().any(|captured| captured.level == Level::Debug
+        && captured.message == "No route matches GET /unlogged"));
+    assert!(logged
+        .iter()
+        .any(|captured| captured.key_values.get("status").map(String::as_str) == Some("404")));
+}
+
+#[test]
+fn should_log_warning_when_handler_fails_with_server_error() {
+    let logged = serve_logged(&server(), "GET /failing HTTP/1.1\r\n\r\n", "/failing");
+    assert!(logged.iter().any(|captured| captured.level == Level::Info));
+    let mut captured = CAPTURE.0.lock().unwrap();
+    let index = captured
+        .iter()
+        .position(|captured| captured.message == "Handler failed with 502 Bad Gateway: upstream")
+        .unwrap();
+    assert_eq!(captured.remove(index).level, Level::Warn);
+}
@@ -0,0 +1,272 @@
+//! Counting the requests a [`Server`] handles and how long they take, to be
+//! scraped by [Prometheus](https://prometheus.io) off of a route of its own.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+use super::{mount, Server};
+
+/// The upper bounds of the latency buckets, in seconds, as the Prometheus
+/// clients have them by default.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The route label of a request no route matches.
+const UNMATCHED: &str = "unmatched";
+
+const STATUS_CLASSES: [&str; 5] = ["1xx", "2xx", "3xx", "4xx", "5xx"];
+
+/// The metrics of every request handled by the [`Server`] they are given to
+/// through [`Server::metrics`]. Requests are counted by method, route and
+/// status class, and timed by method and route, the route being the uri it
+/// was bound with, such as `/users/{id}`, so that the metrics stay few no
+/// matter how many paths are requested. Requests no route matches share the
+/// `unmatched` route.
+///
+/// Clones share the same metrics, so one can be handed to the `Server` and
+/// another to the route serving them, see [`handler`].
+///
+/// # Examples:
+/// ```
+/// use martian::server::metrics::Metrics;
+/// use martian::server::{Route, Server};
+/// use martian::web::HttpMethod;
+/// let metrics = Metrics::new();
+/// let mut server = Server::default();
+/// server.metrics(metrics.clone());
+/// server
+///     .route(|| Route::bind(HttpMethod::Get).to("/metrics", metrics.handler()))
+///     .unwrap();
+/// ```
+///
+/// [`Server`]: ../struct.Server.html
+/// [`Server::metrics`]: ../struct.Server.html#method.metrics
+/// [`handler`]: ./struct.Metrics.html#method.handler
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    in_flight: AtomicUsize,
+    routes: Mutex<BTreeMap<(&'static str, String), RouteMetrics>>,
+}
+
+/// What is known of the requests of a single method and route.
+#[derive(Debug, Default)]
+struct RouteMetrics {
+    /// Indexed by status class, `1xx` first.
+    requests: [u64; 5],
+    /// How many requests took no longer than each of the [`BUCKETS`], each
+    /// counted in the first bucket it fits in only.
+    ///
+    /// [`BUCKETS`]: ./constant.BUCKETS.html
+    buckets: [u64; 11],
+    seconds: f64,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// How many requests are being handled right now.
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// How many requests of the method and route have been answered with a
+    /// status of the same class as the one given, such as any `2xx` for a
+    /// `StatusCode::Ok`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::metrics::Metrics;
+    /// use martian::web::{HttpMethod, StatusCode};
+    /// let metrics = Metrics::new();
+    /// assert_eq!(metrics.requests(&HttpMethod::Get, "/", StatusCode::Ok), 0);
+    /// ```
+    pub fn requests(&self, http_method: &HttpMethod, route: &str, status_code: StatusCode) -> u64 {
+        let routes = self.inner.routes.lock().unwrap_or_else(|e| e.into_inner());
+        routes
+            .get(&(http_method.as_str(), route.to_string()))
+            .map_or(0, |route_metrics| {
+                route_metrics.requests[status_class(status_code)]
+            })
+    }
+
+    /// Renders every metric in the Prometheus
+    /// [text exposition format](https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format).
+    pub fn render(&self) -> String {
+        let routes = self.inner.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut text = String::new();
+        text.push_str("# HELP martian_http_requests_total Requests answered, by method, route and status class.\n");
+        text.push_str("# TYPE martian_http_requests_total counter\n");
+        for ((http_method, route), route_metrics) in routes.iter() {
+            for (status_class, requests) in STATUS_CLASSES.iter().zip(&route_metrics.requests) {
+                if *requests > 0 {
+                    let _ = writeln!(
+                        text,
+                        "martian_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                        http_method,
+                        escape(route),
+                        status_class,
+                        requests
+                    );
+                }
+            }
+        }
+        text.push_str("# HELP martian_http_requests_in_flight Requests being handled.\n");
+        text.push_str("# TYPE martian_http_requests_in_flight gauge\n");
+        let _ = writeln!(text, "martian_http_requests_in_flight {}", self.in_flight());
+        text.push_str("# HELP martian_http_request_duration_seconds Time taken to answer requests, by method and route.\n");
+        text.push_str("# TYPE martian_http_request_duration_seconds histogram\n");
+        for ((http_method, route), route_metrics) in routes.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", http_method, escape(route));
+            let mut cumulative = 0;
+            for (bucket, count) in BUCKETS.iter().zip(&route_metrics.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "martian_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bucket, cumulative
+                );
+            }
+            let count = route_metrics.requests.iter().sum::<u64>();
+            let _ = writeln!(
+                text,
+                "martian_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, count
+            );
+            let _ = writeln!(
+                text,
+                "martian_http_request_duration_seconds_sum{{{}}} {}",
+                labels, route_metrics.seconds
+            );
+            let _ = writeln!(
+                text,
+                "martian_http_request_duration_seconds_count{{{}}} {}",
+                labels, count
+            );
+        }
+        text
+    }
+
+    /// A callback answering with every metric, as [`render`] has them, to be
+    /// bound to the route Prometheus scrapes, usually `/metrics`.
+    ///
+    /// [`render`]: ./struct.Metrics.html#method.render
+    pub fn handler(&self) -> impl Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static {
+        let metrics = self.clone();
+        move |_| {
+            let mut response = HttpResponse::text(&metrics.render());
+            response.headers.insert(
+                "Content-Type".into(),
+                "text/plain; version=0.0.4; charset=utf-8".into(),
+            );
+            response
+        }
+    }
+
+    /// Counts the request as in flight until it is answered.
+    pub(in crate::server) fn start(&self, http_method: &HttpMethod, route: String) -> InFlight {
+        self.inner.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight {
+            metrics: self.clone(),
+            http_method: http_method.as_str(),
+            route,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// A request still being handled, counted once answered.
+pub(in crate::server) struct InFlight {
+    metrics: Metrics,
+    http_method: &'static str,
+    route: String,
+    started: Instant,
+}
+
+impl InFlight {
+    pub(in crate::server) fn complete(self, response: &HttpResponse) {
+        let seconds = self.started.elapsed().as_secs_f64();
+        let inner = &self.metrics.inner;
+        {
+            let mut routes = inner.routes.lock().unwrap_or_else(|e| e.into_inner());
+            let route_metrics = routes.entry((self.http_method, self.route)).or_default();
+            route_metrics.requests[status_class(response.status_code)] += 1;
+            if let Some(bucket) = BUCKETS.iter().position(|bucket| seconds <= *bucket) {
+                route_metrics.buckets[bucket] += 1;
+            }
+            route_metrics.seconds += seconds;
+        }
+        inner.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Server {
+    /// The uri of the [`Route`] the request would be delegated to, under the
+    /// prefix of any `Server` it is mounted through, or `unmatched` when no
+    /// route matches. The host of a request given its own `Server` is left
+    /// out.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    pub(in crate::server) fn route_label(&self, request: &HttpRequest) -> String {
+        if let Some(server) = self.virtual_host(request) {
+            return server.route_label(request);
+        }
+        self.route_uri(request, request.uri.as_str())
+            .unwrap_or_else(|| UNMATCHED.into())
+    }
+
+    fn route_uri(&self, request: &HttpRequest, uri: &str) -> Option<String> {
+        let http_method = &request.http_method;
+        for (prefix, server) in &self.mounts {
+            if let Some(uri) = mount::strip_prefix(prefix, uri) {
+                let route_uri = server.route_uri(request, &uri)?;
+                return Some(format!("{}{}", prefix, route_uri));
+            }
+        }
+        let path = uri.split('?').next().unwrap_or_default();
+        match self.find_route(request, http_method, path) {
+            Some((route, _)) => Some(route.pattern.uri().into()),
+            None => {
+                let alternate_path = self.alternate_path(path)?;
+                let (route, _) = self.find_route(request, http_method, &alternate_path)?;
+                Some(route.pattern.uri().into())
+            }
+        }
+    }
+}
+
+/// The index of the class of the status among the [`STATUS_CLASSES`], any
+/// status outside of them counted as a server error.
+///
+/// [`STATUS_CLASSES`]: ./constant.STATUS_CLASSES.html
+fn status_class(status_code: StatusCode) -> usize {
+    match status_code.code() / 100 {
+        class @ 1..=5 => class as usize - 1,
+        _ => 4,
+    }
+}
+
+/// Escapes a label value as the exposition format asks.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests;
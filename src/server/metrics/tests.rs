@@ -0,0 +1,89 @@
+use crate::server::metrics::Metrics;
+use crate::server::{Route, Server};
+use crate::test::TestClient;
+use crate::web::{HttpMethod, HttpRequest, StatusCode};
+
+fn metered_server(metrics: &Metrics) -> Server {
+    let mut server = Server::default();
+    server.metrics(metrics.clone());
+    let in_flight = metrics.clone();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/users/{id}", |request: HttpRequest| {
+                    request.path_param("id").unwrap_or_default().to_string()
+                })
+                .to("/in-flight", move |_| in_flight.in_flight().to_string())
+                .to("/metrics", metrics.handler())
+        })
+        .unwrap();
+    server
+}
+
+#[test]
+fn should_count_requests_by_route_and_status_class_when_handled() {
+    let metrics = Metrics::new();
+    let client = TestClient::new(metered_server(&metrics));
+    client.get("/users/1");
+    client.get("/users/2?full=true");
+    client.get("/missing");
+    assert_eq!(
+        metrics.requests(&HttpMethod::Get, "/users/{id}", StatusCode::Ok),
+        2
+    );
+    assert_eq!(
+        metrics.requests(&HttpMethod::Get, "unmatched", StatusCode::NotFound),
+        1
+    );
+    assert_eq!(
+        metrics.requests(&HttpMethod::Get, "/users/{id}", StatusCode::NotFound),
+        0
+    );
+    assert_eq!(metrics.in_flight(), 0);
+}
+
+#[test]
+fn should_count_request_as_in_flight_when_being_handled() {
+    let metrics = Metrics::new();
+    let client = TestClient::new(metered_server(&metrics));
+    client.get("/in-flight").assert_body("1");
+    assert_eq!(metrics.in_flight(), 0);
+}
+
+#[test]
+fn should_render_prometheus_text_when_metrics_route_is_requested() {
+    let metrics = Metrics::new();
+    let client = TestClient::new(metered_server(&metrics));
+    client.get("/users/1");
+    client.get("/users/1");
+    let response = client.get("/metrics");
+    response.assert_header("Content-Type", "text/plain; version=0.0.4; charset=utf-8");
+    for line in &[
+        "# TYPE martian_http_requests_total counter",
+        "martian_http_requests_total{method=\"GET\",route=\"/users/{id}\",status=\"2xx\"} 2",
+        "martian_http_requests_in_flight 1",
+        "# TYPE martian_http_request_duration_seconds histogram",
+        "martian_http_request_duration_seconds_bucket{method=\"GET\",route=\"/users/{id}\",le=\"10\"} 2",
+        "martian_http_request_duration_seconds_bucket{method=\"GET\",route=\"/users/{id}\",le=\"+Inf\"} 2",
+        "martian_http_request_duration_seconds_count{method=\"GET\",route=\"/users/{id}\"} 2",
+    ] {
+        assert!(response.body_text().lines().any(|l| l == *line), "{}", line);
+    }
+    assert!(!response.body_text().contains("route=\"/metrics\",status"));
+}
+
+#[test]
+fn should_label_request_with_mount_prefix_when_mounted() {
+    let metrics = Metrics::new();
+    let mut api = Server::default();
+    api.route(|| Route::bind(HttpMethod::Get).to("/users/{id}", |_| "user"))
+        .unwrap();
+    let mut server = Server::default();
+    server.metrics(metrics.clone());
+    server.mount("/api", api);
+    TestClient::new(server).get("/api/users/3");
+    assert_eq!(
+        metrics.requests(&HttpMethod::Get, "/api/users/{id}", StatusCode::Ok),
+        1
+    );
+}
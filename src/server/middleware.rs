@@ -0,0 +1,80 @@
+//! Behaviour wrapped around the delegation of every request, such as logging,
+//! authentication or compression, without touching each callback.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::web::{HttpRequest, HttpResponse};
+
+/// Runs before and after the rest of the chain, see [`Server::wrap`] and
+/// [`Binding::with`]. It may change the request before passing it on through
+/// [`Next::run`], change the response it gets back, or answer the request
+/// itself without passing it on at all.
+///
+/// Any `Fn(HttpRequest, Next) -> HttpResponse` closure is a `Middleware`.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Middleware, Next};
+/// use martian::web::{HttpRequest, HttpResponse};
+/// struct PoweredBy;
+/// impl Middleware for PoweredBy {
+///     fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+///         let mut response = next.run(request);
+///         response.headers.insert("X-Powered-By".into(), "martian".into());
+///         response
+///     }
+/// }
+/// ```
+///
+/// [`Server::wrap`]: ./struct.Server.html#method.wrap
+/// [`Binding::with`]: ./struct.Binding.html#method.with
+/// [`Next::run`]: ./struct.Next.html#method.run
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(HttpRequest, Next) -> HttpResponse + Send + Sync,
+{
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        self(request, next)
+    }
+}
+
+impl fmt::Debug for dyn Middleware {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Middleware(..)")
+    }
+}
+
+/// The rest of the chain after the [`Middleware`] it is handed to, ending in
+/// the delegation of the request to its callback.
+///
+/// [`Middleware`]: ./trait.Middleware.html
+pub struct Next<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    endpoint: &'a dyn Fn(HttpRequest) -> HttpResponse,
+}
+
+impl<'a> Next<'a> {
+    pub(in crate::server) fn new(
+        middleware: &'a [Arc<dyn Middleware>],
+        endpoint: &'a dyn Fn(HttpRequest) -> HttpResponse,
+    ) -> Next<'a> {
+        Next {
+            middleware,
+            endpoint,
+        }
+    }
+
+    /// Passes the request on to the rest of the chain, returning the response
+    /// it is answered with.
+    pub fn run(self, request: HttpRequest) -> HttpResponse {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(request, Next::new(rest, self.endpoint)),
+            None => (self.endpoint)(request),
+        }
+    }
+}
@@ -3,11 +3,132 @@
 //! service. Built to hopefully be easy to use, but configurable if you are
 //! into pumping out the most performance you possibly can out of a thread.
 
+use std::any::Any;
 use std::clone::Clone;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::iter;
+use std::mem;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::web::{HttpMethod, HttpRequest, HttpResponse};
+use crate::web::websocket::WebSocket;
+use crate::web::{
+    Body, ConnectionInfo, HttpMethod, HttpRequest, HttpResponse, IntoResponse, MartianError, State,
+    StatusCode,
+};
 
-type Callback = fn(HttpRequest) -> HttpResponse;
+use self::access_log::PendingLog;
+use self::connection::{Connection, ReadError, UntilDeadline};
+use self::health::Health;
+use self::load_shed::{Concurrency, ConcurrencyLimit, OwnedPermit, Permit};
+use self::metrics::{InFlight, Metrics};
+use self::pattern::Pattern;
+
+pub use self::access_log::{AccessLog, LogFormat, RequestLog};
+#[cfg(feature = "async-std")]
+pub use self::async_server::AsyncStd;
+#[cfg(feature = "smol")]
+pub use self::async_server::Smol;
+#[cfg(feature = "tokio")]
+pub use self::async_server::Tokio;
+#[cfg(feature = "async")]
+pub use self::async_server::{AsyncRuntime, BoxFuture};
+#[cfg(any(feature = "gzip", feature = "deflate"))]
+pub use self::compression::{Compression, Decompression};
+pub use self::conditional::ConditionalGet;
+pub use self::config::{ConfigError, ServerConfig};
+pub use self::connection::{KeepAlive, Limits};
+pub use self::cors::Cors;
+pub use self::csrf::{Csrf, CsrfToken};
+pub use self::default_headers::DefaultHeaders;
+#[cfg(feature = "json")]
+pub use self::extract::Json;
+#[cfg(feature = "serde")]
+pub use self::extract::Query;
+pub use self::extract::{FromRequest, PathParam, TypedHandler};
+pub use self::guard::{ContentTypeIs, Guard, HeaderEquals, HeaderPresent};
+pub use self::health::Lifecycle;
+pub use self::http_server::{HttpServer, HttpServerBuilder};
+pub use self::middleware::{Middleware, Next};
+pub use self::mount::Mounted;
+pub use self::range::RangeRequests;
+pub use self::rate_limit::RateLimit;
+pub use self::shutdown::Shutdown;
+pub use self::socket::SocketOptions;
+#[cfg(feature = "tls")]
+pub use self::tls::{ClientAuth, TlsConfig, TlsHost};
+pub use self::trailing_slash::TrailingSlashRedirect;
+#[cfg(feature = "macros")]
+pub use martian_macros::{delete, get, head, options, patch, post, put, routes, trace};
+
+mod access_log;
+#[cfg(feature = "async")]
+mod async_server;
+pub mod auth;
+#[cfg(any(feature = "gzip", feature = "deflate"))]
+mod compression;
+mod conditional;
+mod config;
+mod connection;
+mod cors;
+mod csrf;
+mod default_headers;
+#[cfg(feature = "mio")]
+mod event_loop;
+mod extract;
+mod guard;
+mod health;
+#[cfg(feature = "http2")]
+mod http2;
+mod http_server;
+mod load_shed;
+#[cfg(feature = "log")]
+mod logging;
+pub mod metrics;
+mod middleware;
+mod mount;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+mod pattern;
+mod range;
+mod rate_limit;
+mod shutdown;
+mod socket;
+pub mod static_files;
+#[cfg(feature = "tls")]
+mod tls;
+mod trailing_slash;
+
+type Callback = Arc<dyn Fn(HttpRequest) -> HttpResponse + Send + Sync>;
+type FallibleCallback =
+    Arc<dyn Fn(HttpRequest) -> Result<HttpResponse, MartianError> + Send + Sync>;
+#[cfg(feature = "async")]
+type ResponseFuture = std::pin::Pin<Box<dyn std::future::Future<Output = HttpResponse> + Send>>;
+#[cfg(feature = "async")]
+type AsyncCallback = Arc<dyn Fn(HttpRequest) -> ResponseFuture + Send + Sync>;
+/// In the order they are listed in an `Allow` header.
+const HTTP_METHODS: [HttpMethod; 9] = [
+    HttpMethod::Get,
+    HttpMethod::Head,
+    HttpMethod::Post,
+    HttpMethod::Put,
+    HttpMethod::Patch,
+    HttpMethod::Delete,
+    HttpMethod::Options,
+    HttpMethod::Trace,
+    HttpMethod::Connect,
+];
+
+type PanicHook = fn(&HttpRequest, &dyn Any);
+type CompletionHook = Box<dyn Fn(&RequestLog) + Send + Sync>;
+type ErrorHandler = Box<dyn Fn(MartianError) -> HttpResponse + Send + Sync>;
 
 /// `Server` is the primary layer of communication being used to delegate work
 /// to the correct handlers. The `Server` is the first to see a [`HttpRequest`] and
@@ -18,42 +139,1366 @@ type Callback = fn(HttpRequest) -> HttpResponse;
 #[derive(Default)]
 pub struct Server {
     routes: Vec<Route>,
+    trailing_slash: TrailingSlash,
+    path_normalization: PathNormalization,
+    panic_hook: Option<PanicHook>,
+    completion_hook: Option<CompletionHook>,
+    workers: usize,
+    state: State,
+    not_found: Option<Handler>,
+    keep_alive: KeepAlive,
+    timeouts: Timeouts,
+    limits: Limits,
+    error_handler: Option<ErrorHandler>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    mounts: Vec<(String, Server)>,
+    vhosts: Vec<(String, Server)>,
+    io_model: IoModel,
+    socket_options: SocketOptions,
+    metrics: Option<Metrics>,
+    health: Arc<Health>,
+    concurrency: Concurrency,
+    /// The handlers running on threads of their own, see `Timeouts::handler`.
+    timed_handlers: Arc<Concurrency>,
+    default_headers: DefaultHeaders,
+}
+
+/// How long the [`Server`] waits on a client and on a handler.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Server, Timeouts};
+/// use std::time::Duration;
+/// let mut server = Server::default();
+/// server.timeouts(Timeouts {
+///     handler: Some(Duration::from_secs(10)),
+///     ..Timeouts::default()
+/// });
+/// ```
+///
+/// [`Server`]: ./struct.Server.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// How long the client may take to send a request in whole, head and
+    /// body, 30 seconds by default. The first request on a connection is
+    /// timed from when the connection is accepted, any following it from
+    /// when they start. A request not in by then is answered with a 408 and
+    /// its connection closed.
+    pub read: Option<Duration>,
+    /// How long a handler may take before the request is answered with a
+    /// 503 in its place, no limit by default. The handler is not stopped,
+    /// but carries on in the background with its response thrown away, so
+    /// it is best not left to run forever regardless.
+    pub handler: Option<Duration>,
+    /// How many handlers timed by `handler` may be running at once, those
+    /// given up on included, 32 by default. Each runs on a thread of its
+    /// own, so any request past it is answered with a 503 right away rather
+    /// than leaving handlers which never finish to pile up threads.
+    pub max_handler_threads: usize,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            read: Some(Duration::from_secs(30)),
+            handler: None,
+            max_handler_threads: 32,
+        }
+    }
+}
+
+/// How the [`Server`] treats a request path differing from a bound uri only
+/// by a trailing slash, such as `/hello/` and `/hello`. The root `/` is never
+/// affected.
+///
+/// [`Server`]: ./struct.Server.html
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum TrailingSlash {
+    /// `/hello` and `/hello/` are different routes entirely.
+    #[default]
+    Strict,
+    /// A request to `/hello/` is answered with a 301 to `/hello` when only
+    /// `/hello` is bound, or with a 308 when it is neither a `GET` nor a
+    /// `HEAD`, for it to be repeated as it was. The slashless form is always
+    /// the canonical one, see [`TrailingSlashRedirect`] for the other.
+    ///
+    /// [`TrailingSlashRedirect`]: ./struct.TrailingSlashRedirect.html
+    RedirectToCanonical,
+    /// `/hello` and `/hello/` are the same route, whichever of the two it was
+    /// bound with. Binding both is a conflict.
+    Merge,
+}
+
+/// How the [`Server`] normalizes a request path before matching it against
+/// any [`Route`], the request being handed on with the normalized path, as
+/// middleware and callbacks alike see it. Nothing is normalized by default.
+/// Whether `/hello` and `/hello/` are the same is up to the
+/// [`TrailingSlash`] policy.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{PathNormalization, Server, TrailingSlash};
+/// let mut server = Server::default();
+/// // `/docs//guide/./intro/` is matched the same as `/docs/guide/intro`.
+/// server.normalize_paths(PathNormalization {
+///     merge_slashes: true,
+///     resolve_dot_segments: true,
+/// });
+/// server.trailing_slash(TrailingSlash::Merge);
+/// ```
+///
+/// [`Server`]: ./struct.Server.html
+/// [`Route`]: ./struct.Route.html
+/// [`TrailingSlash`]: ./enum.TrailingSlash.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct PathNormalization {
+    /// `/a//b` is matched as `/a/b`.
+    pub merge_slashes: bool,
+    /// `/a/./b/../c` is matched as `/a/c`, and so is `/a/%2E/b/%2E%2E/c`. A
+    /// `..` never steps above the root.
+    pub resolve_dot_segments: bool,
+}
+
+/// How the [`Server`] waits on connections kept alive between requests.
+///
+/// [`Server`]: ./struct.Server.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum IoModel {
+    /// Every connection holds on to a worker for as long as it is open,
+    /// whether a request is coming in on it or not.
+    #[default]
+    ThreadPerConnection,
+    /// Connections idle between requests are all watched by a single thread
+    /// through [mio](https://github.com/tokio-rs/mio), only holding on to a
+    /// worker while a request is served. Far more connections can be kept
+    /// alive than there are workers, and even a `Server` without any keeps
+    /// them alive, serving on a single worker.
+    #[cfg(feature = "mio")]
+    EventLoop,
 }
 
 impl Server {
+    /// A `Server` handing accepted connections off to `workers` threads,
+    /// so that as many requests are handled in parallel. Connections waiting
+    /// on a free worker are queued, at most one per worker, after which
+    /// accepting holds off until the queue has room again.
+    ///
+    /// A `Server` with no workers, as is the default, handles every
+    /// connection on the thread accepting them.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Server;
+    /// let server = Server::with_workers(4);
+    /// ```
+    pub fn with_workers(workers: usize) -> Server {
+        Server {
+            workers,
+            ..Server::default()
+        }
+    }
+
     /// Setups up a [`Route`] based off a function or closure passed in. The
-    /// [`Route`] bound will be the return of the closure.
+    /// [`Route`] bound will be the return of the closure, which may capture
+    /// whatever its callbacks need.
     ///
     /// # Examples:
     /// ```
     /// use martian::server::{Server, Route};
-    /// use martian::web::{HttpMethod, HttpResponse, StatusCode};
+    /// use martian::web::{Body, HttpMethod, HttpResponse, HttpVersion, StatusCode};
+    /// use std::collections::HashMap;
     /// let mut server = Server::default();
     /// server.route(|| Route::bind(HttpMethod::Get).to("/", |_|
     ///     HttpResponse {
-    ///         http_version: 1.1,
+    ///         http_version: HttpVersion::Http1_1,
     ///         status_code: StatusCode::Ok,
+    ///         headers: HashMap::new(),
+    ///         cookies: Vec::new(),
+    ///         body: Body::Empty,
     ///     }
-    /// ));
+    /// )).unwrap();
     /// ```
     ///
+    /// # Returns:
+    /// A [`RouteConflict`] if a [`Route`] with the same [`HttpMethod`] is
+    /// already bound to a uri matching the exact same paths, such as two
+    /// catch-alls under the same prefix. None of the routes of the binding are
+    /// bound when that happens.
+    ///
     /// [`Route`]: ./struct.Route.html
-    pub fn route(&mut self, binding_fn: fn() -> Binding) {
-        binding_fn().routes.iter().for_each(|route| {
-            if self.routes.iter().find(|r| *r == route).is_some() {
-                panic!("Callback already bound with: {:?}", route);
+    /// [`HttpMethod`]: ../web/enum.HttpMethod.html
+    /// [`RouteConflict`]: ./struct.RouteConflict.html
+    pub fn route<F>(&mut self, binding_fn: F) -> Result<(), RouteConflict>
+    where
+        F: FnOnce() -> Binding,
+    {
+        let routes = binding_fn().routes;
+        for (i, route) in routes.iter().enumerate() {
+            let conflict = self
+                .routes
+                .iter()
+                .chain(&routes[..i])
+                .find(|r| r.conflicts_with(route, self.trailing_slash));
+            if let Some(existing) = conflict {
+                return Err(RouteConflict {
+                    http_method: route.http_method.clone(),
+                    existing_uri: existing.pattern.uri().into(),
+                    new_uri: route.pattern.uri().into(),
+                });
             }
-            self.routes.push(route.clone());
-        });
+        }
+        self.routes.extend(routes);
+        Ok(())
     }
 
-    pub(in crate::server) fn delegate(&self, request: HttpRequest) -> Option<HttpResponse> {
+    /// Same as [`route`], for when a conflict can only be a programming
+    /// error.
+    ///
+    /// # Panics:
+    /// On any [`RouteConflict`].
+    ///
+    /// [`route`]: ./struct.Server.html#method.route
+    /// [`RouteConflict`]: ./struct.RouteConflict.html
+    pub fn route_or_panic<F>(&mut self, binding_fn: F)
+    where
+        F: FnOnce() -> Binding,
+    {
+        if let Err(conflict) = self.route(binding_fn) {
+            panic!("{}", conflict);
+        }
+    }
+
+    /// Sets how a trailing slash on a request path is treated, see
+    /// [`TrailingSlash`]. This is best done before binding any routes, as
+    /// conflicts are only checked for as they are bound.
+    ///
+    /// [`TrailingSlash`]: ./enum.TrailingSlash.html
+    pub fn trailing_slash(&mut self, trailing_slash: TrailingSlash) {
+        self.trailing_slash = trailing_slash;
+    }
+
+    /// Sets how a request path is normalized before it is matched, see
+    /// [`PathNormalization`].
+    ///
+    /// [`PathNormalization`]: ./struct.PathNormalization.html
+    pub fn normalize_paths(&mut self, path_normalization: PathNormalization) {
+        self.path_normalization = path_normalization;
+    }
+
+    /// Registers state shared by every callback, handed to them through
+    /// [`HttpRequest::state`]. Only one value of each type is kept, managing
+    /// another of the same type replaces it.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Route, Server};
+    /// use martian::web::{HttpMethod, HttpResponse};
+    /// struct Config {
+    ///     greeting: &'static str,
+    /// }
+    /// let mut server = Server::default();
+    /// server.manage(Config { greeting: "howdy" });
+    /// server
+    ///     .route(|| {
+    ///         Route::bind(HttpMethod::Get).to("/", |request| {
+    ///             let config = request.state.get::<Config>().unwrap();
+    ///             HttpResponse::text(config.greeting)
+    ///         })
+    ///     })
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`HttpRequest::state`]: ../web/struct.HttpRequest.html#structfield.state
+    pub fn manage<T: Send + Sync + 'static>(&mut self, state: T) {
+        self.state.insert(state);
+    }
+
+    /// Sets how long and for how many requests a connection is kept open,
+    /// in place of the defaults of [`KeepAlive`].
+    ///
+    /// [`KeepAlive`]: ./struct.KeepAlive.html
+    pub fn keep_alive(&mut self, keep_alive: KeepAlive) {
+        self.keep_alive = keep_alive;
+    }
+
+    /// Sets the headers stamped on every response written out, in place of
+    /// the defaults of [`DefaultHeaders`].
+    ///
+    /// [`DefaultHeaders`]: ./struct.DefaultHeaders.html
+    pub fn default_headers(&mut self, default_headers: DefaultHeaders) {
+        self.default_headers = default_headers;
+    }
+
+    /// Sets how long to wait on clients and handlers, in place of the
+    /// defaults of [`Timeouts`].
+    ///
+    /// [`Timeouts`]: ./struct.Timeouts.html
+    pub fn timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Sets how connections are waited on between requests, see [`IoModel`].
+    ///
+    /// # Examples:
+    /// ```
+    /// # #[cfg(feature = "mio")]
+    /// # {
+    /// use martian::server::{IoModel, Server};
+    /// let mut server = Server::with_workers(4);
+    /// server.io_model(IoModel::EventLoop);
+    /// # }
+    /// ```
+    ///
+    /// [`IoModel`]: ./enum.IoModel.html
+    pub fn io_model(&mut self, io_model: IoModel) {
+        self.io_model = io_model;
+    }
+
+    /// Sets up the sockets listened on and those of the connections accepted
+    /// through them, in place of the defaults of [`SocketOptions`]. Only
+    /// listeners bound after it are affected.
+    ///
+    /// [`SocketOptions`]: ./struct.SocketOptions.html
+    pub fn socket_options(&mut self, socket_options: SocketOptions) {
+        self.socket_options = socket_options;
+    }
+
+    /// Counts every request handled in the [`Metrics`], see there. The
+    /// `Server` keeps a clone, so the metrics are still to be served through
+    /// a route of their own. Only those of the outermost `Server` count the
+    /// requests of any [`mount`]ed under it.
+    ///
+    /// [`Metrics`]: ./metrics/struct.Metrics.html
+    /// [`mount`]: ./struct.Server.html#method.mount
+    pub fn metrics(&mut self, metrics: Metrics) {
+        self.metrics = Some(metrics);
+    }
+
+    /// Sets the most of a request read in from a client, in place of the
+    /// defaults of [`Limits`].
+    ///
+    /// [`Limits`]: ./struct.Limits.html
+    pub fn limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Hands every request under `prefix` over to `server`, with the prefix
+    /// stripped off of its uri, so `/api/v1/users` is routed as `/users`. A
+    /// mounted `Server` answers these requests entirely by itself, using its
+    /// own middleware, state and not found handler, while those of this
+    /// `Server` wrap around it.
+    ///
+    /// The prefix only matches whole segments, `/api` is not a prefix of
+    /// `/apiary`. Mounts are checked in the order they were made, before any
+    /// [`Route`] of this `Server`. What was stripped off is kept on the request
+    /// as its [`Mounted`], and put back on any redirect `server` sends it.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Route, Server};
+    /// use martian::web::{HttpMethod, HttpResponse};
+    /// let mut api = Server::default();
+    /// api.route(|| Route::bind(HttpMethod::Get).to("/users", |_| HttpResponse::text("[]")))
+    ///     .unwrap();
+    /// let mut server = Server::default();
+    /// server.mount("/api/v1", api);
+    /// ```
+    ///
+    /// [`Route`]: ./struct.Route.html
+    /// [`Mounted`]: ./struct.Mounted.html
+    pub fn mount(&mut self, prefix: &str, server: Server) {
+        self.mounts
+            .push((prefix.trim_end_matches('/').into(), server));
+    }
+
+    /// Hands every request for the host over to `server`, which answers it
+    /// entirely by itself, as it would if [`mount`]ed. Any other request is
+    /// answered by this `Server`, as the default for hosts without one of
+    /// their own. Hosts are checked before any mount, in the order they were
+    /// given.
+    ///
+    /// The host of a request is that of its `Host` header, or of its uri when
+    /// in absolute form, ignoring case and any port. Without either, as may
+    /// be the case for HTTP/1.0, the hostname asked for through SNI during
+    /// a TLS handshake is used instead.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Route, Server};
+    /// use martian::web::{HttpMethod, HttpResponse};
+    /// let mut api = Server::default();
+    /// api.route(|| Route::bind(HttpMethod::Get).to("/", |_| HttpResponse::text("api")))
+    ///     .unwrap();
+    /// let mut server = Server::default();
+    /// server.route(|| Route::bind(HttpMethod::Get).to("/", |_| HttpResponse::text("www")))
+    ///     .unwrap();
+    /// server.vhost("api.example.com", api);
+    /// ```
+    ///
+    /// [`mount`]: ./struct.Server.html#method.mount
+    pub fn vhost(&mut self, host: &str, server: Server) {
+        self.vhosts
+            .push((host.trim_end_matches('.').into(), server));
+    }
+
+    /// The `Server` given the host of the request through [`vhost`], if any.
+    ///
+    /// [`vhost`]: ./struct.Server.html#method.vhost
+    pub(in crate::server) fn virtual_host(&self, request: &HttpRequest) -> Option<&Server> {
+        if self.vhosts.is_empty() {
+            return None;
+        }
+        let host = match request.host() {
+            Some(host) => strip_port(host),
+            None => request.connection.as_ref()?.server_name.as_deref()?,
+        };
+        let host = host.trim_end_matches('.');
+        self.vhosts
+            .iter()
+            .find(|(vhost, _)| vhost.eq_ignore_ascii_case(host))
+            .map(|(_, server)| server)
+    }
+
+    /// The path to the route named through [`Binding::to_named`], its
+    /// `{name}` and catch-all segments filled with the params given under
+    /// their names, each percent-encoded. Any other params are left out. A
+    /// route of a `Server` [`mount`]ed under this one is found too, under
+    /// the prefix it is mounted with, though those of this `Server` are
+    /// searched first.
+    ///
+    /// # Returns:
+    /// `None` if no route has the name, or a param of its uri is missing or
+    /// empty, or its uri has a `*` short of its final segment.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Route, Server};
+    /// use martian::web::{HttpMethod, HttpResponse};
+    /// let mut server = Server::default();
+    /// server
+    ///     .route(|| {
+    ///         Route::bind(HttpMethod::Get)
+    ///             .to_named("user_detail", "/users/{id}", |_| HttpResponse::ok())
+    ///     })
+    ///     .unwrap();
+    /// let url = server.url_for("user_detail", &[("id", "42")]);
+    /// assert_eq!(url.as_deref(), Some("/users/42"));
+    /// assert_eq!(server.url_for("user_detail", &[]), None);
+    /// ```
+    ///
+    /// [`Binding::to_named`]: ./struct.Binding.html#method.to_named
+    /// [`mount`]: ./struct.Server.html#method.mount
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Option<String> {
         let route = self
             .routes
             .iter()
-            .find(|route| route.http_method == request.http_method && route.uri == request.uri);
-        Some((route?.callback)(request))
+            .find(|route| route.name.as_deref() == Some(name));
+        if let Some(route) = route {
+            return route.pattern.build(params);
+        }
+        self.mounts.iter().find_map(|(prefix, server)| {
+            let path = server.url_for(name, params)?;
+            Some(under_prefix(prefix, &path))
+        })
+    }
+
+    /// Every [`Route`] bound, in the order it was bound, as its method, its
+    /// uri and its name if it was given one through [`Binding::to_named`].
+    /// Those of a `Server` [`mount`]ed under this one follow, their uris
+    /// under the prefix it is mounted with.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Route, Server};
+    /// use martian::web::{HttpMethod, HttpResponse};
+    /// let mut server = Server::default();
+    /// server
+    ///     .route(|| {
+    ///         Route::bind(HttpMethod::Get)
+    ///             .to("/", |_| HttpResponse::ok())
+    ///             .to_named("user_detail", "/users/{id}", |_| HttpResponse::ok())
+    ///     })
+    ///     .unwrap();
+    /// for (http_method, uri, name) in server.routes() {
+    ///     println!("{} {} {}", http_method.as_str(), uri, name.unwrap_or_default());
+    /// }
+    /// assert_eq!(server.routes().nth(1).unwrap().1, "/users/{id}");
+    /// ```
+    ///
+    /// [`Route`]: ./struct.Route.html
+    /// [`Binding::to_named`]: ./struct.Binding.html#method.to_named
+    /// [`mount`]: ./struct.Server.html#method.mount
+    pub fn routes(&self) -> impl Iterator<Item = (&HttpMethod, String, Option<&str>)> {
+        let own = self.routes.iter().map(|route| {
+            (
+                &route.http_method,
+                route.pattern.uri().to_string(),
+                route.name.as_deref(),
+            )
+        });
+        let mounted = self.mounts.iter().flat_map(|(prefix, server)| {
+            server.routes().map(move |(http_method, uri, name)| {
+                (http_method, under_prefix(prefix, &uri), name)
+            })
+        });
+        own.chain(mounted).collect::<Vec<_>>().into_iter()
+    }
+
+    /// Wraps every request in the [`Middleware`], including those no
+    /// [`Route`] matches. The first registered is the first to see the
+    /// request and the last to see the response.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Next, Server};
+    /// use martian::web::{HttpRequest, HttpResponse};
+    /// let mut server = Server::default();
+    /// server.wrap(|request: HttpRequest, next: Next| {
+    ///     match request.uri.path().starts_with("/admin") {
+    ///         true => HttpResponse::not_found(),
+    ///         false => next.run(request),
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// [`Middleware`]: ./trait.Middleware.html
+    /// [`Route`]: ./struct.Route.html
+    pub fn wrap<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// Sets the callback answering any request whose uri no [`Route`] is
+    /// bound to, in place of the empty 404 it is otherwise answered with. A
+    /// uri bound with other methods only is answered with a 405 instead.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Server;
+    /// use martian::web::{HttpResponse, StatusCode};
+    /// let mut server = Server::default();
+    /// server.not_found(|request| {
+    ///     let mut response = HttpResponse::html(&format!("<h1>No {} here</h1>", request.uri));
+    ///     response.status_code = StatusCode::NotFound;
+    ///     response
+    /// });
+    /// ```
+    ///
+    /// [`Route`]: ./struct.Route.html
+    pub fn not_found<F, R>(&mut self, handler: F)
+    where
+        F: Fn(HttpRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.not_found = Some(Handler::Callback(Arc::new(move |request| {
+            handler(request).into_response()
+        })));
+    }
+
+    /// Sets the callback answering the [`MartianError`] of a handler, in
+    /// place of the default described there. It also sees every
+    /// [`ParamError`], as a 400, such as that of a [`FromRequest`] argument.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Server;
+    /// use martian::web::HttpResponse;
+    /// let mut server = Server::default();
+    /// server.on_error(|error| {
+    ///     let mut response = HttpResponse::html(&format!("<p>{}</p>", error.message));
+    ///     response.status_code = error.status_code;
+    ///     response
+    /// });
+    /// ```
+    ///
+    /// [`MartianError`]: ../web/struct.MartianError.html
+    /// [`ParamError`]: ../web/enum.ParamError.html
+    /// [`FromRequest`]: ./trait.FromRequest.html
+    pub fn on_error<F, R>(&mut self, handler: F)
+    where
+        F: Fn(MartianError) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.error_handler = Some(Box::new(move |error| handler(error).into_response()));
+    }
+
+    /// Registers a hook invoked with the request and the panic payload
+    /// whenever a callback or [`Middleware`] panics, before the 500 replacing
+    /// its response is sent. Useful for logging what went wrong. The 500
+    /// itself is answered through the error handler, see [`on_error`].
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Server;
+    /// let mut server = Server::default();
+    /// server.on_panic(|request, payload| {
+    ///     let message = payload.downcast_ref::<&str>().unwrap_or(&"unknown");
+    ///     eprintln!("{} panicked: {}", request.uri, message);
+    /// });
+    /// ```
+    ///
+    /// [`Middleware`]: ./trait.Middleware.html
+    /// [`on_error`]: ./struct.Server.html#method.on_error
+    pub fn on_panic(&mut self, hook: PanicHook) {
+        self.panic_hook = Some(hook);
+    }
+
+    /// Registers a hook invoked with a [`RequestLog`] after every request the
+    /// `Server` has handled, including those answered with a 404 or with a 500
+    /// after a panic. This is the place to write an access log from.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Server;
+    /// let mut server = Server::default();
+    /// server.on_request_complete(|request_log| {
+    ///     eprintln!("{} took {:?}", request_log.path, request_log.elapsed);
+    /// });
+    /// ```
+    ///
+    /// [`RequestLog`]: ./struct.RequestLog.html
+    pub fn on_request_complete<F>(&mut self, hook: F)
+    where
+        F: Fn(&RequestLog) + Send + Sync + 'static,
+    {
+        self.completion_hook = Some(Box::new(hook));
+    }
+
+    /// Prints every request handled to stdout in the Common Log Format, see
+    /// [`RequestLog::common_log_format`].
+    ///
+    /// [`RequestLog::common_log_format`]: ./struct.RequestLog.html#method.common_log_format
+    pub fn log_to_stdout(&mut self) {
+        self.on_request_complete(|request_log| println!("{}", request_log.common_log_format()));
+    }
+
+    /// Delegates the request, answering it through the not found handler
+    /// when no [`Route`] matches, and reports it to the completion hook.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    pub(crate) fn handle(&self, request: HttpRequest) -> HttpResponse {
+        let tracked = self.track(&request);
+        let response = match self.enter() {
+            Some(_permit) => self.respond(request),
+            None => load_shed::overloaded(),
+        };
+        self.report(tracked, &response);
+        response
+    }
+
+    /// Counts the request as in flight, as long as [`Limits::max_in_flight`]
+    /// allows for another.
+    ///
+    /// [`Limits::max_in_flight`]: ./struct.Limits.html#structfield.max_in_flight
+    pub(in crate::server) fn enter(&self) -> Option<Permit<'_>> {
+        let max_in_flight = self.limits.max_in_flight.unwrap_or(usize::MAX);
+        self.concurrency.enter(max_in_flight)
+    }
+
+    /// Starts keeping track of the request, as far as anything is to see it
+    /// once it is answered.
+    pub(in crate::server) fn track(&self, request: &HttpRequest) -> Tracked {
+        #[cfg(feature = "log")]
+        let is_logged = logging::is_completion_logged();
+        #[cfg(not(feature = "log"))]
+        let is_logged = false;
+        Tracked {
+            pending_log: match self.completion_hook.is_some() || is_logged {
+                true => Some(PendingLog::start(request)),
+                false => None,
+            },
+            in_flight: self
+                .metrics
+                .as_ref()
+                .map(|metrics| metrics.start(&request.http_method, self.route_label(request))),
+        }
+    }
+
+    /// Reports the request, once answered, to the completion hook, the
+    /// [`Metrics`] and, with the `log` feature, to the logger.
+    ///
+    /// [`Metrics`]: ./metrics/struct.Metrics.html
+    pub(in crate::server) fn report(&self, tracked: Tracked, response: &HttpResponse) {
+        if let Some(in_flight) = tracked.in_flight {
+            in_flight.complete(response);
+        }
+        let request_log = match tracked.pending_log {
+            Some(pending_log) => pending_log.complete(response),
+            None => return,
+        };
+        #[cfg(feature = "log")]
+        logging::completed(&request_log);
+        if let Some(hook) = &self.completion_hook {
+            hook(&request_log);
+        }
+    }
+
+    /// Passes the request through the middleware, in the order it was
+    /// registered, before resolving it.
+    fn respond(&self, mut request: HttpRequest) -> HttpResponse {
+        self.normalize(&mut request);
+        let hook_request = self.panic_hook.map(|hook| (hook, request.clone()));
+        let middleware = AssertUnwindSafe(|| {
+            Next::new(&self.middleware, &|request| self.resolve(request)).run(request)
+        });
+        panic::catch_unwind(middleware)
+            .unwrap_or_else(|payload| self.recover(hook_request, payload))
+    }
+
+    /// Rewrites the path of the request following the [`PathNormalization`],
+    /// keeping its query and fragment.
+    ///
+    /// [`PathNormalization`]: ./struct.PathNormalization.html
+    pub(in crate::server) fn normalize(&self, request: &mut HttpRequest) {
+        let path = request.uri.path();
+        if let Some(normalized) = pattern::normalize(path, self.path_normalization) {
+            let rest = &request.uri.as_str()[path.len()..];
+            request.uri = format!("{}{}", normalized, rest).into();
+        }
+    }
+
+    /// Hands the request to the `Server` of its host or mounted under its
+    /// prefix, if any, or delegates it. One no [`Route`] matches is answered
+    /// with a 405 when the uri is bound with other methods, or a 204 when it
+    /// is an `OPTIONS` request, both listing the methods in an `Allow`
+    /// header. Any other is answered through the not found handler.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    fn resolve(&self, mut request: HttpRequest) -> HttpResponse {
+        if let Some(server) = self.virtual_host(&request) {
+            return server.respond(request);
+        }
+        for (prefix, server) in &self.mounts {
+            if let Some(uri) = mount::strip_prefix(prefix, request.uri.as_str()) {
+                mount::hand_over(&mut request, prefix, uri);
+                return server.respond(request);
+            }
+        }
+        let mut request = match self.dispatch(request) {
+            Ok(response) => return response,
+            Err(request) => request,
+        };
+        #[cfg(feature = "log")]
+        logging::unmatched(&request);
+        let allowed_methods = self.allowed_methods(&request);
+        if !allowed_methods.is_empty() {
+            let mut response = HttpResponse::new(match request.http_method {
+                HttpMethod::Options => StatusCode::NoContent,
+                _ => StatusCode::MethodNotAllowed,
+            });
+            response
+                .headers
+                .insert("Allow".into(), allowed_methods.join(", "));
+            return response;
+        }
+        match &self.not_found {
+            Some(handler) => {
+                request.state = self.state.clone();
+                self.invoke(handler, *request)
+            }
+            None => HttpResponse::not_found(),
+        }
+    }
+
+    /// Every method a [`Route`] is bound to the path with, as listed in an
+    /// `Allow` header. `OPTIONS` is always allowed for a bound path, being
+    /// answered by the `Server` itself when not bound.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    fn allowed_methods(&self, request: &HttpRequest) -> Vec<&'static str> {
+        let path = request.uri.path();
+        let is_bound =
+            |http_method: &&HttpMethod| self.find_route(request, http_method, path).is_some();
+        if !HTTP_METHODS
+            .iter()
+            .any(|http_method| is_bound(&http_method))
+        {
+            return Vec::new();
+        }
+        HTTP_METHODS
+            .iter()
+            .filter(|http_method| **http_method == HttpMethod::Options || is_bound(http_method))
+            .map(HttpMethod::as_str)
+            .collect()
+    }
+
+    /// Finds the [`Route`] best matching the request and invokes it. Of the
+    /// routes bound to its method whose uri matches its path, the one picked
+    /// is decided by, in turn:
+    ///
+    /// 1. The highest [`priority`], 0 unless given.
+    /// 2. The most specific uri, compared segment by segment from the start.
+    ///    A literal segment is preferred over a `{name}`, which is preferred
+    ///    over a `*`, which is preferred over a catch-all. So `/files/readme`
+    ///    always wins over `/files/{name}`, which wins over `/files/*`,
+    ///    regardless of the order they were bound in.
+    /// 3. Having [`guard`]s, which are then tried first.
+    /// 4. Having been bound first.
+    ///
+    /// A route whose guards turn the request away is skipped for the next
+    /// one in that order.
+    ///
+    /// # Returns:
+    /// The response of the [`Route`], or the request handed back when none
+    /// matches.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    /// [`priority`]: ./struct.Binding.html#method.priority
+    /// [`guard`]: ./struct.Binding.html#method.guard
+    pub(in crate::server) fn dispatch(
+        &self,
+        mut request: HttpRequest,
+    ) -> Result<HttpResponse, Box<HttpRequest>> {
+        let path = request.uri.path();
+        let found = match self.find_route(&request, &request.http_method, path) {
+            Some(found) => Some(found),
+            None => match self.alternate_path(path) {
+                Some(alternate_path) => {
+                    match self.find_route(&request, &request.http_method, &alternate_path) {
+                        Some(_) if self.trailing_slash == TrailingSlash::RedirectToCanonical => {
+                            let location = mount::location(&request, &alternate_path);
+                            let location = match request.uri.query() {
+                                Some(query) => format!("{}?{}", location, query),
+                                None => location,
+                            };
+                            return Ok(match request.http_method {
+                                HttpMethod::Get | HttpMethod::Head => {
+                                    HttpResponse::redirect_permanent(&location)
+                                }
+                                _ => HttpResponse::permanent_redirect(&location),
+                            });
+                        }
+                        found => found,
+                    }
+                }
+                None => None,
+            },
+        };
+        let (route, path_params) = match found {
+            Some(found) => found,
+            None => return Err(Box::new(request)),
+        };
+        #[cfg(feature = "log")]
+        logging::routed(&request, route.pattern.uri());
+        request.path_params = path_params;
+        request.state = self.state.clone();
+        let endpoint = |request| self.invoke(&route.handler, request);
+        Ok(Next::new(&route.middleware, &endpoint).run(request))
+    }
+
+    /// The path to try when the request path itself matches no [`Route`], as
+    /// decided by the [`TrailingSlash`] policy.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    /// [`TrailingSlash`]: ./enum.TrailingSlash.html
+    fn alternate_path(&self, path: &str) -> Option<String> {
+        match (self.trailing_slash, path.strip_suffix('/')) {
+            (TrailingSlash::Strict, _) | (_, Some("")) => None,
+            (_, Some(slashless)) => Some(slashless.to_string()),
+            (TrailingSlash::Merge, None) => Some(format!("{}/", path)),
+            (TrailingSlash::RedirectToCanonical, None) => None,
+        }
+    }
+
+    /// A callback which panics is answered with a 500 rather than unwinding
+    /// any further, leaving the `Server` free to carry on with other requests.
+    /// One which fails is answered through the error handler.
+    fn invoke(&self, handler: &Handler, request: HttpRequest) -> HttpResponse {
+        // The request is only kept around when there is a hook to hand it to.
+        let hook_request = self.panic_hook.map(|hook| (hook, request.clone()));
+        let outcome = match self.timeouts.handler {
+            // Nothing borrowed is observed after a panic, the request has
+            // moved into the callback and the `Server` is never mutated by one.
+            None => panic::catch_unwind(AssertUnwindSafe(|| handler.invoke(request))),
+            Some(timeout) => {
+                let max_threads = self.timeouts.max_handler_threads;
+                let permit = match self.timed_handlers.enter_owned(max_threads) {
+                    Some(permit) => permit,
+                    None => {
+                        return self.fail(MartianError::new(
+                            StatusCode::ServiceUnavailable,
+                            "Too many handlers running",
+                        ))
+                    }
+                };
+                match invoke_within(handler, request, timeout, permit) {
+                    Some(outcome) => outcome,
+                    None => {
+                        return self.fail(MartianError::new(
+                            StatusCode::ServiceUnavailable,
+                            "Handler timed out",
+                        ))
+                    }
+                }
+            }
+        };
+        match outcome {
+            Ok(Ok(response)) => response,
+            Ok(Err(error)) => self.fail(error),
+            Err(payload) => self.recover(hook_request, payload),
+        }
+    }
+
+    /// Reports a panic to the panic hook, answering it as a 500 through the
+    /// error handler, its message being that of the panic.
+    fn recover(
+        &self,
+        hook_request: Option<(PanicHook, HttpRequest)>,
+        payload: Box<dyn Any + Send>,
+    ) -> HttpResponse {
+        if let Some((hook, request)) = hook_request {
+            hook(&request, &*payload);
+        }
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message,
+            None => payload
+                .downcast_ref::<String>()
+                .map_or("Handler panicked", String::as_str),
+        };
+        #[cfg(feature = "log")]
+        logging::panicked(message);
+        self.answer(MartianError::new(StatusCode::InternalServerError, message))
+    }
+
+    fn fail(&self, error: MartianError) -> HttpResponse {
+        #[cfg(feature = "log")]
+        logging::failed(&error);
+        self.answer(error)
+    }
+
+    /// Answers the error through the error handler, if there is one.
+    fn answer(&self, error: MartianError) -> HttpResponse {
+        match &self.error_handler {
+            Some(error_handler) => error_handler(error),
+            None => error.into(),
+        }
+    }
+
+    /// Finds the best matching [`Route`] for the method and path whose guards
+    /// let the request through, in the order described by [`dispatch`]. A
+    /// `HEAD` request falls back on the `GET` route when no `HEAD` route is
+    /// bound, its body being dropped once written.
+    ///
+    /// [`Route`]: ./struct.Route.html
+    /// [`dispatch`]: ./struct.Server.html#method.dispatch
+    fn find_route(
+        &self,
+        request: &HttpRequest,
+        http_method: &HttpMethod,
+        path: &str,
+    ) -> Option<(&Route, HashMap<String, String>)> {
+        let mut candidates = self
+            .routes
+            .iter()
+            .filter(|route| route.http_method == *http_method)
+            .filter_map(|route| Some((route, route.pattern.matches(path)?)))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(route, _)| {
+            (
+                Reverse(route.priority),
+                route.pattern.rank(),
+                route.guards.is_empty(),
+            )
+        });
+        let found = candidates
+            .into_iter()
+            .find(|(route, _)| route.admits(request));
+        match (found, http_method) {
+            (None, HttpMethod::Head) => self.find_route(request, &HttpMethod::Get, path),
+            (found, _) => found,
+        }
+    }
+
+    /// Binds to the given address and serves each connection accepted in
+    /// turn, writing back the [`HttpResponse`] of whichever [`Route`] the
+    /// request was delegated to. This blocks for as long as the listener is
+    /// open. The listener is bound with the [`SocketOptions`] of the
+    /// `Server`.
+    ///
+    /// [`HttpResponse`]: ../web/struct.HttpResponse.html
+    /// [`Route`]: ./struct.Route.html
+    /// [`SocketOptions`]: ./struct.SocketOptions.html
+    pub fn listen<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        let listener = self.socket_options.bind(addr)?;
+        self.accept(listener.incoming().flatten(), Ok)
+    }
+
+    /// Same as [`listen`], but returns once the [`Shutdown`] is triggered,
+    /// after serving every connection accepted before then.
+    ///
+    /// [`listen`]: ./struct.Server.html#method.listen
+    /// [`Shutdown`]: ./struct.Shutdown.html
+    pub fn listen_until<A: ToSocketAddrs>(&self, addr: A, shutdown: &Shutdown) -> io::Result<()> {
+        let listener = self.socket_options.bind(addr)?;
+        let streams = shutdown.incoming(&listener)?;
+        self.accept(streams, Ok)
     }
+
+    /// Same as [`listen`], but over HTTPS using the certificate and key in
+    /// the [`TlsConfig`]. A client failing its handshake is simply dropped.
+    ///
+    /// # Returns:
+    /// An `Err` straight away if the certificate or key can not be loaded.
+    ///
+    /// [`listen`]: ./struct.Server.html#method.listen
+    /// [`TlsConfig`]: ./struct.TlsConfig.html
+    #[cfg(feature = "tls")]
+    pub fn listen_tls<A: ToSocketAddrs>(&self, addr: A, tls_config: TlsConfig) -> io::Result<()> {
+        let server_config = tls_config.server_config()?;
+        let listener = self.socket_options.bind(addr)?;
+        self.accept(listener.incoming().flatten(), |stream| {
+            tls::accept(&server_config, stream)
+        })
+    }
+
+    /// Serves every stream in turn, or through the worker threads when there
+    /// are any, once set up as the [`SocketOptions`] ask. Once the streams run
+    /// out, the connections already queued are still served before returning.
+    ///
+    /// [`SocketOptions`]: ./struct.SocketOptions.html
+    pub(in crate::server) fn accept<C, F>(
+        &self,
+        streams: impl Iterator<Item = TcpStream>,
+        connect: F,
+    ) -> io::Result<()>
+    where
+        C: Connection + Send,
+        F: Fn(TcpStream) -> io::Result<C> + Sync,
+    {
+        self.health.set_lifecycle(Lifecycle::Serving);
+        let streams = streams
+            .inspect(|stream| {
+                #[cfg(feature = "log")]
+                if let Ok(peer_addr) = stream.peer_addr() {
+                    logging::accepted(peer_addr);
+                }
+                self.socket_options.apply(stream);
+            })
+            // Only reached once the streams run out, while the connections
+            // already accepted may still be served.
+            .chain(iter::from_fn(|| {
+                self.health.set_lifecycle(Lifecycle::Draining);
+                None
+            }));
+        #[cfg(feature = "mio")]
+        if self.io_model == IoModel::EventLoop {
+            return self.run_event_loop(streams, connect);
+        }
+        if self.workers == 0 {
+            streams.for_each(|stream| self.serve_connection(stream, &connect));
+            return Ok(());
+        }
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(self.workers);
+        let receiver = Mutex::new(receiver);
+        thread::scope(|scope| {
+            for _ in 0..self.workers {
+                scope.spawn(|| loop {
+                    // The lock is let go of before serving, so the other
+                    // workers can take the next stream in the meantime.
+                    let next = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    match next {
+                        Ok(stream) => self.serve_connection(stream, &connect),
+                        // The sender is gone and the queue is drained.
+                        Err(_) => break,
+                    }
+                });
+            }
+            for stream in streams {
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+            drop(sender);
+        });
+        Ok(())
+    }
+
+    fn serve_connection<C, F>(&self, stream: TcpStream, connect: &F)
+    where
+        C: Connection,
+        F: Fn(TcpStream) -> io::Result<C>,
+    {
+        let addrs = stream
+            .peer_addr()
+            .and_then(|peer| Ok((peer, stream.local_addr()?)));
+        // Kept for setting the timeouts of reads through the connection.
+        let socket = stream.try_clone().ok();
+        // A single bad connection has no bearing on the ones after it.
+        let connected = connect(stream);
+        #[cfg(feature = "log")]
+        if let Err(e) = &connected {
+            logging::dropped(e);
+        }
+        if let Ok(mut connection) = connected {
+            let connection_info = addrs.ok().map(|(peer_addr, local_addr)| ConnectionInfo {
+                peer_addr,
+                local_addr,
+                is_tls: connection.is_tls(),
+                peer_identity: None,
+                server_name: None,
+            });
+            let _ = self
+                .serve(&mut connection, connection_info, socket.as_ref())
+                .and_then(|_| connection.close());
+        }
+    }
+
+    /// Serves the requests of the connection in turn, for as long as it is
+    /// kept alive, see [`KeepAlive`]. It is closed once the client asks for
+    /// it to be, stops sending requests or times out between them. Reads
+    /// wait on the client as long as the [`Timeouts`] allow, when the socket
+    /// under the connection is given.
+    ///
+    /// [`KeepAlive`]: ./struct.KeepAlive.html
+    /// [`Timeouts`]: ./struct.Timeouts.html
+    pub(in crate::server) fn serve<S: Connection>(
+        &self,
+        stream: &mut S,
+        mut connection_info: Option<ConnectionInfo>,
+        socket: Option<&TcpStream>,
+    ) -> io::Result<()> {
+        let set_read_timeout = |timeout| {
+            if let Some(socket) = socket {
+                let _ = socket.set_read_timeout(timeout);
+            }
+        };
+        let accepted = Instant::now();
+        let mut reader = BufReader::new(stream);
+        let max_requests = self.max_requests();
+        for served in 1..=max_requests {
+            set_read_timeout(match served {
+                1 => self.timeouts.read,
+                _ => Some(self.keep_alive.timeout),
+            });
+            // Anything but the start of another request ends the connection
+            // quietly, be it closed by the client or left idle.
+            if reader.fill_buf().map_or(true, |buffer| buffer.is_empty()) {
+                break;
+            }
+            let started = match served {
+                1 => accepted,
+                _ => Instant::now(),
+            };
+            if !self.serve_next(
+                &mut reader,
+                &mut connection_info,
+                socket,
+                served,
+                started,
+                max_requests,
+            )? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serves the request coming in on the connection, the `served`th on it
+    /// and `started` when it was first waited on, once its start has been
+    /// read into the buffer.
+    ///
+    /// # Returns:
+    /// Whether the connection is kept alive for another request.
+    fn serve_next<S: Connection>(
+        &self,
+        reader: &mut BufReader<&mut S>,
+        connection_info: &mut Option<ConnectionInfo>,
+        socket: Option<&TcpStream>,
+        served: usize,
+        started: Instant,
+        max_requests: usize,
+    ) -> io::Result<bool> {
+        // Any TLS handshake is done by the time the first request starts.
+        if let (1, Some(connection_info)) = (served, connection_info.as_mut()) {
+            connection_info.peer_identity = reader.get_ref().peer_identity();
+            connection_info.server_name = reader.get_ref().server_name();
+        }
+        // Chosen through ALPN, or known by the client to be spoken here.
+        #[cfg(feature = "http2")]
+        if served == 1
+            && (reader.get_ref().is_http2() || reader.buffer().starts_with(&http2::PREFACE[..4]))
+        {
+            http2::serve(self, reader, connection_info.clone(), socket, None)?;
+            return Ok(false);
+        }
+        if let Some(socket) = socket {
+            let _ = socket.set_read_timeout(self.timeouts.read);
+        }
+        let deadline = self.timeouts.read.map(|read| started + read);
+        let may_keep_alive = served < max_requests;
+        self.serve_request(
+            reader,
+            connection_info.clone(),
+            socket,
+            deadline,
+            may_keep_alive,
+        )
+    }
+
+    /// The most requests served on a single connection.
+    pub(in crate::server) fn max_requests(&self) -> usize {
+        match self.workers {
+            0 => 1,
+            _ => self.keep_alive.max_requests.max(1),
+        }
+    }
+
+    /// Reads a single request off of the connection and writes its response.
+    /// A request which can not be parsed is answered with a 400, one not in
+    /// by the deadline with a 408, one over the [`Limits`] as they
+    /// describe and one not matching any route with a 404. The response is
+    /// never of a newer version than the request, so that an HTTP/1.0 client
+    /// is not sent a chunked body.
+    ///
+    /// With the `http2` feature, a cleartext request asking to be upgraded to
+    /// HTTP/2 is answered over it, and so is the rest of the connection.
+    ///
+    /// # Returns:
+    /// Whether the connection is kept alive for another request.
+    ///
+    /// [`Limits`]: ./struct.Limits.html
+    fn serve_request<S: Connection>(
+        &self,
+        reader: &mut BufReader<&mut S>,
+        connection_info: Option<ConnectionInfo>,
+        socket: Option<&TcpStream>,
+        deadline: Option<Instant>,
+        may_keep_alive: bool,
+    ) -> io::Result<bool> {
+        let mut until_deadline = UntilDeadline::new(reader, socket, deadline);
+        let mut request = match connection::read_request(&mut until_deadline, &self.limits) {
+            Ok(request) => request,
+            Err(ReadError::Rejected(status_code)) => {
+                return close_with(reader.get_mut(), status_code, &self.default_headers);
+            }
+            Err(ReadError::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                return close_with(
+                    reader.get_mut(),
+                    StatusCode::RequestTimeout,
+                    &self.default_headers,
+                );
+            }
+            Err(ReadError::Io(e)) => return Err(e),
+        };
+        request.connection = connection_info;
+        #[cfg(feature = "http2")]
+        if let Some(settings) = http2::upgrade_settings(&request) {
+            if !reader.get_ref().is_tls() {
+                http2::switch_protocols(reader.get_mut())?;
+                let connection_info = request.connection.clone();
+                let upgraded = Some((request, settings));
+                http2::serve(self, reader, connection_info, socket, upgraded)?;
+                return Ok(false);
+            }
+        }
+        let is_head = request.http_method == HttpMethod::Head;
+        let http_version = request.http_version;
+        let keep_alive = may_keep_alive && connection::wants_keep_alive(&request);
+        let mut response = self.handle(request);
+        self.default_headers.stamp(&mut response);
+        if matches!(response.body, Body::Upgrade(_)) {
+            return hand_over(reader, socket, response);
+        }
+        let stream = reader.get_mut();
+        response.http_version = response.http_version.min(http_version);
+        let keep_alive = connection::set_keep_alive(&mut response, keep_alive);
+        match is_head {
+            true => response.write_head_to(stream)?,
+            false => response.write_to(stream)?,
+        }
+        Ok(keep_alive)
+    }
+}
+
+/// Writes the head of the response and hands the connection over to its
+/// [`Body::Upgrade`], which is done with the connection once it returns.
+///
+/// [`Body::Upgrade`]: ../web/enum.Body.html#variant.Upgrade
+fn hand_over<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    socket: Option<&TcpStream>,
+    mut response: HttpResponse,
+) -> io::Result<bool> {
+    if let Body::Upgrade(upgrade) = mem::take(&mut response.body) {
+        response.write_to(reader.get_mut())?;
+        // A WebSocket may go quiet for far longer than a request would.
+        if let Some(socket) = socket {
+            socket.set_read_timeout(None)?;
+        }
+        upgrade.run(WebSocket::new(connection::Upgraded(reader)).with_socket(socket));
+    }
+    Ok(false)
+}
+
+/// Answers with the status and no body, closing the connection after.
+fn close_with<W: Write>(
+    stream: &mut W,
+    status_code: StatusCode,
+    default_headers: &DefaultHeaders,
+) -> io::Result<bool> {
+    #[cfg(feature = "log")]
+    logging::rejected(status_code);
+    let mut response = HttpResponse::new(status_code);
+    default_headers.stamp(&mut response);
+    connection::set_keep_alive(&mut response, false);
+    response.write_to(stream).map(|_| false)
+}
+
+/// Invokes the handler on a thread of its own, so that it can be given up
+/// on after the timeout, `None` if it was. The thread is counted by the
+/// permit until the handler finishes, given up on or not.
+fn invoke_within(
+    handler: &Handler,
+    request: HttpRequest,
+    timeout: Duration,
+    permit: OwnedPermit,
+) -> Option<thread::Result<Result<HttpResponse, MartianError>>> {
+    let handler = handler.clone();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| handler.invoke(request)));
+        drop(permit);
+        // Nobody is listening anymore once timed out.
+        let _ = sender.send(outcome);
+    });
+    receiver.recv_timeout(timeout).ok()
+}
+
+/// The path of a mounted `Server` as seen from the one it is mounted under,
+/// the inverse of [`mount::strip_prefix`].
+///
+/// [`mount::strip_prefix`]: ./mount/fn.strip_prefix.html
+fn under_prefix(prefix: &str, path: &str) -> String {
+    match path {
+        "/" if !prefix.is_empty() => prefix.into(),
+        _ => format!("{}{}", prefix, path),
+    }
+}
+
+/// The host without any port following it, keeping the brackets around an
+/// IPv6 address.
+fn strip_port(host: &str) -> &str {
+    let port_start = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => i,
+        _ => return host,
+    };
+    match host.starts_with('[') || !host[..port_start].contains(':') {
+        true => &host[..port_start],
+        false => host,
+    }
+}
+
+/// What the [`Server`] keeps of a request while handling it, to report it
+/// once answered.
+///
+/// [`Server`]: ./struct.Server.html
+pub(in crate::server) struct Tracked {
+    pending_log: Option<PendingLog>,
+    in_flight: Option<InFlight>,
 }
 
 /// The delegate being invoked from the [`Server`] when an [`HttpRequest`]
@@ -61,11 +1506,50 @@ impl Server {
 ///
 /// [`Server`]: ./struct.Server.html
 /// [`HttpRequest`]: ../web/struct.HttpRequest.html
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct Route {
     http_method: HttpMethod,
-    uri: String,
-    callback: Callback,
+    pattern: Pattern,
+    handler: Handler,
+    middleware: Vec<Arc<dyn Middleware>>,
+    guards: Vec<Arc<dyn Guard>>,
+    priority: i32,
+    name: Option<String>,
+}
+
+/// The callbacks a [`Route`] can be bound to.
+///
+/// [`Route`]: ./struct.Route.html
+#[derive(Clone)]
+enum Handler {
+    Callback(Callback),
+    FallibleCallback(FallibleCallback),
+    #[cfg(feature = "async")]
+    Async(AsyncCallback),
+}
+
+impl Handler {
+    /// Invokes the callback on the calling thread, blocking on an `Async`
+    /// one until its future is done.
+    fn invoke(&self, request: HttpRequest) -> Result<HttpResponse, MartianError> {
+        match self {
+            Handler::Callback(callback) => Ok(callback(request)),
+            Handler::FallibleCallback(callback) => callback(request),
+            #[cfg(feature = "async")]
+            Handler::Async(callback) => Ok(async_server::block_on(callback(request))),
+        }
+    }
+}
+
+impl fmt::Debug for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Handler::Callback(_) => write!(f, "Callback(..)"),
+            Handler::FallibleCallback(_) => write!(f, "FallibleCallback(..)"),
+            #[cfg(feature = "async")]
+            Handler::Async(_) => write!(f, "Async(..)"),
+        }
+    }
 }
 
 impl Route {
@@ -81,23 +1565,92 @@ impl Route {
             routes: Vec::new(),
         }
     }
+
+    /// Whether every guard of the route lets the request through.
+    fn admits(&self, request: &HttpRequest) -> bool {
+        self.guards.iter().all(|guard| guard.check(request))
+    }
+
+    fn conflicts_with(&self, other: &Route, trailing_slash: TrailingSlash) -> bool {
+        self.http_method == other.http_method
+            && self.guards.is_empty()
+            && other.guards.is_empty()
+            && match trailing_slash {
+                TrailingSlash::Merge => self
+                    .pattern
+                    .without_trailing_slash()
+                    .conflicts_with(&other.pattern.without_trailing_slash()),
+                _ => self.pattern.conflicts_with(&other.pattern),
+            }
+    }
 }
 
+/// Returned by [`Server::route`] when a [`Route`] being bound would match the
+/// very same requests as one already bound. Being an `Error`, it can be passed
+/// on with `?` while setting up a `Server`.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Route, Server};
+/// use martian::web::{HttpMethod, HttpResponse};
+/// use std::error::Error;
+/// fn server() -> Result<Server, Box<dyn Error>> {
+///     let mut server = Server::default();
+///     server.route(|| Route::bind(HttpMethod::Get).to("/{id}", |_| HttpResponse::ok()))?;
+///     server.route(|| Route::bind(HttpMethod::Get).to("/{name}", |_| HttpResponse::ok()))?;
+///     Ok(server)
+/// }
+/// assert_eq!(
+///     server().err().unwrap().to_string(),
+///     "GET /{name} conflicts with already bound GET /{id}"
+/// );
+/// ```
+///
+/// [`Server::route`]: ./struct.Server.html#method.route
+/// [`Route`]: ./struct.Route.html
+#[derive(PartialEq, Debug, Clone)]
+pub struct RouteConflict {
+    pub http_method: HttpMethod,
+    /// The uri the conflicting route was bound with first.
+    pub existing_uri: String,
+    /// The uri which was being bound.
+    pub new_uri: String,
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} conflicts with already bound {} {}",
+            self.http_method.as_str(),
+            self.new_uri,
+            self.http_method.as_str(),
+            self.existing_uri
+        )
+    }
+}
+
+impl Error for RouteConflict {}
+
 /// Simple abstraction for binding a [`Route`] to an [`HttpMethod`].
 ///
 /// # Examples:
 /// ```
 /// use martian::server::Route;
-/// use martian::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+/// use martian::web::{Body, HttpMethod, HttpRequest, HttpResponse, HttpVersion, StatusCode};
+/// use std::collections::HashMap;
 /// Route::bind(HttpMethod::Get).to("/", |_| HttpResponse {
-///     http_version: 1.1,
-///     status_code: StatusCode::Ok
+///     http_version: HttpVersion::Http1_1,
+///     status_code: StatusCode::Ok,
+///     headers: HashMap::new(),
+///     cookies: Vec::new(),
+///     body: Body::Empty,
 /// });
 /// ```
 ///
 /// [`Route`]: ./struct.Route.html
 /// [`HttpMethod`]: ../web/enum.HttpMethod.html
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct Binding {
     http_method: HttpMethod,
     routes: Vec<Route>,
@@ -106,15 +1659,333 @@ pub struct Binding {
 impl Binding {
     /// The callback to route to this `Binding`, this will be invoked when a
     /// call to the [`Server`] is made with the same [`HttpMethod`] and `Uri`.
+    /// It may be a closure capturing state, such as a connection pool, as long
+    /// as that state can be shared between threads. It may return anything
+    /// which is [`IntoResponse`], such as a `String` or a
+    /// `(StatusCode, String)`, rather than a whole [`HttpResponse`].
+    ///
+    /// Any segment of the `Uri` written as `{name}` matches a single segment
+    /// of the request path, available to the callback through
+    /// [`HttpRequest::path_param`], while a `*` matches one without capturing
+    /// it. The `Uri` may also end in a catch-all segment, `*`, `**`, `*name`
+    /// or `**name`, matching one or more remaining segments, available under
+    /// `name` or `*` when left unnamed. A literal segment is always preferred
+    /// over a `{name}`, which is preferred over a `*`, which in turn is
+    /// preferred over a catch-all, unless another route is given a higher
+    /// [`priority`].
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Route;
+    /// use martian::web::{Body, HttpMethod, HttpResponse, HttpVersion, StatusCode};
+    /// use std::collections::HashMap;
+    /// Route::bind(HttpMethod::Get).to("/files/*path", |_| HttpResponse {
+    ///     http_version: HttpVersion::Http1_1,
+    ///     status_code: StatusCode::Ok,
+    ///     headers: HashMap::new(),
+    ///     cookies: Vec::new(),
+    ///     body: Body::Empty,
+    /// });
+    /// ```
     ///
     /// [`Server`]: ./struct.Server.html
     /// [`HttpMethod`]: ../web/enum.HttpMethod.html
-    pub fn to(mut self, uri: &str, callback: Callback) -> Binding {
-        let binding = self.clone();
+    /// [`HttpRequest::path_param`]: ../web/struct.HttpRequest.html#method.path_param
+    /// [`IntoResponse`]: ../web/trait.IntoResponse.html
+    /// [`HttpResponse`]: ../web/struct.HttpResponse.html
+    /// [`priority`]: ./struct.Binding.html#method.priority
+    pub fn to<F, R>(self, uri: &str, callback: F) -> Binding
+    where
+        F: Fn(HttpRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.push(
+            uri,
+            Handler::Callback(Arc::new(move |request| callback(request).into_response())),
+        )
+    }
+
+    /// Same as [`to`], but naming the route, so that the path to it can be
+    /// found through [`Server::url_for`] rather than written out again.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Route;
+    /// use martian::web::HttpMethod;
+    /// Route::bind(HttpMethod::Get).to_named("user_detail", "/users/{id}", |request| {
+    ///     format!("user {}", request.path_param("id").unwrap_or_default())
+    /// });
+    /// ```
+    ///
+    /// [`to`]: ./struct.Binding.html#method.to
+    /// [`Server::url_for`]: ./struct.Server.html#method.url_for
+    pub fn to_named<F, R>(self, name: &str, uri: &str, callback: F) -> Binding
+    where
+        F: Fn(HttpRequest) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        let mut binding = self.to(uri, callback);
+        if let Some(route) = binding.routes.last_mut() {
+            route.name = Some(name.into());
+        }
+        binding
+    }
+
+    /// Same as [`to`], but for a callback which may fail with a
+    /// [`MartianError`], answered by the [`Server`] as it is configured to
+    /// through [`Server::on_error`]. A [`ParamError`] or an `io::Error` can
+    /// be returned with `?`, the former being a 400 describing which param
+    /// was missing or invalid.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Route;
+    /// use martian::web::{Body, HttpMethod, HttpResponse, HttpVersion, StatusCode};
+    /// use std::collections::HashMap;
+    /// Route::bind(HttpMethod::Get).to_fallible("/users", |request| {
+    ///     let page = request.param::<u32>("page")?;
+    ///     Ok(HttpResponse {
+    ///         http_version: HttpVersion::Http1_1,
+    ///         status_code: StatusCode::Ok,
+    ///         headers: HashMap::new(),
+    ///         cookies: Vec::new(),
+    ///         body: Body::Bytes(format!("page {}", page).into_bytes()),
+    ///     })
+    /// });
+    /// ```
+    ///
+    /// [`to`]: ./struct.Binding.html#method.to
+    /// [`MartianError`]: ../web/struct.MartianError.html
+    /// [`Server`]: ./struct.Server.html
+    /// [`Server::on_error`]: ./struct.Server.html#method.on_error
+    /// [`ParamError`]: ../web/enum.ParamError.html
+    pub fn to_fallible<F, R>(self, uri: &str, callback: F) -> Binding
+    where
+        F: Fn(HttpRequest) -> Result<R, MartianError> + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.push(
+            uri,
+            Handler::FallibleCallback(Arc::new(move |request| {
+                callback(request).map(IntoResponse::into_response)
+            })),
+        )
+    }
+
+    /// Same as [`to`], but for a handler taking typed arguments rather than
+    /// the request, each pulled off of it through [`FromRequest`]. When any
+    /// of them can not be, the request is answered with a 400 describing why
+    /// and the handler is not called.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{PathParam, Route};
+    /// use martian::web::{HttpMethod, HttpResponse, QueryParams};
+    /// fn user(PathParam(id): PathParam<u32>, params: QueryParams) -> HttpResponse {
+    ///     let greeting = params.get("greeting").unwrap_or("hello");
+    ///     HttpResponse::text(&format!("{} user {}", greeting, id))
+    /// }
+    /// Route::bind(HttpMethod::Get).to_typed("/users/{id}", user);
+    /// ```
+    ///
+    /// [`to`]: ./struct.Binding.html#method.to
+    /// [`FromRequest`]: ./trait.FromRequest.html
+    pub fn to_typed<H, Args>(self, uri: &str, handler: H) -> Binding
+    where
+        H: TypedHandler<Args>,
+    {
+        self.push(
+            uri,
+            Handler::FallibleCallback(Arc::new(move |request| {
+                handler.call(request).map_err(Into::into)
+            })),
+        )
+    }
+
+    /// Same as [`to`], but for an `async` callback, awaited on the runtime
+    /// served on through [`Server::listen_async`] without holding up a
+    /// thread while it waits. Served any other way, or behind [`Middleware`],
+    /// a mount or a virtual host, the thread serving the request blocks on it
+    /// instead, see [`Server::listen_async`].
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Route;
+    /// use martian::web::{HttpMethod, HttpRequest};
+    /// async fn user(request: HttpRequest) -> String {
+    ///     format!("user {}", request.path_param("id").unwrap_or_default())
+    /// }
+    /// Route::bind(HttpMethod::Get).to_async("/users/{id}", user);
+    /// ```
+    ///
+    /// [`to`]: ./struct.Binding.html#method.to
+    /// [`Server::listen_async`]: ./struct.Server.html#method.listen_async
+    /// [`Middleware`]: ./trait.Middleware.html
+    #[cfg(feature = "async")]
+    pub fn to_async<F, Fut, R>(self, uri: &str, callback: F) -> Binding
+    where
+        F: Fn(HttpRequest) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = R> + Send + 'static,
+        R: IntoResponse,
+    {
+        self.push(
+            uri,
+            Handler::Async(Arc::new(move |request| {
+                let future = callback(request);
+                Box::pin(async move { future.await.into_response() })
+            })),
+        )
+    }
+
+    /// Wraps the route bound last, by [`to`], [`to_fallible`] or
+    /// [`to_typed`], in the [`Middleware`]. It runs after any registered with
+    /// [`Server::wrap`], and the first attached to a route is the first to
+    /// see its request.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Next, Route};
+    /// use martian::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+    /// Route::bind(HttpMethod::Get)
+    ///     .to("/admin", |_| HttpResponse::text("secrets"))
+    ///     .with(|request: HttpRequest, next: Next| match request.param::<String>("key") {
+    ///         Ok(key) if key == "letmein" => next.run(request),
+    ///         _ => HttpResponse::new(StatusCode::Forbidden),
+    ///     })
+    ///     .to("/", |_| HttpResponse::text("welcome"));
+    /// ```
+    ///
+    /// # Panics:
+    /// If no route has been bound yet.
+    ///
+    /// [`to`]: ./struct.Binding.html#method.to
+    /// [`to_fallible`]: ./struct.Binding.html#method.to_fallible
+    /// [`to_typed`]: ./struct.Binding.html#method.to_typed
+    /// [`Middleware`]: ./trait.Middleware.html
+    /// [`Server::wrap`]: ./struct.Server.html#method.wrap
+    pub fn with<M: Middleware + 'static>(mut self, middleware: M) -> Binding {
+        self.routes
+            .last_mut()
+            .expect("Middleware must follow the route it wraps")
+            .middleware
+            .push(Arc::new(middleware));
+        self
+    }
+
+    /// Limits how many requests the route bound last handles at once, on top
+    /// of [`Limits::max_in_flight`]. A request over it is answered with a 503
+    /// right away rather than waiting for another to finish. The limit is
+    /// checked where it falls among the route's [`Middleware`], by the order
+    /// of the calls.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Route;
+    /// use martian::web::HttpMethod;
+    /// Route::bind(HttpMethod::Post)
+    ///     .to("/reports", |_| "generated")
+    ///     .max_in_flight(4);
+    /// ```
+    ///
+    /// # Panics:
+    /// If no route has been bound yet.
+    ///
+    /// [`Limits::max_in_flight`]: ./struct.Limits.html#structfield.max_in_flight
+    /// [`Middleware`]: ./trait.Middleware.html
+    pub fn max_in_flight(self, max_in_flight: usize) -> Binding {
+        self.with(ConcurrencyLimit::new(max_in_flight))
+    }
+
+    /// Only routes a request to the route bound last when the [`Guard`] lets
+    /// it through, on top of any other guards of the route. A request turned
+    /// away is matched against the next best route instead, such as another
+    /// bound to the same uri with different guards or none at all, so that
+    /// routes with guards never conflict.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{ContentTypeIs, Route};
+    /// use martian::web::{HttpMethod, Mime};
+    /// Route::bind(HttpMethod::Post)
+    ///     .to("/orders", |_| "from json")
+    ///     .guard(ContentTypeIs(Mime::ApplicationJson))
+    ///     .to("/orders", |_| "from a form");
+    /// ```
+    ///
+    /// # Panics:
+    /// If no route has been bound yet.
+    ///
+    /// [`Guard`]: ./trait.Guard.html
+    pub fn guard<G: Guard + 'static>(mut self, guard: G) -> Binding {
+        self.routes
+            .last_mut()
+            .expect("A guard must follow the route it guards")
+            .guards
+            .push(Arc::new(guard));
+        self
+    }
+
+    /// Tries the route bound last before any other matching the request with
+    /// a lower priority, however much more specific its uri, 0 being that of
+    /// every route unless given. A negative priority leaves the route to be
+    /// tried last. Routes matching the very same paths still conflict,
+    /// whatever their priorities.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::Route;
+    /// use martian::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// static MAINTENANCE: AtomicBool = AtomicBool::new(false);
+    /// Route::bind(HttpMethod::Get)
+    ///     .to("/users/{id}", |_| "user")
+    ///     .to("/**", |_| HttpResponse::new(StatusCode::ServiceUnavailable))
+    ///     .guard(|_: &HttpRequest| MAINTENANCE.load(Ordering::Relaxed))
+    ///     .priority(1);
+    /// ```
+    ///
+    /// # Panics:
+    /// If no route has been bound yet.
+    pub fn priority(mut self, priority: i32) -> Binding {
+        self.routes
+            .last_mut()
+            .expect("A priority must follow the route it is given to")
+            .priority = priority;
+        self
+    }
+
+    /// Binds the routes of the other binding along with these, each to the
+    /// method it was bound to, so that routes of several methods can be
+    /// bound at once. Routes bound after this are bound to the method of
+    /// this binding still.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::{Route, Server};
+    /// use martian::web::HttpMethod;
+    /// let mut server = Server::default();
+    /// server
+    ///     .route(|| {
+    ///         Route::bind(HttpMethod::Get)
+    ///             .to("/users", |_| "users")
+    ///             .and(Route::bind(HttpMethod::Post).to("/users", |_| "created"))
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn and(mut self, other: Binding) -> Binding {
+        self.routes.extend(other.routes);
+        self
+    }
+
+    fn push(mut self, uri: &str, handler: Handler) -> Binding {
         self.routes.push(Route {
-            http_method: binding.http_method,
-            uri: uri.into(),
-            callback,
+            http_method: self.http_method.clone(),
+            pattern: Pattern::parse(uri),
+            handler,
+            middleware: Vec::new(),
+            guards: Vec::new(),
+            priority: 0,
+            name: None,
         });
         self
     }
@@ -0,0 +1,85 @@
+//! Handing the requests under a path prefix over to another `Server`, see
+//! `Server::mount`.
+
+use crate::web::{HttpRequest, Uri};
+
+/// Where a request was handed over to a `Server` [`mount`]ed under a prefix,
+/// as found in its [`extensions`]. The uri of the request has the prefix
+/// stripped off, this keeps the uri as the client sent it.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Mounted, Route, Server};
+/// use martian::web::{HttpMethod, HttpRequest};
+/// let mut api = Server::default();
+/// api.route(|| {
+///     Route::bind(HttpMethod::Get).to("/users", |request: HttpRequest| {
+///         let mounted = request.extensions.get::<Mounted>().unwrap();
+///         format!("{} under {}", mounted.original_uri(), mounted.prefix())
+///     })
+/// })
+/// .unwrap();
+/// let mut server = Server::default();
+/// server.mount("/api", api);
+/// ```
+///
+/// [`mount`]: ./struct.Server.html#method.mount
+/// [`extensions`]: ../web/struct.HttpRequest.html#structfield.extensions
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Mounted {
+    prefix: String,
+    original_uri: Uri,
+}
+
+impl Mounted {
+    /// The prefix stripped off, those of every mount the request was handed
+    /// through joined together when mounted within one another.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The uri as the client sent it, prefix and all.
+    pub fn original_uri(&self) -> &Uri {
+        &self.original_uri
+    }
+}
+
+/// The uri under the prefix, `None` unless the prefix is made up of whole
+/// segments of it.
+pub(in crate::server) fn strip_prefix(prefix: &str, uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix(prefix)?;
+    match rest.chars().next() {
+        None => Some("/".into()),
+        Some('/') => Some(rest.into()),
+        Some('?') => Some(format!("/{}", rest)),
+        Some(_) => None,
+    }
+}
+
+/// Sets the uri under the prefix on the request, keeping what was stripped
+/// off as its [`Mounted`].
+///
+/// [`Mounted`]: ./struct.Mounted.html
+pub(in crate::server) fn hand_over(request: &mut HttpRequest, prefix: &str, uri: String) {
+    let mounted = match request.extensions.get::<Mounted>() {
+        Some(outer) => Mounted {
+            prefix: format!("{}{}", outer.prefix, prefix),
+            original_uri: outer.original_uri.clone(),
+        },
+        None => Mounted {
+            prefix: prefix.into(),
+            original_uri: request.uri.clone(),
+        },
+    };
+    request.extensions.insert(mounted);
+    request.uri = uri.into();
+}
+
+/// The path as the client would ask for it of the `Server` the request was
+/// handed over to, for a `Location` to send it on to.
+pub(in crate::server) fn location(request: &HttpRequest, path: &str) -> String {
+    match request.extensions.get::<Mounted>() {
+        Some(mounted) => format!("{}{}", mounted.prefix, path),
+        None => path.into(),
+    }
+}
@@ -0,0 +1,300 @@
+//! Describing the routes of a [`Server`] as an
+//! [OpenAPI 3](https://spec.openapis.org/oas/v3.0.3) document, to be served
+//! off of a route of its own for clients and tooling to read.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use serde_json::{json, Map, Value};
+
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, Mime, StatusCode};
+
+use super::Server;
+
+/// The version of the specification documents are written against.
+const OPENAPI_VERSION: &str = "3.0.3";
+
+/// The description of an API, its routes taken from a [`Server`] along with
+/// whatever is said of each through an [`Operation`]. Every path param of a
+/// route is described as a string, a catch-all included, those captured
+/// under no name being named `*` followed by their position, and the route
+/// is identified by its name when it has one.
+///
+/// # Examples:
+/// ```
+/// use martian::server::openapi::{OpenApi, Operation};
+/// use martian::server::{Route, Server};
+/// use martian::web::{HttpMethod, HttpResponse, StatusCode};
+/// let mut server = Server::default();
+/// server
+///     .route(|| {
+///         Route::bind(HttpMethod::Get)
+///             .to_named("user_detail", "/users/{id}", |_| HttpResponse::ok())
+///     })
+///     .unwrap();
+/// let openapi = OpenApi::new("Users", "1.0.0").operation(
+///     HttpMethod::Get,
+///     "/users/{id}",
+///     Operation::new()
+///         .summary("Finds a user")
+///         .response(StatusCode::Ok, "The user")
+///         .response(StatusCode::NotFound, "No such user"),
+/// );
+/// let document = openapi.document(&server);
+/// let operation = &document["paths"]["/users/{id}"]["get"];
+/// assert_eq!(operation["operationId"], "user_detail");
+/// assert_eq!(operation["parameters"][0]["name"], "id");
+/// let handler = openapi.handler(&server);
+/// server
+///     .route(|| Route::bind(HttpMethod::Get).to("/openapi.json", handler))
+///     .unwrap();
+/// ```
+///
+/// [`Server`]: ../struct.Server.html
+/// [`Operation`]: ./struct.Operation.html
+#[derive(PartialEq, Debug, Clone)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    operations: Vec<(HttpMethod, String, Operation)>,
+}
+
+impl OpenApi {
+    /// Describes an API of the title, at the version of the API itself.
+    pub fn new(title: &str, version: &str) -> OpenApi {
+        OpenApi {
+            title: title.into(),
+            version: version.into(),
+            description: None,
+            operations: Vec::new(),
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> OpenApi {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Describes the route bound to the method and uri, exactly as it was
+    /// bound. Describing it again replaces what was said of it before.
+    pub fn operation(
+        mut self,
+        http_method: HttpMethod,
+        uri: &str,
+        operation: Operation,
+    ) -> OpenApi {
+        self.operations
+            .retain(|(method, bound_uri, _)| *method != http_method || bound_uri != uri);
+        self.operations.push((http_method, uri.into(), operation));
+        self
+    }
+
+    /// The document describing every route of the server, see
+    /// [`Server::routes`]. A `CONNECT` route is left out, as the
+    /// specification has no place for one.
+    ///
+    /// [`Server::routes`]: ../struct.Server.html#method.routes
+    pub fn document(&self, server: &Server) -> Value {
+        let mut paths = Map::new();
+        for (http_method, uri, name) in server.routes() {
+            let method = match http_method {
+                HttpMethod::Connect => continue,
+                http_method => http_method.as_str().to_ascii_lowercase(),
+            };
+            let (path, params) = path_template(&uri);
+            let described = self
+                .operations
+                .iter()
+                .find(|(method, bound_uri, _)| method == http_method && *bound_uri == uri)
+                .map(|(_, _, operation)| operation);
+            let mut operation = described.map_or_else(Map::new, Operation::to_json);
+            if let Some(name) = name {
+                operation
+                    .entry("operationId")
+                    .or_insert_with(|| name.into());
+            }
+            if !params.is_empty() {
+                let params = params
+                    .iter()
+                    .map(|param| {
+                        json!({
+                            "name": param,
+                            "in": "path",
+                            "required": true,
+                            "schema": { "type": "string" },
+                        })
+                    })
+                    .collect();
+                operation.insert("parameters".into(), Value::Array(params));
+            }
+            operation
+                .entry("responses")
+                .or_insert_with(|| json!({ "default": { "description": "Response" } }));
+            let path_item = paths
+                .entry(path)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(path_item) = path_item {
+                path_item.entry(method).or_insert(Value::Object(operation));
+            }
+        }
+        let mut info = Map::new();
+        info.insert("title".into(), self.title.as_str().into());
+        info.insert("version".into(), self.version.as_str().into());
+        if let Some(description) = &self.description {
+            info.insert("description".into(), description.as_str().into());
+        }
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": info,
+            "paths": paths,
+        })
+    }
+
+    /// A callback answering with the [`document`] of the server as it is
+    /// now, so it is best made once every other route is bound.
+    ///
+    /// [`document`]: ./struct.OpenApi.html#method.document
+    pub fn handler(
+        &self,
+        server: &Server,
+    ) -> impl Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static {
+        let document = self.document(server).to_string();
+        move |_| HttpResponse::ok_with_body(document.as_str()).content_type(Mime::ApplicationJson)
+    }
+}
+
+/// What is said of a single route in an [`OpenApi`] document, beyond its
+/// method, uri and params. Any part left out is left out of the document,
+/// other than its responses, a route without any being said to have a
+/// `default` one.
+///
+/// [`OpenApi`]: ./struct.OpenApi.html
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Operation {
+    operation_id: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    request_body: Option<(String, Value)>,
+    responses: Vec<(StatusCode, String)>,
+    deprecated: bool,
+}
+
+impl Operation {
+    pub fn new() -> Operation {
+        Operation::default()
+    }
+
+    /// Identifies the operation, in place of the name of its route.
+    pub fn operation_id(mut self, operation_id: &str) -> Operation {
+        self.operation_id = Some(operation_id.into());
+        self
+    }
+
+    pub fn summary(mut self, summary: &str) -> Operation {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Operation {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Groups the operation under the tag, on top of any given before.
+    pub fn tag(mut self, tag: &str) -> Operation {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Says the operation takes a body of the media type, such as
+    /// `application/json`, as described by the
+    /// [schema](https://spec.openapis.org/oas/v3.0.3#schema-object).
+    pub fn request_body(mut self, media_type: &str, schema: Value) -> Operation {
+        self.request_body = Some((media_type.into(), schema));
+        self
+    }
+
+    /// Says the operation may answer with the status, for the reason given.
+    pub fn response(mut self, status_code: StatusCode, description: &str) -> Operation {
+        self.responses.push((status_code, description.into()));
+        self
+    }
+
+    pub fn deprecated(mut self) -> Operation {
+        self.deprecated = true;
+        self
+    }
+
+    fn to_json(&self) -> Map<String, Value> {
+        let mut operation = Map::new();
+        let fields = [
+            ("operationId", &self.operation_id),
+            ("summary", &self.summary),
+            ("description", &self.description),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                operation.insert(key.into(), value.as_str().into());
+            }
+        }
+        if !self.tags.is_empty() {
+            operation.insert("tags".into(), self.tags.clone().into());
+        }
+        if let Some((media_type, schema)) = &self.request_body {
+            operation.insert(
+                "requestBody".into(),
+                json!({ "content": { media_type: { "schema": schema } } }),
+            );
+        }
+        if !self.responses.is_empty() {
+            let responses = self
+                .responses
+                .iter()
+                .map(|(status_code, description)| {
+                    (
+                        status_code.code().to_string(),
+                        json!({ "description": description }),
+                    )
+                })
+                .collect();
+            operation.insert("responses".into(), Value::Object(responses));
+        }
+        if self.deprecated {
+            operation.insert("deprecated".into(), true.into());
+        }
+        operation
+    }
+}
+
+/// The uri of a route as an OpenAPI path template, along with the names of
+/// its path params in order.
+fn path_template(uri: &str) -> (String, Vec<String>) {
+    let mut params = Vec::new();
+    let segments = uri
+        .split('/')
+        .enumerate()
+        .map(|(i, segment)| {
+            let name = match segment.strip_prefix('*') {
+                Some(name) => {
+                    let name = name.strip_prefix('*').unwrap_or(name);
+                    match name.is_empty() {
+                        true => format!("*{}", i),
+                        false => name.to_string(),
+                    }
+                }
+                None => match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Some(name) => name.to_string(),
+                    None => return segment.to_string(),
+                },
+            };
+            let template = format!("{{{}}}", name);
+            params.push(name);
+            template
+        })
+        .collect::<Vec<_>>();
+    (segments.join("/"), params)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,111 @@
+use serde_json::json;
+
+use crate::server::openapi::{path_template, OpenApi, Operation};
+use crate::server::{Route, Server};
+use crate::test::TestClient;
+use crate::web::{HttpMethod, HttpResponse, StatusCode};
+
+fn server() -> Server {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", |_| HttpResponse::ok())
+                .to_named("file", "/files/{owner}/**path", |_| HttpResponse::ok())
+        })
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/", |_| HttpResponse::ok()))
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Connect).to("/", |_| HttpResponse::ok()))
+        .unwrap();
+    server
+}
+
+#[test]
+fn should_template_path_params_when_uri_captures_segments() {
+    assert_eq!(
+        path_template("/a/{id}/*/b/**"),
+        (
+            "/a/{id}/{*3}/b/{*5}".to_string(),
+            vec!["id".to_string(), "*3".into(), "*5".into()]
+        )
+    );
+    assert_eq!(path_template("/"), ("/".to_string(), Vec::new()));
+}
+
+#[test]
+fn should_describe_every_route_when_documenting_server() {
+    let openapi = OpenApi::new("Files", "2.1.0")
+        .description("Serves files")
+        .operation(HttpMethod::Post, "/", Operation::new().summary("Replaced"))
+        .operation(
+            HttpMethod::Post,
+            "/",
+            Operation::new()
+                .operation_id("upload")
+                .tag("files")
+                .request_body("application/json", json!({ "type": "object" }))
+                .response(StatusCode::Created, "Uploaded")
+                .deprecated(),
+        );
+    let document = openapi.document(&server());
+    assert_eq!(
+        document,
+        json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Files", "version": "2.1.0", "description": "Serves files" },
+            "paths": {
+                "/": {
+                    "get": { "responses": { "default": { "description": "Response" } } },
+                    "post": {
+                        "operationId": "upload",
+                        "tags": ["files"],
+                        "requestBody": {
+                            "content": {
+                                "application/json": { "schema": { "type": "object" } }
+                            }
+                        },
+                        "responses": { "201": { "description": "Uploaded" } },
+                        "deprecated": true,
+                    },
+                },
+                "/files/{owner}/{path}": {
+                    "get": {
+                        "operationId": "file",
+                        "parameters": [
+                            {
+                                "name": "owner",
+                                "in": "path",
+                                "required": true,
+                                "schema": { "type": "string" },
+                            },
+                            {
+                                "name": "path",
+                                "in": "path",
+                                "required": true,
+                                "schema": { "type": "string" },
+                            },
+                        ],
+                        "responses": { "default": { "description": "Response" } },
+                    },
+                },
+            },
+        })
+    );
+}
+
+#[test]
+fn should_serve_document_as_json_when_handler_is_bound() {
+    let mut server = server();
+    let handler = OpenApi::new("Files", "1").handler(&server);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/openapi.json", handler))
+        .unwrap();
+    let response = TestClient::new(server).get("/openapi.json");
+    response.assert_header("Content-Type", "application/json");
+    let document = response.json::<serde_json::Value>();
+    assert_eq!(document["info"]["title"], "Files");
+    assert!(document["paths"].get("/openapi.json").is_none());
+}
@@ -0,0 +1,249 @@
+//! Uri patterns a [`Route`] is bound to. A pattern is a `/` separated list of
+//! segments, each either matched literally or capturing part of the request
+//! path.
+//!
+//! [`Route`]: ../struct.Route.html
+
+use std::collections::HashMap;
+
+use crate::web::encoding::{percent_decode, percent_encode};
+
+use super::PathNormalization;
+
+/// The key a bare trailing `*` captures its remainder under.
+pub(in crate::server) const WILDCARD: &str = "*";
+
+#[derive(PartialEq, Debug, Clone)]
+pub(in crate::server) enum Segment {
+    Static(String),
+    Param(String),
+    /// A `*` short of the final segment, matching any single segment without
+    /// capturing it.
+    Wildcard,
+    CatchAll(String),
+}
+
+impl Segment {
+    /// Lower is more specific, matches are ranked on this segment by segment.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard => 2,
+            Segment::CatchAll(_) => 3,
+        }
+    }
+
+    /// Whether two segments would match exactly the same paths, regardless of
+    /// what they capture under.
+    fn overlaps(&self, other: &Segment) -> bool {
+        match (self, other) {
+            (Segment::Static(a), Segment::Static(b)) => a == b,
+            (Segment::Param(_) | Segment::Wildcard, Segment::Param(_) | Segment::Wildcard) => true,
+            (Segment::CatchAll(_), Segment::CatchAll(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub(in crate::server) struct Pattern {
+    uri: String,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    /// Parses the uri given to a [`Binding`]. A `{name}` segment captures
+    /// exactly one segment, as does a `*` without capturing it. A final `*`,
+    /// `**`, `*name` or `**name` segment is a catch-all of every remaining
+    /// segment. Everything else is matched literally, once percent-decoded.
+    ///
+    /// # Panics:
+    /// If a named or `**` catch-all is used anywhere other than the final
+    /// segment.
+    ///
+    /// [`Binding`]: ../struct.Binding.html
+    pub(in crate::server) fn parse(uri: &str) -> Pattern {
+        let parts = uri.split('/').collect::<Vec<&str>>();
+        let segments = parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                let is_last = i == parts.len() - 1;
+                match part.strip_prefix('*') {
+                    Some("") if !is_last => Segment::Wildcard,
+                    Some(_) if !is_last => {
+                        panic!("Catch-all must be the last segment of: {}", uri)
+                    }
+                    Some(name) => {
+                        let name = name.strip_prefix('*').unwrap_or(name);
+                        let name = if name.is_empty() { WILDCARD } else { name };
+                        Segment::CatchAll(name.into())
+                    }
+                    None => match part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                        Some(name) => Segment::Param(name.into()),
+                        None => Segment::Static(percent_decode(part).into()),
+                    },
+                }
+            })
+            .collect();
+        Pattern {
+            uri: uri.into(),
+            segments,
+        }
+    }
+
+    pub(in crate::server) fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// The same pattern without its trailing slash, if it has one. The root
+    /// `/` is left as is.
+    pub(in crate::server) fn without_trailing_slash(&self) -> Pattern {
+        let mut pattern = self.clone();
+        if pattern.segments.len() > 2
+            && pattern.segments.last() == Some(&Segment::Static("".into()))
+        {
+            pattern.segments.pop();
+            pattern.uri.pop();
+        }
+        pattern
+    }
+
+    /// Specificity of this pattern, an exact pattern always ranks before one
+    /// capturing at the same position.
+    pub(in crate::server) fn rank(&self) -> Vec<u8> {
+        self.segments.iter().map(Segment::rank).collect()
+    }
+
+    /// Two patterns conflict when they would match the very same paths.
+    pub(in crate::server) fn conflicts_with(&self, other: &Pattern) -> bool {
+        self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(other.segments.iter())
+                .all(|(a, b)| a.overlaps(b))
+    }
+
+    /// Matches a request path against this pattern, returning the captured
+    /// path params if it matches. Each segment of the path is percent-decoded
+    /// before being compared or captured.
+    pub(in crate::server) fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut params = HashMap::new();
+        let mut parts = path.split('/');
+        for segment in &self.segments {
+            match segment {
+                Segment::Static(expected) => {
+                    if percent_decode(parts.next()?) != *expected {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => match parts.next()? {
+                    "" => return None,
+                    value => {
+                        params.insert(name.clone(), percent_decode(value).into());
+                    }
+                },
+                Segment::Wildcard => {
+                    if parts.next()?.is_empty() {
+                        return None;
+                    }
+                }
+                Segment::CatchAll(name) => {
+                    let rest = parts.by_ref().collect::<Vec<&str>>().join("/");
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    params.insert(name.clone(), percent_decode(&rest).into());
+                }
+            }
+        }
+        match parts.next() {
+            Some(_) => None,
+            None => Some(params),
+        }
+    }
+
+    /// The path this pattern matches with the params captured as given,
+    /// each percent-encoded, a catch-all segment by segment.
+    ///
+    /// # Returns:
+    /// `None` if a param is missing or empty, or the pattern has a `*` short
+    /// of its final segment, which captures nothing to fill it with.
+    pub(in crate::server) fn build(&self, params: &[(&str, &str)]) -> Option<String> {
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+                .filter(|value| !value.is_empty())
+        };
+        let parts = self
+            .uri
+            .split('/')
+            .zip(&self.segments)
+            .map(|(part, segment)| match segment {
+                Segment::Static(_) => Some(part.into()),
+                Segment::Param(name) => Some(encode_segment(param(name)?)),
+                Segment::Wildcard => None,
+                Segment::CatchAll(name) => Some(
+                    param(name)?
+                        .split('/')
+                        .map(encode_segment)
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                ),
+            })
+            .collect::<Option<Vec<String>>>()?;
+        Some(parts.join("/"))
+    }
+}
+
+/// The segment percent-encoded, a `.` or `..` included, so that it is not
+/// taken for a dot segment.
+fn encode_segment(segment: &str) -> String {
+    match segment {
+        "." | ".." => segment.replace('.', "%2E"),
+        _ => percent_encode(segment),
+    }
+}
+
+/// The path normalized as asked, see [`PathNormalization`]. Only a path
+/// starting with `/` is normalized, a trailing slash being kept.
+///
+/// # Returns:
+/// `None` when the path is normalized as it is.
+///
+/// [`PathNormalization`]: ../struct.PathNormalization.html
+pub(in crate::server) fn normalize(path: &str, normalization: PathNormalization) -> Option<String> {
+    let PathNormalization {
+        merge_slashes,
+        resolve_dot_segments,
+    } = normalization;
+    let rest = path.strip_prefix('/')?;
+    let mut segments: Vec<&str> = Vec::new();
+    let mut parts = rest.split('/').peekable();
+    while let Some(segment) = parts.next() {
+        let is_last = parts.peek().is_none();
+        let dots = match resolve_dot_segments {
+            true => percent_decode(segment),
+            false => "".into(),
+        };
+        match (segment, dots.as_ref()) {
+            ("", _) if merge_slashes && !is_last => {}
+            (_, ".") => {}
+            (_, "..") => {
+                segments.pop();
+            }
+            (segment, _) => segments.push(segment),
+        }
+        // A path ending in a dot segment names a directory, the same as one
+        // ending in a slash.
+        if is_last && matches!(dots.as_ref(), "." | "..") {
+            segments.push("");
+        }
+    }
+    let normalized = format!("/{}", segments.join("/"));
+    (normalized != path).then_some(normalized)
+}
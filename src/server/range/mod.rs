@@ -0,0 +1,340 @@
+//! Answering a request for only some ranges of the bytes of a body, such as
+//! to seek within a video or to resume a download, with a 206 of just those.
+//! See [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-14).
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io::{self, Cursor, Read};
+
+use crate::web::date::parse_http_date;
+use crate::web::{header_value, Body, HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+use super::{Middleware, Next};
+
+/// The most ranges a request is answered with, any more being taken for an
+/// attempt at making the response far larger than the body.
+const MAX_RANGES: usize = 16;
+
+/// [`Middleware`] answering a `GET` with a `Range` of bytes with a 206 of
+/// only those bytes, several ranges being sent as `multipart/byteranges`,
+/// and a range starting past the end with a 416. Every 200 it could answer
+/// so is told to have `Accept-Ranges: bytes`.
+///
+/// That is any with a body in memory, or a streamed one whose length the
+/// callback gave as its `Content-Length`, a stream being read through up to
+/// the ranges. Overlapping ranges are merged and sent in order, and a range
+/// whose `If-Range` no longer holds is answered with the whole body instead.
+/// Files served through [`static_files`] are answered with ranges without
+/// this, seeking to the first rather than reading through to it.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{RangeRequests, Server};
+/// let mut server = Server::default();
+/// server.wrap(RangeRequests::new());
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`static_files`]: ./static_files/index.html
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct RangeRequests;
+
+impl RangeRequests {
+    pub fn new() -> RangeRequests {
+        RangeRequests
+    }
+}
+
+impl Middleware for RangeRequests {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let range_request = RangeRequest::of(&request);
+        let mut response = next.run(request);
+        if response.status_code != StatusCode::Ok {
+            return response;
+        }
+        let len = match &response.body {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::Stream(_) => match response.stream_len() {
+                Some(len) => len,
+                None => return response,
+            },
+            Body::Empty | Body::Upgrade(_) => return response,
+        };
+        response
+            .headers
+            .insert("Accept-Ranges".into(), "bytes".into());
+        let ranges = match range_request {
+            Some(range_request) => range_request.ranges(&response.headers, len),
+            None => return response,
+        };
+        match ranges {
+            Ranges::Whole => response,
+            Ranges::Satisfiable(ranges) => partial(response, &ranges, len, 0),
+            Ranges::Unsatisfiable => unsatisfiable(len),
+        }
+    }
+}
+
+/// What of a body a request asks for.
+#[derive(PartialEq, Eq, Debug)]
+pub(in crate::server) enum Ranges {
+    /// All of it, the `Range` being ignored.
+    Whole,
+    /// The first and last byte of each range, in order and apart.
+    Satisfiable(Vec<(u64, u64)>),
+    /// Nothing it has.
+    Unsatisfiable,
+}
+
+/// The `Range` of a `GET`, along with its `If-Range`.
+pub(in crate::server) struct RangeRequest {
+    range: String,
+    if_range: Option<String>,
+}
+
+impl RangeRequest {
+    pub(in crate::server) fn of(request: &HttpRequest) -> Option<RangeRequest> {
+        if request.http_method != HttpMethod::Get {
+            return None;
+        }
+        Some(RangeRequest {
+            range: request.header("Range")?.into(),
+            if_range: request.header("If-Range").map(String::from),
+        })
+    }
+
+    /// The ranges asked for of a body of the length, as long as the response
+    /// is still what its `If-Range` has, going by the headers of it.
+    pub(in crate::server) fn ranges(&self, headers: &HashMap<String, String>, len: u64) -> Ranges {
+        match &self.if_range {
+            Some(if_range) if !is_unchanged(if_range, headers) => Ranges::Whole,
+            _ => parse(&self.range, len),
+        }
+    }
+}
+
+/// Whether the response is the same the `If-Range` was taken from, which an
+/// `ETag` is only taken for when strong.
+fn is_unchanged(if_range: &str, headers: &HashMap<String, String>) -> bool {
+    if if_range.starts_with('"') {
+        return header_value(headers, "ETag") == Some(if_range);
+    }
+    if if_range.starts_with("W/") {
+        return false;
+    }
+    let last_modified = header_value(headers, "Last-Modified").and_then(parse_http_date);
+    last_modified.is_some() && last_modified == parse_http_date(if_range)
+}
+
+fn parse(range: &str, len: u64) -> Ranges {
+    let specs = match range.split_once('=') {
+        Some((unit, specs)) if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+        _ => return Ranges::Whole,
+    };
+    let specs = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect::<Vec<_>>();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return Ranges::Whole;
+    }
+    let mut ranges = Vec::new();
+    for spec in specs {
+        let (first, last) = match spec.split_once('-') {
+            Some(bounds) if spec.bytes().all(|b| b.is_ascii_digit() || b == b'-') => bounds,
+            _ => return Ranges::Whole,
+        };
+        let range = match (first.parse::<u64>(), last.parse::<u64>()) {
+            // The last so many bytes.
+            (Err(_), Ok(suffix)) if first.is_empty() => {
+                (suffix > 0 && len > 0).then(|| (len.saturating_sub(suffix), len - 1))
+            }
+            (Ok(first), Err(_)) if last.is_empty() => (first < len).then(|| (first, len - 1)),
+            (Ok(first), Ok(last)) if first <= last => {
+                (first < len).then(|| (first, last.min(len - 1)))
+            }
+            _ => return Ranges::Whole,
+        };
+        ranges.extend(range);
+    }
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match merged.last_mut() {
+            Some(previous) if first <= previous.1.saturating_add(1) => {
+                previous.1 = previous.1.max(last);
+            }
+            _ => merged.push((first, last)),
+        }
+    }
+    Ranges::Satisfiable(merged)
+}
+
+/// The 206 of only the ranges of the body of the response, whose length is
+/// given. A streamed body is read from the position it is at, which is no
+/// further than the first byte of the first range.
+pub(in crate::server) fn partial(
+    mut response: HttpResponse,
+    ranges: &[(u64, u64)],
+    len: u64,
+    position: u64,
+) -> HttpResponse {
+    response
+        .headers
+        .retain(|key, _| !key.eq_ignore_ascii_case("Content-Length"));
+    let (heads, tail) = match ranges {
+        [(first, last)] => {
+            response.headers.insert(
+                "Content-Range".into(),
+                format!("bytes {}-{}/{}", first, last, len),
+            );
+            (vec![Vec::new()], Vec::new())
+        }
+        _ => {
+            let boundary = match generate_boundary() {
+                Some(boundary) => boundary,
+                None => return HttpResponse::new(StatusCode::InternalServerError),
+            };
+            let content_type = header_value(&response.headers, "Content-Type")
+                .map(|content_type| format!("Content-Type: {}\r\n", content_type))
+                .unwrap_or_default();
+            let heads = ranges
+                .iter()
+                .enumerate()
+                .map(|(i, (first, last))| {
+                    format!(
+                        "{}--{}\r\n{}Content-Range: bytes {}-{}/{}\r\n\r\n",
+                        if i == 0 { "" } else { "\r\n" },
+                        boundary,
+                        content_type,
+                        first,
+                        last,
+                        len
+                    )
+                    .into_bytes()
+                })
+                .collect();
+            response
+                .headers
+                .retain(|key, _| !key.eq_ignore_ascii_case("Content-Type"));
+            response.headers.insert(
+                "Content-Type".into(),
+                format!("multipart/byteranges; boundary={}", boundary),
+            );
+            (heads, format!("\r\n--{}--\r\n", boundary).into_bytes())
+        }
+    };
+    let parts = ranges
+        .iter()
+        .zip(heads)
+        .map(|((first, last), head)| Part {
+            head: Cursor::new(head),
+            first: *first,
+            last: *last,
+        })
+        .collect();
+    response.status_code = StatusCode::PartialContent;
+    response.body = match response.body {
+        Body::Bytes(bytes) => {
+            let mut parts = Parts::new(Box::new(Cursor::new(bytes)), 0, parts, tail);
+            let mut partial = Vec::new();
+            match parts.read_to_end(&mut partial) {
+                Ok(_) => Body::Bytes(partial),
+                Err(_) => return HttpResponse::new(StatusCode::InternalServerError),
+            }
+        }
+        Body::Stream(reader) => Body::Stream(Box::new(Parts::new(reader, position, parts, tail))),
+        body => body,
+    };
+    response
+}
+
+/// The 416 to a request for nothing a body of the length has.
+pub(in crate::server) fn unsatisfiable(len: u64) -> HttpResponse {
+    let mut response = HttpResponse::new(StatusCode::RangeNotSatisfiable);
+    response
+        .headers
+        .insert("Content-Range".into(), format!("bytes */{}", len));
+    response
+}
+
+/// 16 random bytes as hex, which no body holds by chance.
+fn generate_boundary() -> Option<String> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).ok()?;
+    Some(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Reads each range of a reader after its head, then the tail, reading
+/// through whatever comes between the ranges.
+struct Parts {
+    reader: Box<dyn Read + Send>,
+    position: u64,
+    parts: VecDeque<Part>,
+    tail: Cursor<Vec<u8>>,
+}
+
+struct Part {
+    head: Cursor<Vec<u8>>,
+    first: u64,
+    last: u64,
+}
+
+impl Parts {
+    fn new(
+        reader: Box<dyn Read + Send>,
+        position: u64,
+        parts: VecDeque<Part>,
+        tail: Vec<u8>,
+    ) -> Parts {
+        Parts {
+            reader,
+            position,
+            parts,
+            tail: Cursor::new(tail),
+        }
+    }
+}
+
+impl Read for Parts {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let part = match self.parts.front_mut() {
+                Some(part) => part,
+                None => return self.tail.read(buf),
+            };
+            let read = part.head.read(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+            if self.position > part.last {
+                self.parts.pop_front();
+                continue;
+            }
+            if self.position < part.first {
+                let gap = part.first - self.position;
+                let skipped = io::copy(&mut (&mut self.reader).take(gap), &mut io::sink())?;
+                if skipped < gap {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.position = part.first;
+            }
+            let left = part.last + 1 - self.position;
+            let max = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+            let read = self.reader.read(&mut buf[..max])?;
+            if read == 0 && max > 0 {
+                // Shorter than its length was said to be.
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.position += read as u64;
+            return Ok(read);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
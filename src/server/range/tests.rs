@@ -0,0 +1,189 @@
+use std::io::Cursor;
+
+use crate::server::range::{parse, Ranges};
+use crate::server::{RangeRequests, Route, Server};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+#[test]
+fn should_parse_ranges_within_length_when_satisfiable() {
+    let satisfiable = |ranges: &[(u64, u64)]| Ranges::Satisfiable(ranges.to_vec());
+    assert_eq!(parse("bytes=0-99", 1000), satisfiable(&[(0, 99)]));
+    assert_eq!(parse("bytes=900-", 1000), satisfiable(&[(900, 999)]));
+    assert_eq!(parse("bytes=-100", 1000), satisfiable(&[(900, 999)]));
+    assert_eq!(parse("bytes=-5000", 1000), satisfiable(&[(0, 999)]));
+    assert_eq!(parse("bytes=990-2000", 1000), satisfiable(&[(990, 999)]));
+    assert_eq!(
+        parse("Bytes=500-599, 0-9, 2000-3000", 1000),
+        satisfiable(&[(0, 9), (500, 599)])
+    );
+    // Overlapping or adjacent ranges are sent as one.
+    assert_eq!(
+        parse("bytes=0-9,5-19,20-29,40-49", 1000),
+        satisfiable(&[(0, 29), (40, 49)])
+    );
+}
+
+#[test]
+fn should_not_parse_ranges_when_none_are_satisfiable() {
+    assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+    assert_eq!(parse("bytes=2000-3000, -0", 1000), Ranges::Unsatisfiable);
+    assert_eq!(parse("bytes=-10", 0), Ranges::Unsatisfiable);
+}
+
+#[test]
+fn should_ignore_range_when_malformed() {
+    for range in [
+        "bytes=",
+        "bytes=9-0",
+        "bytes=a-b",
+        "bytes=+1-5",
+        "bytes=1-2-3",
+        "bytes=5",
+        "items=0-9",
+        "0-9",
+        "bytes=0-0,1-1,2-2,3-3,4-4,5-5,6-6,7-7,8-8,9-9,10-10,11-11,12-12,13-13,14-14,15-15,16-16",
+    ] {
+        assert_eq!(parse(range, 1000), Ranges::Whole, "{}", range);
+    }
+}
+
+fn digits() -> String {
+    (0..100).map(|i| char::from(b'0' + i % 10)).collect()
+}
+
+fn ranged_server() -> Server {
+    let mut server = Server::default();
+    server.wrap(RangeRequests::new());
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/digits", |_| {
+                    let mut response = HttpResponse::text(&digits());
+                    response.headers.insert("ETag".into(), "\"v1\"".into());
+                    response
+                })
+                .to("/stream", |_| {
+                    let mut response = HttpResponse::ok_with_body(Body::Stream(Box::new(
+                        Cursor::new(digits().into_bytes()),
+                    )));
+                    response
+                        .headers
+                        .insert("Content-Length".into(), "100".into());
+                    response
+                })
+                .to("/unknown", |_| {
+                    HttpResponse::ok_with_body(Body::Stream(Box::new(Cursor::new(
+                        digits().into_bytes(),
+                    ))))
+                })
+        })
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/digits", |_| HttpResponse::text(&digits())))
+        .unwrap();
+    server
+}
+
+fn request(client: &TestClient, request_line: &str, headers: &str) -> TestResponse {
+    client.send(HttpRequest::from(
+        format!("{} HTTP/1.1\r\n{}\r\n", request_line, headers).as_str(),
+    ))
+}
+
+fn header<'a>(response: &'a HttpResponse, name: &str) -> Option<&'a str> {
+    response.headers.get(name).map(String::as_str)
+}
+
+#[test]
+fn should_answer_partial_content_when_single_range_is_requested() {
+    let client = TestClient::new(ranged_server());
+    let response = request(&client, "GET /digits", "Range: bytes=10-14\r\n");
+    assert_eq!(response.status_code, StatusCode::PartialContent);
+    assert_eq!(header(&response, "Content-Range"), Some("bytes 10-14/100"));
+    assert_eq!(
+        header(&response, "Content-Type"),
+        Some("text/plain; charset=utf-8")
+    );
+    assert_eq!(response.body, Body::from("01234"));
+    let response = request(&client, "GET /stream", "Range: bytes=-3\r\n");
+    assert_eq!(response.status_code, StatusCode::PartialContent);
+    assert_eq!(header(&response, "Content-Range"), Some("bytes 97-99/100"));
+    assert_eq!(header(&response, "Content-Length"), None);
+    assert_eq!(response.body_bytes(), b"789");
+}
+
+#[test]
+fn should_answer_multipart_byteranges_when_several_ranges_are_requested() {
+    let client = TestClient::new(ranged_server());
+    for path in ["/digits", "/stream"] {
+        let response = request(
+            &client,
+            &format!("GET {}", path),
+            "Range: bytes=95-, 2-3\r\n",
+        );
+        assert_eq!(response.status_code, StatusCode::PartialContent);
+        let content_type = header(&response, "Content-Type").unwrap().to_string();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        assert_eq!(boundary.len(), 32);
+        let part_type = match path {
+            "/digits" => "Content-Type: text/plain; charset=utf-8\r\n",
+            _ => "",
+        };
+        let expected = format!(
+            "--{b}\r\n{t}Content-Range: bytes 2-3/100\r\n\r\n23\
+             \r\n--{b}\r\n{t}Content-Range: bytes 95-99/100\r\n\r\n56789\
+             \r\n--{b}--\r\n",
+            b = boundary,
+            t = part_type
+        );
+        assert_eq!(response.body_text(), expected);
+    }
+}
+
+#[test]
+fn should_answer_range_not_satisfiable_when_range_starts_past_end() {
+    let response = request(
+        &TestClient::new(ranged_server()),
+        "GET /digits",
+        "Range: bytes=100-\r\n",
+    );
+    assert_eq!(response.status_code, StatusCode::RangeNotSatisfiable);
+    assert_eq!(header(&response, "Content-Range"), Some("bytes */100"));
+}
+
+#[test]
+fn should_answer_whole_body_when_if_range_no_longer_holds() {
+    let client = TestClient::new(ranged_server());
+    let current = request(
+        &client,
+        "GET /digits",
+        "Range: bytes=0-0\r\nIf-Range: \"v1\"\r\n",
+    );
+    assert_eq!(current.status_code, StatusCode::PartialContent);
+    for if_range in ["\"v0\"", "W/\"v1\"", "Sun, 06 Nov 1994 08:49:37 GMT"] {
+        let response = request(
+            &client,
+            "GET /digits",
+            &format!("Range: bytes=0-0\r\nIf-Range: {}\r\n", if_range),
+        );
+        assert_eq!(response.status_code, StatusCode::Ok, "{}", if_range);
+        assert_eq!(response.body_bytes().len(), 100);
+    }
+}
+
+#[test]
+fn should_only_accept_ranges_when_length_of_get_body_is_known() {
+    let client = TestClient::new(ranged_server());
+    let response = request(&client, "GET /digits", "");
+    assert_eq!(header(&response, "Accept-Ranges"), Some("bytes"));
+    let unknown = request(&client, "GET /unknown", "Range: bytes=0-9\r\n");
+    assert_eq!(unknown.status_code, StatusCode::Ok);
+    assert_eq!(header(&unknown, "Accept-Ranges"), None);
+    assert_eq!(unknown.body_bytes().len(), 100);
+    let posted = request(&client, "POST /digits", "Range: bytes=0-9\r\n");
+    assert_eq!(posted.status_code, StatusCode::Ok);
+    assert_eq!(posted.body_bytes().len(), 100);
+}
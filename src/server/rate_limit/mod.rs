@@ -0,0 +1,160 @@
+//! Keeping any one client from making more requests than its share, through a
+//! [token bucket](https://en.wikipedia.org/wiki/Token_bucket) of its own.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::web::{HttpRequest, HttpResponse, StatusCode};
+
+use super::{Middleware, Next};
+
+type KeyFn = Box<dyn Fn(&HttpRequest) -> Option<String> + Send + Sync>;
+
+/// How many buckets are kept before those which have filled back up are
+/// dropped, at the least.
+const SWEEP_AT: usize = 1024;
+
+/// [`Middleware`] answering with a 429 and a `Retry-After` once a client runs
+/// out of requests. Every client has a bucket of as many requests as the
+/// burst, which each of its requests takes one from, and which is given back
+/// one every refill period, up to the burst again.
+///
+/// Clients are told apart by the ip of their connection, unless told apart
+/// through a [`key`] of their own. Requests without a key, such as those not
+/// read off of a connection, are never limited.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{RateLimit, Server};
+/// use std::time::Duration;
+/// let mut server = Server::default();
+/// // Bursts of up to 20 requests, then one every 100ms.
+/// server.wrap(RateLimit::new(20, Duration::from_millis(100)));
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`key`]: ./struct.RateLimit.html#method.key
+pub struct RateLimit {
+    burst: u32,
+    refill: Duration,
+    key: KeyFn,
+    buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    sweep_at: usize,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimit {
+    /// # Panics:
+    /// If the burst is 0 or the refill period is zero.
+    pub fn new(burst: u32, refill: Duration) -> RateLimit {
+        assert!(burst > 0, "a rate limit needs a burst of at least 1");
+        assert!(!refill.is_zero(), "a rate limit needs a refill period");
+        RateLimit {
+            burst,
+            refill,
+            key: Box::new(|request: &HttpRequest| {
+                let connection = request.connection.as_ref()?;
+                Some(connection.peer_addr.ip().to_string())
+            }),
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                sweep_at: SWEEP_AT,
+            }),
+        }
+    }
+
+    /// Tells clients apart by the key, such as a user or api key, in place of
+    /// the ip of their connection. A request the key is `None` for is not
+    /// limited.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::server::RateLimit;
+    /// use martian::web::HttpRequest;
+    /// use std::time::Duration;
+    /// let rate_limit = RateLimit::new(100, Duration::from_secs(1))
+    ///     .key(|request: &HttpRequest| request.header("X-Api-Key").map(String::from));
+    /// ```
+    pub fn key<F>(mut self, key: F) -> RateLimit
+    where
+        F: Fn(&HttpRequest) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+
+    /// Takes a request from the bucket of the key.
+    ///
+    /// # Returns:
+    /// How long until the bucket has a request to take, if it is empty.
+    fn take(&self, key: String) -> Result<(), Duration> {
+        let now = Instant::now();
+        let burst = f64::from(self.burst);
+        let refill = self.refill.as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.buckets.len() >= buckets.sweep_at {
+            // Full buckets are no different from new ones.
+            buckets.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled).as_secs_f64() / refill < burst
+            });
+            buckets.sweep_at = SWEEP_AT.max(buckets.buckets.len() * 2);
+        }
+        let bucket = buckets.buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            refilled: now,
+        });
+        let refilled = now.duration_since(bucket.refilled).as_secs_f64() / refill;
+        bucket.tokens = (bucket.tokens + refilled).min(burst);
+        bucket.refilled = now;
+        match bucket.tokens >= 1.0 {
+            true => {
+                bucket.tokens -= 1.0;
+                Ok(())
+            }
+            false => Err(Duration::from_secs_f64((1.0 - bucket.tokens) * refill)),
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let key = match (self.key)(&request) {
+            Some(key) => key,
+            None => return next.run(request),
+        };
+        match self.take(key) {
+            Ok(()) => next.run(request),
+            Err(retry_after) => {
+                let mut response = HttpResponse::new(StatusCode::TooManyRequests);
+                // In whole seconds, rounded up so as not to be retried too soon.
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                response
+                    .headers
+                    .insert("Retry-After".into(), seconds.max(1).to_string());
+                response
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("burst", &self.burst)
+            .field("refill", &self.refill)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,113 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::server::{RateLimit, Route, Server};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{ConnectionInfo, HttpMethod, HttpRequest, StatusCode};
+
+fn limited_client(rate_limit: RateLimit) -> TestClient {
+    let mut server = Server::default();
+    server.wrap(rate_limit);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "hello"))
+        .unwrap();
+    TestClient::new(server)
+}
+
+fn get_from(client: &TestClient, peer_addr: &str) -> TestResponse {
+    let mut request = HttpRequest::from("GET / HTTP/1.1\r\n\r\n");
+    request.connection = Some(ConnectionInfo {
+        peer_addr: peer_addr.parse().unwrap(),
+        local_addr: "10.0.0.2:80".parse().unwrap(),
+        is_tls: false,
+        peer_identity: None,
+        server_name: None,
+    });
+    client.send(request)
+}
+
+#[test]
+fn should_answer_too_many_requests_when_burst_is_used_up() {
+    let client = limited_client(RateLimit::new(2, Duration::from_secs(30)));
+    assert_eq!(
+        get_from(&client, "10.0.0.1:4000").status_code,
+        StatusCode::Ok
+    );
+    assert_eq!(
+        get_from(&client, "10.0.0.1:4001").status_code,
+        StatusCode::Ok
+    );
+    let response = get_from(&client, "10.0.0.1:4002");
+    assert_eq!(response.status_code, StatusCode::TooManyRequests);
+    let retry_after = response.headers.get("Retry-After").unwrap();
+    assert!(
+        (29..=30).contains(&retry_after.parse::<u64>().unwrap()),
+        "{}",
+        retry_after
+    );
+}
+
+#[test]
+fn should_limit_each_client_apart_when_ips_differ() {
+    let client = limited_client(RateLimit::new(1, Duration::from_secs(30)));
+    assert_eq!(
+        get_from(&client, "10.0.0.1:4000").status_code,
+        StatusCode::Ok
+    );
+    assert_eq!(
+        get_from(&client, "10.0.0.3:4000").status_code,
+        StatusCode::Ok
+    );
+    assert_eq!(
+        get_from(&client, "10.0.0.1:4000").status_code,
+        StatusCode::TooManyRequests
+    );
+}
+
+#[test]
+fn should_allow_requests_again_when_bucket_refills() {
+    let client = limited_client(RateLimit::new(1, Duration::from_millis(20)));
+    assert_eq!(
+        get_from(&client, "10.0.0.1:4000").status_code,
+        StatusCode::Ok
+    );
+    let response = get_from(&client, "10.0.0.1:4000");
+    assert_eq!(response.status_code, StatusCode::TooManyRequests);
+    assert_eq!(response.headers.get("Retry-After").unwrap(), "1");
+    thread::sleep(Duration::from_millis(30));
+    assert_eq!(
+        get_from(&client, "10.0.0.1:4000").status_code,
+        StatusCode::Ok
+    );
+}
+
+#[test]
+fn should_limit_by_key_when_key_is_given() {
+    let client = limited_client(
+        RateLimit::new(1, Duration::from_secs(30))
+            .key(|request: &HttpRequest| request.header("X-Api-Key").map(String::from)),
+    );
+    let get = |api_key: Option<&str>| {
+        let header = api_key.map_or(String::new(), |api_key| {
+            format!("X-Api-Key: {}\r\n", api_key)
+        });
+        client
+            .send(HttpRequest::from(
+                format!("GET / HTTP/1.1\r\n{}\r\n", header).as_str(),
+            ))
+            .status_code
+    };
+    assert_eq!(get(Some("a")), StatusCode::Ok);
+    assert_eq!(get(Some("b")), StatusCode::Ok);
+    assert_eq!(get(Some("a")), StatusCode::TooManyRequests);
+    assert_eq!(get(None), StatusCode::Ok);
+    assert_eq!(get(None), StatusCode::Ok);
+}
+
+#[test]
+fn should_not_limit_request_when_it_has_no_connection() {
+    let client = limited_client(RateLimit::new(1, Duration::from_secs(30)));
+    for _ in 0..3 {
+        client.get("/").assert_status(StatusCode::Ok);
+    }
+}
@@ -0,0 +1,194 @@
+//! Stopping a listening [`Server`] from outside of it, letting whatever it
+//! is already serving finish first.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::io;
+use std::iter;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A handle for stopping a [`Server`] listening through
+/// [`Server::listen_until`] or [`HttpServer::start`]. Once triggered, no more
+/// connections are accepted and those already accepted are served before the
+/// listen returns. Clones share the same trigger, so one can be handed to
+/// another thread.
+///
+/// # Examples:
+/// ```no_run
+/// use martian::server::{Server, Shutdown};
+/// use std::thread;
+/// use std::time::Duration;
+/// let server = Server::default();
+/// let shutdown = Shutdown::new();
+/// let trigger = shutdown.clone();
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(60));
+///     trigger.trigger();
+/// });
+/// server.listen_until("127.0.0.1:8080", &shutdown).unwrap();
+/// ```
+///
+/// [`Server`]: ./struct.Server.html
+/// [`Server::listen_until`]: ./struct.Server.html#method.listen_until
+/// [`HttpServer::start`]: ./struct.HttpServer.html#method.start
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    triggered: AtomicBool,
+    /// The addresses to connect to for waking the listeners blocked on
+    /// accepting.
+    wake_addrs: Mutex<Vec<SocketAddr>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    /// Stops the listeners from accepting any more connections. Triggering
+    /// more than once has no further effect.
+    pub fn trigger(&self) {
+        self.inner.triggered.store(true, Ordering::SeqCst);
+        let wake_addrs = self.wake_addrs().clone();
+        for wake_addr in wake_addrs {
+            // The connection only serves to return from the blocking accept.
+            let _ = TcpStream::connect(wake_addr);
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.inner.triggered.load(Ordering::SeqCst)
+    }
+
+    /// The connections accepted by the listener, until triggered. The
+    /// listener is no longer woken once they are dropped.
+    pub(in crate::server) fn incoming<'a>(
+        &'a self,
+        listener: &'a TcpListener,
+    ) -> io::Result<impl Iterator<Item = TcpStream> + 'a> {
+        let mut wake_addr = listener.local_addr()?;
+        // A listener on every interface is reached through the loopback.
+        match wake_addr {
+            SocketAddr::V4(addr) if addr.ip().is_unspecified() => {
+                wake_addr.set_ip(Ipv4Addr::LOCALHOST.into())
+            }
+            SocketAddr::V6(addr) if addr.ip().is_unspecified() => {
+                wake_addr.set_ip(Ipv6Addr::LOCALHOST.into())
+            }
+            _ => {}
+        }
+        self.wake_addrs().push(wake_addr);
+        let woken = Woken {
+            shutdown: self,
+            wake_addr,
+        };
+        // Checking before accepting covers a trigger made before the wake
+        // address was known, checking after drops the waking connection.
+        let mut incoming = listener.incoming();
+        Ok(iter::from_fn(move || loop {
+            if woken.shutdown.is_triggered() {
+                return None;
+            }
+            match incoming.next()? {
+                Ok(_) if woken.shutdown.is_triggered() => return None,
+                Ok(stream) => return Some(stream),
+                Err(_) => continue,
+            }
+        }))
+    }
+
+    fn wake_addrs(&self) -> MutexGuard<'_, Vec<SocketAddr>> {
+        self.inner
+            .wake_addrs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Triggers on `SIGINT` or `SIGTERM`, such as a Ctrl-C in the terminal.
+    /// A second signal after that is left to terminate the process as it
+    /// normally would, for when serving what is left takes too long.
+    ///
+    /// # Returns:
+    /// An `Err` if the signals could not be listened for.
+    #[cfg(all(unix, feature = "signals"))]
+    pub fn on_signals(&self) -> io::Result<()> {
+        signals::register(self.clone())
+    }
+}
+
+/// The wake address of a listener, for as long as it is listened on.
+struct Woken<'a> {
+    shutdown: &'a Shutdown,
+    wake_addr: SocketAddr,
+}
+
+impl Drop for Woken<'_> {
+    fn drop(&mut self) {
+        let mut wake_addrs = self.shutdown.wake_addrs();
+        // Another listener may have been bound to the same address since.
+        if let Some(i) = wake_addrs.iter().position(|addr| *addr == self.wake_addr) {
+            wake_addrs.remove(i);
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "signals"))]
+mod signals {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::unix::io::FromRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+
+    use super::Shutdown;
+
+    /// The write end of the pipe the signal handler wakes the watching
+    /// thread through, writing being one of the few things safe to do there.
+    static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+    static REGISTERED: Mutex<Vec<Shutdown>> = Mutex::new(Vec::new());
+
+    pub(super) fn register(shutdown: Shutdown) -> io::Result<()> {
+        let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+        if WAKE_FD.load(Ordering::SeqCst) < 0 {
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut wake = unsafe { File::from_raw_fd(fds[0]) };
+            WAKE_FD.store(fds[1], Ordering::SeqCst);
+            thread::spawn(move || {
+                let mut signal = [0; 1];
+                if wake.read_exact(&mut signal).is_ok() {
+                    set_handler(libc::SIG_DFL);
+                    let registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+                    registered.iter().for_each(Shutdown::trigger);
+                }
+            });
+            set_handler(on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+        registered.push(shutdown);
+        Ok(())
+    }
+
+    fn set_handler(handler: libc::sighandler_t) {
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+
+    extern "C" fn on_signal(_: libc::c_int) {
+        let fd = WAKE_FD.load(Ordering::SeqCst);
+        let signal = [1u8];
+        unsafe {
+            libc::write(fd, signal.as_ptr().cast(), 1);
+        }
+    }
+}
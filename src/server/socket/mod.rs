@@ -0,0 +1,137 @@
+//! Binding the listeners of a [`Server`] with its [`SocketOptions`], tuning
+//! the socket before it is bound as `TcpListener::bind` gives no chance to.
+//!
+//! [`Server`]: ../struct.Server.html
+//! [`SocketOptions`]: ../struct.SocketOptions.html
+
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+#[cfg(feature = "log")]
+use super::logging;
+
+/// How the sockets of a [`Server`] are set up, both those it listens on and
+/// the connections it accepts through them. The defaults are those of
+/// `TcpListener::bind`.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Server, SocketOptions};
+/// let mut server = Server::with_workers(4);
+/// server.socket_options(SocketOptions {
+///     nodelay: true,
+///     backlog: 1024,
+///     ..SocketOptions::default()
+/// });
+/// ```
+///
+/// [`Server`]: ./struct.Server.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SocketOptions {
+    /// `SO_REUSEADDR`, so that the address can be bound again straight away
+    /// while the connections of a listener before it linger on. On by
+    /// default everywhere but Windows, where it lets the port be taken over
+    /// by another listener entirely.
+    pub reuse_address: bool,
+    /// `SO_REUSEPORT`, so that several processes can listen on the same port
+    /// at once, each being handed a share of the connections. Off by
+    /// default, and binding fails with it anywhere but unix.
+    pub reuse_port: bool,
+    /// `TCP_NODELAY` on every connection accepted, so that a response is
+    /// sent as soon as it is written rather than held back to be sent along
+    /// with more. Off by default.
+    pub nodelay: bool,
+    /// How many connections are queued up by the kernel while waiting to be
+    /// accepted, 128 by default.
+    pub backlog: i32,
+    /// `SO_RCVBUF` of the listener, passed on to the connections it accepts.
+    /// Left up to the kernel by default.
+    pub recv_buffer_size: Option<usize>,
+    /// `SO_SNDBUF` of the listener, passed on to the connections it accepts.
+    /// Left up to the kernel by default.
+    pub send_buffer_size: Option<usize>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> SocketOptions {
+        SocketOptions {
+            reuse_address: !cfg!(windows),
+            reuse_port: false,
+            nodelay: false,
+            backlog: 128,
+            recv_buffer_size: None,
+            send_buffer_size: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// Binds to the first of the addresses which can be bound to, the same as
+    /// `TcpListener::bind`.
+    ///
+    /// # Returns:
+    /// The error of the last address tried when none can be bound to.
+    pub(in crate::server) fn bind<A: ToSocketAddrs>(&self, addr: A) -> io::Result<TcpListener> {
+        let mut last_error = None;
+        for addr in addr.to_socket_addrs()? {
+            match self.bind_addr(addr) {
+                Ok(listener) => return Ok(listener),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| io::Error::new(ErrorKind::InvalidInput, "No address to bind to")))
+    }
+
+    fn bind_addr(&self, addr: SocketAddr) -> io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_reuse_address(self.reuse_address)?;
+        if self.reuse_port {
+            set_reuse_port(&socket)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        socket.bind(&addr.into())?;
+        socket.listen(self.backlog)?;
+        let listener = socket.into();
+        #[cfg(feature = "log")]
+        logging::bound(&listener);
+        Ok(listener)
+    }
+
+    /// Sets up a connection accepted by the `Server`, which is served
+    /// regardless of whether it could be.
+    pub(in crate::server) fn apply(&self, stream: &TcpStream) {
+        if self.nodelay {
+            let _ = stream.set_nodelay(true);
+        }
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+)))]
+fn set_reuse_port(_: &Socket) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,82 @@
+use crate::server::{HttpServer, Route, Server, SocketOptions};
+use crate::web::HttpMethod;
+use socket2::SockRef;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+#[cfg(unix)]
+#[test]
+fn should_bind_to_same_port_twice_when_reusing_port() {
+    let socket_options = SocketOptions {
+        reuse_port: true,
+        ..SocketOptions::default()
+    };
+    let first = socket_options.bind("127.0.0.1:0").unwrap();
+    let address = first.local_addr().unwrap();
+    let second = socket_options.bind(address).unwrap();
+    assert_eq!(second.local_addr().unwrap(), address);
+}
+
+#[test]
+fn should_fail_to_bind_to_same_port_twice_when_not_reusing_port() {
+    let socket_options = SocketOptions::default();
+    let first = socket_options.bind("127.0.0.1:0").unwrap();
+    assert!(socket_options.bind(first.local_addr().unwrap()).is_err());
+}
+
+#[test]
+fn should_set_buffer_sizes_when_given() {
+    let listener = SocketOptions {
+        recv_buffer_size: Some(64 * 1024),
+        send_buffer_size: Some(64 * 1024),
+        ..SocketOptions::default()
+    }
+    .bind("127.0.0.1:0")
+    .unwrap();
+    let socket = SockRef::from(&listener);
+    // The kernel is free to round the sizes up.
+    assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+}
+
+#[test]
+fn should_set_nodelay_on_accepted_stream_when_asked() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    SocketOptions::default().apply(&stream);
+    assert!(!stream.nodelay().unwrap());
+    SocketOptions {
+        nodelay: true,
+        ..SocketOptions::default()
+    }
+    .apply(&stream);
+    assert!(stream.nodelay().unwrap());
+}
+
+#[test]
+fn should_serve_on_listener_bound_with_socket_options() {
+    let mut server = Server::with_workers(1);
+    server.socket_options(SocketOptions {
+        nodelay: true,
+        backlog: 16,
+        ..SocketOptions::default()
+    });
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "hello"))
+        .unwrap();
+    let http_server = HttpServer::new(server).bind("127.0.0.1:0").unwrap();
+    let (address, shutdown) = (http_server.addr(), http_server.shutdown());
+    let started = thread::spawn(move || http_server.start());
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut raw_response = String::new();
+    stream.read_to_string(&mut raw_response).unwrap();
+    assert!(raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(raw_response.ends_with("hello"));
+    shutdown.trigger();
+    started.join().unwrap().unwrap();
+}
@@ -0,0 +1,260 @@
+//! Serving the files of a directory as they are, such as the stylesheets,
+//! scripts and images of a web app.
+
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::web::date::format_http_date;
+use crate::web::encoding::{escape_html, percent_encode};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, Mime};
+
+use super::conditional;
+use super::mount;
+use super::pattern::WILDCARD;
+use super::range::{self, RangeRequest, Ranges};
+use super::{Binding, Route};
+
+const INDEX: &str = "index.html";
+
+/// Binds `GET`, and with it `HEAD`, requests under `prefix` to the files
+/// under `dir`, with the defaults of [`StaticFiles`].
+///
+/// # Examples:
+/// ```
+/// use martian::server::{static_files, Server};
+/// let mut server = Server::default();
+/// server
+///     .route(|| static_files::serve("/assets", "./public"))
+///     .unwrap();
+/// ```
+///
+/// [`StaticFiles`]: ./struct.StaticFiles.html
+pub fn serve<P: Into<PathBuf>>(prefix: &str, dir: P) -> Binding {
+    StaticFiles::new(dir).bind(prefix)
+}
+
+/// The files of a directory, the rest of the request path being the path of
+/// the file. A file is streamed out with its `Content-Length` and a
+/// `Content-Type` following from its extension, along with a `Last-Modified`
+/// and a weak `ETag` following from its modification time and size. A request
+/// sending either back for a file which has not changed since is answered
+/// with a 304 instead, and one for a `Range` of bytes with a 206 of those, as
+/// [`RangeRequests`] would.
+///
+/// A request for a directory is answered with its `index.html`, if it has
+/// one, and otherwise with a 404 unless [`listing`] is enabled. So is a
+/// request for anything else which is not a file, or for a path stepping out
+/// of the directory through `..`, unless in [`spa`] mode.
+///
+/// # Examples:
+/// ```
+/// use martian::server::static_files::StaticFiles;
+/// use martian::server::Server;
+/// let mut server = Server::default();
+/// server
+///     .route(|| StaticFiles::new("./dist").spa().bind("/"))
+///     .unwrap();
+/// ```
+///
+/// [`RangeRequests`]: ../struct.RangeRequests.html
+/// [`listing`]: ./struct.StaticFiles.html#method.listing
+/// [`spa`]: ./struct.StaticFiles.html#method.spa
+#[derive(PartialEq, Debug, Clone)]
+pub struct StaticFiles {
+    dir: PathBuf,
+    index: Option<String>,
+    listing: bool,
+    spa: bool,
+}
+
+impl StaticFiles {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> StaticFiles {
+        StaticFiles {
+            dir: dir.into(),
+            index: Some(INDEX.into()),
+            listing: false,
+            spa: false,
+        }
+    }
+
+    /// The file answering a request for the directory it is in, rather than
+    /// `index.html`.
+    pub fn index(mut self, file_name: &str) -> StaticFiles {
+        self.index = Some(file_name.into());
+        self
+    }
+
+    /// Never answer a request for a directory with a file in it.
+    pub fn without_index(mut self) -> StaticFiles {
+        self.index = None;
+        self
+    }
+
+    /// Answer a request for a directory without an index with a page
+    /// linking to everything in it.
+    pub fn listing(mut self) -> StaticFiles {
+        self.listing = true;
+        self
+    }
+
+    /// Answer a request for anything which is not found with the index at
+    /// the top of the directory, so that a single page app can route on the
+    /// path itself.
+    pub fn spa(mut self) -> StaticFiles {
+        self.spa = true;
+        self
+    }
+
+    /// Binds `GET`, and with it `HEAD`, requests under `prefix` to these
+    /// files.
+    pub fn bind(self, prefix: &str) -> Binding {
+        let prefix = prefix.trim_end_matches('/');
+        let files = Arc::new(self);
+        let root_files = Arc::clone(&files);
+        Route::bind(HttpMethod::Get)
+            .to(&format!("{}/", prefix), move |request| {
+                root_files.respond(&request)
+            })
+            .to(&format!("{}/*", prefix), move |request| {
+                files.respond(&request)
+            })
+    }
+
+    fn respond(&self, request: &HttpRequest) -> HttpResponse {
+        let request_path = request.path_param(WILDCARD).unwrap_or_default();
+        let path = match relative_path(request_path) {
+            Some(relative_path) => self.dir.join(relative_path),
+            None => return HttpResponse::not_found(),
+        };
+        if path.is_dir() {
+            if let Some(response) = self.respond_with_dir(&path, request) {
+                return response;
+            }
+        } else if let Some(response) = respond_with_file(&path, request) {
+            return response;
+        }
+        let spa_index = self.dir.join(self.index.as_deref().unwrap_or(INDEX));
+        self.spa
+            .then(|| respond_with_file(&spa_index, request))
+            .flatten()
+            .unwrap_or_else(HttpResponse::not_found)
+    }
+
+    /// Its index or listing, redirecting to the path with a trailing `/`
+    /// first so that relative links within it resolve.
+    fn respond_with_dir(&self, dir: &Path, request: &HttpRequest) -> Option<HttpResponse> {
+        let uri_path = request.uri.path();
+        let index = self
+            .index
+            .as_ref()
+            .map(|index| dir.join(index))
+            .filter(|index| index.is_file());
+        if index.is_none() && !self.listing {
+            return None;
+        }
+        if !uri_path.ends_with('/') {
+            let location = mount::location(request, &format!("{}/", uri_path));
+            return Some(HttpResponse::redirect_permanent(&location));
+        }
+        match index {
+            Some(index) => respond_with_file(&index, request),
+            None => listing(dir, &mount::location(request, uri_path)).ok(),
+        }
+    }
+}
+
+/// The request path as a path relative to the served directory, `None` if it
+/// would step out of it.
+fn relative_path(request_path: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for segment in request_path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment if segment.contains(['\\', ':', '\0']) => return None,
+            segment => path.push(segment),
+        }
+    }
+    Some(path)
+}
+
+fn respond_with_file(path: &Path, request: &HttpRequest) -> Option<HttpResponse> {
+    let mut file = File::open(path).ok()?;
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let mut response = HttpResponse::ok().content_type(Mime::from_path(path));
+    if let Ok(modified) = metadata.modified() {
+        // Weak, as a file changed twice within the same second would keep
+        // the same `ETag`.
+        let secs = modified
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let etag = format!("W/\"{:x}-{:x}\"", secs, metadata.len());
+        response.headers.insert("ETag".into(), etag);
+        response
+            .headers
+            .insert("Last-Modified".into(), format_http_date(modified));
+    }
+    if conditional::is_fresh(request, &response.headers) {
+        return Some(conditional::not_modified(response));
+    }
+    response
+        .headers
+        .insert("Accept-Ranges".into(), "bytes".into());
+    let len = metadata.len();
+    let ranges = RangeRequest::of(request).map_or(Ranges::Whole, |range_request| {
+        range_request.ranges(&response.headers, len)
+    });
+    Some(match ranges {
+        Ranges::Whole => {
+            response.body = Body::Stream(Box::new(file));
+            response
+                .headers
+                .insert("Content-Length".into(), len.to_string());
+            response
+        }
+        Ranges::Satisfiable(ranges) => {
+            // Seeking past whatever comes before the first range, rather
+            // than reading through it.
+            let position = file.seek(SeekFrom::Start(ranges[0].0)).ok()?;
+            response.body = Body::Stream(Box::new(file));
+            range::partial(response, &ranges, len, position)
+        }
+        Ranges::Unsatisfiable => range::unsatisfiable(len),
+    })
+}
+
+/// A page linking to every entry of the directory, directories first.
+fn listing(dir: &Path, uri_path: &str) -> io::Result<HttpResponse> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            let is_dir = entry.file_type()?.is_dir();
+            Ok((!is_dir, entry.file_name().to_string_lossy().into_owned()))
+        })
+        .collect::<io::Result<Vec<(bool, String)>>>()?;
+    entries.sort();
+    let title = format!("Index of {}", escape_html(uri_path));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n\
+         <body>\n<h1>{}</h1>\n<ul>\n",
+        title, title
+    );
+    for (is_file, name) in entries {
+        let slash = if is_file { "" } else { "/" };
+        html.push_str(&format!(
+            "<li><a href=\"{}{}\">{}{}</a></li>\n",
+            percent_encode(&name),
+            slash,
+            escape_html(&name),
+            slash
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    Ok(HttpResponse::html(&html))
+}
@@ -1,50 +1,2615 @@
-use crate::server::{Route, Server};
-use crate::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+use crate::server::connection::Connection;
+use crate::server::{
+    static_files, static_files::StaticFiles, ContentTypeIs, DefaultHeaders, HeaderEquals,
+    HeaderPresent, KeepAlive, Limits, Middleware, Mounted, Next, PathNormalization, PathParam,
+    RequestLog, Route, RouteConflict, Server, Shutdown, Timeouts, TrailingSlash,
+};
+use crate::test::TestClient;
+use crate::web::websocket::Message;
+use crate::web::{
+    Body, ConnectionInfo, Event, HttpMethod, HttpRequest, HttpResponse, HttpVersion, MartianError,
+    Mime, QueryParams, SpooledBody, SseStream, State, StatusCode,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+use std::iter;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 fn test_get(_: HttpRequest) -> HttpResponse {
     HttpResponse {
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         status_code: StatusCode::Ok,
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Empty,
     }
 }
 
 fn test_bad_get(_: HttpRequest) -> HttpResponse {
     HttpResponse {
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         status_code: StatusCode::Ok,
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Empty,
     }
 }
 
 #[test]
 fn should_invoke_given_handler_function_when_request_has_correct_spec() {
     let expected_response = HttpResponse {
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         status_code: StatusCode::Ok,
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Empty,
     };
     let request = HttpRequest {
         http_method: HttpMethod::Get,
-        uri: "/".to_string(),
-        http_version: 1.1,
+        uri: "/".into(),
+        http_version: HttpVersion::Http1_1,
         headers: None,
         body: None,
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection: None,
     };
     let mut server = Server::default();
-    server.route(|| {
-        Route::bind(HttpMethod::Get)
-            .to("/", test_get)
-            .to("/bad", test_bad_get)
-    });
-    let actual_response = server.delegate(request).unwrap();
-    assert_eq!(actual_response, expected_response);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", test_get)
+                .to("/bad", test_bad_get)
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.send(request);
+    assert_eq!(actual_response.into_inner(), expected_response);
 }
 
 #[test]
 #[should_panic]
 fn should_panic_when_attempting_to_bind_to_path_already_bound() {
     let mut server = Server::default();
-    server.route(|| {
+    server.route_or_panic(|| {
         Route::bind(HttpMethod::Get)
             .to("/", test_get)
             .to("/", test_get)
     });
 }
+
+fn test_path_param(request: HttpRequest) -> HttpResponse {
+    HttpResponse {
+        http_version: HttpVersion::Http1_1,
+        status_code: match request.path_params.get("rest").map(String::as_str) {
+            Some("a/b.txt") => StatusCode::Ok,
+            _ => StatusCode::InternalServerError,
+        },
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Empty,
+    }
+}
+
+fn test_error(_: HttpRequest) -> HttpResponse {
+    HttpResponse {
+        http_version: HttpVersion::Http1_1,
+        status_code: StatusCode::InternalServerError,
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Empty,
+    }
+}
+
+fn request_to(uri: &str) -> HttpRequest {
+    HttpRequest {
+        http_method: HttpMethod::Get,
+        uri: uri.into(),
+        http_version: HttpVersion::Http1_1,
+        headers: None,
+        body: None,
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection: None,
+    }
+}
+
+#[test]
+fn should_expose_remainder_to_handler_when_catch_all_matches() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/files/*rest", test_path_param))
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.get("/files/a/b.txt");
+    assert_eq!(actual_response.status_code, StatusCode::Ok);
+}
+
+#[test]
+fn should_not_match_catch_all_when_no_segments_remain() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/files/*", test_get))
+        .unwrap();
+    let client = TestClient::new(server);
+    client.get("/files").assert_status(StatusCode::NotFound);
+    client.get("/files/").assert_status(StatusCode::NotFound);
+}
+
+#[test]
+fn should_prefer_exact_route_over_catch_all_regardless_of_bind_order() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/*", test_error)
+                .to("/files/*", test_error)
+                .to("/files/readme", test_get)
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.get("/files/readme");
+    assert_eq!(actual_response.status_code, StatusCode::Ok);
+}
+
+#[test]
+fn should_match_single_segment_when_wildcard_is_not_last() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/*/readme", test_get))
+        .unwrap();
+    let client = TestClient::new(server);
+    client.get("/docs/readme").assert_status(StatusCode::Ok);
+    client
+        .get("/docs/v1/readme")
+        .assert_status(StatusCode::NotFound);
+    client.get("//readme").assert_status(StatusCode::NotFound);
+}
+
+#[test]
+fn should_serve_sub_tree_when_route_ends_in_double_star() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/static/**", test_get)
+                .to("/assets/**rest", test_path_param)
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    client
+        .get("/static/css/site.css")
+        .assert_status(StatusCode::Ok);
+    let actual_response = client.get("/assets/a/b.txt");
+    assert_eq!(actual_response.status_code, StatusCode::Ok);
+}
+
+#[test]
+#[should_panic(expected = "Catch-all must be the last segment of: /**/readme")]
+fn should_panic_when_double_star_is_not_last() {
+    Route::bind(HttpMethod::Get).to("/**/readme", test_get);
+}
+
+fn test_user(request: HttpRequest) -> HttpResponse {
+    HttpResponse::text(&format!("user {}", request.path_param("id").unwrap()))
+}
+
+#[test]
+fn should_capture_path_param_when_route_has_named_segment() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/users/{id}", test_user))
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.get("/users/42");
+    assert_eq!(actual_response.body, Body::from("user 42"));
+    client.get("/users/").assert_status(StatusCode::NotFound);
+    client
+        .get("/users/42/posts")
+        .assert_status(StatusCode::NotFound);
+}
+
+#[test]
+fn should_prefer_exact_then_path_param_then_catch_all_when_all_match() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/users/*", test_error)
+                .to("/users/{id}", test_user)
+                .to("/users/me", test_get)
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let me_response = client.get("/users/me");
+    assert_eq!(me_response.status_code, StatusCode::Ok);
+    assert_eq!(me_response.body, Body::Empty);
+    let user_response = client.get("/users/7");
+    assert_eq!(user_response.body, Body::from("user 7"));
+}
+
+#[test]
+fn should_resolve_by_priority_then_specificity_then_guards_then_bind_order() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/files/{name}/raw", |_| "param")
+                .to("/files/*/raw", |_| "wildcard")
+                .guard(|_: &HttpRequest| true)
+                .to("/files/**", |_| "catch-all")
+                .to("/files/{name}/{format}", |_| "first guarded")
+                .guard(HeaderPresent("X-Format"))
+                .to("/files/{id}/{kind}", |_| "second guarded")
+                .guard(HeaderPresent("X-Format"))
+                .to("/files/{name}/{format}/**", |_| "overridden")
+                .priority(-1)
+                .to("/files/latest/**", |_| "prioritized")
+                .priority(1)
+                .guard(HeaderPresent("X-Latest"))
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let respond = |uri: &str, header: &str| {
+        let raw_request = format!("GET {} HTTP/1.1\r\n{}\r\n", uri, header);
+        client
+            .send(HttpRequest::from(raw_request.as_str()))
+            .into_inner()
+            .body
+    };
+    assert_eq!(respond("/files/a/raw", ""), Body::from("param"));
+    assert_eq!(respond("/files/a/pdf", ""), Body::from("catch-all"));
+    assert_eq!(
+        respond("/files/a/pdf", "X-Format: 1\r\n"),
+        Body::from("first guarded")
+    );
+    assert_eq!(
+        respond("/files/latest/raw", "X-Latest: 1\r\n"),
+        Body::from("prioritized")
+    );
+    assert_eq!(respond("/files/a/b/c", ""), Body::from("catch-all"));
+}
+
+#[test]
+fn should_conflict_when_path_params_differ_only_by_name() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/users/{id}", test_user))
+        .unwrap();
+    let actual_conflict = server
+        .route(|| Route::bind(HttpMethod::Get).to("/users/{name}", test_user))
+        .unwrap_err();
+    assert_eq!(actual_conflict.existing_uri, "/users/{id}");
+}
+
+#[test]
+fn should_conflict_when_binding_identical_catch_alls_on_same_method() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/files/*", test_get))
+        .unwrap();
+    let actual_conflict = server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/other", test_get)
+                .to("/files/*rest", test_bad_get)
+        })
+        .unwrap_err();
+    let expected_conflict = RouteConflict {
+        http_method: HttpMethod::Get,
+        existing_uri: "/files/*".into(),
+        new_uri: "/files/*rest".into(),
+    };
+    assert_eq!(actual_conflict, expected_conflict);
+    assert_eq!(
+        actual_conflict.to_string(),
+        "GET /files/*rest conflicts with already bound GET /files/*"
+    );
+    // Nothing of a conflicting binding is bound.
+    let client = TestClient::new(server);
+    client.get("/other").assert_status(StatusCode::NotFound);
+}
+
+/// An in memory connection, reading from the raw request and collecting the
+/// raw response written back.
+pub(in crate::server) struct TestStream {
+    input: Cursor<Vec<u8>>,
+    pub(in crate::server) output: Vec<u8>,
+}
+
+impl TestStream {
+    pub(in crate::server) fn of<B: AsRef<[u8]>>(raw_request: B) -> TestStream {
+        TestStream {
+            input: Cursor::new(raw_request.as_ref().to_vec()),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Read for TestStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for TestStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for TestStream {}
+
+fn test_echo(request: HttpRequest) -> HttpResponse {
+    HttpResponse {
+        http_version: HttpVersion::Http1_1,
+        status_code: StatusCode::Ok,
+        headers: HashMap::new(),
+        cookies: Vec::new(),
+        body: Body::Stream(Box::new(Cursor::new(request.body.unwrap()))),
+    }
+}
+
+#[test]
+fn should_stream_chunked_response_when_echoing_chunked_request() {
+    let mut server = Server::default();
+    server.default_headers(DefaultHeaders::none());
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", test_echo))
+        .unwrap();
+    let mut stream = TestStream::of(
+        "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+         6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n",
+    );
+    server.serve(&mut stream, None, None).unwrap();
+    assert_eq!(
+        String::from_utf8(stream.output).unwrap(),
+        "HTTP/1.1 200 OK\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n\
+         B\r\nhello world\r\n0\r\n\r\n"
+    );
+}
+
+#[test]
+fn should_stream_unframed_response_when_echoing_http_1_0_request() {
+    let mut server = Server::default();
+    server.default_headers(DefaultHeaders::none());
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", test_echo))
+        .unwrap();
+    let mut stream = TestStream::of("POST /echo HTTP/1.0\r\nContent-Length: 5\r\n\r\nhello");
+    server.serve(&mut stream, None, None).unwrap();
+    assert_eq!(
+        String::from_utf8(stream.output).unwrap(),
+        "HTTP/1.0 200 OK\r\nConnection: close\r\n\r\nhello"
+    );
+}
+
+#[test]
+fn should_echo_binary_body_unchanged_when_request_has_content_length() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", test_echo))
+        .unwrap();
+    let mut raw_request = b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\n".to_vec();
+    raw_request.extend([0xff, 0x00, b'\r', 0x89]);
+    let mut stream = TestStream::of(raw_request);
+    server.serve(&mut stream, None, None).unwrap();
+    assert!(stream.output.ends_with(b"4\r\n\xff\x00\r\x89\r\n0\r\n\r\n"));
+}
+
+#[test]
+fn should_respond_bad_request_when_chunk_size_is_malformed() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", test_echo))
+        .unwrap();
+    let mut stream = TestStream::of(
+        "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nnope\r\nhello\r\n0\r\n\r\n",
+    );
+    server.serve(&mut stream, None, None).unwrap();
+    assert!(stream.output.starts_with(b"HTTP/1.1 400 Bad Request\r\n"));
+}
+
+#[test]
+fn should_respond_bad_request_when_chunk_runs_past_its_size() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", test_echo))
+        .unwrap();
+    let raw_response = serve_to_string(
+        &server,
+        "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nhello\r\n0\r\n\r\n",
+    );
+    assert!(raw_response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+}
+
+/// Serves the request followed by a `GET /secret` on a connection kept alive,
+/// as a request smuggled past a proxy framing the first differently would be.
+fn serve_smuggled(first_request: &str) -> String {
+    let mut server = Server::with_workers(2);
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/", test_echo))
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/secret", |_| "secret"))
+        .unwrap();
+    serve_to_string(
+        &server,
+        &format!("{}GET /secret HTTP/1.1\r\n\r\n", first_request),
+    )
+}
+
+#[test]
+fn should_respond_bad_request_and_close_when_content_length_is_malformed() {
+    let raw_response = serve_smuggled("POST / HTTP/1.1\r\nContent-Length: 2 4\r\n\r\n");
+    assert!(raw_response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(raw_response.contains("Connection: close\r\n"));
+    assert!(!raw_response.contains("secret"));
+    for length in ["-1", "+0", "abc", ""] {
+        let raw_response = serve_smuggled(&format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            length
+        ));
+        assert!(raw_response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(!raw_response.contains("secret"));
+    }
+}
+
+#[test]
+fn should_respond_bad_request_and_close_when_content_lengths_conflict() {
+    let raw_response =
+        serve_smuggled("POST / HTTP/1.1\r\nContent-Length: 0\r\nContent-Length: 26\r\n\r\n");
+    assert!(raw_response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(raw_response.contains("Connection: close\r\n"));
+    assert!(!raw_response.contains("secret"));
+}
+
+#[test]
+fn should_keep_serving_when_content_length_is_repeated_with_same_value() {
+    let raw_response =
+        serve_smuggled("POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 2\r\n\r\nhi");
+    assert!(raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(raw_response.ends_with("secret"));
+}
+
+#[test]
+fn should_respond_bad_request_and_close_when_transfer_encoding_is_unknown() {
+    for transfer_encoding in ["gzip", "chunked, gzip", "gzip, chunked", "xchunked"] {
+        let raw_response = serve_smuggled(&format!(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: {}\r\n\r\n0\r\n\r\n",
+            transfer_encoding
+        ));
+        assert!(raw_response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(raw_response.contains("Connection: close\r\n"));
+        assert!(!raw_response.contains("secret"));
+    }
+}
+
+#[test]
+fn should_respond_bad_request_and_close_when_chunked_body_also_has_content_length() {
+    let raw_response = serve_smuggled(
+        "POST / HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+    );
+    assert!(raw_response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(raw_response.contains("Connection: close\r\n"));
+    assert!(!raw_response.contains("secret"));
+}
+
+#[test]
+fn should_respond_bad_request_and_keep_serving_when_request_line_is_malformed() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", test_get))
+        .unwrap();
+    let mut bad_stream = TestStream::of("GET\r\n\r\n");
+    server.serve(&mut bad_stream, None, None).unwrap();
+    assert!(bad_stream
+        .output
+        .starts_with(b"HTTP/1.1 400 Bad Request\r\n"));
+    let mut stream = TestStream::of("GET / HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    assert!(stream.output.starts_with(b"HTTP/1.1 200 OK\r\n"));
+}
+
+fn test_panic(request: HttpRequest) -> HttpResponse {
+    let name = request.path_params["name"].clone();
+    panic!("{} is not welcome", name)
+}
+
+static PANICKED_URI: Mutex<Option<String>> = Mutex::new(None);
+
+#[test]
+fn should_respond_internal_server_error_and_keep_serving_when_handler_panics() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/panic/*name", test_panic)
+                .to("/", test_get)
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let panicking_response = client.get("/panic/mars");
+    assert_eq!(
+        panicking_response.status_code,
+        StatusCode::InternalServerError
+    );
+    let healthy_response = client.get("/");
+    assert_eq!(healthy_response.status_code, StatusCode::Ok);
+}
+
+#[test]
+fn should_invoke_panic_hook_with_request_and_payload_when_handler_panics() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/panic/*name", test_panic))
+        .unwrap();
+    server.on_panic(|request, payload| {
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert_eq!(message, "mars is not welcome");
+        *PANICKED_URI.lock().unwrap() = Some(request.uri.to_string());
+    });
+    let client = TestClient::new(server);
+    client.get("/panic/mars");
+    assert_eq!(PANICKED_URI.lock().unwrap().as_deref(), Some("/panic/mars"));
+}
+
+#[test]
+fn should_answer_panic_of_handler_or_middleware_through_error_handler() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/panic/*name", test_panic))
+        .unwrap();
+    server.wrap(|request: HttpRequest, next: Next| {
+        if request.uri.path() == "/middleware" {
+            panic!("middleware gave up");
+        }
+        next.run(request)
+    });
+    server.on_error(|error| (error.status_code, error.message));
+    let handler_response = serve_to_string(&server, "GET /panic/mars HTTP/1.1\r\n\r\n");
+    assert!(handler_response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    assert!(handler_response.ends_with("\r\n\r\nmars is not welcome"));
+    let middleware_response = serve_to_string(&server, "GET /middleware HTTP/1.1\r\n\r\n");
+    assert!(middleware_response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    assert!(middleware_response.ends_with("\r\n\r\nmiddleware gave up"));
+}
+
+fn test_page(request: HttpRequest) -> Result<HttpResponse, MartianError> {
+    request.param::<u32>("page")?;
+    Ok(test_get(request))
+}
+
+#[test]
+fn should_respond_bad_request_when_fallible_handler_returns_param_error() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to_fallible("/users/*page", test_page))
+        .unwrap();
+    let client = TestClient::new(server);
+    let ok_response = client.get("/users/2");
+    assert_eq!(ok_response.status_code, StatusCode::Ok);
+    let bad_response = client.get("/users/two");
+    assert_eq!(bad_response.status_code, StatusCode::BadRequest);
+    assert_eq!(
+        bad_response.body,
+        Body::Bytes(
+            b"Invalid value \"two\" for param \"page\": invalid digit found in string".to_vec()
+        )
+    );
+}
+
+fn test_missing_file(_: HttpRequest) -> Result<HttpResponse, MartianError> {
+    std::fs::read("/martian/no/such/file")?;
+    Ok(HttpResponse::ok())
+}
+
+#[test]
+fn should_hide_message_of_server_error_when_no_error_handler_is_set() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to_fallible("/file", test_missing_file)
+                .to_fallible("/gone", |_| {
+                    Err::<HttpResponse, _>(MartianError::new(StatusCode::Gone, "moved on"))
+                })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let file_response = client.get("/file");
+    assert_eq!(file_response.status_code, StatusCode::InternalServerError);
+    assert_eq!(file_response.body, Body::Empty);
+    let gone_response = client.get("/gone");
+    assert_eq!(gone_response.status_code, StatusCode::Gone);
+    assert_eq!(gone_response.body, Body::from("moved on"));
+}
+
+#[test]
+fn should_answer_every_handler_error_through_error_handler_when_set() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to_fallible("/file", test_missing_file)
+                .to_fallible("/users/*page", test_page)
+                .to_typed("/typed/{id}", |PathParam(id): PathParam<u32>| {
+                    id.to_string()
+                })
+        })
+        .unwrap();
+    server.on_error(|error| {
+        let internal = error.source().is_some();
+        (error.status_code, format!("{} {}", internal, error.message))
+    });
+    let client = TestClient::new(server);
+    let file_response = client.get("/file");
+    assert_eq!(file_response.status_code, StatusCode::InternalServerError);
+    assert_eq!(
+        file_response.body,
+        Body::from("true No such file or directory (os error 2)")
+    );
+    let page_response = client.get("/users/two");
+    assert_eq!(page_response.status_code, StatusCode::BadRequest);
+    assert_eq!(
+        page_response.body,
+        Body::from("false Invalid value \"two\" for param \"page\": invalid digit found in string")
+    );
+    let typed_response = client.get("/typed/x");
+    assert_eq!(typed_response.status_code, StatusCode::BadRequest);
+    let ok_response = client.get("/typed/3");
+    assert_eq!(ok_response.body, Body::from("3"));
+}
+
+#[test]
+fn should_report_every_request_to_completion_hook_including_failures() {
+    let request_logs = Arc::new(Mutex::new(Vec::<RequestLog>::new()));
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", test_get)
+                .to("/panic/*name", test_panic)
+        })
+        .unwrap();
+    let captured_logs = request_logs.clone();
+    server.on_request_complete(move |request_log| {
+        captured_logs.lock().unwrap().push(request_log.clone());
+    });
+    let connection_info = ConnectionInfo {
+        peer_addr: "10.0.0.1:4000".parse().unwrap(),
+        local_addr: "10.0.0.2:80".parse().unwrap(),
+        is_tls: false,
+        peer_identity: None,
+        server_name: None,
+    };
+    let peer_addr = Some(connection_info.peer_addr);
+    for raw_request in &[
+        "GET / HTTP/1.1\r\n\r\n",
+        "GET /missing?greet=world HTTP/1.1\r\n\r\n",
+        "GET /panic/mars HTTP/1.1\r\n\r\n",
+    ] {
+        server
+            .serve(
+                &mut TestStream::of(raw_request),
+                Some(connection_info.clone()),
+                None,
+            )
+            .unwrap();
+    }
+    let request_logs = request_logs.lock().unwrap();
+    let actual_entries = request_logs
+        .iter()
+        .map(|log| {
+            (
+                log.path.as_str(),
+                log.status_code,
+                log.body_size,
+                log.peer_addr,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actual_entries,
+        vec![
+            ("/", StatusCode::Ok, Some(0), peer_addr),
+            ("/missing", StatusCode::NotFound, Some(0), peer_addr),
+            (
+                "/panic/mars",
+                StatusCode::InternalServerError,
+                Some(0),
+                peer_addr
+            ),
+        ]
+    );
+}
+
+#[cfg(any(feature = "tls", feature = "jwt"))]
+pub(in crate::server) fn fixture(name: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Listens with TLS on a free port of the loopback, waiting until the port
+/// takes connections. `/whoami` answers with the common name of the client
+/// certificate.
+#[cfg(feature = "tls")]
+pub(in crate::server) fn listen_tls(tls_config: crate::server::TlsConfig) -> std::net::SocketAddr {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    thread::spawn(move || {
+        let mut server = Server::default();
+        server
+            .route(|| {
+                Route::bind(HttpMethod::Get).to("/", test_get).to(
+                    "/whoami",
+                    |request: HttpRequest| {
+                        let connection = request.connection.unwrap();
+                        assert!(connection.is_tls);
+                        format!("{:?}", connection.peer_identity)
+                    },
+                )
+            })
+            .unwrap();
+        server.listen_tls(address, tls_config).unwrap();
+    });
+    let stream = (0..50)
+        .find_map(|_| {
+            thread::sleep(Duration::from_millis(10));
+            TcpStream::connect(address).ok()
+        })
+        .unwrap();
+    // A client refusing the handshake must not stop the next one being served.
+    drop(stream);
+    address
+}
+
+/// A client trusting only the certificate in the PEM file, presenting the
+/// client certificate of the fixtures when asked to.
+#[cfg(feature = "tls")]
+pub(in crate::server) fn tls_client(
+    trusted_cert: &std::path::Path,
+    with_client_cert: bool,
+) -> Arc<rustls::ClientConfig> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use rustls::{ClientConfig, RootCertStore};
+
+    let mut root_store = RootCertStore::empty();
+    root_store
+        .add(CertificateDer::from_pem_file(trusted_cert).unwrap())
+        .unwrap();
+    let builder =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(root_store);
+    Arc::new(match with_client_cert {
+        true => builder
+            .with_client_auth_cert(
+                vec![CertificateDer::from_pem_file(fixture("client-cert.pem")).unwrap()],
+                PrivateKeyDer::from_pem_file(fixture("client-key.pem")).unwrap(),
+            )
+            .unwrap(),
+        false => builder.with_no_client_auth(),
+    })
+}
+
+#[cfg(feature = "tls")]
+fn https_get(
+    address: std::net::SocketAddr,
+    client: Arc<rustls::ClientConfig>,
+    hostname: &str,
+    path: &str,
+) -> io::Result<HttpResponse> {
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConnection, StreamOwned};
+    use std::convert::TryFrom;
+
+    let server_name = ServerName::try_from(hostname.to_string()).unwrap();
+    let connection = ClientConnection::new(client, server_name).unwrap();
+    let mut tls_stream = StreamOwned::new(connection, TcpStream::connect(address)?);
+    let raw_request = format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path);
+    tls_stream.write_all(raw_request.as_bytes())?;
+    let mut raw_response = String::new();
+    tls_stream.read_to_string(&mut raw_response)?;
+    Ok(HttpResponse::from(&raw_response))
+}
+
+#[cfg(feature = "tls")]
+fn https_get_trusting(
+    address: std::net::SocketAddr,
+    trusted_cert: &str,
+    hostname: &str,
+) -> io::Result<HttpResponse> {
+    https_get(
+        address,
+        tls_client(&fixture(trusted_cert), false),
+        hostname,
+        "/",
+    )
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_serve_request_over_https_when_listening_with_tls() {
+    use crate::server::{ClientAuth, TlsConfig};
+
+    let address = listen_tls(TlsConfig {
+        cert_pem_path: fixture("cert.pem"),
+        key_pem_path: fixture("key.pem"),
+        hosts: Vec::new(),
+        client_auth: ClientAuth::None,
+    });
+    let response = https_get_trusting(address, "cert.pem", "localhost").unwrap();
+    assert_eq!(response.status_code, StatusCode::Ok);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_present_certificate_of_hostname_when_asked_for_through_sni() {
+    use crate::server::{ClientAuth, TlsConfig, TlsHost};
+
+    let address = listen_tls(TlsConfig {
+        cert_pem_path: fixture("cert.pem"),
+        key_pem_path: fixture("key.pem"),
+        hosts: vec![TlsHost {
+            hostname: "OTHER.test".into(),
+            cert_pem_path: fixture("other-cert.pem"),
+            key_pem_path: fixture("other-key.pem"),
+        }],
+        client_auth: ClientAuth::None,
+    });
+    let other = https_get_trusting(address, "other-cert.pem", "other.test").unwrap();
+    assert_eq!(other.status_code, StatusCode::Ok);
+    let default = https_get_trusting(address, "cert.pem", "localhost").unwrap();
+    assert_eq!(default.status_code, StatusCode::Ok);
+    assert!(https_get_trusting(address, "other-cert.pem", "localhost").is_err());
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_present_renewed_certificate_when_files_change() {
+    use crate::server::{ClientAuth, TlsConfig};
+
+    let dir = std::env::temp_dir().join(format!("martian-tls-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(fixture("other-cert.pem"), dir.join("cert.pem")).unwrap();
+    std::fs::copy(fixture("other-key.pem"), dir.join("key.pem")).unwrap();
+    let address = listen_tls(TlsConfig {
+        cert_pem_path: dir.join("cert.pem"),
+        key_pem_path: dir.join("key.pem"),
+        hosts: Vec::new(),
+        client_auth: ClientAuth::None,
+    });
+    assert!(https_get_trusting(address, "cert.pem", "localhost").is_err());
+    // Only half renewed, the certificate loaded last is still presented.
+    std::fs::copy(fixture("cert.pem"), dir.join("cert.pem")).unwrap();
+    let response = https_get_trusting(address, "other-cert.pem", "other.test").unwrap();
+    assert_eq!(response.status_code, StatusCode::Ok);
+    std::fs::copy(fixture("key.pem"), dir.join("key.pem")).unwrap();
+    let response = https_get_trusting(address, "cert.pem", "localhost").unwrap();
+    assert_eq!(response.status_code, StatusCode::Ok);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_put_peer_identity_on_request_when_client_certificate_is_verified() {
+    use crate::server::{ClientAuth, TlsConfig};
+    use crate::web::PeerIdentity;
+
+    let address = listen_tls(TlsConfig {
+        cert_pem_path: fixture("cert.pem"),
+        key_pem_path: fixture("key.pem"),
+        hosts: Vec::new(),
+        client_auth: ClientAuth::Required(fixture("client-cert.pem")),
+    });
+    let client = tls_client(&fixture("cert.pem"), true);
+    let response = https_get(address, client, "localhost", "/whoami").unwrap();
+    let expected_identity = Some(PeerIdentity {
+        common_name: Some("alice".into()),
+        dns_names: vec!["alice.example.com".into()],
+        uri_names: vec!["spiffe://example.com/alice".into()],
+    });
+    assert_eq!(
+        response.body,
+        Body::from(format!("{:?}", expected_identity))
+    );
+    let client = tls_client(&fixture("cert.pem"), false);
+    assert!(https_get(address, client, "localhost", "/whoami").is_err());
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_serve_client_without_certificate_when_client_auth_is_optional() {
+    use crate::server::{ClientAuth, TlsConfig};
+
+    let address = listen_tls(TlsConfig {
+        cert_pem_path: fixture("cert.pem"),
+        key_pem_path: fixture("key.pem"),
+        hosts: Vec::new(),
+        client_auth: ClientAuth::Optional(fixture("client-cert.pem")),
+    });
+    let client = tls_client(&fixture("cert.pem"), false);
+    let response = https_get(address, client, "localhost", "/whoami").unwrap();
+    assert_eq!(response.body, Body::from("None"));
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn should_fail_to_start_when_tls_certificate_is_missing() {
+    use crate::server::{ClientAuth, HttpServer, TlsConfig};
+
+    let http_server = HttpServer::new(Server::default())
+        .bind("127.0.0.1:0")
+        .unwrap()
+        .with_tls(TlsConfig {
+            cert_pem_path: "no/such/cert.pem".into(),
+            key_pem_path: "no/such/key.pem".into(),
+            hosts: Vec::new(),
+            client_auth: ClientAuth::None,
+        });
+    assert!(http_server.start().is_err());
+}
+
+fn slash_server(trailing_slash: TrailingSlash) -> Server {
+    let mut server = Server::default();
+    server.trailing_slash(trailing_slash);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/hello", test_get)
+                .to("/dir/", test_get)
+        })
+        .unwrap();
+    server
+}
+
+fn status_of(client: &TestClient, uri: &str) -> StatusCode {
+    client.get(uri).status_code
+}
+
+#[test]
+fn should_only_match_exact_slash_form_when_trailing_slash_is_strict() {
+    let client = TestClient::new(slash_server(TrailingSlash::Strict));
+    assert_eq!(status_of(&client, "/hello"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/hello/"), StatusCode::NotFound);
+    assert_eq!(status_of(&client, "/dir/"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/dir"), StatusCode::NotFound);
+    assert_eq!(status_of(&client, "/missing/"), StatusCode::NotFound);
+}
+
+#[test]
+fn should_redirect_to_slashless_form_when_trailing_slash_is_canonicalized() {
+    let client = TestClient::new(slash_server(TrailingSlash::RedirectToCanonical));
+    assert_eq!(status_of(&client, "/hello"), StatusCode::Ok);
+    let redirect = client.get("/hello/");
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(redirect.headers["Location"], "/hello");
+    assert_eq!(status_of(&client, "/dir/"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/dir"), StatusCode::NotFound);
+    assert_eq!(status_of(&client, "/missing/"), StatusCode::NotFound);
+}
+
+#[test]
+fn should_keep_method_on_redirect_when_trailing_slash_is_canonicalized_for_post() {
+    let mut server = slash_server(TrailingSlash::RedirectToCanonical);
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/orders", test_get))
+        .unwrap();
+    let client = TestClient::new(server);
+    let redirect = client.post("/orders/", "");
+    assert_eq!(redirect.status_code, StatusCode::PermanentRedirect);
+    assert_eq!(redirect.headers["Location"], "/orders");
+}
+
+#[test]
+fn should_match_either_slash_form_when_trailing_slash_is_merged() {
+    let client = TestClient::new(slash_server(TrailingSlash::Merge));
+    assert_eq!(status_of(&client, "/hello"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/hello/"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/dir/"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/dir"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/missing/"), StatusCode::NotFound);
+}
+
+#[test]
+fn should_keep_query_on_redirect_when_trailing_slash_is_canonicalized() {
+    let client = TestClient::new(slash_server(TrailingSlash::RedirectToCanonical));
+    let redirect = client.get("/hello/?a=b");
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(redirect.headers["Location"], "/hello?a=b");
+}
+
+#[test]
+fn should_match_either_slash_form_with_query_when_trailing_slash_is_merged() {
+    let client = TestClient::new(slash_server(TrailingSlash::Merge));
+    assert_eq!(status_of(&client, "/hello/?a=b"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/dir?a=b"), StatusCode::Ok);
+}
+
+#[test]
+fn should_normalize_path_only_as_asked_when_normalizing_paths() {
+    use crate::server::pattern::normalize;
+    let all = PathNormalization {
+        merge_slashes: true,
+        resolve_dot_segments: true,
+    };
+    let slashes = PathNormalization {
+        merge_slashes: true,
+        ..PathNormalization::default()
+    };
+    let dots = PathNormalization {
+        resolve_dot_segments: true,
+        ..PathNormalization::default()
+    };
+    for (path, normalized) in [
+        ("//a///b//", Some("/a/b/")),
+        ("/a/./b/../c", Some("/a/c")),
+        ("/a/b/..", Some("/a/")),
+        ("/a/%2e/b/%2E%2E/c", Some("/a/c")),
+        ("/../../etc/passwd", Some("/etc/passwd")),
+        ("//", Some("/")),
+        ("/a/...", None),
+        ("/a%2F../b", None),
+        ("/a/b/", None),
+        ("/", None),
+        ("*", None),
+    ] {
+        assert_eq!(normalize(path, all).as_deref(), normalized, "{}", path);
+    }
+    assert_eq!(normalize("/a//./b", slashes).as_deref(), Some("/a/./b"));
+    assert_eq!(normalize("/a//./b", dots).as_deref(), Some("/a//b"));
+    assert_eq!(normalize("/a//./b", PathNormalization::default()), None);
+}
+
+#[test]
+fn should_match_normalized_path_when_normalizing_paths() {
+    let mut server = slash_server(TrailingSlash::Merge);
+    server.normalize_paths(PathNormalization {
+        merge_slashes: true,
+        resolve_dot_segments: true,
+    });
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/echo/{name}", |request: HttpRequest| {
+                HttpResponse::text(request.uri.as_str())
+            })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    for uri in ["//hello", "/dir//", "/x/../hello/.", "/dir/%2e"] {
+        let response = client.send(request_to(uri));
+        assert_eq!(response.status_code, StatusCode::Ok, "{}", uri);
+    }
+    let response = client.send(request_to("/echo//./x/../y?q=a//b#top"));
+    assert_eq!(response.body, Body::from("/echo/y?q=a//b#top"));
+    assert_eq!(
+        TestClient::new(slash_server(TrailingSlash::Merge))
+            .send(request_to("//hello"))
+            .status_code,
+        StatusCode::NotFound
+    );
+}
+
+#[test]
+fn should_match_route_on_path_when_request_has_query() {
+    let client = TestClient::new(slash_server(TrailingSlash::Strict));
+    assert_eq!(status_of(&client, "/hello?a=b"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/hello?"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/hello#top"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/dir/?a=b&c"), StatusCode::Ok);
+    assert_eq!(status_of(&client, "/missing?a=b"), StatusCode::NotFound);
+}
+
+#[test]
+fn should_capture_path_param_without_query_when_request_has_query() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/users/{id}", |request: HttpRequest| {
+                HttpResponse::text(request.path_param("id").unwrap_or_default())
+            })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let response = client.get("/users/7?expand=all");
+    assert_eq!(response.body, Body::from("7"));
+}
+
+#[test]
+fn should_answer_method_not_allowed_when_request_with_query_has_unbound_method() {
+    let server = slash_server(TrailingSlash::Strict);
+    let mut request = request_to("/hello?a=b");
+    request.http_method = HttpMethod::Post;
+    let response = server.respond(request);
+    assert_eq!(response.status_code, StatusCode::MethodNotAllowed);
+    assert_eq!(response.headers["Allow"], "GET, HEAD, OPTIONS");
+}
+
+#[test]
+fn should_conflict_on_both_slash_forms_only_when_trailing_slash_is_merged() {
+    let mut merged_server = slash_server(TrailingSlash::Merge);
+    let conflict = merged_server.route(|| Route::bind(HttpMethod::Get).to("/hello/", test_get));
+    assert!(conflict.is_err());
+    let mut strict_server = slash_server(TrailingSlash::Strict);
+    let no_conflict = strict_server.route(|| Route::bind(HttpMethod::Get).to("/hello/", test_get));
+    assert!(no_conflict.is_ok());
+}
+
+fn test_put(_: HttpRequest) -> HttpResponse {
+    HttpResponse::no_content()
+}
+
+#[test]
+fn should_delegate_to_route_bound_with_put_method() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Put).to("/users/*id", test_put))
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.put("/users/7", "");
+    assert_eq!(actual_response.status_code, StatusCode::NoContent);
+}
+
+#[test]
+fn should_answer_head_with_get_route_without_body_when_no_head_route_is_bound() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| HttpResponse::text("hello")))
+        .unwrap();
+    let mut stream = TestStream::of("HEAD / HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    assert!(String::from_utf8(stream.output)
+        .unwrap()
+        .ends_with("Content-Length: 5\r\n\r\n"));
+}
+
+static BOTH_HANDLING: Barrier = Barrier::new(2);
+
+/// Only returns once a second request is being handled at the same time.
+fn test_rendezvous(_: HttpRequest) -> HttpResponse {
+    BOTH_HANDLING.wait();
+    HttpResponse::text("together")
+}
+
+#[test]
+fn should_handle_requests_in_parallel_and_drain_queue_when_using_workers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let clients = (0..2)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut stream = TcpStream::connect(address).unwrap();
+                stream
+                    .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .unwrap();
+                let mut raw_response = String::new();
+                stream.read_to_string(&mut raw_response).unwrap();
+                raw_response
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut server = Server::with_workers(2);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", test_rendezvous))
+        .unwrap();
+    server
+        .accept(listener.incoming().flatten().take(2), Ok)
+        .unwrap();
+    for client in clients {
+        assert!(client.join().unwrap().ends_with("\r\n\r\ntogether"));
+    }
+}
+
+#[test]
+fn should_serve_accepted_connections_then_stop_when_shutdown_is_triggered() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let trigger = shutdown.clone();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut raw_response = String::new();
+        stream.read_to_string(&mut raw_response).unwrap();
+        trigger.trigger();
+        raw_response
+    });
+    let mut server = Server::with_workers(2);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "bye"))
+        .unwrap();
+    server
+        .accept(shutdown.incoming(&listener).unwrap(), Ok)
+        .unwrap();
+    assert!(client.join().unwrap().ends_with("\r\n\r\nbye"));
+    assert!(shutdown.is_triggered());
+}
+
+#[test]
+fn should_not_accept_anything_when_shutdown_is_triggered_before_listening() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let shutdown = Shutdown::new();
+    shutdown.trigger();
+    let server = Server::default();
+    server
+        .accept(shutdown.incoming(&listener).unwrap(), Ok)
+        .unwrap();
+}
+
+#[test]
+fn should_not_wake_address_when_listening_on_it_has_stopped() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let streams = shutdown.incoming(&listener).unwrap();
+    drop(streams);
+    drop(listener);
+    let listener = TcpListener::bind(address).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    shutdown.trigger();
+    let accepted = listener.accept().map(|_| ());
+    assert_eq!(accepted.unwrap_err().kind(), ErrorKind::WouldBlock);
+}
+
+#[cfg(all(unix, feature = "signals"))]
+#[test]
+fn should_stop_listening_when_process_receives_sigterm() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let shutdown = Shutdown::new();
+    shutdown.on_signals().unwrap();
+    unsafe {
+        libc::kill(libc::getpid(), libc::SIGTERM);
+    }
+    Server::default()
+        .accept(shutdown.incoming(&listener).unwrap(), Ok)
+        .unwrap();
+    assert!(shutdown.is_triggered());
+}
+
+#[test]
+fn should_attach_connection_info_when_request_is_read_off_of_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let client_address = stream.local_addr().unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut raw_response = String::new();
+        stream.read_to_string(&mut raw_response).unwrap();
+        (client_address, raw_response)
+    });
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/", |request| {
+                let connection = request.connection.unwrap();
+                format!(
+                    "{} {} {}",
+                    connection.peer_addr, connection.local_addr, connection.is_tls
+                )
+            })
+        })
+        .unwrap();
+    server
+        .accept(listener.incoming().flatten().take(1), Ok)
+        .unwrap();
+    let (client_address, raw_response) = client.join().unwrap();
+    let expected_body = format!("{} {} false", client_address, address);
+    assert!(raw_response.ends_with(&format!("\r\n\r\n{}", expected_body)));
+    assert_eq!(request_to("/").connection, None);
+}
+
+#[test]
+fn should_serve_requests_on_connection_until_client_asks_to_close_it() {
+    let mut server = Server::with_workers(1);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "again"))
+        .unwrap();
+    let mut stream = TestStream::of(
+        "GET / HTTP/1.1\r\n\r\n\
+         GET / HTTP/1.1\r\nConnection: close\r\n\r\n\
+         GET / HTTP/1.1\r\n\r\n",
+    );
+    server.serve(&mut stream, None, None).unwrap();
+    let raw_responses = String::from_utf8(stream.output).unwrap();
+    let responses = raw_responses.split_inclusive("again").collect::<Vec<_>>();
+    assert_eq!(responses.len(), 2);
+    assert!(!responses[0].contains("Connection"));
+    assert!(responses[1].contains("Connection: close\r\n"));
+}
+
+#[test]
+fn should_hand_connection_over_to_websocket_when_handshake_is_accepted() {
+    let mut server = Server::with_workers(1);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to_fallible("/echo", |request: HttpRequest| {
+                request.upgrade_websocket(|mut websocket| {
+                    while let Ok(Message::Text(text)) = websocket.receive() {
+                        websocket.send(Message::Text(text.to_uppercase())).unwrap();
+                    }
+                })
+            })
+        })
+        .unwrap();
+    // "hi" and a close, each masked with a mask of zeroes.
+    let mut stream = TestStream::of(
+        b"GET /echo HTTP/1.1\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Version: 13\r\n\r\n\
+        \x81\x82\0\0\0\0hi\x88\x80\0\0\0\0\
+        GET /echo HTTP/1.1\r\n\r\n",
+    );
+    server.serve(&mut stream, None, None).unwrap();
+    let head = b"HTTP/1.1 101 Switching Protocols\r\n";
+    assert!(stream.output.starts_with(head));
+    assert!(!String::from_utf8_lossy(&stream.output).contains("keep-alive"));
+    assert!(stream.output.ends_with(b"\r\n\r\n\x81\x02HI\x88\x00"));
+}
+
+#[test]
+fn should_write_events_as_chunks_when_handler_returns_event_stream() {
+    let mut server = Server::with_workers(1);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to("/events", |_| {
+                let (sender, stream) = SseStream::channel();
+                thread::spawn(move || {
+                    sender.send(Event::data("first").id("1")).unwrap();
+                    sender.send(Event::data("second").id("2")).unwrap();
+                });
+                stream
+            })
+        })
+        .unwrap();
+    let mut stream = TestStream::of("GET /events HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let raw_response = String::from_utf8(stream.output).unwrap();
+    assert!(raw_response.contains("Content-Type: text/event-stream; charset=utf-8\r\n"));
+    assert!(raw_response.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(raw_response.ends_with(
+        "\r\n\r\n13\r\nid: 1\ndata: first\n\n\r\n14\r\nid: 2\ndata: second\n\n\r\n0\r\n\r\n"
+    ));
+}
+
+#[test]
+fn should_close_connection_when_max_requests_are_served() {
+    let mut server = Server::with_workers(1);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "again"))
+        .unwrap();
+    server.keep_alive(KeepAlive {
+        max_requests: 2,
+        ..KeepAlive::default()
+    });
+    let mut stream = TestStream::of(
+        "GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n\
+         GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n\
+         GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n",
+    );
+    server.serve(&mut stream, None, None).unwrap();
+    let raw_responses = String::from_utf8(stream.output).unwrap();
+    let responses = raw_responses.split_inclusive("again").collect::<Vec<_>>();
+    assert_eq!(responses.len(), 2);
+    assert!(responses[0].contains("Connection: keep-alive\r\n"));
+    assert!(!responses[1].contains("Connection"));
+}
+
+#[test]
+fn should_close_idle_connection_when_keep_alive_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut raw_response = String::new();
+        stream.read_to_string(&mut raw_response).unwrap();
+        raw_response
+    });
+    let mut server = Server::with_workers(1);
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "idle"))
+        .unwrap();
+    server.keep_alive(KeepAlive {
+        timeout: Duration::from_millis(50),
+        ..KeepAlive::default()
+    });
+    server
+        .accept(listener.incoming().flatten().take(1), Ok)
+        .unwrap();
+    assert!(client.join().unwrap().ends_with("\r\n\r\nidle"));
+}
+
+fn read_with_timeouts(server: &Server, sent: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(sent).unwrap();
+        let mut raw_response = String::new();
+        stream.read_to_string(&mut raw_response).unwrap();
+        raw_response
+    });
+    server
+        .accept(listener.incoming().flatten().take(1), Ok)
+        .unwrap();
+    client.join().unwrap()
+}
+
+#[test]
+fn should_answer_request_timeout_when_client_stops_sending_part_way() {
+    let mut server = Server::default();
+    server.timeouts(Timeouts {
+        read: Some(Duration::from_millis(50)),
+        ..Timeouts::default()
+    });
+    let partial_response = read_with_timeouts(&server, b"GET / HTTP/1.1\r\nHost: mars");
+    assert!(partial_response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    assert!(partial_response.contains("Connection: close\r\n"));
+    assert_eq!(read_with_timeouts(&server, b""), "");
+}
+
+/// Sends a request a line at a time every 20 milliseconds, stopping once
+/// answered.
+pub(in crate::server) fn trickle_request(address: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+    let lines = iter::once("GET / HTTP/1.1")
+        .chain(iter::repeat_n("X-Slow: 1", 20))
+        .chain(iter::once(""));
+    let mut raw_response = Vec::new();
+    for line in lines {
+        write!(stream, "{}\r\n", line).unwrap();
+        match stream.read_to_end(&mut raw_response) {
+            Ok(_) => break,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => panic!("{}", e),
+        }
+    }
+    String::from_utf8(raw_response).unwrap()
+}
+
+#[test]
+fn should_answer_request_timeout_when_client_trickles_request_past_read_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = thread::spawn(move || trickle_request(address));
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| "served"))
+        .unwrap();
+    server.timeouts(Timeouts {
+        read: Some(Duration::from_millis(100)),
+        ..Timeouts::default()
+    });
+    server
+        .accept(listener.incoming().flatten().take(1), Ok)
+        .unwrap();
+    assert!(client
+        .join()
+        .unwrap()
+        .starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+}
+
+#[test]
+fn should_answer_service_unavailable_when_handler_takes_too_long() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/slow", |_| {
+                    thread::sleep(Duration::from_millis(500));
+                    "finally"
+                })
+                .to("/fast", |_| "quick")
+        })
+        .unwrap();
+    server.timeouts(Timeouts {
+        handler: Some(Duration::from_millis(50)),
+        ..Timeouts::default()
+    });
+    let client = TestClient::new(server);
+    let slow_response = client.send(request_to("/slow"));
+    assert_eq!(slow_response.status_code, StatusCode::ServiceUnavailable);
+    let fast_response = client.send(request_to("/fast"));
+    assert_eq!(fast_response.body, Body::from("quick"));
+}
+
+#[test]
+fn should_not_leave_thread_behind_for_each_handler_when_handlers_time_out() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let release_rx = Arc::new(Mutex::new(release_rx));
+    let mut server = Server::default();
+    let handler_started = started.clone();
+    server
+        .route(move || {
+            let started = handler_started.clone();
+            let release_rx = release_rx.clone();
+            Route::bind(HttpMethod::Get).to("/stuck", move |_| {
+                started.fetch_add(1, Ordering::SeqCst);
+                let _ = release_rx.lock().unwrap().recv();
+                "unstuck"
+            })
+        })
+        .unwrap();
+    server.timeouts(Timeouts {
+        handler: Some(Duration::from_millis(10)),
+        max_handler_threads: 2,
+        ..Timeouts::default()
+    });
+    let client = TestClient::new(server);
+    for _ in 0..20 {
+        let response = client.send(request_to("/stuck"));
+        assert_eq!(response.status_code, StatusCode::ServiceUnavailable);
+    }
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    drop(release_tx);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.send(request_to("/stuck")).status_code == StatusCode::ServiceUnavailable {
+        assert!(Instant::now() < deadline);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+pub(in crate::server) fn small_limits_server() -> Server {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/echo", |request: HttpRequest| request.body))
+        .unwrap();
+    server.limits(Limits {
+        max_request_line: 32,
+        max_header_bytes: 64,
+        max_headers: 2,
+        max_body: 8,
+        max_body_in_memory: 8,
+        spool_dir: None,
+        max_in_flight: None,
+    });
+    server
+}
+
+#[test]
+fn should_answer_uri_too_long_when_request_line_is_over_limit() {
+    let server = small_limits_server();
+    let raw_response = serve_to_string(
+        &server,
+        "POST /echo?padding=aaaaaaaaaaaaaaaaaaaa HTTP/1.1\r\n\r\n",
+    );
+    assert!(raw_response.starts_with("HTTP/1.1 414 URI Too Long\r\n"));
+}
+
+#[test]
+fn should_answer_header_fields_too_large_when_headers_are_over_limits() {
+    let server = small_limits_server();
+    let too_many = serve_to_string(
+        &server,
+        "POST /echo HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n",
+    );
+    assert!(too_many.starts_with("HTTP/1.1 431 "));
+    let too_long = serve_to_string(
+        &server,
+        &format!("POST /echo HTTP/1.1\r\nA: {}\r\n\r\n", "a".repeat(64)),
+    );
+    assert!(too_long.starts_with("HTTP/1.1 431 "));
+}
+
+#[test]
+fn should_answer_content_too_large_when_body_is_over_limit() {
+    let server = small_limits_server();
+    let declared = serve_to_string(
+        &server,
+        "POST /echo HTTP/1.1\r\nContent-Length: 9\r\n\r\n123456789",
+    );
+    assert!(declared.starts_with("HTTP/1.1 413 "));
+    let chunked = serve_to_string(
+        &server,
+        "POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n12345\r\n5\r\n67890\r\n0\r\n\r\n",
+    );
+    assert!(chunked.starts_with("HTTP/1.1 413 "));
+}
+
+#[test]
+fn should_read_file_part_back_from_temp_file_when_multipart_body_is_over_memory_limit() {
+    let spooled_path = Arc::new(Mutex::new(None));
+    let seen_path = Arc::clone(&spooled_path);
+    let mut server = Server::default();
+    server
+        .route(move || {
+            let seen_path = Arc::clone(&seen_path);
+            Route::bind(HttpMethod::Post).to("/upload", move |request: HttpRequest| {
+                assert_eq!(request.body, None);
+                let spooled = request.extensions.get::<SpooledBody>().unwrap();
+                *seen_path.lock().unwrap() = Some(spooled.path().to_path_buf());
+                let mut multipart = request.multipart().unwrap();
+                let mut part = multipart.next_part().unwrap().unwrap();
+                let filename = part.filename().unwrap().to_string();
+                let size = io::copy(&mut part, &mut io::sink()).unwrap();
+                HttpResponse::text(&format!("{} {}", filename, size))
+            })
+        })
+        .unwrap();
+    server.limits(Limits {
+        max_body: 8 * 1024 * 1024,
+        max_body_in_memory: 64 * 1024,
+        ..Limits::default()
+    });
+    let raw_response = serve_to_string(&server, &upload_request(4 * 1024 * 1024));
+    assert!(raw_response.ends_with("\r\n\r\nbig.bin 4194304"));
+    let spooled_path = spooled_path.lock().unwrap().take().unwrap();
+    assert!(!spooled_path.exists());
+}
+
+#[test]
+fn should_write_body_only_the_server_can_read_to_spool_dir_when_one_is_set() {
+    let spool_dir = static_dir("spool-dir");
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post).to("/upload", |request: HttpRequest| {
+                let spooled = request.extensions.get::<SpooledBody>().unwrap();
+                let path = spooled.path();
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mode = std::fs::metadata(path).unwrap().permissions().mode();
+                    assert_eq!(mode & 0o777, 0o600);
+                }
+                format!("{}", path.display())
+            })
+        })
+        .unwrap();
+    server.limits(Limits {
+        max_body_in_memory: 1024,
+        spool_dir: Some(spool_dir.clone()),
+        ..Limits::default()
+    });
+    let first = serve_to_string(&server, &upload_request(4096));
+    let second = serve_to_string(&server, &upload_request(4096));
+    let first_path = first.split("\r\n\r\n").nth(1).unwrap();
+    let second_path = second.split("\r\n\r\n").nth(1).unwrap();
+    assert!(first_path.starts_with(spool_dir.to_str().unwrap()));
+    assert!(second_path.starts_with(spool_dir.to_str().unwrap()));
+    assert_ne!(first_path, second_path);
+    let file_name = std::path::Path::new(first_path).file_name().unwrap();
+    assert!(!file_name
+        .to_str()
+        .unwrap()
+        .contains(&std::process::id().to_string()));
+}
+
+/// A multipart request uploading a file of the size.
+fn upload_request(size: usize) -> String {
+    let body = format!(
+        "--XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"big.bin\"\r\n\r\n\
+        {}\r\n\
+        --XyZ--\r\n",
+        "x".repeat(size)
+    );
+    format!(
+        "POST /upload HTTP/1.1\r\n\
+        Content-Type: multipart/form-data; boundary=XyZ\r\n\
+        Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+#[test]
+fn should_serve_request_when_within_limits() {
+    let server = small_limits_server();
+    let raw_response = serve_to_string(
+        &server,
+        "POST /echo HTTP/1.1\r\nContent-Length: 8\r\n\r\n12345678",
+    );
+    assert!(raw_response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(raw_response.ends_with("12345678"));
+}
+
+#[test]
+fn should_invoke_capturing_closure_when_bound_to_route() {
+    let greeting = String::from("howdy");
+    let hits = Arc::new(AtomicUsize::new(0));
+    let route_hits = Arc::clone(&hits);
+    let mut server = Server::default();
+    server
+        .route(move || {
+            Route::bind(HttpMethod::Get).to("/", move |_| {
+                route_hits.fetch_add(1, Ordering::SeqCst);
+                HttpResponse::text(&greeting)
+            })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.get("/");
+    client.get("/");
+    assert_eq!(actual_response.body, Body::from("howdy"));
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+struct Visits(Mutex<u32>);
+
+fn test_visit(request: HttpRequest) -> HttpResponse {
+    let mut visits = request.state.get::<Visits>().unwrap().0.lock().unwrap();
+    *visits += 1;
+    HttpResponse::text(&visits.to_string())
+}
+
+#[test]
+fn should_hand_managed_state_to_every_request() {
+    let mut server = Server::default();
+    server.manage(Visits(Mutex::new(0)));
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", test_visit))
+        .unwrap();
+    let client = TestClient::new(server);
+    client.get("/");
+    let actual_response = client.get("/");
+    assert_eq!(actual_response.body, Body::from("2"));
+}
+
+#[test]
+fn should_replace_managed_state_of_the_same_type() {
+    let mut server = Server::default();
+    server.manage(Visits(Mutex::new(0)));
+    server.manage(Visits(Mutex::new(41)));
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", test_visit))
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.get("/");
+    assert_eq!(actual_response.body, Body::from("42"));
+}
+
+#[test]
+fn should_respond_empty_not_found_when_no_route_matches() {
+    let mut server = Server::default();
+    server.default_headers(DefaultHeaders::none());
+    let mut stream = TestStream::of("GET /nowhere HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    assert_eq!(
+        String::from_utf8(stream.output).unwrap(),
+        "HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+    );
+}
+
+#[test]
+fn should_respond_through_not_found_handler_when_one_is_set() {
+    let mut server = Server::default();
+    server.manage(Visits(Mutex::new(0)));
+    server.not_found(|request| {
+        let visits = request.state.get::<Visits>().unwrap().0.lock().unwrap();
+        let mut response = HttpResponse::text(&format!("no {} after {}", request.uri, visits));
+        response.status_code = StatusCode::NotFound;
+        response
+    });
+    let mut stream = TestStream::of("GET /nowhere HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let raw_response = String::from_utf8(stream.output).unwrap();
+    assert!(raw_response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(raw_response.ends_with("\r\n\r\nno /nowhere after 0"));
+}
+
+#[test]
+fn should_respond_method_not_allowed_with_allow_header_when_only_method_differs() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Post).to("/users/{id}", test_user))
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/users/*", test_get))
+        .unwrap();
+    server.not_found(|_| HttpResponse::text("not found"));
+    let mut stream = TestStream::of("DELETE /users/7 HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let response = HttpResponse::from(&String::from_utf8(stream.output).unwrap());
+    assert_eq!(response.status_code, StatusCode::MethodNotAllowed);
+    assert_eq!(
+        response.headers.get("Allow").map(String::as_str),
+        Some("GET, HEAD, POST, OPTIONS")
+    );
+    assert_eq!(response.body, Body::Empty);
+}
+
+#[test]
+fn should_answer_options_with_allowed_methods_when_no_options_route_is_bound() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Put).to("/users/{id}", test_put))
+        .unwrap();
+    let mut stream = TestStream::of("OPTIONS /users/7 HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let response = HttpResponse::from(&String::from_utf8(stream.output).unwrap());
+    assert_eq!(response.status_code, StatusCode::NoContent);
+    assert_eq!(
+        response.headers.get("Allow").map(String::as_str),
+        Some("PUT, OPTIONS")
+    );
+    let mut stream = TestStream::of("OPTIONS /posts/7 HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    assert!(stream.output.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[test]
+fn should_delegate_options_to_route_when_one_is_bound() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Options).to("/users", |_| HttpResponse::text("custom")))
+        .unwrap();
+    let client = TestClient::new(server);
+    let actual_response = client.options("/users");
+    assert_eq!(actual_response.body, Body::from("custom"));
+}
+
+/// Records the order it sees the request and the response in.
+struct Trace(&'static str);
+
+impl Middleware for Trace {
+    fn handle(&self, mut request: HttpRequest, next: Next) -> HttpResponse {
+        request.uri = format!("{}/{}", request.uri, self.0).into();
+        let mut response = next.run(request);
+        if let Body::Bytes(bytes) = &mut response.body {
+            bytes.extend_from_slice(format!(" {}", self.0).as_bytes());
+        }
+        response
+    }
+}
+
+#[test]
+fn should_run_middleware_in_order_around_delegation() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/*", |request| HttpResponse::text(request.uri.as_str()))
+        })
+        .unwrap();
+    server.wrap(Trace("outer"));
+    server.wrap(Trace("inner"));
+    let client = TestClient::new(server);
+    let actual_response = client.send(request_to(""));
+    assert_eq!(actual_response.body, Body::from("/outer/inner inner outer"));
+}
+
+#[test]
+fn should_answer_without_delegating_when_middleware_does_not_run_next() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", test_error))
+        .unwrap();
+    server.wrap(|_: HttpRequest, _: Next| HttpResponse::new(StatusCode::Unauthorized));
+    let client = TestClient::new(server);
+    let actual_response = client.send(request_to("/"));
+    assert_eq!(actual_response.status_code, StatusCode::Unauthorized);
+}
+
+#[test]
+fn should_run_route_middleware_only_for_the_route_it_follows() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/admin", |request| HttpResponse::text(request.uri.as_str()))
+                .with(Trace("first"))
+                .with(Trace("second"))
+                .to("/{page}", |request| {
+                    HttpResponse::text(request.uri.as_str())
+                })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let admin_response = client.send(request_to("/admin"));
+    assert_eq!(
+        admin_response.body,
+        Body::from("/admin/first/second second first")
+    );
+    let page_response = client.send(request_to("/about"));
+    assert_eq!(page_response.body, Body::from("/about"));
+}
+
+#[test]
+#[should_panic(expected = "Middleware must follow the route it wraps")]
+fn should_panic_when_middleware_precedes_any_route() {
+    Route::bind(HttpMethod::Get).with(Trace("nothing"));
+}
+
+fn api_server() -> Server {
+    let mut api = Server::default();
+    api.route(|| {
+        Route::bind(HttpMethod::Get)
+            .to("/", |_| HttpResponse::text("api root"))
+            .to("/users/{id}", |request| {
+                HttpResponse::text(&format!(
+                    "{} {}",
+                    request.uri,
+                    request.path_param("id").unwrap()
+                ))
+            })
+    })
+    .unwrap();
+    api.not_found(|_| HttpResponse::text("api not found"));
+    api
+}
+
+#[test]
+fn should_route_through_mounted_server_with_prefix_stripped() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/*", test_error))
+        .unwrap();
+    server.mount("/api/v1/", api_server());
+    let client = TestClient::new(server);
+    let user_response = client.send(request_to("/api/v1/users/3"));
+    assert_eq!(user_response.body, Body::from("/users/3 3"));
+    let root_response = client.send(request_to("/api/v1"));
+    assert_eq!(root_response.body, Body::from("api root"));
+    let missing_response = client.send(request_to("/api/v1/posts"));
+    assert_eq!(missing_response.body, Body::from("api not found"));
+}
+
+#[test]
+fn should_not_route_through_mounted_server_when_prefix_only_partly_matches_segment() {
+    let mut server = Server::default();
+    server.mount("/api", api_server());
+    let client = TestClient::new(server);
+    let actual_response = client.send(request_to("/apiary"));
+    assert_eq!(actual_response.status_code, StatusCode::NotFound);
+    assert_eq!(actual_response.body, Body::Empty);
+}
+
+#[test]
+fn should_keep_prefix_on_redirect_when_sent_from_within_mounted_server() {
+    let dir = static_dir("mounted-redirect");
+    let mut api = slash_server(TrailingSlash::RedirectToCanonical);
+    api.route(|| StaticFiles::new(&dir).listing().bind("/static"))
+        .unwrap();
+    let mut outer = Server::default();
+    outer.mount("/v1", api);
+    let mut server = Server::default();
+    server.mount("/api", outer);
+    let client = TestClient::new(server);
+    let redirect = client.send(request_to("/api/v1/hello/?page=2"));
+    assert_eq!(redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(redirect.headers["Location"], "/api/v1/hello?page=2");
+    let dir_redirect = client.send(request_to("/api/v1/static/css"));
+    assert_eq!(dir_redirect.status_code, StatusCode::MovedPermanently);
+    assert_eq!(dir_redirect.headers["Location"], "/api/v1/static/css/");
+    let listing = client.send(request_to("/api/v1/static/css/"));
+    assert!(listing
+        .body_text()
+        .contains("<title>Index of /api/v1/static/css/</title>"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn should_keep_original_uri_when_routed_through_mounted_server() {
+    let mut api = Server::default();
+    api.route(|| {
+        Route::bind(HttpMethod::Get).to("/users", |request: HttpRequest| {
+            let mounted = request.extensions.get::<Mounted>().unwrap();
+            HttpResponse::text(&format!("{} {}", mounted.original_uri(), mounted.prefix()))
+        })
+    })
+    .unwrap();
+    let mut server = Server::default();
+    server.mount("/api/", api);
+    let client = TestClient::new(server);
+    let actual_response = client.send(request_to("/api/users?all"));
+    assert_eq!(actual_response.body, Body::from("/api/users?all /api"));
+}
+
+#[test]
+fn should_route_through_virtual_host_when_host_matches_ignoring_case_and_port() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", |_| HttpResponse::text("www")))
+        .unwrap();
+    server.vhost("api.example.com", api_server());
+    let client = TestClient::new(server);
+    let api_response = client.send(HttpRequest::from(
+        "GET /users/3 HTTP/1.1\r\nHost: API.example.com:8080\r\n\r\n",
+    ));
+    assert_eq!(api_response.body, Body::from("/users/3 3"));
+    let dotted_response = client.send(HttpRequest::from(
+        "GET / HTTP/1.1\r\nHost: api.example.com.\r\n\r\n",
+    ));
+    assert_eq!(dotted_response.body, Body::from("api root"));
+    let default_response = client.send(HttpRequest::from(
+        "GET / HTTP/1.1\r\nHost: www.example.com\r\n\r\n",
+    ));
+    assert_eq!(default_response.body, Body::from("www"));
+    let hostless_response = client.send(request_to("/"));
+    assert_eq!(hostless_response.body, Body::from("www"));
+}
+
+#[test]
+fn should_route_through_virtual_host_of_server_name_when_request_has_no_host() {
+    let mut server = Server::default();
+    server.vhost("[::1]", api_server());
+    server.vhost("api.example.com", api_server());
+    let mut request = HttpRequest::from("GET /posts HTTP/1.0\r\n\r\n");
+    request.connection = Some(ConnectionInfo {
+        peer_addr: "203.0.113.7:51234".parse().unwrap(),
+        local_addr: "192.0.2.1:443".parse().unwrap(),
+        is_tls: true,
+        peer_identity: None,
+        server_name: Some("api.example.com".into()),
+    });
+    let client = TestClient::new(server);
+    assert_eq!(client.send(request).body, Body::from("api not found"));
+    let ipv6_response = client.send(HttpRequest::from(
+        "GET / HTTP/1.1\r\nHost: [::1]:80\r\n\r\n",
+    ));
+    assert_eq!(ipv6_response.body, Body::from("api root"));
+}
+
+#[test]
+fn should_match_routes_with_spaces_and_utf8_when_path_is_percent_encoded() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/café/menu", |_| HttpResponse::text("menu"))
+                .to("/users/{name}", |request| {
+                    HttpResponse::text(request.path_param("name").unwrap())
+                })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let menu_response = client.get("/caf%C3%A9/menu");
+    assert_eq!(menu_response.body, Body::from("menu"));
+    let user_response = client.get("/users/J%C3%BCrgen%20M");
+    assert_eq!(user_response.body, Body::from("Jürgen M"));
+}
+
+fn static_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("martian-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(dir.join("css")).unwrap();
+    std::fs::write(dir.join("css/site.CSS"), "body {}").unwrap();
+    std::fs::write(dir.join("data.bin"), [0u8, 1, 2]).unwrap();
+    dir
+}
+
+fn serve_to_string(server: &Server, raw_request: &str) -> String {
+    let mut stream = TestStream::of(raw_request);
+    server.serve(&mut stream, None, None).unwrap();
+    String::from_utf8_lossy(&stream.output).into_owned()
+}
+
+#[test]
+fn should_stream_file_with_content_type_and_length_when_serving_static_files() {
+    let dir = static_dir("static-files");
+    let mut server = Server::default();
+    server
+        .route(|| static_files::serve("/assets/", &dir))
+        .unwrap();
+    let css = serve_to_string(&server, "GET /assets/css/site.CSS?v=2 HTTP/1.1\r\n\r\n");
+    assert!(css.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(css.contains("Content-Type: text/css; charset=utf-8\r\n"));
+    assert!(css.ends_with("Content-Length: 7\r\n\r\nbody {}"));
+    let bin = serve_to_string(&server, "GET /assets/data.bin HTTP/1.1\r\n\r\n");
+    assert!(bin.contains("Content-Type: application/octet-stream\r\n"));
+    let head = serve_to_string(&server, "HEAD /assets/css/site.CSS HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.ends_with("Content-Length: 7\r\n\r\n"));
+    let http_1_0 = serve_to_string(&server, "GET /assets/css/site.CSS HTTP/1.0\r\n\r\n");
+    assert!(http_1_0.starts_with("HTTP/1.0 200 OK\r\n"));
+    assert!(http_1_0.ends_with("Content-Length: 7\r\n\r\nbody {}"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn should_not_find_anything_but_files_under_static_dir() {
+    let dir = static_dir("static-traversal");
+    let mut server = Server::default();
+    server
+        .route(|| static_files::serve("/assets", dir.join("css")))
+        .unwrap();
+    for uri in [
+        "/assets/../data.bin",
+        "/assets/%2E%2E/data.bin",
+        "/assets/..%2Fdata.bin",
+        "/assets/..%5Cdata.bin",
+        "/assets/missing.css",
+        "/assets/",
+        "/assets",
+    ] {
+        let response = serve_to_string(&server, &format!("GET {} HTTP/1.1\r\n\r\n", uri));
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{} was found",
+            uri
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn should_answer_not_modified_when_static_file_is_unchanged_since_client_got_it() {
+    let dir = static_dir("static-conditional");
+    let modified = std::time::UNIX_EPOCH + Duration::from_secs(784_111_777);
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("css/site.CSS"))
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let mut server = Server::default();
+    server
+        .route(|| static_files::serve("/assets", &dir))
+        .unwrap();
+    let css = serve_to_string(&server, "GET /assets/css/site.CSS HTTP/1.1\r\n\r\n");
+    assert!(css.contains("Last-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n"));
+    assert!(css.contains("ETag: W/\"2ebc98a1-7\"\r\n"));
+    for validator in [
+        "If-None-Match: \"other\", W/\"2ebc98a1-7\"",
+        "If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT",
+        "If-Modified-Since: Mon, 07 Nov 1994 00:00:00 GMT",
+    ] {
+        let response = serve_to_string(
+            &server,
+            &format!("GET /assets/css/site.CSS HTTP/1.1\r\n{}\r\n\r\n", validator),
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 304 Not Modified\r\n"),
+            "{}",
+            validator
+        );
+        assert!(response.contains("ETag: W/\"2ebc98a1-7\"\r\n"));
+        assert!(!response.contains("Content-Type"), "{}", validator);
+        assert!(response.ends_with("\r\n\r\n"));
+    }
+    for validator in [
+        "If-None-Match: \"2ebc98a1-8\"",
+        "If-Modified-Since: Sat, 05 Nov 1994 08:49:37 GMT",
+        // Not looked at along with `If-None-Match`.
+        "If-None-Match: \"other\"\r\nIf-Modified-Since: Mon, 07 Nov 1994 00:00:00 GMT",
+    ] {
+        let response = serve_to_string(
+            &server,
+            &format!("GET /assets/css/site.CSS HTTP/1.1\r\n{}\r\n\r\n", validator),
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", validator);
+        assert!(response.ends_with("Content-Length: 7\r\n\r\nbody {}"));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn should_answer_partial_content_when_range_of_static_file_is_requested() {
+    let dir = static_dir("static-range");
+    std::fs::write(dir.join("video.mp4"), "0123456789abcdef").unwrap();
+    let mut server = Server::default();
+    server
+        .route(|| static_files::serve("/media", &dir))
+        .unwrap();
+    let whole = serve_to_string(&server, "GET /media/video.mp4 HTTP/1.1\r\n\r\n");
+    assert!(whole.contains("Accept-Ranges: bytes\r\n"));
+    let single = serve_to_string(
+        &server,
+        "GET /media/video.mp4 HTTP/1.1\r\nRange: bytes=10-\r\n\r\n",
+    );
+    assert!(single.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert!(single.contains("Content-Range: bytes 10-15/16\r\n"));
+    assert!(single.contains("Content-Type: video/mp4\r\n"));
+    assert!(single.ends_with("6\r\nabcdef\r\n0\r\n\r\n"));
+    let multiple = serve_to_string(
+        &server,
+        "GET /media/video.mp4 HTTP/1.1\r\nRange: bytes=1-2,-1\r\n\r\n",
+    );
+    assert!(multiple.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert!(multiple.contains("Content-Type: multipart/byteranges; boundary="));
+    let (_, chunked) = multiple.split_once("\r\n\r\n").unwrap();
+    let parts =
+        String::from_utf8(crate::web::chunked::decode(chunked.as_bytes()).unwrap()).unwrap();
+    assert!(parts.contains("Content-Range: bytes 1-2/16\r\n\r\n12\r\n--"));
+    assert!(parts.contains("Content-Range: bytes 15-15/16\r\n\r\nf\r\n--"));
+    let unsatisfiable = serve_to_string(
+        &server,
+        "GET /media/video.mp4 HTTP/1.1\r\nRange: bytes=16-20\r\n\r\n",
+    );
+    assert!(unsatisfiable.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"));
+    assert!(unsatisfiable.contains("Content-Range: bytes */16\r\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn site_dir(name: &str) -> std::path::PathBuf {
+    let dir = static_dir(name);
+    std::fs::write(dir.join("index.html"), "home").unwrap();
+    std::fs::write(dir.join("css/main.htm"), "styles").unwrap();
+    std::fs::write(dir.join("<b>&.txt"), "odd").unwrap();
+    dir
+}
+
+#[test]
+fn should_answer_directory_with_its_index_when_serving_static_files() {
+    let dir = site_dir("static-index");
+    let mut server = Server::default();
+    server.route(|| StaticFiles::new(&dir).bind("/")).unwrap();
+    assert!(serve_to_string(&server, "GET / HTTP/1.1\r\n\r\n").ends_with("\r\n\r\nhome"));
+    assert!(serve_to_string(&server, "GET /css/ HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404"));
+    let mut custom_server = Server::default();
+    custom_server
+        .route(|| StaticFiles::new(&dir).index("main.htm").bind("/site"))
+        .unwrap();
+    let redirect = serve_to_string(&custom_server, "GET /site/css HTTP/1.1\r\n\r\n");
+    assert!(redirect.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+    assert!(redirect.contains("Location: /site/css/\r\n"));
+    let index = serve_to_string(&custom_server, "GET /site/css/ HTTP/1.1\r\n\r\n");
+    assert!(index.ends_with("\r\n\r\nstyles"));
+    let mut indexless_server = Server::default();
+    indexless_server
+        .route(|| StaticFiles::new(&dir).without_index().bind("/"))
+        .unwrap();
+    assert!(
+        serve_to_string(&indexless_server, "GET / HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404")
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn should_list_directory_without_index_when_listing_is_enabled() {
+    let dir = site_dir("static-listing");
+    let mut server = Server::default();
+    server
+        .route(|| {
+            StaticFiles::new(&dir)
+                .without_index()
+                .listing()
+                .bind("/files")
+        })
+        .unwrap();
+    let listing = serve_to_string(&server, "GET /files/ HTTP/1.1\r\n\r\n");
+    assert!(listing.contains("Content-Type: text/html"));
+    let css = listing.find("<li><a href=\"css/\">css/</a></li>").unwrap();
+    let odd = listing
+        .find("<li><a href=\"%3Cb%3E%26.txt\">&lt;b&gt;&amp;.txt</a></li>")
+        .unwrap();
+    let index = listing.find("<li><a href=\"index.html\">").unwrap();
+    assert!(css < odd && odd < index);
+    assert!(listing.contains("<title>Index of /files/</title>"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn should_fall_back_to_index_for_unknown_paths_in_spa_mode() {
+    let dir = site_dir("static-spa");
+    let mut server = Server::default();
+    server
+        .route(|| StaticFiles::new(&dir).spa().bind("/"))
+        .unwrap();
+    let client_route = serve_to_string(&server, "GET /users/7/profile HTTP/1.1\r\n\r\n");
+    assert!(client_route.ends_with("\r\n\r\nhome"));
+    let file = serve_to_string(&server, "GET /css/main.htm HTTP/1.1\r\n\r\n");
+    assert!(file.ends_with("\r\n\r\nstyles"));
+    let traversal = serve_to_string(&server, "GET /../secret HTTP/1.1\r\n\r\n");
+    assert!(traversal.starts_with("HTTP/1.1 404"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn typed_user(PathParam(id): PathParam<u32>, verbose: Option<QueryParams>) -> HttpResponse {
+    let verbose = verbose.is_some_and(|params| params.contains("verbose"));
+    HttpResponse::text(&format!("user {} verbose {}", id, verbose))
+}
+
+#[test]
+fn should_call_typed_handler_with_extracted_arguments() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to_typed("/users/{id}", typed_user)
+                .to_typed("/ping", || HttpResponse::text("pong"))
+                .to_typed("/raw", |request: HttpRequest| {
+                    HttpResponse::text(request.uri.as_str())
+                })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let user = client.get("/users/7?verbose");
+    assert_eq!(user.body, Body::from("user 7 verbose true"));
+    let ping = client.get("/ping");
+    assert_eq!(ping.body, Body::from("pong"));
+    let raw = client.get("/raw?a=b");
+    assert_eq!(raw.body, Body::from("/raw?a=b"));
+}
+
+#[test]
+fn should_answer_bad_request_without_calling_typed_handler_when_extraction_fails() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to_typed("/users/{id}", typed_user))
+        .unwrap();
+    let client = TestClient::new(server);
+    let response = client.get("/users/seven");
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+    assert_eq!(
+        response.body,
+        Body::from("Invalid value \"seven\" for param \"id\": invalid digit found in string")
+    );
+}
+
+#[test]
+fn should_answer_internal_server_error_when_path_param_is_ambiguous() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get).to_typed("/{a}/{b}", |PathParam(a): PathParam<String>| {
+                HttpResponse::text(&a)
+            })
+        })
+        .unwrap();
+    server.on_error(|error| (error.status_code, error.message));
+    let client = TestClient::new(server);
+    let response = client.get("/x/y");
+    assert_eq!(response.status_code, StatusCode::InternalServerError);
+    assert_eq!(
+        response.body,
+        Body::from(
+            "Unfit for the route: PathParam needs exactly one path param to be captured, \
+             but 2 were for /x/y"
+        )
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_extract_json_body_for_typed_handler() {
+    use crate::server::Json;
+    #[derive(serde::Deserialize)]
+    struct Landing {
+        site: String,
+    }
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post).to_typed("/landings", |Json(landing): Json<Landing>| {
+                HttpResponse::text(&landing.site)
+            })
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let response = client.post("/landings", "{\"site\":\"Jezero\"}");
+    assert_eq!(response.body, Body::from("Jezero"));
+    let response = client.post("/landings", "{}");
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+}
+
+#[test]
+fn should_respond_with_whatever_handler_returns_when_it_is_into_response() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/text", |_| "plain")
+                .to("/created", |_| (StatusCode::Created, String::from("made")))
+                .to("/maybe", |request| {
+                    request.params().get("found").map(String::from)
+                })
+                .to("/result", |request| {
+                    request
+                        .params()
+                        .get("ok")
+                        .map(|_| "fine")
+                        .ok_or(StatusCode::Forbidden)
+                })
+                .to_typed("/typed/{id}", |PathParam(id): PathParam<u32>| {
+                    format!("id {}", id)
+                })
+        })
+        .unwrap();
+    let text = serve_to_string(&server, "GET /text HTTP/1.1\r\n\r\n");
+    assert!(text.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(text.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+    assert!(text.ends_with("\r\n\r\nplain"));
+    let created = serve_to_string(&server, "GET /created HTTP/1.1\r\n\r\n");
+    assert!(created.starts_with("HTTP/1.1 201 Created\r\n"));
+    assert!(created.ends_with("\r\n\r\nmade"));
+    let found = serve_to_string(&server, "GET /maybe?found=yes HTTP/1.1\r\n\r\n");
+    assert!(found.ends_with("\r\n\r\nyes"));
+    let missing = serve_to_string(&server, "GET /maybe HTTP/1.1\r\n\r\n");
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    let forbidden = serve_to_string(&server, "GET /result HTTP/1.1\r\n\r\n");
+    assert!(forbidden.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    let typed = serve_to_string(&server, "GET /typed/7 HTTP/1.1\r\n\r\n");
+    assert!(typed.ends_with("\r\n\r\nid 7"));
+}
+
+#[test]
+fn should_stamp_date_and_server_when_writing_response() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", |_| HttpResponse::text("hello"))
+                .to("/own", |_| {
+                    let mut response = HttpResponse::text("hello");
+                    response.headers.insert("server".into(), "own".into());
+                    response
+                })
+        })
+        .unwrap();
+    let mut stream = TestStream::of("GET / HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let response = HttpResponse::from(std::str::from_utf8(&stream.output).unwrap());
+    let date = crate::web::date::parse_http_date(&response.headers["Date"]).unwrap();
+    assert!(date.elapsed().unwrap_or_default() < Duration::from_secs(60));
+    assert_eq!(
+        response.headers["Server"],
+        concat!("martian/", env!("CARGO_PKG_VERSION"))
+    );
+    let mut stream = TestStream::of("GET /own HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let response = HttpResponse::from(std::str::from_utf8(&stream.output).unwrap());
+    assert_eq!(response.header("Server"), Some("own"));
+    let client = TestClient::new(server);
+    assert_eq!(client.send(request_to("/")).header("Date"), None);
+}
+
+#[test]
+fn should_stamp_only_configured_headers_when_default_headers_are_set() {
+    let mut server = Server::default();
+    server.default_headers(DefaultHeaders {
+        date: false,
+        server: Some("edge".into()),
+    });
+    let mut stream = TestStream::of("GET /nowhere HTTP/1.1\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let response = HttpResponse::from(std::str::from_utf8(&stream.output).unwrap());
+    assert_eq!(response.headers.get("Date"), None);
+    assert_eq!(response.headers["Server"], "edge");
+    let mut stream = TestStream::of("nonsense\r\n\r\n");
+    server.serve(&mut stream, None, None).unwrap();
+    let response = HttpResponse::from(std::str::from_utf8(&stream.output).unwrap());
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+    assert_eq!(response.headers["Server"], "edge");
+}
+
+#[test]
+fn should_fall_through_to_next_route_when_guard_turns_request_away() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post)
+                .to("/hook", |_| "form")
+                .to("/hook", |_| "json")
+                .guard(ContentTypeIs(Mime::ApplicationJson))
+                .to("/{name}", |_| "trusted")
+                .guard(HeaderEquals("X-Token", String::from("s3cret")))
+                .guard(|request: &HttpRequest| request.body_bytes() == b"{}")
+        })
+        .unwrap();
+    let client = TestClient::new(server);
+    let respond = |raw_request: &str| {
+        client
+            .send(HttpRequest::from(raw_request))
+            .into_inner()
+            .body
+    };
+    assert_eq!(
+        respond("POST /hook HTTP/1.1\r\nContent-Type: application/json\r\n\r\n"),
+        Body::from("json")
+    );
+    assert_eq!(respond("POST /hook HTTP/1.1\r\n\r\n"), Body::from("form"));
+    assert_eq!(
+        respond("POST /other HTTP/1.1\r\nX-Token: s3cret\r\nContent-Length: 2\r\n\r\n{}"),
+        Body::from("trusted")
+    );
+    let response = client.send(HttpRequest::from(
+        "POST /other HTTP/1.1\r\nx-token: s3cret\r\nContent-Length: 2\r\n\r\n[]",
+    ));
+    assert_eq!(response.status_code, StatusCode::NotFound);
+}
+
+#[test]
+fn should_conflict_only_when_neither_route_has_guards() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", |_| "plain")
+                .to("/", |_| "guarded")
+                .guard(HeaderPresent("Authorization"))
+        })
+        .unwrap();
+    let conflict = server.route(|| Route::bind(HttpMethod::Get).to("/", |_| "again"));
+    assert!(conflict.is_err());
+    let request = HttpRequest::from("GET / HTTP/1.1\r\nAuthorization: Bearer t\r\n\r\n");
+    let client = TestClient::new(server);
+    assert_eq!(client.send(request).body, Body::from("guarded"));
+}
+
+#[test]
+fn should_build_path_of_named_route_when_params_are_given() {
+    let mut api = Server::default();
+    api.route(|| {
+        Route::bind(HttpMethod::Get)
+            .to_named("api_root", "/", test_get)
+            .to_named("file", "/files/{owner}/**path", test_get)
+    })
+    .unwrap();
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to_named("user_detail", "/users/{id}/caf%C3%A9", test_get)
+                .to_named("any", "/any/*/thing", test_get)
+        })
+        .unwrap();
+    server.mount("/api", api);
+    assert_eq!(
+        server.url_for("user_detail", &[("id", "a b/.."), ("unused", "x")]),
+        Some("/users/a%20b%2F../caf%C3%A9".into())
+    );
+    assert_eq!(
+        server.url_for("user_detail", &[("id", "..")]),
+        Some("/users/%2E%2E/caf%C3%A9".into())
+    );
+    assert_eq!(server.url_for("user_detail", &[("id", "")]), None);
+    assert_eq!(server.url_for("any", &[]), None);
+    assert_eq!(server.url_for("missing", &[]), None);
+    assert_eq!(server.url_for("api_root", &[]), Some("/api".into()));
+    assert_eq!(
+        server.url_for("file", &[("owner", "me"), ("path", "docs/read me.txt")]),
+        Some("/api/files/me/docs/read%20me.txt".into())
+    );
+    let path = server
+        .url_for("file", &[("owner", "me"), ("path", "a/b")])
+        .unwrap();
+    let client = TestClient::new(server);
+    let response = client.send(request_to(&path));
+    assert_eq!(response.status_code, StatusCode::Ok);
+}
+
+#[test]
+fn should_list_routes_in_bind_order_followed_by_mounted_ones() {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post)
+                .to("/users", test_get)
+                .to_named("user_detail", "/users/{id}", test_get)
+        })
+        .unwrap();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/", test_get))
+        .unwrap();
+    server.mount("/api", api_server());
+    let routes = server
+        .routes()
+        .map(|(http_method, uri, name)| (http_method.as_str(), uri, name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        routes,
+        [
+            ("POST", "/users".to_string(), None),
+            ("POST", "/users/{id}".into(), Some("user_detail")),
+            ("GET", "/".into(), None),
+            ("GET", "/api".into(), None),
+            ("GET", "/api/users/{id}".into(), None),
+        ]
+    );
+}
@@ -0,0 +1,311 @@
+//! Terminating TLS on the connections a [`Server`] accepts, so it can serve
+//! HTTPS itself. Only available with the `tls` feature.
+//!
+//! [`Server`]: ../struct.Server.html
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use webpki::EndEntityCert;
+
+use crate::web::PeerIdentity;
+
+/// Where to load the certificate chain and private key presented to clients
+/// from, both PEM encoded.
+///
+/// The files are checked on every handshake and loaded again once changed,
+/// so a renewed certificate is picked up without restarting the listener. A
+/// change which can not be loaded, such as a certificate written before its
+/// key, keeps the certificate loaded last until both are in order.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{ClientAuth, TlsConfig, TlsHost};
+/// let tls_config = TlsConfig {
+///     cert_pem_path: "certs/default.pem".into(),
+///     key_pem_path: "certs/default.key".into(),
+///     hosts: vec![TlsHost {
+///         hostname: "api.example.com".into(),
+///         cert_pem_path: "certs/api.pem".into(),
+///         key_pem_path: "certs/api.key".into(),
+///     }],
+///     client_auth: ClientAuth::None,
+/// };
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct TlsConfig {
+    /// The certificate chain, leaf certificate first.
+    pub cert_pem_path: PathBuf,
+    pub key_pem_path: PathBuf,
+    /// Certificates presented in place of the one above to a client asking
+    /// for their hostname through SNI.
+    pub hosts: Vec<TlsHost>,
+    /// Whether clients are asked for a certificate of their own.
+    pub client_auth: ClientAuth,
+}
+
+/// Whether a [`TlsConfig`] asks clients for a certificate, and which CAs it
+/// has to be signed by. A client presenting one which does not verify fails
+/// its handshake. What a verified one says of the client is on the
+/// [`ConnectionInfo`] of its requests, as its [`PeerIdentity`].
+///
+/// [`TlsConfig`]: ./struct.TlsConfig.html
+/// [`ConnectionInfo`]: ../web/struct.ConnectionInfo.html
+/// [`PeerIdentity`]: ../web/struct.PeerIdentity.html
+#[derive(PartialEq, Debug, Clone, Default)]
+pub enum ClientAuth {
+    /// No certificate is asked for.
+    #[default]
+    None,
+    /// A certificate is asked for, signed by one of the CAs in the PEM file,
+    /// but a client without one is still served.
+    Optional(PathBuf),
+    /// A certificate signed by one of the CAs in the PEM file is needed to
+    /// be served at all.
+    Required(PathBuf),
+}
+
+/// A certificate of a [`TlsConfig`] presented only for the one hostname.
+///
+/// [`TlsConfig`]: ./struct.TlsConfig.html
+#[derive(PartialEq, Debug, Clone)]
+pub struct TlsHost {
+    /// Matched against the hostname asked for, ignoring case.
+    pub hostname: String,
+    pub cert_pem_path: PathBuf,
+    pub key_pem_path: PathBuf,
+}
+
+impl TlsConfig {
+    /// Loads every certificate chain and key, returning an `Err` if any can
+    /// not be read or do not belong together.
+    pub(in crate::server) fn server_config(&self) -> io::Result<Arc<ServerConfig>> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let resolver = CertResolver {
+            default: ReloadingCert::load(&self.cert_pem_path, &self.key_pem_path, &provider)?,
+            hosts: self
+                .hosts
+                .iter()
+                .map(|host| {
+                    let cert =
+                        ReloadingCert::load(&host.cert_pem_path, &host.key_pem_path, &provider)?;
+                    Ok((host.hostname.to_ascii_lowercase(), cert))
+                })
+                .collect::<io::Result<_>>()?,
+            provider: provider.clone(),
+        };
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?;
+        let builder = match &self.client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(ca_pem_path) | ClientAuth::Required(ca_pem_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(ca_pem_path).map_err(invalid_data)? {
+                    roots
+                        .add(cert.map_err(invalid_data)?)
+                        .map_err(invalid_data)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = match self.client_auth {
+                    ClientAuth::Optional(_) => verifier.allow_unauthenticated(),
+                    _ => verifier,
+                };
+                builder.with_client_cert_verifier(verifier.build().map_err(invalid_data)?)
+            }
+        };
+        let server_config = builder.with_cert_resolver(Arc::new(resolver));
+        // Preferred, a client able to speak it being better off doing so.
+        #[cfg(feature = "http2")]
+        let server_config = {
+            let mut server_config = server_config;
+            server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+            server_config
+        };
+        Ok(Arc::new(server_config))
+    }
+}
+
+/// Picks the certificate of the hostname asked for, falling back on the
+/// default one.
+#[derive(Debug)]
+struct CertResolver {
+    default: ReloadingCert,
+    /// Keyed by lowercase hostname.
+    hosts: HashMap<String, ReloadingCert>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let cert = client_hello
+            .server_name()
+            .and_then(|hostname| self.hosts.get(&hostname.to_ascii_lowercase()))
+            .unwrap_or(&self.default);
+        Some(cert.current(&self.provider))
+    }
+}
+
+/// A certificate chain and key, along with when their files were last
+/// modified as of loading them.
+struct ReloadingCert {
+    cert_pem_path: PathBuf,
+    key_pem_path: PathBuf,
+    loaded: Mutex<(Modified, Arc<CertifiedKey>)>,
+}
+
+type Modified = (Option<SystemTime>, Option<SystemTime>);
+
+impl ReloadingCert {
+    fn load(
+        cert_pem_path: &Path,
+        key_pem_path: &Path,
+        provider: &CryptoProvider,
+    ) -> io::Result<ReloadingCert> {
+        let modified = modified(cert_pem_path, key_pem_path);
+        let certified_key = load_certified_key(cert_pem_path, key_pem_path, provider)?;
+        Ok(ReloadingCert {
+            cert_pem_path: cert_pem_path.into(),
+            key_pem_path: key_pem_path.into(),
+            loaded: Mutex::new((modified, Arc::new(certified_key))),
+        })
+    }
+
+    /// The certificate as the files are now, loading them again if they have
+    /// changed since last loaded.
+    fn current(&self, provider: &CryptoProvider) -> Arc<CertifiedKey> {
+        let modified = modified(&self.cert_pem_path, &self.key_pem_path);
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        if loaded.0 != modified {
+            if let Ok(certified_key) =
+                load_certified_key(&self.cert_pem_path, &self.key_pem_path, provider)
+            {
+                *loaded = (modified, Arc::new(certified_key));
+            }
+        }
+        loaded.1.clone()
+    }
+}
+
+impl fmt::Debug for ReloadingCert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReloadingCert")
+            .field("cert_pem_path", &self.cert_pem_path)
+            .field("key_pem_path", &self.key_pem_path)
+            .finish()
+    }
+}
+
+fn modified(cert_pem_path: &Path, key_pem_path: &Path) -> Modified {
+    let modified = |path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    (modified(cert_pem_path), modified(key_pem_path))
+}
+
+fn load_certified_key(
+    cert_pem_path: &Path,
+    key_pem_path: &Path,
+    provider: &CryptoProvider,
+) -> io::Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(cert_pem_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(invalid_data)?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "No certificate in PEM file",
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_pem_path).map_err(invalid_data)?;
+    CertifiedKey::from_der(certs, key, provider).map_err(invalid_data)
+}
+
+/// What the certificate the client presented says of it, which by the end of
+/// the handshake has been verified.
+pub(in crate::server) fn peer_identity(connection: &ServerConnection) -> Option<PeerIdentity> {
+    let cert = EndEntityCert::try_from(connection.peer_certificates()?.first()?).ok()?;
+    Some(PeerIdentity {
+        common_name: common_name(cert.subject()),
+        dns_names: cert.valid_dns_names().map(String::from).collect(),
+        uri_names: cert.valid_uri_names().map(String::from).collect(),
+    })
+}
+
+/// The common name of a DER encoded subject, a sequence of sets of
+/// attributes each being an OID followed by its value.
+fn common_name(mut subject: &[u8]) -> Option<String> {
+    const COMMON_NAME_OID: &[u8] = &[0x55, 0x04, 0x03];
+    while !subject.is_empty() {
+        let (_, mut attributes, rest) = der_element(subject)?;
+        subject = rest;
+        while !attributes.is_empty() {
+            let (_, attribute, rest) = der_element(attributes)?;
+            attributes = rest;
+            let (_, oid, value) = der_element(attribute)?;
+            if oid == COMMON_NAME_OID {
+                let (_, value, _) = der_element(value)?;
+                return String::from_utf8(value.to_vec()).ok();
+            }
+        }
+    }
+    None
+}
+
+/// Splits off the first DER element, into its tag, its contents and what
+/// follows it.
+fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&length, mut rest) = rest.split_first()?;
+    let length = match length {
+        0..=0x7f => length as usize,
+        // The long form, giving how many of the bytes after it hold the length.
+        _ => {
+            let length_bytes = (length & 0x7f) as usize;
+            if length_bytes > 4 || rest.len() < length_bytes {
+                return None;
+            }
+            let (length_bytes, after) = rest.split_at(length_bytes);
+            rest = after;
+            length_bytes
+                .iter()
+                .fold(0, |length, &byte| length << 8 | byte as usize)
+        }
+    };
+    if rest.len() < length {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(length);
+    Some((tag, contents, rest))
+}
+
+/// An accepted connection, the handshake happening as it is first read from.
+pub(in crate::server) type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+pub(in crate::server) fn accept(
+    config: &Arc<ServerConfig>,
+    stream: TcpStream,
+) -> io::Result<TlsStream> {
+    let connection = ServerConnection::new(config.clone()).map_err(invalid_data)?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e)
+}
@@ -0,0 +1,85 @@
+//! Sending clients on to one form of every path, with or without a trailing
+//! slash, whichever the `Server` has its routes bound with.
+
+use crate::web::{HttpMethod, HttpRequest, HttpResponse};
+
+use super::{mount, Middleware, Next};
+
+/// [`Middleware`] answering a request whose path ends in a slash, or does not,
+/// with a redirect to the other form, whether or not it is bound. Unlike
+/// [`TrailingSlash::RedirectToCanonical`], this also sends clients on to the
+/// form with the slash, and redirects requests no route would have matched
+/// either way. The root `/` is never redirected, neither is a path starting
+/// with `//`, which a client would take for another host.
+///
+/// A `GET` or `HEAD` is answered with a 301, any other request with a 308 so
+/// that it is repeated as it was, body and all. The query is kept.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Server, TrailingSlashRedirect};
+/// let mut server = Server::default();
+/// // `/docs` is sent on to `/docs/`, `/app.js` is left be.
+/// server.wrap(TrailingSlashRedirect::append());
+/// ```
+///
+/// [`Middleware`]: ./trait.Middleware.html
+/// [`TrailingSlash::RedirectToCanonical`]: ./enum.TrailingSlash.html#variant.RedirectToCanonical
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TrailingSlashRedirect {
+    is_appending: bool,
+}
+
+impl TrailingSlashRedirect {
+    /// Sends `/hello` on to `/hello/`, as long as its last segment does not
+    /// look like a file name, with a `.` in it.
+    pub fn append() -> TrailingSlashRedirect {
+        TrailingSlashRedirect { is_appending: true }
+    }
+
+    /// Sends `/hello/` on to `/hello`.
+    pub fn remove() -> TrailingSlashRedirect {
+        TrailingSlashRedirect {
+            is_appending: false,
+        }
+    }
+
+    /// The path the request is sent on to, if any.
+    fn canonical_path(&self, path: &str) -> Option<String> {
+        if path == "/" || path.starts_with("//") || path.starts_with("/\\") {
+            return None;
+        }
+        match (self.is_appending, path.strip_suffix('/')) {
+            (true, None) => {
+                let last_segment = path.rsplit('/').next().unwrap_or_default();
+                (!last_segment.contains('.')).then(|| format!("{}/", path))
+            }
+            (false, Some(_)) => {
+                let slashless = path.trim_end_matches('/');
+                (!slashless.is_empty()).then(|| slashless.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Middleware for TrailingSlashRedirect {
+    fn handle(&self, request: HttpRequest, next: Next) -> HttpResponse {
+        let path = match self.canonical_path(request.uri.path()) {
+            Some(path) => path,
+            None => return next.run(request),
+        };
+        let location = mount::location(&request, &path);
+        let location = match request.uri.query() {
+            Some(query) => format!("{}?{}", location, query),
+            None => location,
+        };
+        match request.http_method {
+            HttpMethod::Get | HttpMethod::Head => HttpResponse::redirect_permanent(&location),
+            _ => HttpResponse::permanent_redirect(&location),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use crate::server::{Route, Server, TrailingSlashRedirect};
+use crate::test::{TestClient, TestResponse};
+use crate::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+fn redirecting_server(trailing_slash_redirect: TrailingSlashRedirect) -> Server {
+    let mut server = Server::default();
+    server.wrap(trailing_slash_redirect);
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/", |_| "root")
+                .to("/docs/", |_| "docs")
+                .to("/hello", |_| "hello")
+                .to("/app.js", |_| "script")
+        })
+        .unwrap();
+    server
+}
+
+fn request(client: &TestClient, request_line: &str) -> TestResponse {
+    client.send(HttpRequest::from(
+        format!("{} HTTP/1.1\r\n\r\n", request_line).as_str(),
+    ))
+}
+
+fn location(response: &HttpResponse) -> Option<&str> {
+    response.headers.get("Location").map(String::as_str)
+}
+
+#[test]
+fn should_redirect_to_path_with_slash_when_appending() {
+    let client = TestClient::new(redirecting_server(TrailingSlashRedirect::append()));
+    let response = request(&client, "GET /docs?page=2");
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(location(&response), Some("/docs/?page=2"));
+    let response = request(&client, "POST /api/orders");
+    assert_eq!(response.status_code, StatusCode::PermanentRedirect);
+    assert_eq!(location(&response), Some("/api/orders/"));
+    for request_line in ["GET /docs/", "GET /app.js", "GET /", "GET //evil.example"] {
+        let response = request(&client, request_line);
+        assert_eq!(location(&response), None, "{}", request_line);
+    }
+}
+
+#[test]
+fn should_redirect_to_path_without_slash_when_removing() {
+    let client = TestClient::new(redirecting_server(TrailingSlashRedirect::remove()));
+    let response = request(&client, "HEAD /hello//?a=b");
+    assert_eq!(response.status_code, StatusCode::MovedPermanently);
+    assert_eq!(location(&response), Some("/hello?a=b"));
+    let response = request(&client, "DELETE /api/orders/7/");
+    assert_eq!(response.status_code, StatusCode::PermanentRedirect);
+    assert_eq!(location(&response), Some("/api/orders/7"));
+    for request_line in ["GET /hello", "GET /", "GET //evil.example/", "GET /\\evil/"] {
+        let response = request(&client, request_line);
+        assert_eq!(location(&response), None, "{}", request_line);
+    }
+}
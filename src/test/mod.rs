@@ -0,0 +1,358 @@
+//! Testing a [`Server`] by handing it requests directly, without a socket in
+//! between, and checking what it answers with, or by serving it over TCP on
+//! a port of its own for as long as a test runs.
+//!
+//! [`Server`]: ../server/struct.Server.html
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::panic;
+use std::thread::{self, JoinHandle};
+
+#[cfg(feature = "json")]
+use serde::de::DeserializeOwned;
+
+use crate::server::{HttpServer, Server, Shutdown};
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, HttpVersion, State, StatusCode};
+
+/// Hands requests to a [`Server`] as though they had been read off of a
+/// connection, through its middleware, routes and not found handler alike.
+/// The response is as the `Server` handled it, without the headers it stamps
+/// on those it writes out, see [`DefaultHeaders`], and a response to a
+/// `HEAD` request keeps its body.
+///
+/// # Examples:
+/// ```
+/// use martian::server::{Route, Server};
+/// use martian::test::TestClient;
+/// use martian::web::{HttpMethod, HttpRequest, StatusCode};
+/// fn echo(request: HttpRequest) -> String {
+///     request.body_text().unwrap_or_default().to_string()
+/// }
+/// let mut server = Server::default();
+/// server
+///     .route(|| Route::bind(HttpMethod::Post).to("/echo", echo))
+///     .unwrap();
+/// let client = TestClient::new(server);
+/// client
+///     .post("/echo", "ping")
+///     .assert_status(StatusCode::Ok)
+///     .assert_body("ping");
+/// client
+///     .request(HttpMethod::Get, "/missing")
+///     .header("Accept", "text/plain")
+///     .send()
+///     .assert_status(StatusCode::NotFound);
+/// ```
+///
+/// [`Server`]: ../server/struct.Server.html
+/// [`DefaultHeaders`]: ../server/struct.DefaultHeaders.html
+pub struct TestClient {
+    server: Server,
+}
+
+impl TestClient {
+    pub fn new(server: Server) -> TestClient {
+        TestClient { server }
+    }
+
+    pub fn get(&self, uri: &str) -> TestResponse {
+        self.request(HttpMethod::Get, uri).send()
+    }
+
+    pub fn post(&self, uri: &str, body: &str) -> TestResponse {
+        self.request(HttpMethod::Post, uri).body(body).send()
+    }
+
+    pub fn head(&self, uri: &str) -> TestResponse {
+        self.request(HttpMethod::Head, uri).send()
+    }
+
+    pub fn put(&self, uri: &str, body: &str) -> TestResponse {
+        self.request(HttpMethod::Put, uri).body(body).send()
+    }
+
+    pub fn patch(&self, uri: &str, body: &str) -> TestResponse {
+        self.request(HttpMethod::Patch, uri).body(body).send()
+    }
+
+    pub fn delete(&self, uri: &str) -> TestResponse {
+        self.request(HttpMethod::Delete, uri).send()
+    }
+
+    pub fn options(&self, uri: &str) -> TestResponse {
+        self.request(HttpMethod::Options, uri).send()
+    }
+
+    /// Starts a request of any [`HttpMethod`], to be given headers and a
+    /// body before it is sent. `get`, `post` and the like are shorthands
+    /// for this.
+    ///
+    /// [`HttpMethod`]: ../web/enum.HttpMethod.html
+    pub fn request(&self, http_method: HttpMethod, uri: &str) -> TestRequest<'_> {
+        let mut headers = HashMap::new();
+        headers.insert("Host".into(), "localhost".into());
+        TestRequest {
+            client: self,
+            request: HttpRequest {
+                http_method,
+                uri: uri.into(),
+                http_version: HttpVersion::Http1_1,
+                headers: Some(headers),
+                body: None,
+                path_params: HashMap::new(),
+                state: State::default(),
+                extensions: State::default(),
+                connection: None,
+            },
+        }
+    }
+
+    /// Hands the request to the [`Server`] as it is, with a streamed body
+    /// read in full.
+    ///
+    /// [`Server`]: ../server/struct.Server.html
+    pub fn send(&self, request: HttpRequest) -> TestResponse {
+        let mut response = self.server.handle(request);
+        if let Body::Stream(stream) = &mut response.body {
+            let mut bytes = Vec::new();
+            stream
+                .read_to_end(&mut bytes)
+                .expect("The streamed body could not be read");
+            response.body = Body::Bytes(bytes);
+        }
+        TestResponse { response }
+    }
+}
+
+/// A request being made through a [`TestClient`], with a `Host` of
+/// `localhost` unless given another.
+///
+/// [`TestClient`]: ./struct.TestClient.html
+pub struct TestRequest<'a> {
+    client: &'a TestClient,
+    request: HttpRequest,
+}
+
+impl TestRequest<'_> {
+    /// Sets the header, in place of any of the same name in any case.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        let headers = self.request.headers.get_or_insert_with(HashMap::new);
+        headers.retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+        headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the body along with its `Content-Length`.
+    pub fn body<B: Into<Vec<u8>>>(self, body: B) -> Self {
+        let body = body.into();
+        let mut sent = self.header("Content-Length", &body.len().to_string());
+        sent.request.body = Some(body);
+        sent
+    }
+
+    /// Sets the body to the value as JSON, along with its `Content-Type`.
+    ///
+    /// # Panics:
+    /// If the value can not be serialized.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(self, value: &T) -> Self {
+        let body = serde_json::to_vec(value).expect("The value could not be serialized");
+        self.header("Content-Type", "application/json").body(body)
+    }
+
+    pub fn send(self) -> TestResponse {
+        self.client.send(self.request)
+    }
+}
+
+/// What the [`Server`] answered a [`TestClient`] with, dereferencing to the
+/// [`HttpResponse`] itself. Each assertion panics with the whole response
+/// when it fails, and otherwise returns the response for the next.
+///
+/// [`Server`]: ../server/struct.Server.html
+/// [`TestClient`]: ./struct.TestClient.html
+/// [`HttpResponse`]: ../web/struct.HttpResponse.html
+#[derive(Debug)]
+pub struct TestResponse {
+    response: HttpResponse,
+}
+
+impl TestResponse {
+    pub fn into_inner(self) -> HttpResponse {
+        self.response
+    }
+
+    /// The body, empty if there is none.
+    pub fn body_bytes(&self) -> &[u8] {
+        match &self.response.body {
+            Body::Bytes(bytes) => bytes,
+            _ => &[],
+        }
+    }
+
+    /// The body as text.
+    ///
+    /// # Panics:
+    /// If the body is not UTF-8.
+    #[track_caller]
+    pub fn body_text(&self) -> &str {
+        std::str::from_utf8(self.body_bytes()).expect("The body is not UTF-8")
+    }
+
+    /// The body read as JSON.
+    ///
+    /// # Panics:
+    /// If the body is not JSON of the type.
+    #[cfg(feature = "json")]
+    #[track_caller]
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(self.body_bytes())
+            .unwrap_or_else(|e| panic!("The body could not be read as JSON, {}: {:?}", e, self))
+    }
+
+    #[track_caller]
+    pub fn assert_status(&self, status_code: StatusCode) -> &TestResponse {
+        assert_eq!(
+            self.response.status_code, status_code,
+            "Unexpected status of {:?}",
+            self
+        );
+        self
+    }
+
+    /// Asserts the header, matched ignoring case, is set to the value.
+    #[track_caller]
+    pub fn assert_header(&self, name: &str, value: &str) -> &TestResponse {
+        assert_eq!(
+            self.response.header(name),
+            Some(value),
+            "Unexpected {} header of {:?}",
+            name,
+            self
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_no_header(&self, name: &str) -> &TestResponse {
+        assert_eq!(
+            self.response.header(name),
+            None,
+            "Unexpected {} header of {:?}",
+            name,
+            self
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_body<B: AsRef<[u8]>>(&self, body: B) -> &TestResponse {
+        let body = body.as_ref();
+        assert!(
+            self.body_bytes() == body,
+            "Expected a body of {:?}, but got {:?}",
+            String::from_utf8_lossy(body),
+            self
+        );
+        self
+    }
+}
+
+impl Deref for TestResponse {
+    type Target = HttpResponse;
+
+    fn deref(&self) -> &HttpResponse {
+        &self.response
+    }
+}
+
+/// An [`HttpServer`] serving on a thread of its own, see
+/// [`HttpServer::spawn_for_test`], shut down once dropped.
+///
+/// # Examples:
+/// ```
+/// use martian::client::HttpClient;
+/// use martian::server::{HttpServer, Route, Server};
+/// use martian::web::{HttpMethod, StatusCode};
+/// let mut server = Server::default();
+/// server
+///     .route(|| Route::bind(HttpMethod::Get).to("/hello", |_| "hello"))
+///     .unwrap();
+/// let test_server = HttpServer::spawn_for_test(server).unwrap();
+/// let response = HttpClient::default().get(&test_server.url("/hello")).unwrap();
+/// assert_eq!(response.status_code, StatusCode::Ok);
+/// test_server.shutdown().unwrap();
+/// ```
+///
+/// [`HttpServer`]: ../server/struct.HttpServer.html
+/// [`HttpServer::spawn_for_test`]: ../server/struct.HttpServer.html#method.spawn_for_test
+pub struct TestServer {
+    addr: SocketAddr,
+    shutdown: Shutdown,
+    started: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl TestServer {
+    /// Starts the server, every address of which has to be bound already.
+    pub(crate) fn spawn(http_server: HttpServer) -> TestServer {
+        let addr = http_server.addr();
+        let shutdown = http_server.shutdown();
+        let started = thread::spawn(move || http_server.start());
+        TestServer {
+            addr,
+            shutdown,
+            started: Some(started),
+        }
+    }
+
+    /// The address bound to, on the loopback interface.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The `http://` url of the path on the server, for an [`HttpClient`].
+    ///
+    /// [`HttpClient`]: ../client/struct.HttpClient.html
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Shuts the server down, waiting for it to stop.
+    ///
+    /// # Returns:
+    /// The `Err` the server stopped with, if any.
+    ///
+    /// # Panics:
+    /// If the server panicked, with its panic.
+    pub fn shutdown(mut self) -> io::Result<()> {
+        self.stop().unwrap_or(Ok(()))
+    }
+
+    fn stop(&mut self) -> Option<io::Result<()>> {
+        self.shutdown.trigger();
+        let started = self.started.take()?;
+        Some(
+            started
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload)),
+        )
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        // Waiting on a server which panicked would panic again, aborting a
+        // test already failing for its own reasons.
+        if thread::panicking() {
+            self.shutdown.trigger();
+        } else {
+            let _ = self.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,112 @@
+use std::io::Cursor;
+
+use crate::client::HttpClient;
+use crate::server::{HttpServer, Route, Server};
+use crate::test::TestClient;
+use crate::web::{Body, HttpMethod, HttpRequest, HttpResponse, StatusCode};
+
+fn client() -> TestClient {
+    let mut server = Server::default();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Post).to("/echo", |request: HttpRequest| {
+                let mut response = HttpResponse::ok_with_body(request.body_bytes().to_vec());
+                let length = request.content_length().unwrap_or_default();
+                response.set_header("X-Length", &length.to_string());
+                response
+            })
+        })
+        .unwrap();
+    server
+        .route(|| {
+            Route::bind(HttpMethod::Get)
+                .to("/host", |request: HttpRequest| {
+                    request.host().unwrap_or_default().to_string()
+                })
+                .to("/stream", |_| {
+                    HttpResponse::ok_with_body(Body::Stream(Box::new(Cursor::new("streamed"))))
+                })
+        })
+        .unwrap();
+    TestClient::new(server)
+}
+
+#[test]
+fn should_send_body_with_length_when_posting() {
+    client()
+        .post("/echo", "ping")
+        .assert_status(StatusCode::Ok)
+        .assert_header("x-length", "4")
+        .assert_body("ping");
+}
+
+#[test]
+fn should_send_localhost_unless_given_host_when_requesting() {
+    let client = client();
+    assert_eq!(client.get("/host").body_text(), "localhost");
+    let response = client
+        .request(HttpMethod::Get, "/host")
+        .header("host", "example.com")
+        .send();
+    assert_eq!(response.body_text(), "example.com");
+}
+
+#[test]
+fn should_read_streamed_body_when_answered() {
+    let response = client().get("/stream");
+    assert_eq!(response.body, Body::Bytes(b"streamed".to_vec()));
+}
+
+#[test]
+fn should_answer_through_not_found_handler_when_unrouted() {
+    client()
+        .delete("/echo")
+        .assert_status(StatusCode::MethodNotAllowed)
+        .assert_header("Allow", "POST, OPTIONS");
+    client()
+        .get("/missing")
+        .assert_status(StatusCode::NotFound)
+        .assert_no_header("Allow");
+}
+
+#[test]
+#[should_panic(expected = "Expected a body of \"pong\"")]
+fn should_panic_when_body_differs() {
+    client().post("/echo", "ping").assert_body("pong");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_round_trip_json_when_sent_as_json() {
+    let value = serde_json::json!({ "id": 7 });
+    let response = client()
+        .request(HttpMethod::Post, "/echo")
+        .json(&value)
+        .send();
+    assert_eq!(response.json::<serde_json::Value>(), value);
+}
+
+#[test]
+fn should_serve_over_tcp_until_shut_down_when_spawned() {
+    let mut server = Server::default();
+    server
+        .route(|| Route::bind(HttpMethod::Get).to("/hello", |_| "hello"))
+        .unwrap();
+    let test_server = HttpServer::spawn_for_test(server).unwrap();
+    assert!(test_server.addr().ip().is_loopback());
+    assert_ne!(test_server.addr().port(), 0);
+    let url = test_server.url("/hello");
+    let client = HttpClient::default();
+    let response = client.get(&url).unwrap();
+    assert_eq!(response.body, Body::from("hello"));
+    test_server.shutdown().unwrap();
+    assert!(client.get(&url).is_err());
+}
+
+#[test]
+fn should_shut_down_when_dropped() {
+    let test_server = HttpServer::spawn_for_test(Server::default()).unwrap();
+    let url = test_server.url("/");
+    drop(test_server);
+    assert!(HttpClient::default().get(&url).is_err());
+}
@@ -0,0 +1,75 @@
+//! Building up an [`HttpResponse`] a piece at a time, rather than all at once
+//! through a struct literal.
+//!
+//! [`HttpResponse`]: ../struct.HttpResponse.html
+
+use super::{Body, HttpResponse, HttpVersion, Mime, SetCookie, StatusCode};
+
+/// Started by [`HttpResponse::builder`], as an HTTP/1.1 200 without any
+/// headers. A header set more than once keeps the last value given.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{Body, HttpResponse, StatusCode};
+/// let response = HttpResponse::builder()
+///     .status(StatusCode::Created)
+///     .header("Content-Type", "application/json")
+///     .body("{}");
+/// assert_eq!(response.status_code, StatusCode::Created);
+/// assert_eq!(response.headers["Content-Type"], "application/json");
+/// assert_eq!(response.body, Body::from("{}"));
+/// ```
+///
+/// [`HttpResponse::builder`]: ./struct.HttpResponse.html#method.builder
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    response: HttpResponse,
+}
+
+impl ResponseBuilder {
+    pub(in crate::web) fn new() -> ResponseBuilder {
+        ResponseBuilder {
+            response: HttpResponse::ok(),
+        }
+    }
+
+    pub fn status(mut self, status_code: StatusCode) -> ResponseBuilder {
+        self.response.status_code = status_code;
+        self
+    }
+
+    pub fn version(mut self, http_version: HttpVersion) -> ResponseBuilder {
+        self.response.http_version = http_version;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> ResponseBuilder {
+        self.response.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the `Content-Type`, see [`HttpResponse::content_type`].
+    ///
+    /// [`HttpResponse::content_type`]: ./struct.HttpResponse.html#method.content_type
+    pub fn content_type(mut self, mime: Mime) -> ResponseBuilder {
+        self.response = self.response.content_type(mime);
+        self
+    }
+
+    /// Adds a cookie, each kept in addition to those already added.
+    pub fn cookie(mut self, cookie: SetCookie) -> ResponseBuilder {
+        self.response.cookies.push(cookie);
+        self
+    }
+
+    /// Finishes the response with the given body.
+    pub fn body<B: Into<Body>>(mut self, body: B) -> HttpResponse {
+        self.response.body = body.into();
+        self.response
+    }
+
+    /// Finishes the response without a body.
+    pub fn build(self) -> HttpResponse {
+        self.response
+    }
+}
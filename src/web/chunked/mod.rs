@@ -0,0 +1,125 @@
+//! `Transfer-Encoding: chunked` framing, used when the length of a body is not
+//! known before it is sent. Each chunk is prefixed with its size in hex and the
+//! body is terminated by a zero sized chunk, optionally followed by trailers.
+//! More documentation
+//! [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Transfer-Encoding).
+use std::io::{self, BufRead, Read, Write};
+
+use crate::web::ParseError;
+
+/// Parses the size line of a single chunk, ignoring any chunk extensions
+/// following a `;`.
+///
+/// # Examples:
+/// ```
+/// use martian::web::chunked::chunk_size;
+/// assert_eq!(chunk_size("1A").unwrap(), 26);
+/// assert_eq!(chunk_size("4;name=value").unwrap(), 4);
+/// assert!(chunk_size("zz").is_err());
+/// ```
+pub fn chunk_size(line: &str) -> Result<usize, ParseError> {
+    let size = line.split(';').next().unwrap_or_default().trim();
+    usize::from_str_radix(size, 16).map_err(|_| ParseError::InvalidChunkSize(line.into()))
+}
+
+/// Reassembles a chunked body into its original bytes, discarding the framing
+/// and any trailers.
+///
+/// # Examples:
+/// ```
+/// use martian::web::chunked::decode;
+/// let body = decode(&b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n"[..]).unwrap();
+/// assert_eq!(body, b"Wikipedia");
+/// ```
+pub fn decode<R: BufRead>(mut reader: R) -> Result<Vec<u8>, ParseError> {
+    let mut body = Vec::new();
+    loop {
+        let size = chunk_size(&read_line(&mut reader)?)?;
+        if size == 0 {
+            break;
+        }
+        let mut chunk = vec![0; size];
+        reader
+            .read_exact(&mut chunk)
+            .map_err(|_| ParseError::IncompleteChunk)?;
+        body.extend(chunk);
+        if !read_line(&mut reader)?.is_empty() {
+            return Err(ParseError::IncompleteChunk);
+        }
+    }
+    while !read_line(&mut reader)?.is_empty() {}
+    Ok(body)
+}
+
+/// Copies everything from `reader` into `writer` as a chunked body, including
+/// the terminating chunk.
+pub fn encode<R: Read, W: Write>(reader: &mut R, writer: W) -> io::Result<W> {
+    let mut chunked_writer = ChunkedWriter::new(writer);
+    io::copy(reader, &mut chunked_writer)?;
+    chunked_writer.finish()
+}
+
+/// Wraps a `Write`, framing everything written to it as a chunk. The body is
+/// not complete until [`finish`] has been called.
+///
+/// # Examples:
+/// ```
+/// use martian::web::chunked::ChunkedWriter;
+/// use std::io::Write;
+/// let mut writer = ChunkedWriter::new(Vec::new());
+/// writer.write_all(b"Wiki").unwrap();
+/// writer.write_all(b"pedia").unwrap();
+/// let raw = writer.finish().unwrap();
+/// assert_eq!(raw, b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n");
+/// ```
+///
+/// [`finish`]: ./struct.ChunkedWriter.html#method.finish
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> ChunkedWriter<W> {
+        ChunkedWriter { inner }
+    }
+
+    /// Writes the terminating zero sized chunk, handing back the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // An empty chunk would terminate the body early.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:X}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, ParseError> {
+    let mut line = Vec::new();
+    match reader.read_until(b'\n', &mut line) {
+        Ok(0) | Err(_) => return Err(ParseError::IncompleteChunk),
+        Ok(_) => {}
+    }
+    if !line.ends_with(b"\r\n") {
+        return Err(ParseError::IncompleteChunk);
+    }
+    line.truncate(line.len() - 2);
+    String::from_utf8(line).map_err(|_| ParseError::IncompleteChunk)
+}
+
+#[cfg(test)]
+mod tests;
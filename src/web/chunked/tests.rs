@@ -0,0 +1,43 @@
+use crate::web::chunked::{chunk_size, decode, encode, ChunkedWriter};
+use crate::web::ParseError;
+use std::io::Write;
+
+#[test]
+fn should_round_trip_two_chunk_body_through_writer_and_decoder() {
+    let mut writer = ChunkedWriter::new(Vec::new());
+    writer.write_all(b"hello ").unwrap();
+    writer.write_all(b"world").unwrap();
+    let raw = writer.finish().unwrap();
+    assert_eq!(raw, b"6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n".to_vec());
+    assert_eq!(decode(&raw[..]).unwrap(), b"hello world".to_vec());
+}
+
+#[test]
+fn should_discard_trailers_when_decoding() {
+    let raw = b"3\r\nfoo\r\n0\r\nExpires: never\r\n\r\n";
+    assert_eq!(decode(&raw[..]).unwrap(), b"foo".to_vec());
+}
+
+#[test]
+fn should_have_an_error_result_when_chunk_size_is_not_hex() {
+    let actual_error = chunk_size("foo").unwrap_err();
+    assert_eq!(actual_error, ParseError::InvalidChunkSize("foo".into()));
+}
+
+#[test]
+fn should_have_an_error_result_when_chunk_is_shorter_than_its_size() {
+    let raw = b"A\r\nfoo\r\n0\r\n\r\n";
+    assert_eq!(decode(&raw[..]).unwrap_err(), ParseError::IncompleteChunk);
+}
+
+#[test]
+fn should_have_an_error_result_when_terminating_chunk_is_missing() {
+    let raw = b"3\r\nfoo\r\n";
+    assert_eq!(decode(&raw[..]).unwrap_err(), ParseError::IncompleteChunk);
+}
+
+#[test]
+fn should_encode_reader_contents_as_single_chunk() {
+    let raw = encode(&mut &b"body"[..], Vec::new()).unwrap();
+    assert_eq!(raw, b"4\r\nbody\r\n0\r\n\r\n".to_vec());
+}
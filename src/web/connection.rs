@@ -0,0 +1,60 @@
+//! What is known of the connection a request was read off of.
+
+use std::net::SocketAddr;
+
+/// The connection an [`HttpRequest`] was read off of by the `Server`.
+///
+/// # Examples:
+/// ```
+/// use martian::web::ConnectionInfo;
+/// let connection = ConnectionInfo {
+///     peer_addr: "203.0.113.7:51234".parse().unwrap(),
+///     local_addr: "192.0.2.1:443".parse().unwrap(),
+///     is_tls: true,
+///     peer_identity: None,
+///     server_name: Some("example.com".into()),
+/// };
+/// assert_eq!(connection.peer_addr.ip().to_string(), "203.0.113.7");
+/// ```
+///
+/// [`HttpRequest`]: ./struct.HttpRequest.html
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ConnectionInfo {
+    /// The address of the client, or of the last proxy in front of the
+    /// `Server` when there is one.
+    pub peer_addr: SocketAddr,
+    /// The address the connection was accepted on.
+    pub local_addr: SocketAddr,
+    /// Whether the connection was made over HTTPS.
+    pub is_tls: bool,
+    /// Who the client proved to be through its certificate, when the
+    /// `Server` asks for one and the client presented it.
+    pub peer_identity: Option<PeerIdentity>,
+    /// The hostname the client asked for through SNI during the TLS
+    /// handshake, if it did.
+    pub server_name: Option<String>,
+}
+
+/// What the verified certificate of a client says of it, for authorizing
+/// the client by more than its having a certificate at all.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{HttpRequest, PeerIdentity};
+/// fn is_admin(request: &HttpRequest) -> bool {
+///     request
+///         .connection
+///         .as_ref()
+///         .and_then(|connection| connection.peer_identity.as_ref())
+///         .is_some_and(|identity| identity.common_name.as_deref() == Some("admin"))
+/// }
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct PeerIdentity {
+    /// The common name (`CN`) of the subject of the certificate.
+    pub common_name: Option<String>,
+    /// The DNS names among its subject alternative names.
+    pub dns_names: Vec<String>,
+    /// The URIs among its subject alternative names, such as a SPIFFE ID.
+    pub uri_names: Vec<String>,
+}
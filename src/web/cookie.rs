@@ -0,0 +1,235 @@
+//! Cookies, as sent by a client in its `Cookie` header and set by a server
+//! through `Set-Cookie`, see
+//! [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265).
+
+use std::fmt;
+use std::time::Duration;
+
+/// A single name and value pair from the `Cookie` header of a request. A
+/// value wrapped in double quotes is given without them.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{Cookie, HttpRequest};
+/// let raw_request = "GET / HTTP/1.1\r\nCookie: session=abc123; theme=\"dark\"\r\n\r\n";
+/// let http_request = HttpRequest::from(raw_request);
+/// assert_eq!(
+///     http_request.cookies(),
+///     vec![
+///         Cookie { name: "session", value: "abc123" },
+///         Cookie { name: "theme", value: "dark" },
+///     ]
+/// );
+/// assert_eq!(http_request.cookie("theme"), Some("dark"));
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Cookie<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+impl<'a> Cookie<'a> {
+    /// Every cookie in the value of a `Cookie` header, in order. A pair
+    /// without a `=` or without a name is skipped.
+    pub fn parse_header(header: &'a str) -> Vec<Cookie<'a>> {
+        header
+            .split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, value)| Cookie {
+                name,
+                value: value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value),
+            })
+            .collect()
+    }
+}
+
+/// Whether a cookie is sent along with requests made from other sites.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Browsers only accept this for a [`SetCookie::secure`] cookie.
+    ///
+    /// [`SetCookie::secure`]: ./struct.SetCookie.html#method.secure
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A cookie for the client to store, written out as its own `Set-Cookie`
+/// header for each one in [`HttpResponse::cookies`]. Without a
+/// [`max_age`] the cookie lasts as long as the browser session does.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{HttpResponse, SameSite, SetCookie};
+/// use std::time::Duration;
+/// let cookie = SetCookie::new("session", "abc123")
+///     .max_age(Duration::from_secs(3600))
+///     .path("/")
+///     .secure()
+///     .http_only()
+///     .same_site(SameSite::Lax);
+/// assert_eq!(
+///     cookie.to_string(),
+///     "session=abc123; Max-Age=3600; Path=/; Secure; HttpOnly; SameSite=Lax"
+/// );
+/// let mut response = HttpResponse::ok();
+/// response.set_cookie(cookie);
+/// assert_eq!(response.cookies.len(), 1);
+/// ```
+///
+/// [`HttpResponse::cookies`]: ./struct.HttpResponse.html#structfield.cookies
+/// [`max_age`]: ./struct.SetCookie.html#method.max_age
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub max_age: Option<Duration>,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+impl SetCookie {
+    /// A cookie with only a name and value, none of its attributes set.
+    pub fn new(name: &str, value: &str) -> SetCookie {
+        SetCookie {
+            name: name.into(),
+            value: value.into(),
+            max_age: None,
+            path: None,
+            domain: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// A cookie which tells the client to drop the one it has under `name`,
+    /// being already expired.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::SetCookie;
+    /// assert_eq!(SetCookie::removal("session").to_string(), "session=; Max-Age=0");
+    /// ```
+    pub fn removal(name: &str) -> SetCookie {
+        SetCookie::new(name, "").max_age(Duration::ZERO)
+    }
+
+    /// How long the client keeps the cookie, to the second.
+    pub fn max_age(mut self, max_age: Duration) -> SetCookie {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The path under which the client sends the cookie back.
+    pub fn path(mut self, path: &str) -> SetCookie {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// The domain, along with its subdomains, the client sends the cookie
+    /// back to. Only the domain which set it when not given.
+    pub fn domain(mut self, domain: &str) -> SetCookie {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Only send the cookie back over https.
+    pub fn secure(mut self) -> SetCookie {
+        self.secure = true;
+        self
+    }
+
+    /// Keep the cookie away from scripts running in the browser.
+    pub fn http_only(mut self) -> SetCookie {
+        self.http_only = true;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> SetCookie {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Reads the value of a `Set-Cookie` header. Attributes are matched
+    /// regardless of case, and any not known here, such as `Expires`, are
+    /// skipped.
+    ///
+    /// # Returns:
+    /// `None` if the header does not start with a name and value.
+    pub fn parse(header: &str) -> Option<SetCookie> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = SetCookie::new(name, value.trim());
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "max-age" => cookie.max_age = value.parse().ok().map(Duration::from_secs),
+                "path" => cookie.path = Some(value.into()),
+                "domain" => cookie.domain = Some(value.into()),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some(SameSite::Strict),
+                        "lax" => Some(SameSite::Lax),
+                        "none" => Some(SameSite::None),
+                        _ => None,
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(cookie)
+    }
+}
+
+impl fmt::Display for SetCookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        Ok(())
+    }
+}
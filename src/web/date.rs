@@ -0,0 +1,132 @@
+//! Dates as written in headers such as `Date`, `Last-Modified` and
+//! `If-Modified-Since`, see
+//! [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7).
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Formats a time as `Sun, 06 Nov 1994 08:49:37 GMT`, dropping any fraction
+/// of a second.
+///
+/// # Examples:
+/// ```
+/// use martian::web::date::format_http_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+/// let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+/// assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+/// ```
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let days = (secs / 86_400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        // The epoch was a Thursday.
+        WEEKDAYS[(days + 3).rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3_600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parses a date in any of the formats a recipient has to accept: the
+/// preferred `Sun, 06 Nov 1994 08:49:37 GMT`, along with the obsolete
+/// `Sunday, 06-Nov-94 08:49:37 GMT` and `Sun Nov  6 08:49:37 1994`. The day
+/// of the week is not checked against the date.
+///
+/// # Returns:
+/// `None` if the date is in none of them, or is before the unix epoch.
+///
+/// # Examples:
+/// ```
+/// use martian::web::date::parse_http_date;
+/// use std::time::{Duration, UNIX_EPOCH};
+/// let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+/// assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+/// assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(time));
+/// assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(time));
+/// assert_eq!(parse_http_date("yesterday"), None);
+/// ```
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let (weekday, rest) = date.trim().split_once([',', ' '])?;
+    if !weekday.is_ascii() || weekday.len() < 3 || !WEEKDAYS.contains(&&weekday[..3]) {
+        return None;
+    }
+    let fields = rest.split_whitespace().collect::<Vec<_>>();
+    let (day, month, year, time) = match fields.as_slice() {
+        [day, month, year, time, "GMT"] => (*day, *month, year.parse().ok()?, *time),
+        [date, time, "GMT"] => {
+            let mut parts = date.split('-');
+            let (day, month, year) = (parts.next()?, parts.next()?, parts.next()?);
+            let year = year.parse::<i64>().ok()?;
+            // Two digit years more than 50 years ahead are of the last
+            // century, which as of now is all of them from 70 on.
+            let year = match year {
+                0..=69 => 2000 + year,
+                70..=99 => 1900 + year,
+                _ => year,
+            };
+            (day, month, year, *time)
+        }
+        [month, day, time, year] => (*day, *month, year.parse().ok()?, *time),
+        _ => return None,
+    };
+    let day = day
+        .parse::<u32>()
+        .ok()
+        .filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u32 + 1;
+    let mut time = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if time.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let secs = days * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Converts days since the unix epoch into a `(year, month, day)` of the
+/// proleptic Gregorian calendar. See
+/// [here](http://howardhinnant.github.io/date_algorithms.html#civil_from_days).
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// The inverse of [`civil_from_days`]. See
+/// [here](http://howardhinnant.github.io/date_algorithms.html#days_from_civil).
+///
+/// [`civil_from_days`]: ./fn.civil_from_days.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
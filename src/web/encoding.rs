@@ -0,0 +1,221 @@
+//! Percent-encoding as used in uris, see
+//! [RFC 3986](https://www.rfc-editor.org/rfc/rfc3986#section-2.1), and its
+//! `application/x-www-form-urlencoded` variant writing a space as `+`. Along
+//! with base64 as used in headers, see
+//! [RFC 4648](https://www.rfc-editor.org/rfc/rfc4648#section-4), and its
+//! base64url variant, and the escaping of text written into HTML.
+
+use std::borrow::Cow;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Escapes every byte of the input other than the unreserved characters,
+/// `A-Z a-z 0-9 - . _ ~`, as `%XX`.
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::percent_encode;
+/// assert_eq!(percent_encode("café au lait"), "caf%C3%A9%20au%20lait");
+/// assert_eq!(percent_encode("a/b"), "a%2Fb");
+/// ```
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decodes every `%XX` escape of the input. An escape which is not followed
+/// by two hex digits is left as is, and invalid UTF-8 is replaced.
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::percent_decode;
+/// assert_eq!(percent_decode("caf%C3%A9%20au+lait"), "café au+lait");
+/// assert_eq!(percent_decode("100%"), "100%");
+/// ```
+pub fn percent_decode(input: &str) -> Cow<'_, str> {
+    decode(input, false)
+}
+
+/// Same as [`percent_encode`], but with a space written as `+`, as is done
+/// for the keys and values of a query or form.
+///
+/// [`percent_encode`]: ./fn.percent_encode.html
+pub fn form_encode(input: &str) -> String {
+    percent_encode(input).replace("%20", "+")
+}
+
+/// Same as [`percent_decode`], but with a `+` read as a space, as is done for
+/// the keys and values of a query or form.
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::form_decode;
+/// assert_eq!(form_decode("caf%C3%A9+au+lait"), "café au lait");
+/// ```
+///
+/// [`percent_decode`]: ./fn.percent_decode.html
+pub fn form_decode(input: &str) -> Cow<'_, str> {
+    decode(input, true)
+}
+
+/// Joins the pairs into a query or form body, each key and value encoded with
+/// [`form_encode`].
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::form_encode_pairs;
+/// assert_eq!(
+///     form_encode_pairs(&[("q", "rust & go"), ("page", "2")]),
+///     "q=rust+%26+go&page=2"
+/// );
+/// ```
+///
+/// [`form_encode`]: ./fn.form_encode.html
+pub fn form_encode_pairs<K: AsRef<str>, V: AsRef<str>>(pairs: &[(K, V)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                form_encode(key.as_ref()),
+                form_encode(value.as_ref())
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Escapes the characters of the text which would otherwise be read as
+/// markup, in an element or a quoted attribute alike.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Encodes the bytes as base64, padded with `=`.
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::base64_encode;
+/// assert_eq!(base64_encode(b"user:pass"), "dXNlcjpwYXNz");
+/// assert_eq!(base64_encode(b"hi?"), "aGk/");
+/// assert_eq!(base64_encode(b"hi"), "aGk=");
+/// ```
+pub fn base64_encode(bytes: &[u8]) -> String {
+    encode_base64(bytes, BASE64, true)
+}
+
+/// Same as [`base64_encode`], but with the base64url alphabet and without
+/// padding, as is done in uris and tokens.
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::base64url_encode;
+/// assert_eq!(base64url_encode(b"hi?"), "aGk_");
+/// assert_eq!(base64url_encode(b"hi"), "aGk");
+/// ```
+///
+/// [`base64_encode`]: ./fn.base64_encode.html
+pub fn base64url_encode(bytes: &[u8]) -> String {
+    encode_base64(bytes, BASE64URL, false)
+}
+
+fn encode_base64(bytes: &[u8], alphabet: &[u8; 64], is_padded: bool) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(alphabet[(bits >> (18 - 6 * i)) as usize & 63] as char),
+                false if is_padded => encoded.push('='),
+                false => {}
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes base64, its padding being optional.
+///
+/// # Examples:
+/// ```
+/// use martian::web::encoding::base64_decode;
+/// assert_eq!(base64_decode("aGk="), Some(b"hi".to_vec()));
+/// assert_eq!(base64_decode("aGk"), Some(b"hi".to_vec()));
+/// assert_eq!(base64_decode("a-k"), None);
+/// ```
+///
+/// # Returns:
+/// `None` if anything other than the base64 alphabet is found.
+pub fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    decode_base64(encoded, BASE64)
+}
+
+/// Same as [`base64_decode`], but with the base64url alphabet writing `-`
+/// and `_` in place of `+` and `/`, as is done in uris and tokens.
+///
+/// [`base64_decode`]: ./fn.base64_decode.html
+pub fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    decode_base64(encoded, BASE64URL)
+}
+
+fn decode_base64(encoded: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let (mut bits, mut bit_count) = (0u32, 0);
+    for byte in encoded.trim_end_matches('=').bytes() {
+        let value = alphabet.iter().position(|symbol| *symbol == byte)?;
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            decoded.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(decoded)
+}
+
+fn decode(input: &str, plus_as_space: bool) -> Cow<'_, str> {
+    let needs_decoding = input.contains('%') || plus_as_space && input.contains('+');
+    if !needs_decoding {
+        return Cow::Borrowed(input);
+    }
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_as_space => decoded.push(b' '),
+            b'%' => match bytes.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+fn hex_byte(digits: &[u8]) -> Option<u8> {
+    let high = char::from(digits[0]).to_digit(16)?;
+    let low = char::from(digits[1]).to_digit(16)?;
+    Some((high * 16 + low) as u8)
+}
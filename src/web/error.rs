@@ -0,0 +1,118 @@
+//! Errors a handler hands back to the [`Server`] to be answered with a 4xx or
+//! 5xx, rather than building the response itself.
+//!
+//! [`Server`]: ../server/struct.Server.html
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use super::{HttpResponse, ParamError, StatusCode};
+
+/// An error of a handler bound through [`Binding::to_fallible`], carrying the
+/// status it is to be answered with. A [`ParamError`] or an `io::Error`
+/// converts into one, so either can be returned with `?`.
+///
+/// Unless the [`Server`] maps errors itself, through [`Server::on_error`], a
+/// 4xx is answered with its message as a `text/plain` body. A 5xx is answered
+/// with its status only, the message being kept out of the response as it
+/// may describe the inner workings of the server.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{MartianError, StatusCode};
+/// let error = MartianError::not_found("no user 7");
+/// assert_eq!(error.status_code, StatusCode::NotFound);
+/// assert_eq!(error.to_string(), "404 Not Found: no user 7");
+/// ```
+///
+/// [`Binding::to_fallible`]: ../server/struct.Binding.html#method.to_fallible
+/// [`ParamError`]: ./enum.ParamError.html
+/// [`Server`]: ../server/struct.Server.html
+/// [`Server::on_error`]: ../server/struct.Server.html#method.on_error
+#[derive(Debug)]
+pub struct MartianError {
+    pub status_code: StatusCode,
+    pub message: String,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl MartianError {
+    pub fn new(status_code: StatusCode, message: &str) -> MartianError {
+        MartianError {
+            status_code,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn bad_request(message: &str) -> MartianError {
+        MartianError::new(StatusCode::BadRequest, message)
+    }
+
+    pub fn not_found(message: &str) -> MartianError {
+        MartianError::new(StatusCode::NotFound, message)
+    }
+
+    /// A 500 caused by the given error, which is kept as its source.
+    pub fn internal<E>(error: E) -> MartianError
+    where
+        E: Error + Send + Sync + 'static,
+    {
+        MartianError {
+            status_code: StatusCode::InternalServerError,
+            message: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+}
+
+impl fmt::Display for MartianError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.status_code.code(),
+            self.status_code.reason_phrase(),
+            self.message
+        )
+    }
+}
+
+impl Error for MartianError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// A 400 describing what was wrong with the param, or a 500 when it is
+/// unfit for the route.
+impl From<ParamError> for MartianError {
+    fn from(param_error: ParamError) -> MartianError {
+        match param_error {
+            ParamError::Unfit(_) => MartianError::internal(param_error),
+            _ => MartianError::bad_request(&param_error.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for MartianError {
+    fn from(io_error: io::Error) -> MartianError {
+        MartianError::internal(io_error)
+    }
+}
+
+impl From<MartianError> for HttpResponse {
+    fn from(error: MartianError) -> HttpResponse {
+        match error.status_code.is_server_error() {
+            true => HttpResponse::new(error.status_code),
+            false => {
+                let mut response = HttpResponse::text(&error.message);
+                response.status_code = error.status_code;
+                response
+            }
+        }
+    }
+}
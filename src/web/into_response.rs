@@ -0,0 +1,97 @@
+//! Turning whatever a handler returns into the [`HttpResponse`] sent back.
+//!
+//! [`HttpResponse`]: ../struct.HttpResponse.html
+
+use super::{HttpResponse, ParamError, StatusCode};
+
+/// Anything a handler can return in place of an [`HttpResponse`], such as a
+/// `String` for a `text/plain` body or a `(StatusCode, String)` for one with
+/// a status other than 200.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{Body, IntoResponse, StatusCode};
+/// let created = (StatusCode::Created, "made it").into_response();
+/// assert_eq!(created.status_code, StatusCode::Created);
+/// assert_eq!(created.body, Body::from("made it"));
+/// let missing = None::<String>.into_response();
+/// assert_eq!(missing.status_code, StatusCode::NotFound);
+/// ```
+///
+/// [`HttpResponse`]: ./struct.HttpResponse.html
+pub trait IntoResponse {
+    fn into_response(self) -> HttpResponse;
+}
+
+impl IntoResponse for HttpResponse {
+    fn into_response(self) -> HttpResponse {
+        self
+    }
+}
+
+/// A 200 with a `text/plain` body.
+impl IntoResponse for String {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::text(&self)
+    }
+}
+
+/// A 200 with a `text/plain` body.
+impl IntoResponse for &'static str {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::text(self)
+    }
+}
+
+/// A 200 carrying the bytes as they are, without a `Content-Type`.
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::ok_with_body(self)
+    }
+}
+
+/// The status without a body.
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::new(self)
+    }
+}
+
+/// A 200 without a body.
+impl IntoResponse for () {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::ok()
+    }
+}
+
+/// The response with its status replaced.
+impl<R: IntoResponse> IntoResponse for (StatusCode, R) {
+    fn into_response(self) -> HttpResponse {
+        let mut response = self.1.into_response();
+        response.status_code = self.0;
+        response
+    }
+}
+
+/// A 404 without a body when there is nothing to respond with.
+impl<R: IntoResponse> IntoResponse for Option<R> {
+    fn into_response(self) -> HttpResponse {
+        self.map_or_else(HttpResponse::not_found, IntoResponse::into_response)
+    }
+}
+
+impl<R: IntoResponse, E: IntoResponse> IntoResponse for Result<R, E> {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            Ok(response) => response.into_response(),
+            Err(error) => error.into_response(),
+        }
+    }
+}
+
+/// A 400 describing what was wrong with the param.
+impl IntoResponse for ParamError {
+    fn into_response(self) -> HttpResponse {
+        self.into()
+    }
+}
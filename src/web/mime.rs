@@ -0,0 +1,179 @@
+//! Media types, as given in a `Content-Type` or `Accept` header, and the file
+//! extensions they are known by.
+
+use std::fmt;
+use std::path::Path;
+
+/// The media types this crate knows by name. More documentation
+/// [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types/Common_types).
+///
+/// # Examples:
+/// ```
+/// use martian::web::Mime;
+/// assert_eq!(Mime::from_extension("PNG"), Some(Mime::ImagePng));
+/// assert_eq!(Mime::from("Application/JSON; charset=utf-8"), Ok(Mime::ApplicationJson));
+/// assert_eq!(Mime::TextHtml.as_str(), "text/html");
+/// assert_eq!(Mime::TextHtml.to_string(), "text/html; charset=utf-8");
+/// ```
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Mime {
+    TextPlain,
+    TextHtml,
+    TextCss,
+    TextCsv,
+    TextJavascript,
+    TextXml,
+    TextEventStream,
+    ApplicationJson,
+    ApplicationPdf,
+    ApplicationWasm,
+    ApplicationZip,
+    ApplicationGzip,
+    ApplicationOctetStream,
+    ApplicationFormUrlencoded,
+    MultipartFormData,
+    ImagePng,
+    ImageJpeg,
+    ImageGif,
+    ImageWebp,
+    ImageAvif,
+    ImageSvg,
+    ImageIcon,
+    FontWoff,
+    FontWoff2,
+    AudioMpeg,
+    AudioOgg,
+    VideoMp4,
+    VideoWebm,
+}
+
+/// Every known type with the extensions it goes by, the first being the one
+/// it is usually written with.
+const EXTENSIONS: [(Mime, &[&str]); 28] = [
+    (Mime::TextPlain, &["txt", "text"]),
+    (Mime::TextHtml, &["html", "htm"]),
+    (Mime::TextCss, &["css"]),
+    (Mime::TextCsv, &["csv"]),
+    (Mime::TextJavascript, &["js", "mjs"]),
+    (Mime::TextXml, &["xml"]),
+    (Mime::TextEventStream, &[]),
+    (Mime::ApplicationJson, &["json", "map"]),
+    (Mime::ApplicationPdf, &["pdf"]),
+    (Mime::ApplicationWasm, &["wasm"]),
+    (Mime::ApplicationZip, &["zip"]),
+    (Mime::ApplicationGzip, &["gz"]),
+    (Mime::ApplicationOctetStream, &["bin"]),
+    (Mime::ApplicationFormUrlencoded, &[]),
+    (Mime::MultipartFormData, &[]),
+    (Mime::ImagePng, &["png"]),
+    (Mime::ImageJpeg, &["jpg", "jpeg"]),
+    (Mime::ImageGif, &["gif"]),
+    (Mime::ImageWebp, &["webp"]),
+    (Mime::ImageAvif, &["avif"]),
+    (Mime::ImageSvg, &["svg"]),
+    (Mime::ImageIcon, &["ico"]),
+    (Mime::FontWoff, &["woff"]),
+    (Mime::FontWoff2, &["woff2"]),
+    (Mime::AudioMpeg, &["mp3"]),
+    (Mime::AudioOgg, &["ogg", "oga"]),
+    (Mime::VideoMp4, &["mp4"]),
+    (Mime::VideoWebm, &["webm"]),
+];
+
+impl Mime {
+    /// Finds the type of a `Content-Type` or similar header value, ignoring
+    /// case and any parameters following a `;`.
+    ///
+    /// # Returns:
+    /// The type, or an `Err` of the type as given when it is not known.
+    pub fn from(content_type: &str) -> Result<Mime, &str> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        EXTENSIONS
+            .iter()
+            .map(|(mime, _)| *mime)
+            .find(|mime| mime.as_str().eq_ignore_ascii_case(essence))
+            .ok_or(essence)
+    }
+
+    /// The type of a file with the given extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<Mime> {
+        EXTENSIONS
+            .iter()
+            .find(|(_, extensions)| {
+                extensions
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(extension))
+            })
+            .map(|(mime, _)| *mime)
+    }
+
+    /// The type of a file going by its extension, arbitrary bytes when it
+    /// has none or one which is not known.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Mime {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Mime::from_extension)
+            .unwrap_or(Mime::ApplicationOctetStream)
+    }
+
+    /// The type without any parameters, such as `text/html`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mime::TextPlain => "text/plain",
+            Mime::TextHtml => "text/html",
+            Mime::TextCss => "text/css",
+            Mime::TextCsv => "text/csv",
+            Mime::TextJavascript => "text/javascript",
+            Mime::TextXml => "text/xml",
+            Mime::TextEventStream => "text/event-stream",
+            Mime::ApplicationJson => "application/json",
+            Mime::ApplicationPdf => "application/pdf",
+            Mime::ApplicationWasm => "application/wasm",
+            Mime::ApplicationZip => "application/zip",
+            Mime::ApplicationGzip => "application/gzip",
+            Mime::ApplicationOctetStream => "application/octet-stream",
+            Mime::ApplicationFormUrlencoded => "application/x-www-form-urlencoded",
+            Mime::MultipartFormData => "multipart/form-data",
+            Mime::ImagePng => "image/png",
+            Mime::ImageJpeg => "image/jpeg",
+            Mime::ImageGif => "image/gif",
+            Mime::ImageWebp => "image/webp",
+            Mime::ImageAvif => "image/avif",
+            Mime::ImageSvg => "image/svg+xml",
+            Mime::ImageIcon => "image/x-icon",
+            Mime::FontWoff => "font/woff",
+            Mime::FontWoff2 => "font/woff2",
+            Mime::AudioMpeg => "audio/mpeg",
+            Mime::AudioOgg => "audio/ogg",
+            Mime::VideoMp4 => "video/mp4",
+            Mime::VideoWebm => "video/webm",
+        }
+    }
+
+    /// Whether this is one of the `text/*` types, which are written with a
+    /// UTF-8 charset.
+    pub fn is_text(&self) -> bool {
+        self.as_str().starts_with("text/")
+    }
+
+    /// The usual extension of a file of this type.
+    pub fn extension(&self) -> Option<&'static str> {
+        EXTENSIONS
+            .iter()
+            .find(|(mime, _)| mime == self)
+            .and_then(|(_, extensions)| extensions.first().copied())
+    }
+}
+
+impl fmt::Display for Mime {
+    /// Writes the type as a `Content-Type` header value, with a UTF-8
+    /// charset for a text type.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())?;
+        if self.is_text() {
+            f.write_str("; charset=utf-8")?;
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,62 @@
 //! Web module which is centered itself around web communication, primarily
 //! Http.
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::{self, FromStr, Utf8Error};
+use std::time::SystemTime;
+
+#[cfg(any(feature = "serde", feature = "json"))]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+use self::negotiation::{Negotiable, QualityItem};
+
+mod authorization;
+mod builder;
+pub mod chunked;
+mod connection;
+mod cookie;
+pub mod date;
+pub mod encoding;
+mod error;
+mod into_response;
+mod mime;
+pub mod multipart;
+pub mod negotiation;
+mod parser;
+mod query;
+mod spool;
+mod sse;
+mod state;
+mod status;
+mod uri;
+pub mod websocket;
+
+pub use self::authorization::Authorization;
+pub use self::builder::ResponseBuilder;
+pub use self::connection::{ConnectionInfo, PeerIdentity};
+pub use self::cookie::{Cookie, SameSite, SetCookie};
+pub use self::error::MartianError;
+pub use self::into_response::IntoResponse;
+pub use self::mime::Mime;
+pub use self::parser::{ParseStatus, RequestParser};
+pub use self::query::QueryParams;
+pub use self::spool::BodyReader;
+pub(crate) use self::spool::{Spool, Spooled, SpooledBody};
+pub use self::sse::{Disconnected, Event, SseSender, SseStream};
+pub use self::state::State;
+pub use self::status::StatusCode;
+pub use self::uri::Uri;
+
+/// The [`HttpClient`], as it was named when it was a part of this module.
+///
+/// [`HttpClient`]: ../client/struct.HttpClient.html
+#[deprecated(note = "use martian::client::HttpClient instead")]
+pub type Client = crate::client::HttpClient;
 
 /// Standard across the web, http methods dictate how requests are handled and
 /// what data can be given to the server. More documentation about individual
@@ -8,20 +64,14 @@ use std::collections::HashMap;
 #[derive(PartialEq, Debug, Clone)]
 pub enum HttpMethod {
     Get,
+    Head,
     Post,
+    Put,
+    Patch,
     Delete,
     Options,
-}
-
-/// Standard across the web, status codes are a nice simple description of what
-/// has happened to the original `HttpRequest`. They live on the response and
-/// with a few exceptions will mean the same thing across the world. More
-/// documentation about individual use
-/// [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Status).
-#[derive(PartialEq, Debug)]
-pub enum StatusCode {
-    Ok = 200,
-    InternalServerError = 500,
+    Trace,
+    Connect,
 }
 
 impl HttpMethod {
@@ -45,24 +95,133 @@ impl HttpMethod {
     pub fn from(method_string: &str) -> Result<HttpMethod, &str> {
         match method_string.to_lowercase().as_str() {
             "get" => Ok(HttpMethod::Get),
+            "head" => Ok(HttpMethod::Head),
             "post" => Ok(HttpMethod::Post),
+            "put" => Ok(HttpMethod::Put),
+            "patch" => Ok(HttpMethod::Patch),
             "delete" => Ok(HttpMethod::Delete),
             "options" => Ok(HttpMethod::Options),
+            "trace" => Ok(HttpMethod::Trace),
+            "connect" => Ok(HttpMethod::Connect),
             _ => Err("Given cannot be converted to HttpMethod"),
         }
     }
+
+    /// Whether sending the request more than once has the same effect as
+    /// sending it once, see
+    /// [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-9.2.2), so
+    /// that it can be sent again when it is not known to have arrived.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpMethod;
+    /// assert!(HttpMethod::Put.is_idempotent());
+    /// assert!(!HttpMethod::Post.is_idempotent());
+    /// ```
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(
+            self,
+            HttpMethod::Post | HttpMethod::Patch | HttpMethod::Connect
+        )
+    }
+
+    /// The method as it is written on the request line of a raw request.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Trace => "TRACE",
+            HttpMethod::Connect => "CONNECT",
+        }
+    }
+}
+
+/// The version of the protocol a request or response is sent in, found at the
+/// end of the request line and the start of the status line.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]
+pub enum HttpVersion {
+    Http0_9,
+    Http1_0,
+    #[default]
+    Http1_1,
+    Http2,
+}
+
+impl HttpVersion {
+    /// Finds the version from how it is written on a raw request or response,
+    /// the `HTTP/` prefix included.
+    ///
+    /// # Returns:
+    /// The matching version in a `Result`, or an `Err` if the string is not a
+    /// version this crate knows of.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpVersion;
+    /// assert_eq!(HttpVersion::from("HTTP/1.0"), Ok(HttpVersion::Http1_0));
+    /// assert!(HttpVersion::Http1_1 > HttpVersion::Http1_0);
+    /// assert!(HttpVersion::from("HTTP-1.1").is_err());
+    /// ```
+    pub fn from(version_string: &str) -> Result<HttpVersion, &str> {
+        match version_string {
+            "HTTP/0.9" => Ok(HttpVersion::Http0_9),
+            "HTTP/1.0" => Ok(HttpVersion::Http1_0),
+            "HTTP/1.1" => Ok(HttpVersion::Http1_1),
+            "HTTP/2" | "HTTP/2.0" => Ok(HttpVersion::Http2),
+            _ => Err("Given cannot be converted to HttpVersion"),
+        }
+    }
+
+    /// The version as it is written on a raw request or response.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::Http0_9 => "HTTP/0.9",
+            HttpVersion::Http1_0 => "HTTP/1.0",
+            HttpVersion::Http1_1 => "HTTP/1.1",
+            HttpVersion::Http2 => "HTTP/2",
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// All request made to an http server will be done with an http request. This
 /// is standard across the web and there is some information
 /// [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Messages).
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct HttpRequest {
     pub http_method: HttpMethod,
-    pub uri: String,
-    pub http_version: f32,
+    pub uri: Uri,
+    pub http_version: HttpVersion,
     pub headers: Option<HashMap<String, String>>,
-    pub body: Option<String>,
+    /// The body as it was sent, after undoing any chunked framing. `None`
+    /// when empty, or when too large to be held in memory. See [`body_text`]
+    /// for it as text, and [`body_reader`] for it wherever it is kept.
+    ///
+    /// [`body_text`]: ./struct.HttpRequest.html#method.body_text
+    /// [`body_reader`]: ./struct.HttpRequest.html#method.body_reader
+    pub body: Option<Vec<u8>>,
+    /// Segments captured by the route this request was delegated to, empty
+    /// until then.
+    pub path_params: HashMap<String, String>,
+    /// The state registered on the `Server` this request was delegated by,
+    /// empty until then.
+    pub state: State,
+    /// Values attached to this request alone, such as by a middleware for the
+    /// callbacks after it, at most one of each type.
+    pub extensions: State,
+    /// The connection the `Server` read this request off of, `None` for one
+    /// which was not.
+    pub connection: Option<ConnectionInfo>,
 }
 
 impl HttpRequest {
@@ -72,32 +231,138 @@ impl HttpRequest {
     ///
     /// # Examples:
     /// ```
-    /// use martian::web::{HttpMethod, HttpRequest};
+    /// use martian::web::{HttpMethod, HttpRequest, HttpVersion, State};
+    /// use std::collections::HashMap;
     /// let raw_request = "GET / HTTP/1.1\r\n\r\n";
     /// let expected_http_request = HttpRequest {
     ///    http_method: HttpMethod::Get,
     ///    uri: "/".into(),
-    ///    http_version: 1.1,
+    ///    http_version: HttpVersion::Http1_1,
     ///    headers: None,
     ///    body: None,
+    ///    path_params: HashMap::new(),
+    ///    state: State::default(),
+    ///    extensions: State::default(),
+    ///    connection: None,
     /// };
     /// let actual_http_request = HttpRequest::from(raw_request);
     /// assert_eq!(actual_http_request, expected_http_request);
     /// ```
+    ///
+    /// # Panics:
+    /// If the request is malformed, use [`parse`] for anything not already
+    /// known to be valid.
+    ///
+    /// [`parse`]: ./struct.HttpRequest.html#method.parse
     pub fn from(raw_request: &str) -> HttpRequest {
-        let lines = raw_request.split("\r\n").collect::<Vec<&str>>();
-        let status_line = lines[0];
-        let status_line_split = status_line.split(" ").collect::<Vec<&str>>();
-        HttpRequest {
-            http_method: HttpMethod::from(status_line_split[0]).unwrap(),
-            uri: status_line_split[1].into(),
-            http_version: get_http_version(status_line_split[2]).unwrap(),
-            headers: get_headers_from_lines(&lines),
-            body: match get_body_begin_index(&lines) {
-                Some(i) => Some(lines[i..].join("\r\n")),
-                None => None,
-            },
+        HttpRequest::parse(raw_request).unwrap()
+    }
+
+    /// Same as [`from`], but reports a malformed request line, header or body
+    /// as an `Err` rather than panicking. A chunked body is reassembled before
+    /// it is placed on the request.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let raw_request = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+    ///     4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+    /// let http_request = HttpRequest::parse(raw_request).unwrap();
+    /// assert_eq!(http_request.body_text(), Ok("Wikipedia"));
+    /// ```
+    ///
+    /// A request line missing its uri is an error, as is one with a method
+    /// or version this crate does not know:
+    /// ```
+    /// use martian::web::{HttpRequest, ParseError};
+    /// assert_eq!(
+    ///     HttpRequest::parse("GET\r\n\r\n"),
+    ///     Err(ParseError::InvalidRequestLine("GET".into()))
+    /// );
+    /// ```
+    ///
+    /// [`from`]: ./struct.HttpRequest.html#method.from
+    pub fn parse(raw_request: &str) -> Result<HttpRequest, ParseError> {
+        HttpRequest::parse_bytes(raw_request.as_bytes())
+    }
+
+    /// Same as [`parse`], for a request as read off of the wire. Only its
+    /// head needs to be UTF-8, the body is kept as is.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let raw_request = b"PUT /avatar HTTP/1.1\r\nContent-Length: 3\r\n\r\n\x89PNG";
+    /// let http_request = HttpRequest::parse_bytes(raw_request).unwrap();
+    /// assert_eq!(http_request.body_bytes(), b"\x89PN");
+    /// assert!(http_request.body_text().is_err());
+    /// ```
+    ///
+    /// [`parse`]: ./struct.HttpRequest.html#method.parse
+    pub fn parse_bytes(raw_request: &[u8]) -> Result<HttpRequest, ParseError> {
+        Ok(HttpRequestRef::parse_bytes(raw_request)?.to_owned())
+    }
+
+    /// The inverse of [`from`], serializing the request into the raw form it
+    /// is sent over the wire in. The same as its `Display` form.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let raw_request = "POST /hello HTTP/1.1\r\nContent-Length: 5\r\n\r\nworld";
+    /// let http_request = HttpRequest::from(raw_request);
+    /// assert_eq!(http_request.to_raw(), raw_request);
+    /// ```
+    ///
+    /// [`from`]: ./struct.HttpRequest.html#method.from
+    pub fn to_raw(&self) -> String {
+        self.to_string()
+    }
+
+    /// The request in wire format, ready to be written to a socket. Unlike
+    /// [`to_raw`], a body which is not UTF-8 is kept as is.
+    ///
+    /// [`to_raw`]: ./struct.HttpRequest.html#method.to_raw
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut raw_request = self.head().into_bytes();
+        raw_request.extend(self.body_bytes());
+        raw_request
+    }
+
+    /// The body, empty if there is none.
+    pub fn body_bytes(&self) -> &[u8] {
+        self.body.as_deref().unwrap_or_default()
+    }
+
+    /// The body as text, empty if there is none.
+    ///
+    /// # Returns:
+    /// An `Err` if the body is not UTF-8, such as an uploaded image.
+    pub fn body_text(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(self.body_bytes())
+    }
+
+    /// The request line and headers, up to and including the blank line. The
+    /// body has already been decoded when parsing, so rather than any
+    /// `Content-Length` or `Transfer-Encoding` on the headers, a
+    /// `Content-Length` of the body as it is now is written.
+    fn head(&self) -> String {
+        let mut head = format!(
+            "{} {} {}\r\n",
+            self.http_method.as_str(),
+            self.uri,
+            self.http_version
+        );
+        for (key, value) in self.headers.iter().flatten() {
+            if !is_framing_header(key) {
+                head.push_str(&format!("{}: {}\r\n", key, value));
+            }
+        }
+        if let Some(body) = &self.body {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
         }
+        head.push_str("\r\n");
+        head
     }
 
     /// Query params arrive on the uri of the request and can be on any type
@@ -105,38 +370,591 @@ impl HttpRequest {
     /// `?` and multiple query params are separated by `&`.
     ///
     /// # Returns:
-    /// An `Option` of a `HashMap` which contains a representation of the
-    /// params passed to the request via the uri. Will return `None` if no
-    /// params are present.
+    /// The decoded [`QueryParams`], empty if there are none. The query is
+    /// only parsed the first time they are asked for.
     ///
     /// # Example:
     /// ```
     /// use martian::web::HttpRequest;
-    /// use std::collections::HashMap;
-    /// let raw_request = "GET /hello?greet=world HTTP/1.1\r\n\r\n";
+    /// let raw_request = "GET /hello?greet=big+wide%20world HTTP/1.1\r\n\r\n";
     /// let http_request = HttpRequest::from(raw_request);
-    /// let mut expected_query_params = HashMap::new();
-    /// expected_query_params.insert("greet".into(), "world".into());
-    /// let actual_query_params = http_request.params().unwrap();
-    /// assert_eq!(actual_query_params, expected_query_params);
-    /// ```
-    pub fn params(&self) -> Option<HashMap<String, String>> {
-        let mut param_map = HashMap::new();
-        let params_split = self.uri.split("?").collect::<Vec<&str>>();
-        if params_split.len() < 2 {
+    /// assert_eq!(http_request.params().get("greet"), Some("big wide world"));
+    /// ```
+    ///
+    /// [`QueryParams`]: ./struct.QueryParams.html
+    pub fn params(&self) -> &QueryParams {
+        self.uri.params()
+    }
+
+    /// The decoded fields of an `application/x-www-form-urlencoded` body, as
+    /// sent by an html form, read the same way as [`QueryParams`].
+    ///
+    /// # Returns:
+    /// `None` unless the `Content-Type` says the body is a form.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let raw_request = "POST /login HTTP/1.1\r\n\
+    ///     Content-Type: application/x-www-form-urlencoded\r\n\
+    ///     Content-Length: 28\r\n\r\n\
+    ///     user=ada&pass=p%40ss+word%21";
+    /// let form = HttpRequest::from(raw_request).form().unwrap();
+    /// assert_eq!(form.get("user"), Some("ada"));
+    /// assert_eq!(form.get("pass"), Some("p@ss word!"));
+    /// ```
+    ///
+    /// [`QueryParams`]: ./struct.QueryParams.html
+    pub fn form(&self) -> Option<QueryParams> {
+        let content_type = header_value(self.headers.as_ref()?, "Content-Type")?;
+        if Mime::from(content_type) != Ok(Mime::ApplicationFormUrlencoded) {
             return None;
         }
-        let params = params_split[1].split("&").collect::<Vec<&str>>();
-        for param in params {
-            let param_split = param.split("=").collect::<Vec<&str>>();
-            let key = param_split[0].into();
-            let value = param_split[1].into();
-            param_map.insert(key, value);
+        Some(QueryParams::from_form(&String::from_utf8_lossy(
+            self.body_bytes(),
+        )))
+    }
+
+    /// Deserializes the body as JSON, whatever its `Content-Type` says. A
+    /// request without a body is an error, as there is no value to read.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct CreateUser {
+    ///     name: String,
+    ///     admin: bool,
+    /// }
+    /// let raw_request = "POST /users HTTP/1.1\r\n\
+    ///     Content-Type: application/json\r\n\
+    ///     Content-Length: 27\r\n\r\n\
+    ///     {\"name\":\"ada\",\"admin\":true}";
+    /// let user = HttpRequest::from(raw_request).json::<CreateUser>().unwrap();
+    /// assert_eq!((user.name.as_str(), user.admin), ("ada", true));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(self.body_bytes())
+    }
+
+    /// The body as a reader, wherever it is kept. The `Server` writes a
+    /// multipart body too large to be held in memory, see
+    /// [`Limits::max_body_in_memory`], to a temporary file as it reads it in,
+    /// leaving `body` empty, and this reads it back from there.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// use std::io::Read;
+    /// let http_request = HttpRequest::from("PUT /note HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi");
+    /// let mut body = String::new();
+    /// http_request.body_reader().read_to_string(&mut body).unwrap();
+    /// assert_eq!(body, "hi");
+    /// ```
+    ///
+    /// [`Limits::max_body_in_memory`]: ../server/struct.Limits.html#structfield.max_body_in_memory
+    pub fn body_reader(&self) -> BodyReader<'_> {
+        match self.extensions.get::<SpooledBody>() {
+            Some(spooled) => BodyReader::spooled(spooled),
+            None => BodyReader::memory(self.body_bytes()),
         }
-        if !param_map.is_empty() {
-            Some(param_map)
+    }
+
+    /// Reads a `multipart/form-data` body, as sent by an html form with a
+    /// file input, see [`Multipart`]. It is read through [`body_reader`], so
+    /// a large file need never be held in memory whole.
+    ///
+    /// # Returns:
+    /// `None` unless the `Content-Type` says the body is multipart and gives
+    /// its boundary.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let raw_request = "POST /upload HTTP/1.1\r\n\
+    ///     Content-Type: multipart/form-data; boundary=XyZ\r\n\
+    ///     Content-Length: 67\r\n\r\n\
+    ///     --XyZ\r\n\
+    ///     Content-Disposition: form-data; name=\"note\"\r\n\r\n\
+    ///     hi\r\n\
+    ///     --XyZ--\r\n";
+    /// let http_request = HttpRequest::from(raw_request);
+    /// let mut multipart = http_request.multipart().unwrap();
+    /// let note = multipart.next_part().unwrap().unwrap();
+    /// assert_eq!(note.name(), Some("note"));
+    /// assert_eq!(note.text().unwrap(), "hi");
+    /// ```
+    ///
+    /// [`Multipart`]: ./multipart/struct.Multipart.html
+    /// [`body_reader`]: ./struct.HttpRequest.html#method.body_reader
+    pub fn multipart(&self) -> Option<multipart::Multipart<BodyReader<'_>>> {
+        let content_type = header_value(self.headers.as_ref()?, "Content-Type")?;
+        let boundary = multipart::boundary(content_type)?;
+        Some(multipart::Multipart::new(self.body_reader(), boundary))
+    }
+
+    /// Accepts the WebSocket handshake of this request, answering it with a
+    /// 101. Once that is written, the `Server` hands the connection over to
+    /// `on_upgrade` as a [`WebSocket`], on the thread serving it, and closes
+    /// it once `on_upgrade` returns.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::websocket::Message;
+    /// use martian::web::{HttpRequest, StatusCode};
+    /// let request = HttpRequest::from(
+    ///     "GET /chat HTTP/1.1\r\n\
+    ///     Upgrade: websocket\r\n\
+    ///     Connection: Upgrade\r\n\
+    ///     Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+    ///     Sec-WebSocket-Version: 13\r\n\r\n",
+    /// );
+    /// let response = request
+    ///     .upgrade_websocket(|mut websocket| {
+    ///         while let Ok(Message::Text(text)) = websocket.receive() {
+    ///             let _ = websocket.send(Message::Text(text));
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(response.status_code, StatusCode::SwitchingProtocols);
+    /// assert_eq!(
+    ///     response.headers["Sec-WebSocket-Accept"],
+    ///     "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    /// );
+    /// ```
+    ///
+    /// # Returns:
+    /// An `Err` of 400 if the request is not a handshake, or of 426 if it
+    /// asks for a version of the protocol other than 13.
+    ///
+    /// [`WebSocket`]: ./websocket/struct.WebSocket.html
+    pub fn upgrade_websocket<F>(&self, on_upgrade: F) -> Result<HttpResponse, MartianError>
+    where
+        F: FnOnce(websocket::WebSocket<'_>) + Send + 'static,
+    {
+        websocket::accept(self, on_upgrade)
+    }
+
+    /// The value of the header, ignoring the case of its name.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let request = HttpRequest::from("GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    /// assert_eq!(request.header("connection"), Some("close"));
+    /// assert_eq!(request.header("Host"), None);
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_value(self.headers.as_ref()?, name)
+    }
+
+    /// The type of the body, going by the `Content-Type` header, see
+    /// [`Mime::from`].
+    ///
+    /// # Returns:
+    /// `None` if there is no such header, or the type is not a known one.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{HttpRequest, Mime};
+    /// let raw_request = "POST / HTTP/1.1\r\nContent-Type: application/json; charset=utf-8\r\n\r\n";
+    /// let request = HttpRequest::from(raw_request);
+    /// assert_eq!(request.content_type(), Some(Mime::ApplicationJson));
+    /// ```
+    ///
+    /// [`Mime::from`]: ./enum.Mime.html#method.from
+    pub fn content_type(&self) -> Option<Mime> {
+        Mime::from(self.header("Content-Type")?).ok()
+    }
+
+    /// The length of the body as given by the `Content-Length` header.
+    ///
+    /// # Returns:
+    /// `None` if there is no such header, or it is anything other than
+    /// digits.
+    pub fn content_length(&self) -> Option<u64> {
+        let content_length = self.header("Content-Length")?.trim();
+        match content_length.bytes().all(|b| b.is_ascii_digit()) {
+            true => content_length.parse().ok(),
+            false => None,
+        }
+    }
+
+    /// The host, and port if any, the request is for. That is the authority
+    /// of a uri in absolute form, such as sent to a proxy, or otherwise the
+    /// `Host` header.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let request = HttpRequest::from("GET / HTTP/1.1\r\nHost: example.com:8080\r\n\r\n");
+    /// assert_eq!(request.host(), Some("example.com:8080"));
+    /// let request = HttpRequest::from("GET http://example.org/a HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    /// assert_eq!(request.host(), Some("example.org"));
+    /// ```
+    pub fn host(&self) -> Option<&str> {
+        let authority = self
+            .uri
+            .as_str()
+            .split_once("://")
+            .filter(|(scheme, _)| !scheme.contains('/'))
+            .map(|(_, rest)| rest.split(['/', '?', '#']).next().unwrap_or_default());
+        let host = match authority {
+            Some(authority) => authority.rsplit('@').next().unwrap_or_default(),
+            None => self.header("Host")?.trim(),
+        };
+        (!host.is_empty()).then_some(host)
+    }
+
+    /// The credentials sent in the `Authorization` header, see
+    /// [`Authorization`].
+    ///
+    /// # Returns:
+    /// `None` if there is no such header, or it could not be made sense of.
+    ///
+    /// [`Authorization`]: ./enum.Authorization.html
+    pub fn authorization(&self) -> Option<Authorization<'_>> {
+        Authorization::parse(self.header("Authorization")?)
+    }
+
+    /// The media types of the `Accept` header, the most preferred first,
+    /// see [`parse_quality_list`]. Empty if there is none.
+    ///
+    /// [`parse_quality_list`]: ./negotiation/fn.parse_quality_list.html
+    pub fn accept(&self) -> Vec<QualityItem<'_>> {
+        self.quality_list(Negotiable::MediaType)
+    }
+
+    /// The languages of the `Accept-Language` header, the most preferred
+    /// first, see [`parse_quality_list`]. Empty if there is none.
+    ///
+    /// [`parse_quality_list`]: ./negotiation/fn.parse_quality_list.html
+    pub fn accept_language(&self) -> Vec<QualityItem<'_>> {
+        self.quality_list(Negotiable::Language)
+    }
+
+    /// The codings of the `Accept-Encoding` header, the most preferred first,
+    /// see [`parse_quality_list`]. Empty if there is none.
+    ///
+    /// [`parse_quality_list`]: ./negotiation/fn.parse_quality_list.html
+    pub fn accept_encoding(&self) -> Vec<QualityItem<'_>> {
+        self.quality_list(Negotiable::Encoding)
+    }
+
+    fn quality_list(&self, negotiable: Negotiable) -> Vec<QualityItem<'_>> {
+        self.header(negotiable.header())
+            .map(negotiation::parse_quality_list)
+            .unwrap_or_default()
+    }
+
+    /// The offer the client prefers most, see [`Negotiable::preferred`].
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::negotiation::Negotiable;
+    /// use martian::web::HttpRequest;
+    /// let request = HttpRequest::from("GET / HTTP/1.1\r\nAccept-Language: de-CH, en;q=0.5\r\n\r\n");
+    /// let offers = ["en-GB", "fr", "de"];
+    /// assert_eq!(request.preferred(Negotiable::Language, &offers), Some("en-GB"));
+    /// ```
+    ///
+    /// [`Negotiable::preferred`]: ./negotiation/enum.Negotiable.html#method.preferred
+    pub fn preferred<'o>(&self, negotiable: Negotiable, offers: &[&'o str]) -> Option<&'o str> {
+        negotiable.preferred(self.header(negotiable.header()), offers)
+    }
+
+    /// Answers with the response `respond` gives for the offer the client
+    /// prefers most, or with a 406 when it accepts none of them. Either way
+    /// the header negotiated on is added to the `Vary` of the response, for
+    /// caches to tell the responses to different preferences apart.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::negotiation::Negotiable;
+    /// use martian::web::{HttpRequest, HttpResponse, StatusCode};
+    /// let request = HttpRequest::from("GET / HTTP/1.1\r\nAccept: text/html\r\n\r\n");
+    /// let offers = ["application/json", "text/html"];
+    /// let response = request.negotiate(Negotiable::MediaType, &offers, |media_type| {
+    ///     match media_type {
+    ///         "text/html" => HttpResponse::html("<p>hello</p>"),
+    ///         _ => HttpResponse::ok_with_body("{\"greeting\":\"hello\"}"),
+    ///     }
+    /// });
+    /// assert_eq!(response.headers["Content-Type"], "text/html; charset=utf-8");
+    /// assert_eq!(response.headers["Vary"], "Accept");
+    /// let request = HttpRequest::from("GET / HTTP/1.1\r\nAccept: image/png\r\n\r\n");
+    /// let response = request.negotiate(Negotiable::MediaType, &offers, HttpResponse::text);
+    /// assert_eq!(response.status_code, StatusCode::NotAcceptable);
+    /// ```
+    pub fn negotiate<'o, F>(
+        &self,
+        negotiable: Negotiable,
+        offers: &[&'o str],
+        respond: F,
+    ) -> HttpResponse
+    where
+        F: FnOnce(&'o str) -> HttpResponse,
+    {
+        let mut response = match self.preferred(negotiable, offers) {
+            Some(offer) => respond(offer),
+            None => HttpResponse::new(StatusCode::NotAcceptable),
+        };
+        negotiation::add_vary(&mut response.headers, negotiable.header());
+        response
+    }
+
+    /// Every [`Cookie`] sent in the `Cookie` header, empty if there is none.
+    ///
+    /// [`Cookie`]: ./struct.Cookie.html
+    pub fn cookies(&self) -> Vec<Cookie<'_>> {
+        self.headers
+            .as_ref()
+            .and_then(|headers| header_value(headers, "Cookie"))
+            .map(Cookie::parse_header)
+            .unwrap_or_default()
+    }
+
+    /// The value of the first cookie sent under `name`.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies()
+            .into_iter()
+            .find(|cookie| cookie.name == name)
+            .map(|cookie| cookie.value)
+    }
+
+    /// The path param captured under `name` by the route this request was
+    /// delegated to, such as `id` for a route bound to `/users/{id}`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// let mut http_request = HttpRequest::from("GET /users/7 HTTP/1.1\r\n\r\n");
+    /// http_request.path_params.insert("id".into(), "7".into());
+    /// assert_eq!(http_request.path_param("id"), Some("7"));
+    /// assert_eq!(http_request.path_param("name"), None);
+    /// ```
+    pub fn path_param(&self, name: &str) -> Option<&str> {
+        self.path_params.get(name).map(String::as_str)
+    }
+
+    /// Looks up a single param by name and parses it into `T`. Path params
+    /// captured by the route are looked in first, then the query params.
+    ///
+    /// # Returns:
+    /// The parsed value, or a [`ParamError`] telling apart a param which is
+    /// missing from one which failed to parse.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{HttpRequest, ParamError};
+    /// let http_request = HttpRequest::from("GET /users?page=2&size=ten HTTP/1.1\r\n\r\n");
+    /// assert_eq!(http_request.param::<u32>("page"), Ok(2));
+    /// assert!(matches!(
+    ///     http_request.param::<u32>("size"),
+    ///     Err(ParamError::Invalid { .. })
+    /// ));
+    /// assert_eq!(
+    ///     http_request.param::<u32>("sort"),
+    ///     Err(ParamError::Missing("sort".into()))
+    /// );
+    /// ```
+    ///
+    /// [`ParamError`]: ./enum.ParamError.html
+    pub fn param<T>(&self, name: &str) -> Result<T, ParamError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = match self.path_params.get(name) {
+            Some(value) => value.as_str(),
+            None => self
+                .params()
+                .get(name)
+                .ok_or_else(|| ParamError::Missing(name.into()))?,
+        };
+        value.parse().map_err(|e: T::Err| ParamError::Invalid {
+            name: name.into(),
+            value: value.into(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Deserializes all of the query params into a struct at once, each field
+    /// being parsed into its own type.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpRequest;
+    /// use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct Page {
+    ///     page: u32,
+    ///     size: Option<u32>,
+    /// }
+    /// let http_request = HttpRequest::from("GET /users?page=2 HTTP/1.1\r\n\r\n");
+    /// let page = http_request.params_as::<Page>().unwrap();
+    /// assert_eq!((page.page, page.size), (2, None));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn params_as<T: DeserializeOwned>(&self) -> Result<T, ParamError> {
+        let query = self.uri.query().unwrap_or_default();
+        serde_urlencoded::from_str(query).map_err(|e| ParamError::Deserialize(e.to_string()))
+    }
+}
+
+impl fmt::Display for HttpRequest {
+    /// Writes the request in wire format, replacing anything in the body
+    /// which is not UTF-8, see [`to_bytes`] for the body as is.
+    ///
+    /// [`to_bytes`]: ./struct.HttpRequest.html#method.to_bytes
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.head(),
+            String::from_utf8_lossy(self.body_bytes())
+        )
+    }
+}
+
+/// A view of a raw request borrowing everything it can from it, rather than
+/// copying it into an [`HttpRequest`]. Only a chunked body, which has to be
+/// reassembled, is ever copied out of the raw request.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{HttpMethod, HttpRequestRef};
+/// let raw_request = "GET /hello?greet=world HTTP/1.1\r\nHost: localhost\r\n\r\n";
+/// let request_ref = HttpRequestRef::parse(raw_request).unwrap();
+/// assert_eq!(request_ref.http_method, HttpMethod::Get);
+/// assert_eq!(request_ref.header("host"), Some("localhost"));
+/// assert_eq!(request_ref.query_pairs().next(), Some(("greet", "world")));
+/// ```
+///
+/// [`HttpRequest`]: ./struct.HttpRequest.html
+#[derive(PartialEq, Debug)]
+pub struct HttpRequestRef<'a> {
+    pub http_method: HttpMethod,
+    pub uri: &'a str,
+    pub http_version: HttpVersion,
+    /// In the order they appeared on the request.
+    pub headers: Vec<(&'a str, &'a str)>,
+    pub body: Option<Cow<'a, [u8]>>,
+}
+
+impl<'a> HttpRequestRef<'a> {
+    /// Parses a raw request the same way [`HttpRequest::parse`] does, without
+    /// allocating for any of its parts.
+    ///
+    /// [`HttpRequest::parse`]: ./struct.HttpRequest.html#method.parse
+    pub fn parse(raw_request: &'a str) -> Result<HttpRequestRef<'a>, ParseError> {
+        HttpRequestRef::parse_bytes(raw_request.as_bytes())
+    }
+
+    /// Parses a raw request the same way [`HttpRequest::parse_bytes`] does,
+    /// without allocating for any of its parts.
+    ///
+    /// [`HttpRequest::parse_bytes`]: ./struct.HttpRequest.html#method.parse_bytes
+    pub fn parse_bytes(raw_request: &'a [u8]) -> Result<HttpRequestRef<'a>, ParseError> {
+        let (head, rest) = split_head_and_body(raw_request);
+        let mut request = HttpRequestRef::parse_head(head)?;
+        request.body = request.read_body(rest)?;
+        Ok(request)
+    }
+
+    /// Parses the request line and headers, leaving the body to be read.
+    pub(crate) fn parse_head(head: &'a [u8]) -> Result<HttpRequestRef<'a>, ParseError> {
+        let head = utf8_head(head)?;
+        let mut lines = head.split("\r\n");
+        let request_line = lines.next().unwrap_or_default();
+        let (method, uri, version) = match request_line.split(' ').collect::<Vec<&str>>()[..] {
+            [method, uri, version] if !uri.is_empty() => (method, uri, version),
+            _ => return Err(ParseError::InvalidRequestLine(request_line.into())),
+        };
+        Ok(HttpRequestRef {
+            http_method: HttpMethod::from(method)
+                .map_err(|_| ParseError::UnknownMethod(method.into()))?,
+            uri,
+            http_version: HttpVersion::from(version)
+                .map_err(|_| ParseError::InvalidVersion(version.into()))?,
+            headers: parse_headers(lines)?,
+            body: None,
+        })
+    }
+
+    /// Takes the body off of whatever follows the head, as framed by either
+    /// `Transfer-Encoding: chunked` or `Content-Length`. Without either, the
+    /// rest is the body.
+    fn read_body(&self, rest: &'a [u8]) -> Result<Option<Cow<'a, [u8]>>, ParseError> {
+        let body = if self.header("Transfer-Encoding").is_some_and(is_chunked) {
+            Cow::Owned(chunked::decode(rest)?)
+        } else if let Some(length) = self.header("Content-Length") {
+            let length = length
+                .parse()
+                .map_err(|_| ParseError::InvalidHeader(format!("Content-Length: {}", length)))?;
+            Cow::Borrowed(rest.get(..length).ok_or(ParseError::IncompleteBody)?)
         } else {
-            None
+            Cow::Borrowed(rest)
+        };
+        Ok(Some(body).filter(|body| !body.is_empty()))
+    }
+
+    /// The value of the first header with the given name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// The body, empty if there is none. Borrowed from the raw request unless
+    /// it was chunked.
+    pub fn body_bytes(&self) -> &[u8] {
+        self.body.as_deref().unwrap_or_default()
+    }
+
+    /// The body as text, empty if there is none, see
+    /// [`HttpRequest::body_text`].
+    ///
+    /// # Returns:
+    /// An `Err` if the body is not UTF-8.
+    ///
+    /// [`HttpRequest::body_text`]: ./struct.HttpRequest.html#method.body_text
+    pub fn body_text(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(self.body_bytes())
+    }
+
+    /// Iterates over the query params on the uri as they are, see
+    /// [`HttpRequest::params`].
+    ///
+    /// [`HttpRequest::params`]: ./struct.HttpRequest.html#method.params
+    pub fn query_pairs(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        query_pairs(self.uri)
+    }
+
+    /// Copies the request out of the raw request it borrows from.
+    pub fn to_owned(&self) -> HttpRequest {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<String, String>>();
+        HttpRequest {
+            http_method: self.http_method.clone(),
+            uri: self.uri.into(),
+            http_version: self.http_version,
+            headers: if !headers.is_empty() {
+                Some(headers)
+            } else {
+                None
+            },
+            body: self.body.as_ref().map(|body| body.to_vec()),
+            path_params: HashMap::new(),
+            state: State::default(),
+            extensions: State::default(),
+            connection: None,
         }
     }
 }
@@ -146,47 +964,782 @@ impl HttpRequest {
 /// [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Messages).
 #[derive(PartialEq, Debug)]
 pub struct HttpResponse {
-    pub http_version: f32,
+    pub http_version: HttpVersion,
     pub status_code: StatusCode,
+    /// Written out as is, aside from `Content-Length` and `Transfer-Encoding`
+    /// which always follow from the body.
+    pub headers: HashMap<String, String>,
+    /// Each written as its own `Set-Cookie` header, which is why they are
+    /// kept apart from the other headers.
+    pub cookies: Vec<SetCookie>,
+    pub body: Body,
+}
+
+impl HttpResponse {
+    /// An HTTP/1.1 response with the given status, no headers and no body.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse, StatusCode};
+    /// let response = HttpResponse::new(StatusCode::Accepted);
+    /// assert_eq!(response.status_code, StatusCode::Accepted);
+    /// assert!(response.headers.is_empty());
+    /// assert_eq!(response.body, Body::Empty);
+    /// ```
+    pub fn new(status_code: StatusCode) -> HttpResponse {
+        HttpResponse {
+            http_version: HttpVersion::Http1_1,
+            status_code,
+            headers: HashMap::new(),
+            cookies: Vec::new(),
+            body: Body::Empty,
+        }
+    }
+
+    /// Starts building up a response, see [`ResponseBuilder`].
+    ///
+    /// [`ResponseBuilder`]: ./struct.ResponseBuilder.html
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// A 200 without a body.
+    pub fn ok() -> HttpResponse {
+        HttpResponse::new(StatusCode::Ok)
+    }
+
+    /// A 200 carrying `body` as is, without a `Content-Type`. Use one of
+    /// [`text`], [`html`] or [`content_type`] when the client needs to know.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse, StatusCode};
+    /// let response = HttpResponse::ok_with_body(vec![0xde, 0xad]);
+    /// assert_eq!(response.status_code, StatusCode::Ok);
+    /// assert_eq!(response.body, Body::Bytes(vec![0xde, 0xad]));
+    /// ```
+    ///
+    /// [`text`]: #method.text
+    /// [`html`]: #method.html
+    /// [`content_type`]: #method.content_type
+    pub fn ok_with_body<B: Into<Body>>(body: B) -> HttpResponse {
+        let mut response = HttpResponse::ok();
+        response.body = body.into();
+        response
+    }
+
+    /// Sets the `Content-Type`, written with a UTF-8 charset for a text
+    /// type, see [`Mime`].
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{HttpResponse, Mime};
+    /// let response = HttpResponse::ok_with_body(vec![0x89, b'P']).content_type(Mime::ImagePng);
+    /// assert_eq!(response.headers["Content-Type"], "image/png");
+    /// ```
+    ///
+    /// [`Mime`]: ./enum.Mime.html
+    pub fn content_type(mut self, mime: Mime) -> HttpResponse {
+        self.headers.insert("Content-Type".into(), mime.to_string());
+        self
+    }
+
+    /// A 200 with a `text/plain` body.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse, StatusCode};
+    /// let response = HttpResponse::text("hello");
+    /// assert_eq!(response.status_code, StatusCode::Ok);
+    /// assert_eq!(response.headers["Content-Type"], "text/plain; charset=utf-8");
+    /// assert_eq!(response.body, Body::Bytes(b"hello".to_vec()));
+    /// ```
+    pub fn text(body: &str) -> HttpResponse {
+        HttpResponse::with_body(StatusCode::Ok, Mime::TextPlain, body)
+    }
+
+    /// A 200 with a `text/html` body.
+    pub fn html(body: &str) -> HttpResponse {
+        HttpResponse::with_body(StatusCode::Ok, Mime::TextHtml, body)
+    }
+
+    /// A 200 with the value serialized as its `application/json` body.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse};
+    /// let response = HttpResponse::json(&vec![1, 2, 3]).unwrap();
+    /// assert_eq!(response.headers["Content-Type"], "application/json");
+    /// assert_eq!(response.body, Body::Bytes(b"[1,2,3]".to_vec()));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<HttpResponse> {
+        let mut response = HttpResponse::ok().content_type(Mime::ApplicationJson);
+        response.body = Body::Bytes(serde_json::to_vec(value)?);
+        Ok(response)
+    }
+
+    /// A 302, sending the client on to `location` for this request only.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{HttpResponse, StatusCode};
+    /// let response = HttpResponse::redirect("/login");
+    /// assert_eq!(response.status_code, StatusCode::Found);
+    /// assert_eq!(response.headers["Location"], "/login");
+    /// ```
+    pub fn redirect(location: &str) -> HttpResponse {
+        HttpResponse::with_location(StatusCode::Found, location)
+    }
+
+    /// A 301, telling the client the resource has moved to `location` for
+    /// good.
+    pub fn redirect_permanent(location: &str) -> HttpResponse {
+        HttpResponse::with_location(StatusCode::MovedPermanently, location)
+    }
+
+    /// A 303, sending the client on to `location` with a `GET`, as is usual
+    /// after handling a form submission.
+    pub fn see_other(location: &str) -> HttpResponse {
+        HttpResponse::with_location(StatusCode::SeeOther, location)
+    }
+
+    /// A 307, the same as [`redirect`] but for the client having to repeat
+    /// the request as it was, keeping its method and body.
+    ///
+    /// [`redirect`]: ./struct.HttpResponse.html#method.redirect
+    pub fn temporary_redirect(location: &str) -> HttpResponse {
+        HttpResponse::with_location(StatusCode::TemporaryRedirect, location)
+    }
+
+    /// A 308, the same as [`redirect_permanent`] but for the client having to
+    /// repeat the request as it was, keeping its method and body.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{HttpResponse, StatusCode};
+    /// let response = HttpResponse::permanent_redirect("/v2/orders");
+    /// assert_eq!(response.status_code, StatusCode::PermanentRedirect);
+    /// assert_eq!(response.headers["Location"], "/v2/orders");
+    /// ```
+    ///
+    /// [`redirect_permanent`]: ./struct.HttpResponse.html#method.redirect_permanent
+    pub fn permanent_redirect(location: &str) -> HttpResponse {
+        HttpResponse::with_location(StatusCode::PermanentRedirect, location)
+    }
+
+    /// A 204. There is no way to give it a body, and one set on it anyway is
+    /// never written out.
+    pub fn no_content() -> HttpResponse {
+        HttpResponse::new(StatusCode::NoContent)
+    }
+
+    /// A 404 without a body.
+    pub fn not_found() -> HttpResponse {
+        HttpResponse::new(StatusCode::NotFound)
+    }
+
+    /// A 400 with a `text/plain` body describing what was wrong with the
+    /// request.
+    pub fn bad_request(message: &str) -> HttpResponse {
+        HttpResponse::with_body(StatusCode::BadRequest, Mime::TextPlain, message)
+    }
+
+    fn with_body(status_code: StatusCode, mime: Mime, body: &str) -> HttpResponse {
+        let mut response = HttpResponse::new(status_code).content_type(mime);
+        response.body = body.into();
+        response
+    }
+
+    fn with_location(status_code: StatusCode, location: &str) -> HttpResponse {
+        let mut response = HttpResponse::new(status_code);
+        response.headers.insert("Location".into(), location.into());
+        response
+    }
+
+    /// The client side counterpart of [`HttpRequest::from`], turning a raw
+    /// response back into a struct.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse, StatusCode};
+    /// let raw_response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+    /// let http_response = HttpResponse::from(raw_response);
+    /// assert_eq!(http_response.status_code, StatusCode::Ok);
+    /// assert_eq!(http_response.body, Body::Bytes(b"hello".to_vec()));
+    /// ```
+    ///
+    /// [`HttpRequest::from`]: ./struct.HttpRequest.html#method.from
+    pub fn from(raw_response: &str) -> HttpResponse {
+        HttpResponse::parse(raw_response).unwrap()
+    }
+
+    /// Same as [`from`], but returns an `Err` for a response which can not
+    /// be parsed. The body is exactly as long as its `Content-Length` says,
+    /// whatever it contains, or decoded when chunked, and whatever is left
+    /// when it says neither. A `1xx`, `204` or `304` response has no body,
+    /// whatever its headers say. A status code unknown to [`StatusCode`] is
+    /// kept as [`StatusCode::Other`].
+    ///
+    /// [`from`]: ./struct.HttpResponse.html#method.from
+    /// [`StatusCode`]: ./enum.StatusCode.html
+    /// [`StatusCode::Other`]: ./enum.StatusCode.html#variant.Other
+    pub fn parse(raw_response: &str) -> Result<HttpResponse, ParseError> {
+        HttpResponse::parse_bytes(raw_response.as_bytes())
+    }
+
+    /// Same as [`parse`], for a response as read off of the wire. Only its
+    /// head needs to be UTF-8, the body is kept as is.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse};
+    /// let raw_response =
+    ///     b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\n\x89PN\r\n1\r\nG\r\n0\r\n\r\n";
+    /// let http_response = HttpResponse::parse_bytes(raw_response).unwrap();
+    /// assert_eq!(http_response.body, Body::Bytes(b"\x89PNG".to_vec()));
+    /// ```
+    ///
+    /// [`parse`]: ./struct.HttpResponse.html#method.parse
+    pub fn parse_bytes(raw_response: &[u8]) -> Result<HttpResponse, ParseError> {
+        HttpResponse::parse_bytes_to(raw_response, &HttpMethod::Get)
+    }
+
+    /// Same as [`parse_bytes`], for the response to a request of the method,
+    /// that to a `HEAD` request having no body either.
+    ///
+    /// [`parse_bytes`]: ./struct.HttpResponse.html#method.parse_bytes
+    pub(crate) fn parse_bytes_to(
+        raw_response: &[u8],
+        http_method: &HttpMethod,
+    ) -> Result<HttpResponse, ParseError> {
+        let (head, raw_body) = split_head_and_body(raw_response);
+        let head = utf8_head(head).map_err(|e| match e {
+            ParseError::InvalidRequestLine(line) => ParseError::InvalidStatusLine(line),
+            e => e,
+        })?;
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or_default();
+        let mut status_line_split = status_line.splitn(3, ' ');
+        let invalid_status_line = || ParseError::InvalidStatusLine(status_line.into());
+        let http_version = HttpVersion::from(status_line_split.next().unwrap_or_default())
+            .map_err(|_| invalid_status_line())?;
+        let status_code = status_line_split
+            .next()
+            .and_then(|code| code.parse().ok())
+            .map(StatusCode::from_u16)
+            .ok_or_else(invalid_status_line)?;
+        let mut headers = HashMap::new();
+        let mut cookies = Vec::new();
+        for line in lines {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| ParseError::InvalidHeader(line.into()))?;
+            if key.eq_ignore_ascii_case("Set-Cookie") {
+                let cookie = SetCookie::parse(value)
+                    .ok_or_else(|| ParseError::InvalidHeader(line.into()))?;
+                cookies.push(cookie);
+            } else {
+                headers.insert(key.into(), value.trim().into());
+            }
+        }
+        let code = status_code.code();
+        let bodiless = *http_method == HttpMethod::Head || code < 200 || code == 204 || code == 304;
+        let body = if bodiless {
+            Vec::new()
+        } else if header_value(&headers, "Transfer-Encoding").is_some_and(is_chunked) {
+            chunked::decode(raw_body)?
+        } else {
+            match header_value(&headers, "Content-Length") {
+                Some(length) => {
+                    let length = length
+                        .parse()
+                        .map_err(|_| ParseError::InvalidHeader(length.into()))?;
+                    raw_body
+                        .get(..length)
+                        .ok_or(ParseError::IncompleteBody)?
+                        .to_vec()
+                }
+                None => raw_body.to_vec(),
+            }
+        };
+        Ok(HttpResponse {
+            http_version,
+            status_code,
+            headers,
+            cookies,
+            body: if body.is_empty() {
+                Body::Empty
+            } else {
+                Body::Bytes(body)
+            },
+        })
+    }
+
+    /// Adds a cookie for the client to store, see [`SetCookie`].
+    ///
+    /// [`SetCookie`]: ./struct.SetCookie.html
+    pub fn set_cookie(&mut self, cookie: SetCookie) {
+        self.cookies.push(cookie);
+    }
+
+    /// The value of the header, ignoring the case of its name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_value(&self.headers, name)
+    }
+
+    /// Sets the header, replacing any set already under its name in any
+    /// case.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpResponse;
+    /// let mut response = HttpResponse::ok();
+    /// response.headers.insert("cache-control".into(), "no-store".into());
+    /// response.set_header("Cache-Control", "max-age=60");
+    /// assert_eq!(response.header("CACHE-CONTROL"), Some("max-age=60"));
+    /// assert_eq!(response.headers.len(), 1);
+    /// ```
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers
+            .retain(|key, _| !key.eq_ignore_ascii_case(name));
+        self.headers.insert(name.into(), value.into());
+    }
+
+    /// Sets the `Location` the client is sent on to, or where a resource
+    /// created by the request is found.
+    pub fn set_location(&mut self, location: &str) {
+        self.set_header("Location", location);
+    }
+
+    /// Sets the `Last-Modified` as an HTTP date, see [`format_http_date`].
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpResponse;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// let mut response = HttpResponse::ok();
+    /// response.set_last_modified(UNIX_EPOCH + Duration::from_secs(784_111_777));
+    /// assert_eq!(response.headers["Last-Modified"], "Sun, 06 Nov 1994 08:49:37 GMT");
+    /// ```
+    ///
+    /// [`format_http_date`]: ./date/fn.format_http_date.html
+    pub fn set_last_modified(&mut self, time: SystemTime) {
+        self.set_header("Last-Modified", &date::format_http_date(time));
+    }
+
+    /// Sets the `Expires` as an HTTP date, see [`format_http_date`].
+    ///
+    /// [`format_http_date`]: ./date/fn.format_http_date.html
+    pub fn set_expires(&mut self, time: SystemTime) {
+        self.set_header("Expires", &date::format_http_date(time));
+    }
+
+    /// Sets a strong `ETag` of the tag, which is quoted, or a weak one,
+    /// `W/"..."`, promising only the same meaning rather than the very same
+    /// bytes.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpResponse;
+    /// let mut response = HttpResponse::ok();
+    /// response.set_etag("v2", true);
+    /// assert_eq!(response.headers["ETag"], "W/\"v2\"");
+    /// ```
+    ///
+    /// # Panics:
+    /// If the tag has a `"`, a space or a control character in it.
+    pub fn set_etag(&mut self, tag: &str, weak: bool) {
+        assert!(
+            !tag.bytes().any(|b| b == b'"' || b <= b' ' || b == 0x7f),
+            "an entity tag has no quotes, spaces or control characters in it"
+        );
+        let etag = match weak {
+            true => format!("W/\"{}\"", tag),
+            false => format!("\"{}\"", tag),
+        };
+        self.set_header("ETag", &etag);
+    }
+
+    /// Writes the response out in wire format. A [`Body::Stream`] has no known
+    /// length, so it is sent with `Transfer-Encoding: chunked` as it is read.
+    /// Before HTTP/1.1 there is no chunked encoding, so it is sent as is and
+    /// ended by closing the connection instead. One whose length is given by
+    /// a `Content-Length` header is sent as is with that length either way,
+    /// see [`stream_len`]. The body of a response whose status does not allow
+    /// one is dropped.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse, HttpVersion, StatusCode};
+    /// use std::collections::HashMap;
+    /// let response = HttpResponse {
+    ///     http_version: HttpVersion::Http1_1,
+    ///     status_code: StatusCode::Ok,
+    ///     headers: HashMap::new(),
+    ///     cookies: Vec::new(),
+    ///     body: Body::Stream(Box::new(&b"body"[..])),
+    /// };
+    /// let mut raw_response = Vec::new();
+    /// response.write_to(&mut raw_response).unwrap();
+    /// assert_eq!(
+    ///     raw_response,
+    ///     b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nbody\r\n0\r\n\r\n"
+    /// );
+    /// ```
+    ///
+    /// # Returns:
+    /// An `Err` when writing fails, or a stream ends short of its
+    /// `Content-Length`.
+    ///
+    /// [`Body::Stream`]: ./enum.Body.html#variant.Stream
+    /// [`stream_len`]: ./struct.HttpResponse.html#method.stream_len
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        self.write(writer, true)
+    }
+
+    /// The response in wire format, as [`write_to`] would write it. Only a
+    /// [`Body::Stream`] failing to be read makes this an error.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::HttpResponse;
+    /// assert_eq!(
+    ///     HttpResponse::ok_with_body("hi").to_bytes().unwrap(),
+    ///     b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi"
+    /// );
+    /// ```
+    ///
+    /// [`write_to`]: ./struct.HttpResponse.html#method.write_to
+    /// [`Body::Stream`]: ./enum.Body.html#variant.Stream
+    pub fn to_bytes(self) -> io::Result<Vec<u8>> {
+        let mut raw_response = Vec::new();
+        self.write_to(&mut raw_response)?;
+        Ok(raw_response)
+    }
+
+    /// Writes the response out the same as [`write_to`], but without its
+    /// body, as is the answer to a `HEAD` request. The `Content-Length` is
+    /// still that of the body.
+    ///
+    /// [`write_to`]: ./struct.HttpResponse.html#method.write_to
+    pub fn write_head_to<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        self.write(writer, false)
+    }
+
+    /// The length of a [`Body::Stream`], as given by the `Content-Length`
+    /// header, for it to be sent without chunked encoding.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Body, HttpResponse};
+    /// let mut response = HttpResponse::ok();
+    /// response.body = Body::Stream(Box::new(&b"body"[..]));
+    /// assert_eq!(response.stream_len(), None);
+    /// response.set_header("Content-Length", "4");
+    /// assert_eq!(response.stream_len(), Some(4));
+    /// assert_eq!(
+    ///     response.to_bytes().unwrap(),
+    ///     b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody"
+    /// );
+    /// ```
+    ///
+    /// [`Body::Stream`]: ./enum.Body.html#variant.Stream
+    pub fn stream_len(&self) -> Option<u64> {
+        match self.body {
+            Body::Stream(_) => self.header("Content-Length")?.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn write<W: Write>(self, writer: &mut W, include_body: bool) -> io::Result<()> {
+        let stream_len = self.stream_len();
+        write!(
+            writer,
+            "{} {} {}\r\n",
+            self.http_version,
+            self.status_code.code(),
+            self.status_code.reason_phrase()
+        )?;
+        for (key, value) in &self.headers {
+            if !is_framing_header(key) {
+                write!(writer, "{}: {}\r\n", key, value)?;
+            }
+        }
+        for cookie in &self.cookies {
+            write!(writer, "Set-Cookie: {}\r\n", cookie)?;
+        }
+        if !self.status_code.allows_body() {
+            writer.write_all(b"\r\n")?;
+            return writer.flush();
+        }
+        match (self.body, stream_len) {
+            (Body::Empty | Body::Upgrade(_), _) => {
+                writer.write_all(b"Content-Length: 0\r\n\r\n")?
+            }
+            (Body::Bytes(bytes), _) => {
+                write!(writer, "Content-Length: {}\r\n\r\n", bytes.len())?;
+                if include_body {
+                    writer.write_all(&bytes)?;
+                }
+            }
+            (Body::Stream(reader), Some(len)) => {
+                write!(writer, "Content-Length: {}\r\n\r\n", len)?;
+                if include_body && io::copy(&mut reader.take(len), writer)? < len {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "The body ended short of its Content-Length",
+                    ));
+                }
+            }
+            (Body::Stream(mut reader), None) if self.http_version < HttpVersion::Http1_1 => {
+                writer.write_all(b"Connection: close\r\n\r\n")?;
+                if include_body {
+                    io::copy(&mut reader, writer)?;
+                }
+            }
+            (Body::Stream(mut reader), None) => {
+                writer.write_all(b"Transfer-Encoding: chunked\r\n\r\n")?;
+                if include_body {
+                    chunked::encode(&mut reader, &mut *writer)?;
+                }
+            }
+        }
+        writer.flush()
+    }
 }
 
-fn get_http_version(full_version_string: &str) -> Result<f32, &str> {
-    let version_split = full_version_string.split("/").collect::<Vec<&str>>();
-    Ok(version_split[1]
-        .parse::<f32>()
-        .expect("Could not get version float"))
+/// The content of an [`HttpResponse`], either fully in memory or streamed out
+/// of a reader when its length is not known up front.
+///
+/// [`HttpResponse`]: ./struct.HttpResponse.html
+#[derive(Default)]
+pub enum Body {
+    #[default]
+    Empty,
+    Bytes(Vec<u8>),
+    Stream(Box<dyn Read + Send>),
+    /// Nothing is written after the head, the connection being taken over
+    /// as a WebSocket instead, see [`HttpRequest::upgrade_websocket`].
+    ///
+    /// [`HttpRequest::upgrade_websocket`]: ./struct.HttpRequest.html#method.upgrade_websocket
+    Upgrade(websocket::Upgrade),
 }
 
-fn get_headers_from_lines(lines: &[&str]) -> Option<HashMap<String, String>> {
-    let mut headers = HashMap::new();
-    for line in &lines[1..] {
-        if line.is_empty() {
-            break;
+impl PartialEq for Body {
+    /// Streams and upgrades can not be compared without consuming them, so
+    /// they are never equal to anything.
+    fn eq(&self, other: &Body) -> bool {
+        match (self, other) {
+            (Body::Empty, Body::Empty) => true,
+            (Body::Bytes(a), Body::Bytes(b)) => a == b,
+            _ => false,
         }
-        let line_split = line.split(": ").collect::<Vec<&str>>();
-        let key = line_split[0].into();
-        let value = line_split[1].into();
-        headers.insert(key, value);
     }
-    if !headers.is_empty() {
-        Some(headers)
-    } else {
-        None
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Body {
+        Body::Bytes(bytes)
     }
 }
 
-fn get_body_begin_index(lines: &[&str]) -> Option<usize> {
-    let mut i = 0;
-    loop {
-        let line = lines[i];
-        if i + 1 >= lines.len() {
-            break None;
-        } else if line.is_empty() && !lines[i + 1].is_empty() {
-            break Some(i + 1);
+impl From<String> for Body {
+    fn from(text: String) -> Body {
+        Body::Bytes(text.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Body {
+        Body::Bytes(text.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Body::Empty => write!(f, "Empty"),
+            Body::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            Body::Stream(_) => write!(f, "Stream(..)"),
+            Body::Upgrade(_) => write!(f, "Upgrade(..)"),
+        }
+    }
+}
+
+/// The reasons a raw request could not be turned into an [`HttpRequest`].
+///
+/// [`HttpRequest`]: ./struct.HttpRequest.html
+#[derive(PartialEq, Debug)]
+pub enum ParseError {
+    /// The first line of a request not being `<method> <uri> HTTP/<version>`.
+    InvalidRequestLine(String),
+    /// A request method which is not one of [`HttpMethod`].
+    ///
+    /// [`HttpMethod`]: ./enum.HttpMethod.html
+    UnknownMethod(String),
+    /// A version which is not one of [`HttpVersion`].
+    ///
+    /// [`HttpVersion`]: ./enum.HttpVersion.html
+    InvalidVersion(String),
+    /// The first line of a response not being `HTTP/<version> <code>`.
+    InvalidStatusLine(String),
+    /// A header line without a `:` separating its name and value.
+    InvalidHeader(String),
+    /// A chunk size line which is not a hexadecimal number.
+    InvalidChunkSize(String),
+    /// A chunked body ending before its terminating chunk, or a chunk not
+    /// matching its declared size.
+    IncompleteChunk,
+    /// A body shorter than its `Content-Length`.
+    IncompleteBody,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidRequestLine(line) => write!(f, "Invalid request line: {:?}", line),
+            ParseError::UnknownMethod(method) => write!(f, "Unknown method: {:?}", method),
+            ParseError::InvalidVersion(version) => write!(f, "Invalid version: {:?}", version),
+            ParseError::InvalidStatusLine(line) => write!(f, "Invalid status line: {:?}", line),
+            ParseError::InvalidHeader(line) => write!(f, "Invalid header: {:?}", line),
+            ParseError::InvalidChunkSize(line) => write!(f, "Invalid chunk size: {:?}", line),
+            ParseError::IncompleteChunk => write!(f, "Chunked body ended unexpectedly"),
+            ParseError::IncompleteBody => write!(f, "Body is shorter than its Content-Length"),
         }
-        i += 1;
     }
 }
 
+impl Error for ParseError {}
+
+/// The reasons a param could not be taken off of an [`HttpRequest`]. Converts
+/// into a 400 [`HttpResponse`] describing the problem, so a handler can hand
+/// it straight back to the client, unless it is [`Unfit`] for the route.
+///
+/// [`HttpRequest`]: ./struct.HttpRequest.html
+/// [`HttpResponse`]: ./struct.HttpResponse.html
+/// [`Unfit`]: ./enum.ParamError.html#variant.Unfit
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParamError {
+    /// No path or query param with the given name.
+    Missing(String),
+    /// A param which could not be parsed into the requested type.
+    Invalid {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    /// The query params could not be deserialized into a struct.
+    Deserialize(String),
+    /// The body could not be read as what the handler expects, such as JSON.
+    InvalidBody(String),
+    /// What the handler expects can not be taken off of any request to the
+    /// route, which is a mistake of the server rather than of the client, so
+    /// it converts into a 500 instead.
+    Unfit(String),
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamError::Missing(name) => write!(f, "Missing param {:?}", name),
+            ParamError::Invalid {
+                name,
+                value,
+                reason,
+            } => write!(
+                f,
+                "Invalid value {:?} for param {:?}: {}",
+                value, name, reason
+            ),
+            ParamError::Deserialize(reason) => write!(f, "Invalid params: {}", reason),
+            ParamError::InvalidBody(reason) => write!(f, "Invalid body: {}", reason),
+            ParamError::Unfit(reason) => write!(f, "Unfit for the route: {}", reason),
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+impl From<ParamError> for HttpResponse {
+    fn from(param_error: ParamError) -> HttpResponse {
+        MartianError::from(param_error).into()
+    }
+}
+
+/// Splits a raw request or response into its head, the request or status
+/// line and headers, and whatever follows the blank line ending it.
+fn split_head_and_body(raw: &[u8]) -> (&[u8], &[u8]) {
+    match raw.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(i) => (&raw[..i], &raw[i + 4..]),
+        None => (raw, &[]),
+    }
+}
+
+/// The head of a request or response as text, which unlike its body it has
+/// to be. The line with anything else in it is reported, the first as a
+/// request line.
+fn utf8_head(head: &[u8]) -> Result<&str, ParseError> {
+    str::from_utf8(head).map_err(|e| {
+        let line_index = head[..e.valid_up_to()]
+            .windows(2)
+            .filter(|window| window == b"\r\n")
+            .count();
+        let lossy_head = String::from_utf8_lossy(head);
+        let line = lossy_head.split("\r\n").nth(line_index).unwrap_or_default();
+        match line_index {
+            0 => ParseError::InvalidRequestLine(line.into()),
+            _ => ParseError::InvalidHeader(line.into()),
+        }
+    })
+}
+
+fn parse_headers<'a>(
+    lines: impl Iterator<Item = &'a str>,
+) -> Result<Vec<(&'a str, &'a str)>, ParseError> {
+    lines
+        .map(|line| {
+            line.split_once(": ")
+                .ok_or_else(|| ParseError::InvalidHeader(line.into()))
+        })
+        .collect()
+}
+
+fn query_pairs(uri: &str) -> impl Iterator<Item = (&str, &str)> {
+    let uri = uri.split('#').next().unwrap_or_default();
+    form_pairs(uri.split_once('?').map_or("", |(_, query)| query))
+}
+
+/// The pairs of a query or `application/x-www-form-urlencoded` body, as they
+/// are.
+fn form_pairs(encoded: &str) -> impl Iterator<Item = (&str, &str)> {
+    encoded
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| param.split_once('=').unwrap_or((param, "")))
+}
+
+/// The value of the first of the headers with the given name, ignoring case.
+pub(crate) fn header_value<'a>(
+    headers: &'a HashMap<String, String>,
+    name: &str,
+) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn is_framing_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case("Content-Length") || name.eq_ignore_ascii_case("Transfer-Encoding")
+}
+
+pub(crate) fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .to_lowercase()
+        .trim_end()
+        .ends_with("chunked")
+}
+
 #[cfg(test)]
 mod tests;
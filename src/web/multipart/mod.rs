@@ -0,0 +1,345 @@
+//! `multipart/form-data` bodies, as sent by an html form with a file input.
+//! Each part is separated by a boundary given in the `Content-Type` and has
+//! its own headers, naming the form field and, for a file, its filename.
+//! More documentation
+//! [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Disposition).
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use super::spool::create_temp;
+
+/// The most read off of the body at a time, so that a file without any line
+/// breaks is not buffered whole.
+const MAX_CHUNK: u64 = 8 * 1024;
+
+/// The boundary given in a `multipart/*` content type.
+///
+/// # Examples:
+/// ```
+/// use martian::web::multipart::boundary;
+/// assert_eq!(boundary("multipart/form-data; boundary=XyZ"), Some("XyZ"));
+/// assert_eq!(boundary("multipart/form-data; boundary=\"a b\""), Some("a b"));
+/// assert_eq!(boundary("application/json"), None);
+/// ```
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    let media_type = params.next()?.trim();
+    if !media_type
+        .get(..10)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("multipart/"))
+    {
+        return None;
+    }
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Position {
+    Preamble,
+    Headers,
+    Content,
+    Done,
+}
+
+/// Reads the parts of a multipart body one after the other, straight off of
+/// `reader`. Only a small chunk of a part is held at a time, so a large file
+/// can be copied elsewhere, see [`Part::save_temp`], without being read into
+/// memory first.
+///
+/// # Examples:
+/// ```
+/// use martian::web::multipart::Multipart;
+/// use std::io::Read;
+/// let body = "--XyZ\r\n\
+///     Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+///     Holiday\r\n\
+///     --XyZ\r\n\
+///     Content-Disposition: form-data; name=\"photo\"; filename=\"beach.jpg\"\r\n\
+///     Content-Type: image/jpeg\r\n\r\n\
+///     <jpeg>\r\n\
+///     --XyZ--\r\n";
+/// let mut multipart = Multipart::new(body.as_bytes(), "XyZ");
+/// let title = multipart.next_part().unwrap().unwrap();
+/// assert_eq!(title.name(), Some("title"));
+/// assert_eq!(title.text().unwrap(), "Holiday");
+/// let photo = multipart.next_part().unwrap().unwrap();
+/// assert_eq!(photo.filename(), Some("beach.jpg"));
+/// assert_eq!(photo.content_type(), Some("image/jpeg"));
+/// assert_eq!(photo.bytes().unwrap(), b"<jpeg>");
+/// assert!(multipart.next_part().unwrap().is_none());
+/// ```
+///
+/// [`Part::save_temp`]: ./struct.Part.html#method.save_temp
+#[derive(Debug)]
+pub struct Multipart<R> {
+    reader: R,
+    delimiter: Vec<u8>,
+    position: Position,
+    /// Content read but not yet handed out.
+    pending: Vec<u8>,
+    /// The line break ending the content read so far, which belongs to the
+    /// delimiter if one follows it.
+    held_line_break: bool,
+    at_line_start: bool,
+}
+
+impl<R: BufRead> Multipart<R> {
+    pub fn new(reader: R, boundary: &str) -> Multipart<R> {
+        Multipart {
+            reader,
+            delimiter: format!("--{}", boundary).into_bytes(),
+            position: Position::Preamble,
+            pending: Vec::new(),
+            held_line_break: false,
+            at_line_start: true,
+        }
+    }
+
+    /// Skips whatever is left of the current part and reads the headers of
+    /// the next one.
+    ///
+    /// # Returns:
+    /// `None` once the closing boundary has been read, or an `Err` if the
+    /// body ends before it.
+    pub fn next_part(&mut self) -> io::Result<Option<Part<'_, R>>> {
+        if self.position == Position::Content {
+            io::copy(&mut ContentReader(self), &mut io::sink())?;
+        }
+        while self.position == Position::Preamble {
+            let chunk = self.read_chunk()?;
+            self.at_line_start = chunk.ends_with(b"\n");
+        }
+        if self.position == Position::Done {
+            return Ok(None);
+        }
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = Vec::new();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            if line.is_empty() {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                headers.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+        self.position = Position::Content;
+        self.pending.clear();
+        self.held_line_break = false;
+        self.at_line_start = true;
+        Ok(Some(Part::new(self, headers)))
+    }
+
+    /// Reads a line, or as much of one as fits in a chunk, moving past the
+    /// delimiter if that is what it is.
+    fn read_chunk(&mut self) -> io::Result<Vec<u8>> {
+        let mut chunk = Vec::new();
+        if (&mut self.reader)
+            .take(MAX_CHUNK)
+            .read_until(b'\n', &mut chunk)?
+            == 0
+        {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        if chunk.ends_with(b"\r") && self.reader.fill_buf()?.first() == Some(&b'\n') {
+            self.reader.consume(1);
+            chunk.push(b'\n');
+        }
+        if self.at_line_start {
+            let line = trim_line_end(&chunk);
+            if let Some(rest) = line.strip_prefix(&self.delimiter[..]) {
+                match rest {
+                    b"" => self.position = Position::Headers,
+                    b"--" => self.position = Position::Done,
+                    _ => {}
+                }
+            }
+        }
+        Ok(chunk)
+    }
+
+    fn read_content(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            if self.position != Position::Content {
+                return Ok(0);
+            }
+            let mut chunk = self.read_chunk()?;
+            if self.position != Position::Content {
+                self.held_line_break = false;
+                return Ok(0);
+            }
+            if self.held_line_break {
+                self.pending.extend(b"\r\n");
+            }
+            self.at_line_start = chunk.ends_with(b"\n");
+            self.held_line_break = chunk.ends_with(b"\r\n");
+            if self.held_line_break {
+                chunk.truncate(chunk.len() - 2);
+            }
+            self.pending.extend(chunk);
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+/// Reads the content of the current part, ending at the delimiter.
+struct ContentReader<'a, R>(&'a mut Multipart<R>);
+
+impl<R: BufRead> Read for ContentReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read_content(buf)
+    }
+}
+
+/// A single part of a [`Multipart`] body, its content being read through
+/// `Read` or one of the methods taking it whole.
+///
+/// [`Multipart`]: ./struct.Multipart.html
+#[derive(Debug)]
+pub struct Part<'a, R> {
+    multipart: &'a mut Multipart<R>,
+    headers: HashMap<String, String>,
+    name: Option<String>,
+    filename: Option<String>,
+}
+
+impl<'a, R: BufRead> Part<'a, R> {
+    fn new(multipart: &'a mut Multipart<R>, headers: HashMap<String, String>) -> Part<'a, R> {
+        let disposition = header_value(&headers, "Content-Disposition").unwrap_or_default();
+        let name = disposition_param(disposition, "name");
+        let filename = disposition_param(disposition, "filename");
+        Part {
+            multipart,
+            headers,
+            name,
+            filename,
+        }
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    /// The value of a header of this part, regardless of the case of `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_value(&self.headers, name)
+    }
+
+    /// The form field this part is the value of.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The name of the file this part was read from, as given by the client.
+    /// It is not to be trusted as a path.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type")
+    }
+
+    /// Reads the rest of the content.
+    pub fn bytes(mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads the rest of the content, replacing any invalid UTF-8.
+    pub fn text(self) -> io::Result<String> {
+        Ok(String::from_utf8_lossy(&self.bytes()?).into_owned())
+    }
+
+    /// Copies the rest of the content into a new file at `path`.
+    ///
+    /// # Returns:
+    /// The number of bytes written.
+    pub fn save_to<P: AsRef<Path>>(mut self, path: P) -> io::Result<u64> {
+        io::copy(&mut self, &mut File::create(path)?)
+    }
+
+    /// Copies the rest of the content into a new file in the temp directory,
+    /// readable only by the user of this process. The file is left for the
+    /// caller to move or remove.
+    ///
+    /// # Returns:
+    /// The path of the file.
+    pub fn save_temp(mut self) -> io::Result<PathBuf> {
+        let (mut file, path) = create_temp(&env::temp_dir(), "upload")?;
+        io::copy(&mut self, &mut file)?;
+        Ok(path)
+    }
+}
+
+impl<R: BufRead> Read for Part<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.multipart.read_content(buf)
+    }
+}
+
+fn header_value<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// The line without its line break and any whitespace padding before it.
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(0, |i| i + 1);
+    &line[..end]
+}
+
+/// A parameter of a `Content-Disposition`, such as `name` or `filename`. A
+/// quoted value may contain `;` and `\`-escaped quotes.
+fn disposition_param(disposition: &str, key: &str) -> Option<String> {
+    let mut rest = disposition.split_once(';')?.1;
+    loop {
+        let (param, value) = rest.split_once('=')?;
+        let value = value.trim_start();
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let mut unquoted = String::new();
+                let mut chars = quoted.char_indices();
+                let end = loop {
+                    match chars.next()? {
+                        (_, '\\') => unquoted.push(chars.next()?.1),
+                        (i, '"') => break i + 1,
+                        (_, c) => unquoted.push(c),
+                    }
+                };
+                (unquoted, &quoted[end..])
+            }
+            None => {
+                let end = value.find(';').unwrap_or(value.len());
+                (value[..end].trim_end().to_string(), &value[end..])
+            }
+        };
+        if param.trim().eq_ignore_ascii_case(key) {
+            return Some(value);
+        }
+        rest = next.split_once(';')?.1;
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,131 @@
+use crate::web::multipart::{boundary, disposition_param, Multipart, MAX_CHUNK};
+use std::fs;
+use std::io::{BufReader, ErrorKind, Read};
+
+fn two_part_body() -> Vec<u8> {
+    let mut body = b"preamble to be skipped\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        line one\r\nline two\r\n\r\n\
+        --XyZ\r\n\
+        content-disposition: form-data; name=\"file\"; filename=\"a;b \\\"c\\\".bin\"\r\n\
+        Content-Type: application/octet-stream\r\n\r\n"
+        .to_vec();
+    body.extend([0, 159, 146, 150, b'\r', b'\n', 0xff]);
+    body.extend(b"\r\n--XyZ--\r\nepilogue");
+    body
+}
+
+#[test]
+fn should_read_every_part_with_content_up_to_its_delimiter() {
+    let body = two_part_body();
+    let mut multipart = Multipart::new(&body[..], "XyZ");
+    let title = multipart.next_part().unwrap().unwrap();
+    assert_eq!(title.name(), Some("title"));
+    assert_eq!(title.filename(), None);
+    assert_eq!(title.text().unwrap(), "line one\r\nline two\r\n");
+    let file = multipart.next_part().unwrap().unwrap();
+    assert_eq!(file.name(), Some("file"));
+    assert_eq!(file.filename(), Some("a;b \"c\".bin"));
+    assert_eq!(
+        file.header("CONTENT-TYPE"),
+        Some("application/octet-stream")
+    );
+    assert_eq!(
+        file.bytes().unwrap(),
+        vec![0, 159, 146, 150, b'\r', b'\n', 0xff]
+    );
+    assert!(multipart.next_part().unwrap().is_none());
+    assert!(multipart.next_part().unwrap().is_none());
+}
+
+#[test]
+fn should_skip_unread_content_when_moving_to_next_part() {
+    let body = two_part_body();
+    let mut multipart = Multipart::new(&body[..], "XyZ");
+    let mut title = multipart.next_part().unwrap().unwrap();
+    let mut start = [0; 4];
+    title.read_exact(&mut start).unwrap();
+    assert_eq!(&start, b"line");
+    let file = multipart.next_part().unwrap().unwrap();
+    assert_eq!(file.name(), Some("file"));
+}
+
+#[test]
+fn should_not_end_part_on_boundary_within_a_line() {
+    let body = b"--XyZ\r\n\r\nnot --XyZ\r\n--XyZextra\r\n--XyZ--\r\n";
+    let mut multipart = Multipart::new(&body[..], "XyZ");
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(part.text().unwrap(), "not --XyZ\r\n--XyZextra");
+}
+
+#[test]
+fn should_read_content_larger_than_a_chunk_without_line_breaks() {
+    let content = vec![b'x'; MAX_CHUNK as usize * 3 + 1];
+    let mut body = b"--XyZ\r\n\r\n".to_vec();
+    body.extend(&content);
+    body.extend(b"\r\n--XyZ--");
+    let mut multipart = Multipart::new(BufReader::with_capacity(16, &body[..]), "XyZ");
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(part.bytes().unwrap(), content);
+}
+
+#[test]
+fn should_keep_line_break_split_across_chunks() {
+    let mut content = vec![b'x'; MAX_CHUNK as usize - 1];
+    content.extend(b"\r\ny");
+    let mut body = b"--XyZ\r\n\r\n".to_vec();
+    body.extend(&content);
+    body.extend(b"\r\n--XyZ--\r\n");
+    let mut multipart = Multipart::new(&body[..], "XyZ");
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(part.bytes().unwrap(), content);
+}
+
+#[test]
+fn should_fail_when_body_ends_before_closing_delimiter() {
+    let body = b"--XyZ\r\n\r\nunfinished";
+    let mut multipart = Multipart::new(&body[..], "XyZ");
+    let part = multipart.next_part().unwrap().unwrap();
+    assert_eq!(part.bytes().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    let no_delimiter = b"just some text";
+    let mut multipart = Multipart::new(&no_delimiter[..], "XyZ");
+    assert_eq!(
+        multipart.next_part().unwrap_err().kind(),
+        ErrorKind::UnexpectedEof
+    );
+}
+
+#[test]
+fn should_stream_file_part_into_temp_file() {
+    let body = two_part_body();
+    let mut multipart = Multipart::new(&body[..], "XyZ");
+    multipart.next_part().unwrap();
+    let path = multipart.next_part().unwrap().unwrap().save_temp().unwrap();
+    let saved = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(saved, vec![0, 159, 146, 150, b'\r', b'\n', 0xff]);
+}
+
+#[test]
+fn should_only_find_boundary_of_multipart_content_type() {
+    assert_eq!(
+        boundary("Multipart/Mixed; charset=utf-8; Boundary=abc"),
+        Some("abc")
+    );
+    assert_eq!(boundary("multipart/form-data"), None);
+    assert_eq!(boundary("multipart/form-data; boundary="), None);
+    assert_eq!(boundary("text/plain; boundary=abc"), None);
+}
+
+#[test]
+fn should_read_quoted_and_bare_disposition_params() {
+    let disposition = "form-data; name=field; filename=\"x.txt\"";
+    assert_eq!(disposition_param(disposition, "name"), Some("field".into()));
+    assert_eq!(
+        disposition_param(disposition, "filename"),
+        Some("x.txt".into())
+    );
+    assert_eq!(disposition_param(disposition, "size"), None);
+    assert_eq!(disposition_param("form-data", "name"), None);
+}
@@ -0,0 +1,167 @@
+//! Picking the representation a client most prefers out of those on offer,
+//! going by the `Accept`, `Accept-Language` or `Accept-Encoding` of its
+//! request, see [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-12.5).
+
+use std::collections::HashMap;
+
+/// An entry of a header listing what a client prefers, such as
+/// `text/html;q=0.8`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct QualityItem<'a> {
+    /// Without any of its parameters, such as `text/html`.
+    pub value: &'a str,
+    /// How much it is preferred, from 0 for not at all to 1, the default.
+    pub quality: f32,
+}
+
+/// The entries of a header listing what a client prefers, the most preferred
+/// first, in the order they are listed when preferred as much. An entry with
+/// a `q` other than a number from 0 to 1 is left out.
+///
+/// # Examples:
+/// ```
+/// use martian::web::negotiation::parse_quality_list;
+/// let items = parse_quality_list("en;q=0.8, de, fr;q=0.8, *;q=0");
+/// let values = items.iter().map(|item| item.value).collect::<Vec<_>>();
+/// assert_eq!(values, ["de", "en", "fr", "*"]);
+/// assert_eq!(items[1].quality, 0.8);
+/// ```
+pub fn parse_quality_list(header: &str) -> Vec<QualityItem<'_>> {
+    let mut items = header
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';');
+            let value = params.next()?.trim();
+            let q = params.find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                name.trim().eq_ignore_ascii_case("q").then(|| value.trim())
+            });
+            let quality = match q {
+                Some(q) => q.parse().ok().filter(|q| (0.0..=1.0).contains(q))?,
+                None => 1.0,
+            };
+            (!value.is_empty()).then_some(QualityItem { value, quality })
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| b.quality.total_cmp(&a.quality));
+    items
+}
+
+/// What representations are negotiated on, each through its own header.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Negotiable {
+    /// Through `Accept`, such as `text/html` or `application/json`. A range
+    /// such as `text/*` matches every subtype.
+    MediaType,
+    /// Through `Accept-Language`, such as `en-GB`. A range such as `en`
+    /// matches every tag starting with `en-`.
+    Language,
+    /// Through `Accept-Encoding`, such as `gzip`. `identity` is acceptable
+    /// unless ruled out.
+    Encoding,
+}
+
+impl Negotiable {
+    /// The request header preferences are listed in.
+    pub fn header(&self) -> &'static str {
+        match self {
+            Negotiable::MediaType => "Accept",
+            Negotiable::Language => "Accept-Language",
+            Negotiable::Encoding => "Accept-Encoding",
+        }
+    }
+
+    /// The offer the preferences listed in the header favour most, each
+    /// going by the most specific range matching it. Offers preferred as
+    /// much are picked in the order given, so the first is picked when the
+    /// header is missing.
+    ///
+    /// # Returns:
+    /// `None` if none of the offers is acceptable.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::negotiation::Negotiable;
+    /// let offers = ["application/json", "text/html"];
+    /// let accept = Some("text/*, application/json;q=0.5");
+    /// assert_eq!(Negotiable::MediaType.preferred(accept, &offers), Some("text/html"));
+    /// assert_eq!(Negotiable::MediaType.preferred(None, &offers), Some("application/json"));
+    /// assert_eq!(Negotiable::MediaType.preferred(Some("image/*"), &offers), None);
+    /// ```
+    pub fn preferred<'o>(&self, header: Option<&str>, offers: &[&'o str]) -> Option<&'o str> {
+        let header = match header {
+            Some(header) => header,
+            None => return offers.first().copied(),
+        };
+        let items = parse_quality_list(header);
+        let mut best: Option<(&str, f32)> = None;
+        for offer in offers {
+            let quality = self.quality(&items, offer);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((offer, quality));
+            }
+        }
+        best.map(|(offer, _)| offer)
+    }
+
+    /// How much the offer is preferred, going by the most specific item
+    /// matching it.
+    fn quality(&self, items: &[QualityItem], offer: &str) -> f32 {
+        let matched = items
+            .iter()
+            .filter_map(|item| Some((self.specificity(item.value, offer)?, item.quality)))
+            .max_by_key(|(specificity, _)| *specificity);
+        match matched {
+            Some((_, quality)) => quality,
+            None if *self == Negotiable::Encoding && offer.eq_ignore_ascii_case("identity") => 1.0,
+            None => 0.0,
+        }
+    }
+
+    /// How specifically the range matches the offer, if at all, a greater
+    /// one being more specific.
+    fn specificity(&self, range: &str, offer: &str) -> Option<usize> {
+        if range == "*" || (*self == Negotiable::MediaType && range == "*/*") {
+            return Some(0);
+        }
+        match self {
+            Negotiable::MediaType => {
+                let offer = offer.split(';').next().unwrap_or_default().trim();
+                if range.eq_ignore_ascii_case(offer) {
+                    return Some(2);
+                }
+                let (range_type, subtype) = range.split_once('/')?;
+                let (offer_type, _) = offer.split_once('/')?;
+                (subtype == "*" && range_type.eq_ignore_ascii_case(offer_type)).then_some(1)
+            }
+            Negotiable::Language => {
+                let is_prefix = offer
+                    .get(..range.len())
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(range))
+                    && matches!(offer.as_bytes().get(range.len()), None | Some(b'-'));
+                is_prefix.then_some(range.len())
+            }
+            Negotiable::Encoding => range.eq_ignore_ascii_case(offer).then_some(1),
+        }
+    }
+}
+
+/// Adds the header to the `Vary` of a response, unless it is there already.
+pub(crate) fn add_vary(headers: &mut HashMap<String, String>, header: &str) {
+    let key = headers
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case("Vary"))
+        .cloned();
+    let vary = match key.and_then(|key| headers.remove(&key)) {
+        Some(vary)
+            if vary
+                .split(',')
+                .any(|name| name.trim() == "*" || name.trim().eq_ignore_ascii_case(header)) =>
+        {
+            vary
+        }
+        Some(vary) => format!("{}, {}", vary, header),
+        None => header.into(),
+    };
+    headers.insert("Vary".into(), vary);
+}
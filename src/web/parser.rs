@@ -0,0 +1,165 @@
+//! Reading a request as it comes in off of the network, a piece at a time,
+//! rather than all at once as [`HttpRequest::parse`] does.
+//!
+//! [`HttpRequest::parse`]: ../struct.HttpRequest.html#method.parse
+
+use super::{chunked, is_chunked, HttpRequest, HttpRequestRef, ParseError};
+
+/// What became of the bytes last pushed onto a [`RequestParser`].
+///
+/// [`RequestParser`]: ./struct.RequestParser.html
+#[derive(PartialEq, Debug)]
+pub enum ParseStatus {
+    /// The request is not all there yet.
+    NeedMoreData,
+    /// The request is all there. Anything pushed past its end is kept for the
+    /// next request.
+    Complete(Box<HttpRequest>),
+}
+
+/// Parses a request split across any number of reads, such as those of a
+/// `TcpStream`. Bytes are pushed as they arrive until the request is
+/// [`Complete`], the head being checked as soon as it is all there so that a
+/// malformed one is reported without waiting on its body.
+///
+/// # Examples:
+/// ```
+/// use martian::web::{ParseStatus, RequestParser};
+/// let mut parser = RequestParser::new();
+/// let status = parser.push(b"POST /echo HTTP/1.1\r\nContent-Le").unwrap();
+/// assert_eq!(status, ParseStatus::NeedMoreData);
+/// let status = parser.push(b"ngth: 5\r\n\r\nhel").unwrap();
+/// assert_eq!(status, ParseStatus::NeedMoreData);
+/// match parser.push(b"lo").unwrap() {
+///     ParseStatus::Complete(request) => assert_eq!(request.body_text(), Ok("hello")),
+///     ParseStatus::NeedMoreData => unreachable!(),
+/// }
+/// ```
+///
+/// [`Complete`]: ./enum.ParseStatus.html#variant.Complete
+#[derive(Debug, Default)]
+pub struct RequestParser {
+    buffer: Vec<u8>,
+    state: State,
+}
+
+/// How far along the request in the buffer is, each position being an index
+/// into the buffer.
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Looking for the blank line ending the head, which is not before
+    /// `scanned`.
+    Head { scanned: usize },
+    /// Waiting on a body framed by `Content-Length`, or on no body at all, to
+    /// reach `end`.
+    Sized { end: usize },
+    /// Waiting on the size line of a chunk starting at `next`.
+    Chunked { next: usize },
+    /// Past the last chunk, waiting on the trailer line starting at `next`.
+    Trailers { next: usize },
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::Head { scanned: 0 }
+    }
+}
+
+impl RequestParser {
+    pub fn new() -> RequestParser {
+        RequestParser::default()
+    }
+
+    /// Adds the bytes to those already pushed, parsing the request once all
+    /// of it is there. Pushing no bytes parses any request already buffered
+    /// past the last, as when requests are pipelined.
+    ///
+    /// # Returns:
+    /// An `Err` as soon as the head or the framing of the body is known to be
+    /// malformed, see [`HttpRequest::parse`]. What follows can not be told
+    /// apart from the broken request, so the connection is best closed.
+    ///
+    /// [`HttpRequest::parse`]: ./struct.HttpRequest.html#method.parse
+    pub fn push(&mut self, bytes: &[u8]) -> Result<ParseStatus, ParseError> {
+        self.buffer.extend_from_slice(bytes);
+        loop {
+            self.state = match self.state {
+                State::Head { scanned } => match find_blank_line(&self.buffer, scanned) {
+                    Some(head_end) => self.framing(head_end)?,
+                    None => {
+                        // The blank line may start in what has been scanned.
+                        let scanned = self.buffer.len().saturating_sub(3);
+                        self.state = State::Head { scanned };
+                        return Ok(ParseStatus::NeedMoreData);
+                    }
+                },
+                State::Sized { end } if end <= self.buffer.len() => return self.complete(end),
+                State::Sized { .. } => return Ok(ParseStatus::NeedMoreData),
+                State::Chunked { next } => match line_at(&self.buffer, next) {
+                    Some((line, after)) => match chunked::chunk_size(&line)? {
+                        0 => State::Trailers { next: after },
+                        size => State::Chunked {
+                            next: after.saturating_add(size).saturating_add(2),
+                        },
+                    },
+                    None => return Ok(ParseStatus::NeedMoreData),
+                },
+                State::Trailers { next } => match line_at(&self.buffer, next) {
+                    Some((line, after)) if line.is_empty() => return self.complete(after),
+                    Some((_, after)) => State::Trailers { next: after },
+                    None => return Ok(ParseStatus::NeedMoreData),
+                },
+            };
+        }
+    }
+
+    /// Whatever has been pushed but is not yet part of a complete request.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Checks the head ending at `head_end` and works out from it where the
+    /// body ends.
+    fn framing(&self, head_end: usize) -> Result<State, ParseError> {
+        let request = HttpRequestRef::parse_head(&self.buffer[..head_end])?;
+        let body_start = head_end + 4;
+        if request.header("Transfer-Encoding").is_some_and(is_chunked) {
+            Ok(State::Chunked { next: body_start })
+        } else if let Some(length) = request.header("Content-Length") {
+            let length = length
+                .parse::<usize>()
+                .map_err(|_| ParseError::InvalidHeader(format!("Content-Length: {}", length)))?;
+            Ok(State::Sized {
+                end: body_start.saturating_add(length),
+            })
+        } else {
+            Ok(State::Sized { end: body_start })
+        }
+    }
+
+    /// Takes the request ending at `end` out of the buffer.
+    fn complete(&mut self, end: usize) -> Result<ParseStatus, ParseError> {
+        let raw_request = self.buffer.drain(..end).collect::<Vec<u8>>();
+        self.state = State::default();
+        HttpRequest::parse_bytes(&raw_request)
+            .map(|request| ParseStatus::Complete(Box::new(request)))
+    }
+}
+
+/// Where the head ends, if the blank line ending it is in the buffer.
+fn find_blank_line(buffer: &[u8], from: usize) -> Option<usize> {
+    buffer
+        .get(from..)?
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|i| from + i)
+}
+
+/// The line starting at `start` without its line ending, and where the line
+/// after it starts, if all of it is in the buffer.
+fn line_at(buffer: &[u8], start: usize) -> Option<(String, usize)> {
+    let rest = buffer.get(start..)?;
+    let end = rest.windows(2).position(|window| window == b"\r\n")?;
+    let line = String::from_utf8_lossy(&rest[..end]).into_owned();
+    Some((line, start + end + 2))
+}
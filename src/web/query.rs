@@ -0,0 +1,78 @@
+//! The query params on the uri of a request, decoded.
+
+use super::encoding::form_decode;
+
+/// Every query param of a request in the order they were given, with `+` and
+/// `%XX` escapes decoded. A key may be given more than once, and a key without
+/// a `=` has an empty value.
+///
+/// # Examples:
+/// ```
+/// use martian::web::HttpRequest;
+/// let http_request = HttpRequest::from("GET /?tag=a&tag=b%20c&debug HTTP/1.1\r\n\r\n");
+/// let params = http_request.params();
+/// assert_eq!(params.get("tag"), Some("a"));
+/// assert_eq!(params.get_all("tag"), vec!["a", "b c"]);
+/// assert_eq!(params.get("debug"), Some(""));
+/// assert!(!params.contains("page"));
+/// ```
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct QueryParams {
+    pairs: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    /// Decodes the query params of the uri, everything following its `?`.
+    pub fn parse(uri: &str) -> QueryParams {
+        QueryParams::decode(super::query_pairs(uri))
+    }
+
+    /// Decodes the fields of an `application/x-www-form-urlencoded` body,
+    /// written the same as a query but without the `?`.
+    pub fn from_form(body: &str) -> QueryParams {
+        QueryParams::decode(super::form_pairs(body))
+    }
+
+    fn decode<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> QueryParams {
+        QueryParams {
+            pairs: pairs
+                .map(|(key, value)| (form_decode(key).into(), form_decode(value).into()))
+                .collect(),
+        }
+    }
+
+    /// The first value given for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value given for `name`, in order.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.pairs.iter().any(|(key, _)| key == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
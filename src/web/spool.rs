@@ -0,0 +1,204 @@
+//! Request bodies too large to be held in memory, written to a temporary file
+//! as they are read in and read back from it by whoever handles the request.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// Creates a new file in the directory, under a name no one could have
+/// guessed to make it first, which only the user of this process may read.
+///
+/// # Returns:
+/// The file and its path.
+pub(crate) fn create_temp(dir: &Path, kind: &str) -> io::Result<(File, PathBuf)> {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    let random = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let path = dir.join(format!("martian-{}-{}", kind, random));
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    Ok((options.open(&path)?, path))
+}
+
+/// Where a body is written as it is read in, held in memory until it grows
+/// past the most it may be there and then moved to a temporary file.
+#[derive(Debug, Default)]
+pub(crate) struct Spool {
+    /// The most held in memory and the directory of the file for the rest,
+    /// everything being held in memory without.
+    spill: Option<(u64, PathBuf)>,
+    bytes: Vec<u8>,
+    file: Option<(File, SpooledBody)>,
+}
+
+/// A body as it was once all written to its [`Spool`].
+///
+/// [`Spool`]: ./struct.Spool.html
+pub(crate) enum Spooled {
+    Memory(Vec<u8>),
+    File(SpooledBody),
+}
+
+impl Spool {
+    /// Holds the body in memory for as long as it is no larger than
+    /// `max_in_memory`, then moves it to a new file in the directory.
+    pub(crate) fn spilling(max_in_memory: u64, dir: PathBuf) -> Spool {
+        Spool {
+            spill: Some((max_in_memory, dir)),
+            ..Spool::default()
+        }
+    }
+
+    /// How many bytes have been written so far.
+    pub(crate) fn len(&self) -> u64 {
+        match &self.file {
+            Some((_, spooled)) => spooled.len,
+            None => self.bytes.len() as u64,
+        }
+    }
+
+    pub(crate) fn finish(self) -> io::Result<Spooled> {
+        match self.file {
+            Some((mut file, spooled)) => {
+                file.flush()?;
+                Ok(Spooled::File(spooled))
+            }
+            None => Ok(Spooled::Memory(self.bytes)),
+        }
+    }
+}
+
+impl Write for Spool {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.len();
+        let over_dir = self
+            .spill
+            .as_ref()
+            .filter(|(max, _)| len + buf.len() as u64 > *max)
+            .map(|(_, dir)| dir);
+        if let (None, Some(dir)) = (&self.file, over_dir) {
+            let (mut file, path) = create_temp(dir, "body")?;
+            // Removes the file again when dropped, should writing to it fail.
+            let spooled = SpooledBody {
+                path,
+                len: self.bytes.len() as u64,
+            };
+            file.write_all(&mem::take(&mut self.bytes))?;
+            self.file = Some((file, spooled));
+        }
+        match &mut self.file {
+            Some((file, spooled)) => {
+                let written = file.write(buf)?;
+                spooled.len += written as u64;
+                Ok(written)
+            }
+            None => self.bytes.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some((file, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A body the `Server` wrote to a temporary file as it read it in, kept in
+/// the [`extensions`] of its request in place of its `body`. The file is
+/// removed once the last request holding it is dropped.
+///
+/// [`extensions`]: ./struct.HttpRequest.html#structfield.extensions
+#[derive(Debug)]
+pub(crate) struct SpooledBody {
+    path: PathBuf,
+    len: u64,
+}
+
+impl SpooledBody {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledBody {
+    fn drop(&mut self) {
+        // Nothing is to be done about a file which is already gone.
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The body of a request, read from wherever it is kept, see
+/// [`HttpRequest::body_reader`].
+///
+/// [`HttpRequest::body_reader`]: ./struct.HttpRequest.html#method.body_reader
+#[derive(Debug)]
+pub struct BodyReader<'a> {
+    source: Source<'a>,
+}
+
+#[derive(Debug)]
+enum Source<'a> {
+    Memory(&'a [u8]),
+    /// Opened on the first read.
+    File(&'a Path, Option<BufReader<File>>),
+}
+
+impl<'a> BodyReader<'a> {
+    pub(crate) fn memory(bytes: &'a [u8]) -> BodyReader<'a> {
+        BodyReader {
+            source: Source::Memory(bytes),
+        }
+    }
+
+    pub(crate) fn spooled(spooled: &'a SpooledBody) -> BodyReader<'a> {
+        BodyReader {
+            source: Source::File(spooled.path(), None),
+        }
+    }
+}
+
+impl Read for BodyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl BufRead for BodyReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match &mut self.source {
+            Source::Memory(bytes) => Ok(bytes),
+            Source::File(path, reader) => {
+                if reader.is_none() {
+                    *reader = Some(BufReader::new(File::open(path)?));
+                }
+                match reader {
+                    Some(reader) => reader.fill_buf(),
+                    None => Ok(&[]),
+                }
+            }
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match &mut self.source {
+            Source::Memory(bytes) => *bytes = &bytes[amount..],
+            Source::File(_, reader) => {
+                if let Some(reader) = reader {
+                    reader.consume(amount);
+                }
+            }
+        }
+    }
+}
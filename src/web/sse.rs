@@ -0,0 +1,218 @@
+//! Server-Sent Events, a response body kept open for pushing events to the
+//! client as they happen, see
+//! [the spec](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use super::{Body, HttpResponse, IntoResponse, Mime, StatusCode};
+
+/// A single event pushed through an [`SseSender`], written out as its
+/// `event`, `id` and `retry` fields followed by a `data` field for every
+/// line of its data.
+///
+/// # Examples:
+/// ```
+/// use martian::web::Event;
+/// let event = Event::data("line one\nline two").event("update").id("7");
+/// assert_eq!(
+///     event.to_string(),
+///     "event: update\nid: 7\ndata: line one\ndata: line two\n\n"
+/// );
+/// ```
+///
+/// [`SseSender`]: ./struct.SseSender.html
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Event {
+    data: String,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// An event of the default `message` type carrying the data.
+    pub fn data(data: &str) -> Event {
+        Event {
+            data: data.into(),
+            event: None,
+            id: None,
+            retry: None,
+        }
+    }
+
+    /// The type of the event, which a client listens for by name.
+    pub fn event(mut self, event: &str) -> Event {
+        self.event = Some(single_line(event));
+        self
+    }
+
+    /// The id the client sends back in `Last-Event-ID` when reconnecting.
+    pub fn id(mut self, id: &str) -> Event {
+        self.id = Some(single_line(id));
+        self
+    }
+
+    /// How long the client waits before reconnecting once the stream ends.
+    pub fn retry(mut self, retry: Duration) -> Event {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", event)?;
+        }
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", id)?;
+        }
+        if let Some(retry) = self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+        for line in self.data.split('\n') {
+            writeln!(f, "data: {}", line.strip_suffix('\r').unwrap_or(line))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// A `text/event-stream` body, open for as long as any [`SseSender`] of it
+/// is, see [`SseStream::channel`]. Returned from a handler, it is answered
+/// with a 200 which is written out as events are pushed.
+///
+/// [`SseSender`]: ./struct.SseSender.html
+/// [`SseStream::channel`]: ./struct.SseStream.html#method.channel
+pub struct SseStream {
+    receiver: Receiver<String>,
+    keep_alive: Option<Duration>,
+    /// Whatever of the last event has not been read yet.
+    pending: io::Cursor<Vec<u8>>,
+}
+
+impl SseStream {
+    /// The sending and the responding halves of a stream of events. The
+    /// sender is handed to whatever produces the events, usually on a thread
+    /// of its own, as the stream is only written out once the handler
+    /// returns it.
+    ///
+    /// # Examples:
+    /// ```
+    /// use martian::web::{Event, HttpRequest, SseStream};
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// fn ticks(_: HttpRequest) -> SseStream {
+    ///     let (sender, stream) = SseStream::channel();
+    ///     thread::spawn(move || {
+    ///         for tick in 0.. {
+    ///             let event = Event::data(&tick.to_string()).event("tick");
+    ///             // The client is gone once sending fails.
+    ///             if sender.send(event).is_err() {
+    ///                 break;
+    ///             }
+    ///             thread::sleep(Duration::from_secs(1));
+    ///         }
+    ///     });
+    ///     stream.keep_alive(Duration::from_secs(15))
+    /// }
+    /// ```
+    pub fn channel() -> (SseSender, SseStream) {
+        let (sender, receiver) = mpsc::channel();
+        let stream = SseStream {
+            receiver,
+            keep_alive: None,
+            pending: io::Cursor::new(Vec::new()),
+        };
+        (SseSender { sender }, stream)
+    }
+
+    /// Writes a comment whenever no event has been pushed for the interval,
+    /// so that proxies do not close the connection for being idle and a
+    /// client which has gone away is noticed.
+    pub fn keep_alive(mut self, interval: Duration) -> SseStream {
+        self.keep_alive = Some(interval);
+        self
+    }
+}
+
+impl Read for SseStream {
+    /// Waits for the next event once the last one is read, ending once every
+    /// sender is dropped.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.position() as usize == self.pending.get_ref().len() {
+            let next = match self.keep_alive {
+                Some(interval) => self.receiver.recv_timeout(interval),
+                None => self
+                    .receiver
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let next = match next {
+                Ok(next) => next,
+                Err(RecvTimeoutError::Timeout) => ": keep-alive\n\n".into(),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            };
+            self.pending = io::Cursor::new(next.into_bytes());
+        }
+        self.pending.read(buf)
+    }
+}
+
+/// A 200 streaming the events, which caches do not hold on to.
+impl IntoResponse for SseStream {
+    fn into_response(self) -> HttpResponse {
+        let mut response = HttpResponse::new(StatusCode::Ok).content_type(Mime::TextEventStream);
+        response
+            .headers
+            .insert("Cache-Control".into(), "no-cache".into());
+        response.body = Body::Stream(Box::new(self));
+        response
+    }
+}
+
+/// Pushes events to the client of an [`SseStream`]. Clones push to the same
+/// stream, which ends once all of them are dropped.
+///
+/// [`SseStream`]: ./struct.SseStream.html
+#[derive(Debug, Clone)]
+pub struct SseSender {
+    sender: Sender<String>,
+}
+
+/// The stream of an [`SseSender`] no longer being written out, as the client
+/// has gone away.
+///
+/// [`SseSender`]: ./struct.SseSender.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Disconnected;
+
+impl fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the event stream is closed")
+    }
+}
+
+impl std::error::Error for Disconnected {}
+
+impl SseSender {
+    pub fn send(&self, event: Event) -> Result<(), Disconnected> {
+        self.push(event.to_string())
+    }
+
+    /// A comment, which the client ignores.
+    pub fn comment(&self, comment: &str) -> Result<(), Disconnected> {
+        self.push(format!(": {}\n\n", single_line(comment)))
+    }
+
+    fn push(&self, raw: String) -> Result<(), Disconnected> {
+        self.sender.send(raw).map_err(|_| Disconnected)
+    }
+}
+
+/// The text up to its first line break, a field ending at one.
+fn single_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().into()
+}
@@ -0,0 +1,63 @@
+//! Application state shared by every request, such as a connection pool or
+//! configuration, looked up by its type.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Values registered through [`Server::manage`], at most one of each type.
+/// Every [`HttpRequest`] handed to a callback carries the same values, so
+/// anything mutable needs to be behind a `Mutex` or similar.
+///
+/// # Examples:
+/// ```
+/// use martian::web::State;
+/// struct Config {
+///     greeting: &'static str,
+/// }
+/// let mut state = State::default();
+/// state.insert(Config { greeting: "howdy" });
+/// assert_eq!(state.get::<Config>().unwrap().greeting, "howdy");
+/// assert!(state.get::<String>().is_none());
+/// ```
+///
+/// [`Server::manage`]: ../server/struct.Server.html#method.manage
+/// [`HttpRequest`]: ./struct.HttpRequest.html
+#[derive(Clone, Default)]
+pub struct State {
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl State {
+    /// Stores the value, replacing any already stored of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// The value of type `T`, if one has been stored.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+}
+
+impl PartialEq for State {
+    /// Equal when holding the very same values, not merely equal ones.
+    fn eq(&self, other: &State) -> bool {
+        self.values.len() == other.values.len()
+            && self.values.iter().all(|(type_id, value)| {
+                other
+                    .values
+                    .get(type_id)
+                    .is_some_and(|other_value| Arc::ptr_eq(value, other_value))
+            })
+    }
+}
+
+impl fmt::Debug for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "State({} values)", self.values.len())
+    }
+}
@@ -0,0 +1,164 @@
+//! Every status code registered with IANA, see
+//! [here](https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml).
+
+/// Declares the `StatusCode` variants along with their code and reason
+/// phrase, keeping the three from drifting apart.
+macro_rules! status_codes {
+    ($($(#[$attr:meta])* $variant:ident = $code:expr, $reason_phrase:expr;)*) => {
+        /// Standard across the web, status codes are a nice simple description
+        /// of what has happened to the original `HttpRequest`. They live on the
+        /// response and with a few exceptions will mean the same thing across
+        /// the world. More documentation about individual use
+        /// [here](https://developer.mozilla.org/en-US/docs/Web/HTTP/Status).
+        #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+        pub enum StatusCode {
+            $($(#[$attr])* $variant,)*
+            /// Any code without a variant of its own, as can arrive on a
+            /// response from another server.
+            Other(u16),
+        }
+
+        impl StatusCode {
+            /// Maps a numeric code to its `StatusCode`, falling back to
+            /// [`StatusCode::Other`] for codes without a variant.
+            ///
+            /// # Examples:
+            /// ```
+            /// use martian::web::StatusCode;
+            /// assert_eq!(StatusCode::from_u16(404), StatusCode::NotFound);
+            /// assert_eq!(StatusCode::from_u16(418), StatusCode::Other(418));
+            /// ```
+            ///
+            /// [`StatusCode::Other`]: ./enum.StatusCode.html#variant.Other
+            pub fn from_u16(code: u16) -> StatusCode {
+                match code {
+                    $($code => StatusCode::$variant,)*
+                    code => StatusCode::Other(code),
+                }
+            }
+
+            /// The numeric code sent on the status line of a response.
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(StatusCode::$variant => $code,)*
+                    StatusCode::Other(code) => *code,
+                }
+            }
+
+            /// The human readable description sent alongside the code on the
+            /// status line of a response. Empty for [`StatusCode::Other`].
+            ///
+            /// # Examples:
+            /// ```
+            /// use martian::web::StatusCode;
+            /// assert_eq!(StatusCode::NotFound.reason_phrase(), "Not Found");
+            /// ```
+            ///
+            /// [`StatusCode::Other`]: ./enum.StatusCode.html#variant.Other
+            pub fn reason_phrase(&self) -> &'static str {
+                match self {
+                    $(StatusCode::$variant => $reason_phrase,)*
+                    StatusCode::Other(_) => "",
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableContent = 422, "Unprocessable Content";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+    /// 1xx, the request was received and is still being processed.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    /// 2xx, the request was handled successfully.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    /// 3xx, the client has to go elsewhere to complete the request.
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    /// 4xx, there was something wrong with the request.
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    /// 5xx, the server failed to handle a valid request.
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+
+    /// Whether a response with this code may carry a body at all. Responses
+    /// to 1xx, 204 and 304 never do.
+    pub fn allows_body(&self) -> bool {
+        !(self.is_informational()
+            || matches!(self, StatusCode::NoContent | StatusCode::NotModified))
+    }
+}
@@ -1,7 +1,14 @@
 use crate::web::{
-    get_body_begin_index, get_headers_from_lines, get_http_version, HttpMethod, HttpRequest,
+    parse_headers, query_pairs, split_head_and_body, Authorization, Body, Cookie, Disconnected,
+    Event, HttpMethod, HttpRequest, HttpRequestRef, HttpResponse, HttpVersion, IntoResponse,
+    MartianError, Mime, ParamError, ParseError, ParseStatus, RequestParser, SameSite, SetCookie,
+    SseStream, State, StatusCode, Uri,
 };
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read};
+use std::time::Duration;
 
 #[test]
 fn should_serialize_simple_http_request_with_all_fields() {
@@ -11,9 +18,13 @@ fn should_serialize_simple_http_request_with_all_fields() {
     let expected_http_request = HttpRequest {
         http_method: HttpMethod::Get,
         uri: "/".into(),
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         headers: Some(expected_http_headers),
         body: Some("body".into()),
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection: None,
     };
     let actual_serialized_http_request = HttpRequest::from(raw_request);
     assert_eq!(expected_http_request, actual_serialized_http_request);
@@ -43,10 +54,10 @@ fn should_have_an_error_result_when_method_does_not_exist() {
 }
 
 #[test]
-fn should_return_expected_float_when_given_valid_http_version_string() {
+fn should_return_expected_version_when_given_valid_http_version_string() {
     let full_version = "HTTP/1.1";
-    let expected_version = 1.1;
-    let actual_version = get_http_version(full_version);
+    let expected_version = HttpVersion::Http1_1;
+    let actual_version = HttpVersion::from(full_version);
     assert_eq!(actual_version.unwrap(), expected_version);
 }
 
@@ -54,42 +65,60 @@ fn should_return_expected_float_when_given_valid_http_version_string() {
 #[should_panic]
 fn should_have_an_error_result_when_version_is_not_valid() {
     let bad_version = "HTTP/G";
-    get_http_version(bad_version).unwrap();
+    HttpVersion::from(bad_version).unwrap();
+}
+
+#[test]
+fn should_round_trip_every_http_version_through_its_string() {
+    for version in [
+        HttpVersion::Http0_9,
+        HttpVersion::Http1_0,
+        HttpVersion::Http1_1,
+        HttpVersion::Http2,
+    ] {
+        assert_eq!(HttpVersion::from(&version.to_string()), Ok(version));
+    }
+}
+
+#[test]
+fn should_keep_version_of_request_when_it_is_not_http_1_1() {
+    let http_request = HttpRequest::from("GET / HTTP/1.0\r\n\r\n");
+    assert_eq!(http_request.http_version, HttpVersion::Http1_0);
+    assert_eq!(http_request.to_raw(), "GET / HTTP/1.0\r\n\r\n");
 }
 
 #[test]
 #[should_panic]
 fn should_have_an_error_result_when_version_has_invalid_delimiter() {
     let bad_version = "HTTP-1.1";
-    get_http_version(bad_version).unwrap();
+    HttpVersion::from(bad_version).unwrap();
 }
 
 #[test]
 fn should_create_a_simple_map_of_headers_when_string_matches_criteria() {
-    let request = "STATUS_LINE\r\nheader1: foo\r\nheader2: bar\r\n\r\nbody";
-    let request_lines = request.split("\r\n").collect::<Vec<&str>>();
-    let mut expected_headers = HashMap::new();
-    expected_headers.insert("header1".into(), "foo".into());
-    expected_headers.insert("header2".into(), "bar".into());
-    let actual_headers = get_headers_from_lines(&request_lines).unwrap();
+    let request = b"STATUS_LINE\r\nheader1: foo\r\nheader2: bar\r\n\r\nbody";
+    let (head, _) = split_head_and_body(request);
+    let expected_headers = vec![("header1", "foo"), ("header2", "bar")];
+    let head = std::str::from_utf8(head).unwrap();
+    let actual_headers = parse_headers(head.split("\r\n").skip(1)).unwrap();
     assert_eq!(actual_headers, expected_headers);
 }
 
 #[test]
 fn should_return_none_when_headers_are_not_present_on_request() {
-    let request = "STATUSLINE\r\n\r\n\r\n";
-    let request_lines = request.split("\r\n").collect::<Vec<&str>>();
-    let actual_headers = get_headers_from_lines(&request_lines);
-    assert!(actual_headers.is_none());
+    let request = b"STATUSLINE\r\n\r\n\r\n";
+    let (head, _) = split_head_and_body(request);
+    let head = std::str::from_utf8(head).unwrap();
+    let actual_headers = parse_headers(head.split("\r\n").skip(1)).unwrap();
+    assert!(actual_headers.is_empty());
 }
 
 #[test]
-fn should_return_expected_line_when_getting_body_begin_of_full_request() {
-    let request = "GET / HTTP/1.1\r\nContent-Type: plain-text\r\n\r\nbody";
-    let request_lines = request.split("\r\n").collect::<Vec<&str>>();
-    let expected_index = 3;
-    let actual_index = get_body_begin_index(&request_lines).unwrap();
-    assert_eq!(actual_index, expected_index);
+fn should_return_expected_body_when_splitting_full_request() {
+    let request = b"GET / HTTP/1.1\r\nContent-Type: plain-text\r\n\r\nbody";
+    let expected_body = b"body";
+    let (_, actual_body) = split_head_and_body(request);
+    assert_eq!(actual_body, expected_body);
 }
 
 #[test]
@@ -97,14 +126,17 @@ fn should_pull_single_query_param_off_request_when_param_is_on_request() {
     let request = HttpRequest {
         http_method: HttpMethod::Get,
         uri: "/hello?greet=world".into(),
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         headers: None,
         body: None,
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection: None,
     };
-    let mut expected_query_params = HashMap::new();
-    expected_query_params.insert("greet".into(), "world".into());
-    let actual_query_params = request.params().unwrap();
-    assert_eq!(actual_query_params, expected_query_params);
+    let actual_query_params = request.params();
+    assert_eq!(actual_query_params.get("greet"), Some("world"));
+    assert_eq!(actual_query_params.len(), 1);
 }
 
 #[test]
@@ -112,26 +144,1059 @@ fn should_pull_query_params_off_request_when_params_are_on_request() {
     let request = HttpRequest {
         http_method: HttpMethod::Get,
         uri: "/hello?greet=world&foo=bar".into(),
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         headers: None,
         body: None,
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection: None,
     };
-    let mut expected_query_params = HashMap::new();
-    expected_query_params.insert("greet".into(), "world".into());
-    expected_query_params.insert("foo".into(), "bar".into());
-    let actual_query_params = request.params().unwrap();
-    assert_eq!(actual_query_params, expected_query_params);
+    let params = request.params();
+    let actual_query_params = params.iter().collect::<Vec<_>>();
+    assert_eq!(
+        actual_query_params,
+        vec![("greet", "world"), ("foo", "bar")]
+    );
 }
 
 #[test]
-fn should_return_none_when_no_params_are_on_request() {
+fn should_return_empty_params_when_no_params_are_on_request() {
     let request = HttpRequest {
         http_method: HttpMethod::Get,
         uri: "/hello".into(),
-        http_version: 1.1,
+        http_version: HttpVersion::Http1_1,
         headers: None,
         body: None,
+        path_params: HashMap::new(),
+        state: State::default(),
+        extensions: State::default(),
+        connection: None,
     };
     let actual_query_params = request.params();
-    assert!(actual_query_params.is_none());
+    assert!(actual_query_params.is_empty());
+}
+
+#[test]
+fn should_round_trip_request_with_headers_and_body_through_raw() {
+    let raw_request = "POST /hello HTTP/1.1\r\nContent-Type: plain/text\r\n\r\nbody";
+    let http_request = HttpRequest::from(raw_request);
+    let actual_round_trip = HttpRequest::from(&http_request.to_raw());
+    assert_eq!(actual_round_trip.body, http_request.body);
+    assert_eq!(
+        actual_round_trip.headers.unwrap()["Content-Length"],
+        "4".to_string()
+    );
+}
+
+#[test]
+fn should_replace_transfer_encoding_with_content_length_when_chunked_request_is_serialized() {
+    let raw_request = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n0\r\n\r\n";
+    assert_eq!(
+        HttpRequest::from(raw_request).to_string(),
+        "POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nWiki"
+    );
+}
+
+#[test]
+fn should_serialize_request_without_body_or_content_length() {
+    let http_request = HttpRequest::from("GET /hello?name=martian HTTP/1.0\r\n\r\n");
+    assert_eq!(
+        http_request.to_bytes(),
+        b"GET /hello?name=martian HTTP/1.0\r\n\r\n".to_vec()
+    );
+}
+
+#[test]
+fn should_parse_response_with_unknown_status_code_and_headers() {
+    let raw_response = "HTTP/1.1 299 Custom\r\nX-Custom: foo\r\nContent-Length: 3\r\n\r\nbar";
+    let actual_response = HttpResponse::parse(raw_response).unwrap();
+    assert_eq!(actual_response.status_code, StatusCode::Other(299));
+    assert_eq!(actual_response.headers["X-Custom"], "foo".to_string());
+    assert_eq!(actual_response.body, Body::Bytes(b"bar".to_vec()));
+}
+
+#[test]
+fn should_have_an_error_result_when_response_status_line_is_malformed() {
+    let actual_error = HttpResponse::parse("HTTP/1.1 abc\r\n\r\n").unwrap_err();
+    assert_eq!(
+        actual_error,
+        ParseError::InvalidStatusLine("HTTP/1.1 abc".into())
+    );
+}
+
+#[test]
+fn should_have_an_error_result_when_request_line_is_malformed() {
+    let cases = [
+        ("GET\r\n\r\n", ParseError::InvalidRequestLine("GET".into())),
+        ("\r\n\r\n", ParseError::InvalidRequestLine("".into())),
+        (
+            "GET  HTTP/1.1\r\n\r\n",
+            ParseError::InvalidRequestLine("GET  HTTP/1.1".into()),
+        ),
+        (
+            "FETCH / HTTP/1.1\r\n\r\n",
+            ParseError::UnknownMethod("FETCH".into()),
+        ),
+        (
+            "GET / HTTP-1.1\r\n\r\n",
+            ParseError::InvalidVersion("HTTP-1.1".into()),
+        ),
+    ];
+    for (raw_request, expected_error) in cases {
+        assert_eq!(HttpRequest::parse(raw_request), Err(expected_error));
+    }
+}
+
+#[test]
+fn should_borrow_from_raw_request_when_parsing_request_ref() {
+    let raw_request = "GET /hello?greet=world HTTP/1.1\r\nHost: localhost\r\n\r\nbody";
+    let request_ref = HttpRequestRef::parse(raw_request).unwrap();
+    assert_eq!(request_ref.uri, "/hello?greet=world");
+    assert_eq!(request_ref.headers, vec![("Host", "localhost")]);
+    assert!(matches!(request_ref.body, Some(Cow::Borrowed(b"body"))));
+}
+
+#[test]
+fn should_read_body_of_request_ref_without_copying_out() {
+    let raw_request = "POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+    let request_ref = HttpRequestRef::parse(raw_request).unwrap();
+    assert_eq!(request_ref.body_bytes(), b"hello");
+    assert_eq!(request_ref.body_text(), Ok("hello"));
+    let request_ref = HttpRequestRef::parse("GET / HTTP/1.1\r\n\r\n").unwrap();
+    assert_eq!(request_ref.body_bytes(), b"");
+}
+
+#[test]
+fn should_equal_owned_parse_when_request_ref_is_copied_out() {
+    let raw_request = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nfoo\r\n0\r\n\r\n";
+    let request_ref = HttpRequestRef::parse(raw_request).unwrap();
+    assert_eq!(request_ref.to_owned(), HttpRequest::from(raw_request));
+}
+
+#[test]
+fn should_decode_and_keep_repeated_query_params() {
+    let request =
+        HttpRequest::from("GET /?q=caf%C3%A9+au+lait&tag=a&tag=b&debug&bad=%zz HTTP/1.1\r\n\r\n");
+    let actual_query_params = request.params();
+    assert_eq!(actual_query_params.get("q"), Some("café au lait"));
+    assert_eq!(actual_query_params.get_all("tag"), vec!["a", "b"]);
+    assert_eq!(actual_query_params.get("debug"), Some(""));
+    assert_eq!(actual_query_params.get("bad"), Some("%zz"));
+    assert_eq!(request.param::<String>("q"), Ok("café au lait".into()));
+}
+
+#[test]
+fn should_treat_param_without_value_as_empty_when_iterating_query_pairs() {
+    let actual_pairs = query_pairs("/hello?debug&greet=world&").collect::<Vec<_>>();
+    assert_eq!(actual_pairs, vec![("debug", ""), ("greet", "world")]);
+}
+
+#[test]
+fn should_prefer_path_param_over_query_param_when_both_share_a_name() {
+    let mut request = HttpRequest::from("GET /users/7?id=8 HTTP/1.1\r\n\r\n");
+    request.path_params.insert("id".into(), "7".into());
+    assert_eq!(request.param::<u32>("id"), Ok(7));
+}
+
+#[test]
+fn should_carry_offending_value_when_param_fails_to_parse() {
+    let request = HttpRequest::from("GET /users?id=abc HTTP/1.1\r\n\r\n");
+    let actual_error = request.param::<u32>("id").unwrap_err();
+    assert_eq!(
+        actual_error,
+        ParamError::Invalid {
+            name: "id".into(),
+            value: "abc".into(),
+            reason: "invalid digit found in string".into(),
+        }
+    );
+}
+
+#[test]
+fn should_convert_param_error_into_descriptive_bad_request() {
+    let response: HttpResponse = ParamError::Missing("id".into()).into();
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+    assert_eq!(response.body, Body::Bytes(b"Missing param \"id\"".to_vec()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn should_deserialize_query_params_into_struct_when_types_match() {
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    struct Search {
+        term: String,
+        limit: u8,
+    }
+    let request = HttpRequest::from("GET /search?term=mars&limit=5 HTTP/1.1\r\n\r\n");
+    let expected_search = Search {
+        term: "mars".into(),
+        limit: 5,
+    };
+    assert_eq!(request.params_as::<Search>().unwrap(), expected_search);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_deserialize_json_body_when_it_matches_struct() {
+    #[derive(serde::Deserialize, PartialEq, Debug)]
+    struct Rover {
+        name: String,
+        landed: Option<u16>,
+    }
+    let raw_request = "POST /rovers HTTP/1.1\r\nContent-Length: 20\r\n\r\n{\"name\":\"Sojourner\"}";
+    let expected_rover = Rover {
+        name: "Sojourner".into(),
+        landed: None,
+    };
+    assert_eq!(
+        HttpRequest::from(raw_request).json::<Rover>().unwrap(),
+        expected_rover
+    );
+    let wrong_type = HttpRequest::from("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n{\"name\":1}");
+    assert!(wrong_type.json::<Rover>().unwrap_err().is_data());
+    let empty = HttpRequest::from("POST / HTTP/1.1\r\n\r\n");
+    assert!(empty.json::<Rover>().unwrap_err().is_eof());
+}
+
+fn serialize(response: HttpResponse) -> String {
+    String::from_utf8(response.to_bytes().unwrap()).unwrap()
+}
+
+#[test]
+fn should_serialize_location_header_when_redirecting() {
+    assert_eq!(
+        serialize(HttpResponse::redirect("/login")),
+        "HTTP/1.1 302 Found\r\nLocation: /login\r\nContent-Length: 0\r\n\r\n"
+    );
+    assert_eq!(
+        serialize(HttpResponse::redirect_permanent("/new")),
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n"
+    );
+    assert_eq!(
+        serialize(HttpResponse::see_other("/done")),
+        "HTTP/1.1 303 See Other\r\nLocation: /done\r\nContent-Length: 0\r\n\r\n"
+    );
+    assert_eq!(
+        serialize(HttpResponse::temporary_redirect("/retry")),
+        "HTTP/1.1 307 Temporary Redirect\r\nLocation: /retry\r\nContent-Length: 0\r\n\r\n"
+    );
+    assert_eq!(
+        serialize(HttpResponse::permanent_redirect("/v2")),
+        "HTTP/1.1 308 Permanent Redirect\r\nLocation: /v2\r\nContent-Length: 0\r\n\r\n"
+    );
+}
+
+#[test]
+fn should_never_serialize_a_body_when_no_content() {
+    let mut response = HttpResponse::no_content();
+    response.body = Body::Bytes(b"ignored".to_vec());
+    assert_eq!(serialize(response), "HTTP/1.1 204 No Content\r\n\r\n");
+}
+
+#[test]
+fn should_serialize_content_type_when_responding_with_text_or_html() {
+    assert_eq!(
+        serialize(HttpResponse::text("hi")),
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 2\r\n\r\nhi"
+    );
+    assert_eq!(
+        serialize(HttpResponse::html("<p>")),
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: 3\r\n\r\n<p>"
+    );
+    assert_eq!(
+        serialize(HttpResponse::bad_request("no")),
+        "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 2\r\n\r\nno"
+    );
+}
+
+#[test]
+fn should_serialize_without_headers_when_not_found() {
+    assert_eq!(
+        serialize(HttpResponse::not_found()),
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+    );
+}
+
+#[test]
+fn should_serialize_raw_bytes_when_ok_with_body() {
+    assert_eq!(
+        HttpResponse::ok_with_body(vec![0, 159, 146, 150])
+            .to_bytes()
+            .unwrap(),
+        b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n\x00\x9f\x92\x96".to_vec()
+    );
+}
+
+#[test]
+fn should_have_an_error_result_when_streamed_body_fails_to_read() {
+    struct Broken;
+    impl std::io::Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("broken"))
+        }
+    }
+    let mut response = HttpResponse::ok();
+    response.body = Body::Stream(Box::new(Broken));
+    assert!(response.to_bytes().is_err());
+}
+
+#[test]
+fn should_convert_strings_and_bytes_into_the_same_body() {
+    assert_eq!(Body::from("hi"), Body::from(String::from("hi")));
+    assert_eq!(Body::from("hi"), Body::from(b"hi".to_vec()));
+}
+
+#[test]
+fn should_find_enum_from_string_for_every_standard_http_method() {
+    let expected_http_methods = vec![
+        HttpMethod::Get,
+        HttpMethod::Head,
+        HttpMethod::Post,
+        HttpMethod::Put,
+        HttpMethod::Patch,
+        HttpMethod::Delete,
+        HttpMethod::Options,
+        HttpMethod::Trace,
+        HttpMethod::Connect,
+    ];
+    for expected_http_method in expected_http_methods {
+        let actual_http_method = HttpMethod::from(expected_http_method.as_str()).unwrap();
+        assert_eq!(actual_http_method, expected_http_method);
+    }
+}
+
+#[test]
+fn should_keep_content_length_but_drop_body_when_writing_head() {
+    let mut raw_response = Vec::new();
+    HttpResponse::text("hello")
+        .write_head_to(&mut raw_response)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(raw_response).unwrap(),
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\n\r\n"
+    );
+}
+
+#[test]
+fn should_round_trip_every_registered_status_code_through_u16() {
+    for code in 100..600 {
+        let status_code = StatusCode::from_u16(code);
+        assert_eq!(status_code.code(), code);
+        match status_code {
+            StatusCode::Other(_) => assert_eq!(status_code.reason_phrase(), ""),
+            _ => assert!(!status_code.reason_phrase().is_empty()),
+        }
+    }
+}
+
+#[test]
+fn should_classify_status_codes_by_their_hundreds() {
+    assert!(StatusCode::SwitchingProtocols.is_informational());
+    assert!(StatusCode::Created.is_success());
+    assert!(StatusCode::TemporaryRedirect.is_redirection());
+    assert!(StatusCode::Other(499).is_client_error());
+    assert!(StatusCode::ServiceUnavailable.is_server_error());
+    assert!(!StatusCode::NotModified.allows_body());
+}
+
+#[test]
+fn should_build_response_with_defaults_when_nothing_is_set() {
+    assert_eq!(HttpResponse::builder().build(), HttpResponse::ok());
+}
+
+#[test]
+fn should_keep_last_value_when_header_is_set_twice_on_builder() {
+    let response = HttpResponse::builder()
+        .version(HttpVersion::Http1_0)
+        .header("Cache-Control", "no-cache")
+        .header("Cache-Control", "no-store")
+        .body(vec![1, 2]);
+    assert_eq!(
+        serialize(response),
+        "HTTP/1.0 200 OK\r\nCache-Control: no-store\r\nContent-Length: 2\r\n\r\n\u{1}\u{2}"
+    );
+}
+
+#[test]
+fn should_round_trip_through_percent_and_form_encoding() {
+    use crate::web::encoding::{form_decode, form_encode, percent_decode, percent_encode};
+    let input = "naïve ½+½=1 /?&#";
+    assert_eq!(percent_decode(&percent_encode(input)), input);
+    assert_eq!(form_decode(&form_encode(input)), input);
+    assert_eq!(form_encode("a b+c"), "a+b%2Bc");
+}
+
+#[test]
+fn should_leave_incomplete_escapes_as_is_when_percent_decoding() {
+    use crate::web::encoding::percent_decode;
+    assert_eq!(percent_decode("%4"), "%4");
+    assert_eq!(percent_decode("%%41"), "%A");
+    assert_eq!(percent_decode("%+1"), "%+1");
+}
+
+#[test]
+fn should_split_uri_into_path_query_and_fragment() {
+    let cases = [
+        ("/", "/", None, None),
+        ("/a?", "/a", Some(""), None),
+        ("/a#top", "/a", None, Some("top")),
+        ("/a?b=1?c#d#e", "/a", Some("b=1?c"), Some("d#e")),
+        ("*", "*", None, None),
+    ];
+    for (raw, path, query, fragment) in cases {
+        let uri = Uri::from(raw);
+        assert_eq!(
+            (uri.path(), uri.query(), uri.fragment()),
+            (path, query, fragment)
+        );
+        assert_eq!(uri, raw);
+    }
+}
+
+#[test]
+fn should_ignore_fragment_when_reading_query_params() {
+    let request = HttpRequest::from("GET /?page=2#results HTTP/1.1\r\n\r\n");
+    assert_eq!(request.params().get("page"), Some("2"));
+    assert_eq!(request.uri.query(), Some("page=2"));
+}
+
+#[test]
+fn should_fail_writing_stream_when_it_ends_short_of_its_content_length() {
+    let mut response = HttpResponse::ok();
+    response.body = Body::Stream(Box::new(&b"short"[..]));
+    response.set_header("Content-Length", "10");
+    let mut raw_response = Vec::new();
+    let written = response.write_to(&mut raw_response);
+    assert_eq!(written.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert!(raw_response.ends_with(b"Content-Length: 10\r\n\r\nshort"));
+}
+
+#[test]
+fn should_parse_query_once_when_params_are_read_more_than_once() {
+    let mut request = HttpRequest::from("GET /?page=2 HTTP/1.1\r\n\r\n");
+    assert!(std::ptr::eq(request.params(), request.params()));
+    assert_eq!(request.param::<u32>("page"), Ok(2));
+    request.uri = "/?page=3".into();
+    assert_eq!(request.params().get("page"), Some("3"));
+}
+
+#[test]
+fn should_skip_malformed_pairs_when_parsing_cookie_header() {
+    let cookies = Cookie::parse_header("a=1;; flag; =2; b = two words ;c=");
+    assert_eq!(
+        cookies,
+        vec![
+            Cookie {
+                name: "a",
+                value: "1"
+            },
+            Cookie {
+                name: "b",
+                value: "two words"
+            },
+            Cookie {
+                name: "c",
+                value: ""
+            },
+        ]
+    );
+}
+
+#[test]
+fn should_have_no_cookies_when_request_has_no_cookie_header() {
+    let http_request = HttpRequest::from("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    assert!(http_request.cookies().is_empty());
+    assert_eq!(http_request.cookie("session"), None);
+}
+
+#[test]
+fn should_find_cookie_header_regardless_of_case() {
+    let http_request = HttpRequest::from("GET / HTTP/1.1\r\ncookie: id=7\r\n\r\n");
+    assert_eq!(http_request.cookie("id"), Some("7"));
+}
+
+#[test]
+fn should_write_each_cookie_as_its_own_set_cookie_header() {
+    let response = HttpResponse::builder()
+        .cookie(SetCookie::new("a", "1").domain("example.com"))
+        .cookie(SetCookie::removal("b"))
+        .build();
+    assert_eq!(
+        serialize(response),
+        "HTTP/1.1 200 OK\r\n\
+         Set-Cookie: a=1; Domain=example.com\r\n\
+         Set-Cookie: b=; Max-Age=0\r\n\
+         Content-Length: 0\r\n\r\n"
+    );
+}
+
+#[test]
+fn should_round_trip_cookies_through_response_parsing() {
+    let cookie = SetCookie::new("session", "abc")
+        .max_age(Duration::from_secs(60))
+        .path("/app")
+        .domain("example.com")
+        .secure()
+        .http_only()
+        .same_site(SameSite::Strict);
+    let mut response = HttpResponse::ok();
+    response.set_cookie(cookie.clone());
+    response.set_cookie(SetCookie::new("theme", "dark"));
+    let parsed = HttpResponse::from(&serialize(response));
+    assert_eq!(
+        parsed.cookies,
+        vec![cookie, SetCookie::new("theme", "dark")]
+    );
+    assert!(!parsed.headers.contains_key("Set-Cookie"));
+}
+
+#[test]
+fn should_ignore_unknown_attributes_when_parsing_set_cookie() {
+    let cookie =
+        SetCookie::parse("id=7; Expires=Wed, 21 Oct 2026 07:28:00 GMT; secure; SAMESITE=lax")
+            .unwrap();
+    assert_eq!(
+        cookie,
+        SetCookie::new("id", "7").secure().same_site(SameSite::Lax)
+    );
+    assert_eq!(SetCookie::parse("no value"), None);
+    assert_eq!(SetCookie::parse("=7"), None);
+}
+
+#[test]
+fn should_read_form_when_content_type_has_charset() {
+    let raw_request = "POST / HTTP/1.1\r\n\
+        Content-Type: Application/X-WWW-Form-Urlencoded; charset=UTF-8\r\n\
+        Content-Length: 20\r\n\r\n\
+        tag=a&tag=b+c&empty=";
+    let form = HttpRequest::from(raw_request).form().unwrap();
+    assert_eq!(form.get_all("tag"), vec!["a", "b c"]);
+    assert_eq!(form.get("empty"), Some(""));
+    assert_eq!(form.len(), 3);
+}
+
+#[test]
+fn should_have_no_form_when_content_type_is_not_form() {
+    let json_request = HttpRequest::from(
+        "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 7\r\n\r\na=b&c=d",
+    );
+    assert_eq!(json_request.form(), None);
+    let untyped_request = HttpRequest::from("POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\na=b");
+    assert_eq!(untyped_request.form(), None);
+}
+
+#[test]
+fn should_have_empty_form_when_form_request_has_no_body() {
+    let raw_request = "POST / HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\r\n";
+    let form = HttpRequest::from(raw_request).form().unwrap();
+    assert!(form.is_empty());
+}
+
+#[test]
+fn should_round_trip_binary_body_through_bytes() {
+    let mut raw_request = b"PUT /image HTTP/1.1\r\nContent-Length: 5\r\n\r\n".to_vec();
+    raw_request.extend([0x89, b'P', 0x00, 0xff, b'\n']);
+    let http_request = HttpRequest::parse_bytes(&raw_request).unwrap();
+    assert_eq!(http_request.body_bytes(), [0x89, b'P', 0x00, 0xff, b'\n']);
+    assert!(http_request.body_text().is_err());
+    assert_eq!(http_request.to_bytes(), raw_request);
+}
+
+#[test]
+fn should_read_only_content_length_bytes_of_body() {
+    let raw_request = "POST / HTTP/1.1\r\nContent-Length: 6\r\n\r\n\r\nbody and more";
+    let http_request = HttpRequest::parse(raw_request).unwrap();
+    assert_eq!(http_request.body_text(), Ok("\r\nbody"));
+}
+
+#[test]
+fn should_have_an_error_result_when_body_is_shorter_than_content_length() {
+    let raw_request = "POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort";
+    assert_eq!(
+        HttpRequest::parse(raw_request),
+        Err(ParseError::IncompleteBody)
+    );
+    let bad_length = "POST / HTTP/1.1\r\nContent-Length: ten\r\n\r\nshort";
+    assert_eq!(
+        HttpRequest::parse(bad_length),
+        Err(ParseError::InvalidHeader("Content-Length: ten".into()))
+    );
+}
+
+#[test]
+fn should_have_an_error_result_when_head_is_not_utf8() {
+    assert_eq!(
+        HttpRequest::parse_bytes(b"GET /\xff HTTP/1.1\r\n\r\n"),
+        Err(ParseError::InvalidRequestLine(
+            "GET /\u{fffd} HTTP/1.1".into()
+        ))
+    );
+    assert_eq!(
+        HttpRequest::parse_bytes(b"GET / HTTP/1.1\r\nHost: ok\r\nX-Name: \xff\r\n\r\n"),
+        Err(ParseError::InvalidHeader("X-Name: \u{fffd}".into()))
+    );
+}
+
+#[test]
+fn should_keep_leading_blank_lines_of_body_when_parsing_response() {
+    let raw_response = "HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n\r\n\r\nbody\r\nextra";
+    let http_response = HttpResponse::parse(raw_response).unwrap();
+    assert_eq!(http_response.body, Body::from("\r\n\r\nbody\r"));
+}
+
+#[test]
+fn should_keep_body_as_is_when_parsing_response_bytes() {
+    let raw_response = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n\x89PNG";
+    let http_response = HttpResponse::parse_bytes(raw_response).unwrap();
+    assert_eq!(http_response.body, Body::Bytes(b"\x89PNG".to_vec()));
+    assert_eq!(
+        HttpResponse::parse_bytes(b"HTTP/1.1 \xff OK\r\n\r\n"),
+        Err(ParseError::InvalidStatusLine("HTTP/1.1 \u{fffd} OK".into()))
+    );
+}
+
+#[test]
+fn should_read_no_body_when_response_can_not_have_one() {
+    for status_line in ["HTTP/1.1 204 No Content", "HTTP/1.1 304 Not Modified"] {
+        let raw_response = format!("{}\r\nContent-Length: 10\r\n\r\n", status_line);
+        let http_response = HttpResponse::parse(&raw_response).unwrap();
+        assert_eq!(http_response.body, Body::Empty);
+        assert_eq!(http_response.headers["Content-Length"], "10");
+    }
+    let raw_response = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n";
+    let http_response = HttpResponse::parse_bytes_to(raw_response, &HttpMethod::Head).unwrap();
+    assert_eq!(http_response.body, Body::Empty);
+}
+
+#[test]
+fn should_have_an_error_result_when_response_body_is_shorter_than_content_length() {
+    let raw_response = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort";
+    assert!(matches!(
+        HttpResponse::parse(raw_response),
+        Err(ParseError::IncompleteBody)
+    ));
+}
+
+#[test]
+fn should_only_chunk_streamed_body_from_http_1_1() {
+    let mut response = HttpResponse::ok_with_body(Body::Stream(Box::new(&b"hi"[..])));
+    response.http_version = HttpVersion::Http1_0;
+    assert_eq!(
+        serialize(response),
+        "HTTP/1.0 200 OK\r\nConnection: close\r\n\r\nhi"
+    );
+    let mut response = HttpResponse::ok_with_body("hi");
+    response.http_version = HttpVersion::Http1_0;
+    assert_eq!(
+        serialize(response),
+        "HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nhi"
+    );
+}
+
+#[test]
+fn should_round_trip_every_mime_with_an_extension_through_it() {
+    for mime in [
+        Mime::TextCss,
+        Mime::TextJavascript,
+        Mime::ApplicationWasm,
+        Mime::ImageJpeg,
+        Mime::ImageSvg,
+        Mime::FontWoff2,
+    ] {
+        let extension = mime.extension().unwrap();
+        assert_eq!(Mime::from_extension(extension), Some(mime));
+        assert_eq!(Mime::from(mime.as_str()), Ok(mime));
+    }
+    assert_eq!(Mime::MultipartFormData.extension(), None);
+}
+
+#[test]
+fn should_fall_back_to_octet_stream_when_extension_is_unknown() {
+    assert_eq!(Mime::from_path("archive.tar.GZ"), Mime::ApplicationGzip);
+    assert_eq!(
+        Mime::from_path("notes.unknown"),
+        Mime::ApplicationOctetStream
+    );
+    assert_eq!(Mime::from_path("Makefile"), Mime::ApplicationOctetStream);
+    assert_eq!(Mime::from("text/x-unknown; q=1"), Err("text/x-unknown"));
+}
+
+#[test]
+fn should_only_write_charset_for_text_mime() {
+    let response = HttpResponse::builder()
+        .content_type(Mime::TextCsv)
+        .body("a,b");
+    assert_eq!(response.headers["Content-Type"], "text/csv; charset=utf-8");
+    let response = HttpResponse::ok().content_type(Mime::ApplicationJson);
+    assert_eq!(response.headers["Content-Type"], "application/json");
+}
+
+#[test]
+fn should_turn_handler_return_values_into_responses() {
+    let text = String::from("hi").into_response();
+    assert_eq!(text.status_code, StatusCode::Ok);
+    assert_eq!(
+        text.headers.get("Content-Type").map(String::as_str),
+        Some("text/plain; charset=utf-8")
+    );
+    assert_eq!(text.body, Body::from("hi"));
+    assert_eq!(().into_response().status_code, StatusCode::Ok);
+    assert_eq!(
+        StatusCode::NoContent.into_response().status_code,
+        StatusCode::NoContent
+    );
+    let accepted = (StatusCode::Accepted, vec![1, 2]).into_response();
+    assert_eq!(accepted.status_code, StatusCode::Accepted);
+    assert_eq!(accepted.body, Body::Bytes(vec![1, 2]));
+    let error: Result<&str, ParamError> = Err(ParamError::Missing("id".into()));
+    assert_eq!(error.into_response().status_code, StatusCode::BadRequest);
+    assert_eq!(Some("found").into_response().body, Body::from("found"));
+}
+
+#[test]
+fn should_answer_martian_error_with_message_only_when_client_error() {
+    let param_error: MartianError = ParamError::Missing("id".into()).into();
+    assert_eq!(param_error.status_code, StatusCode::BadRequest);
+    assert_eq!(param_error.message, "Missing param \"id\"");
+    let response: HttpResponse = param_error.into();
+    assert_eq!(response.status_code, StatusCode::BadRequest);
+    assert_eq!(response.body, Body::from("Missing param \"id\""));
+    let io_error: MartianError = io::Error::other("disk on fire").into();
+    assert_eq!(io_error.status_code, StatusCode::InternalServerError);
+    assert_eq!(
+        io_error.to_string(),
+        "500 Internal Server Error: disk on fire"
+    );
+    assert!(io_error.source().is_some());
+    let response: HttpResponse = io_error.into();
+    assert_eq!(response.status_code, StatusCode::InternalServerError);
+    assert_eq!(response.body, Body::Empty);
+}
+
+fn push_one_byte_at_a_time(parser: &mut RequestParser, raw_request: &[u8]) -> HttpRequest {
+    let (last, rest) = raw_request.split_last().unwrap();
+    for byte in rest {
+        assert_eq!(parser.push(&[*byte]).unwrap(), ParseStatus::NeedMoreData);
+    }
+    match parser.push(&[*last]).unwrap() {
+        ParseStatus::Complete(request) => *request,
+        ParseStatus::NeedMoreData => panic!("Request should be complete"),
+    }
+}
+
+#[test]
+fn should_parse_request_when_pushed_one_byte_at_a_time() {
+    let mut parser = RequestParser::new();
+    let raw_request = b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+    let request = push_one_byte_at_a_time(&mut parser, raw_request);
+    assert_eq!(request, HttpRequest::parse_bytes(raw_request).unwrap());
+    assert!(parser.buffered().is_empty());
+}
+
+#[test]
+fn should_parse_chunked_request_when_split_across_pushes() {
+    let mut parser = RequestParser::new();
+    let raw_request =
+        b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Sum: 9\r\n\r\n";
+    let request = push_one_byte_at_a_time(&mut parser, raw_request);
+    assert_eq!(request.body_text(), Ok("Wikipedia"));
+}
+
+#[test]
+fn should_complete_request_without_body_when_head_ends() {
+    let mut parser = RequestParser::new();
+    match parser
+        .push(b"GET /hello HTTP/1.1\r\nHost: mars\r\n\r\n")
+        .unwrap()
+    {
+        ParseStatus::Complete(request) => {
+            assert_eq!(request.uri, "/hello");
+            assert_eq!(request.body, None);
+        }
+        ParseStatus::NeedMoreData => panic!("Request should be complete"),
+    }
+}
+
+#[test]
+fn should_keep_pipelined_request_when_pushed_with_previous() {
+    let mut parser = RequestParser::new();
+    let status = parser
+        .push(b"GET /first HTTP/1.1\r\n\r\nGET /second HTTP/1.1\r\n\r\nGET /th")
+        .unwrap();
+    assert!(matches!(status, ParseStatus::Complete(request) if request.uri == "/first"));
+    let status = parser.push(&[]).unwrap();
+    assert!(matches!(status, ParseStatus::Complete(request) if request.uri == "/second"));
+    assert_eq!(parser.push(&[]).unwrap(), ParseStatus::NeedMoreData);
+    assert_eq!(parser.buffered(), b"GET /th");
+}
+
+#[test]
+fn should_report_malformed_head_when_body_has_not_arrived() {
+    let mut parser = RequestParser::new();
+    assert_eq!(
+        parser.push(b"FETCH / HTTP/1.1\r\nContent-Length: 100\r\n\r\n"),
+        Err(ParseError::UnknownMethod("FETCH".into()))
+    );
+    let mut parser = RequestParser::new();
+    assert_eq!(
+        parser.push(b"POST / HTTP/1.1\r\nContent-Length: lots\r\n\r\n"),
+        Err(ParseError::InvalidHeader("Content-Length: lots".into()))
+    );
+    let mut parser = RequestParser::new();
+    assert_eq!(
+        parser.push(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"),
+        Err(ParseError::InvalidChunkSize("zz".into()))
+    );
+}
+
+#[test]
+fn should_write_every_field_of_event_with_a_data_line_per_line() {
+    let event = Event::data("a\r\nb")
+        .event("update\nignored")
+        .id("42")
+        .retry(Duration::from_secs(3));
+    assert_eq!(
+        event.to_string(),
+        "event: update\nid: 42\nretry: 3000\ndata: a\ndata: b\n\n"
+    );
+}
+
+#[test]
+fn should_stream_events_until_every_sender_is_dropped() {
+    let (sender, stream) = SseStream::channel();
+    let other_sender = sender.clone();
+    sender.send(Event::data("one")).unwrap();
+    other_sender.comment("hello").unwrap();
+    drop((sender, other_sender));
+    let response = stream.into_response();
+    assert_eq!(
+        response.headers["Content-Type"],
+        "text/event-stream; charset=utf-8"
+    );
+    assert_eq!(response.headers["Cache-Control"], "no-cache");
+    let mut raw = String::new();
+    match response.body {
+        Body::Stream(mut reader) => reader.read_to_string(&mut raw).unwrap(),
+        body => panic!("expected a stream, got {:?}", body),
+    };
+    assert_eq!(raw, "data: one\n\n: hello\n\n");
+}
+
+#[test]
+fn should_write_keep_alive_comment_when_no_event_is_pushed_in_time() {
+    let (sender, stream) = SseStream::channel();
+    let mut stream = stream.keep_alive(Duration::from_millis(10));
+    let mut buffer = [0; 64];
+    let read = stream.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[..read], b": keep-alive\n\n");
+    drop(stream);
+    assert_eq!(sender.send(Event::data("late")), Err(Disconnected));
+}
+
+#[test]
+fn should_parse_back_formatted_http_date_when_round_tripped() {
+    use crate::web::date::{format_http_date, parse_http_date};
+    use std::time::UNIX_EPOCH;
+    for secs in [0, 951_782_400, 1_709_164_800, 4_107_542_399] {
+        let time = UNIX_EPOCH + Duration::from_secs(secs);
+        let date = format_http_date(time);
+        assert_eq!(parse_http_date(&date), Some(time), "{}", date);
+    }
+    assert_eq!(
+        format_http_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+        "Tue, 29 Feb 2000 00:00:00 GMT"
+    );
+}
+
+#[test]
+fn should_not_parse_http_date_when_malformed() {
+    use crate::web::date::parse_http_date;
+    for date in [
+        "",
+        "Sun, 06 Nov 1994 08:49:37 UTC",
+        "Sun, 06 Foo 1994 08:49:37 GMT",
+        "Sun, 32 Nov 1994 08:49:37 GMT",
+        "Sun, 06 Nov 1994 24:00:00 GMT",
+        "Sun, 06 Nov 1994 08:49 GMT",
+        "Xyz, 06 Nov 1994 08:49:37 GMT",
+        "Wed, 31 Dec 1969 23:59:59 GMT",
+    ] {
+        assert_eq!(parse_http_date(date), None, "{}", date);
+    }
+}
+
+#[test]
+fn should_order_by_quality_when_parsing_quality_list() {
+    use crate::web::negotiation::{parse_quality_list, QualityItem};
+    assert_eq!(
+        parse_quality_list("text/html;level=1;q=0.5, */*;Q=0.1,application/json, bad;q=2, ,x;q=y"),
+        [
+            QualityItem {
+                value: "application/json",
+                quality: 1.0
+            },
+            QualityItem {
+                value: "text/html",
+                quality: 0.5
+            },
+            QualityItem {
+                value: "*/*",
+                quality: 0.1
+            },
+        ]
+    );
+    assert!(parse_quality_list("").is_empty());
+    let request = HttpRequest::from(
+        "GET / HTTP/1.1\r\nAccept-Language: fr;q=0.4, de\r\nAccept-Encoding: br\r\n\r\n",
+    );
+    let values = |items: Vec<crate::web::negotiation::QualityItem>| {
+        items
+            .iter()
+            .map(|item| item.value.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(values(request.accept_language()), ["de", "fr"]);
+    assert_eq!(values(request.accept_encoding()), ["br"]);
+    assert!(request.accept().is_empty());
+}
+
+#[test]
+fn should_prefer_offer_of_most_specific_match_when_negotiating() {
+    use crate::web::negotiation::Negotiable::{Encoding, Language, MediaType};
+    let html_json = ["text/html; charset=utf-8", "application/json"];
+    assert_eq!(
+        MediaType.preferred(Some("text/*;q=0.3, text/html;q=0.7, */*;q=0.5"), &html_json),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(
+        MediaType.preferred(Some("text/html;q=0, */*;q=0.5"), &html_json),
+        Some("application/json")
+    );
+    assert_eq!(
+        MediaType.preferred(Some("application/*, text/html"), &html_json),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(MediaType.preferred(Some("image/*"), &html_json), None);
+    let languages = ["en-US", "en-GB", "de"];
+    assert_eq!(
+        Language.preferred(Some("EN-gb, en;q=0.9"), &languages),
+        Some("en-GB")
+    );
+    assert_eq!(Language.preferred(Some("en"), &languages), Some("en-US"));
+    assert_eq!(Language.preferred(Some("e, fr"), &languages), None);
+    assert_eq!(
+        Language.preferred(Some("fr, *;q=0.1"), &languages),
+        Some("en-US")
+    );
+    let encodings = ["br", "gzip", "identity"];
+    assert_eq!(
+        Encoding.preferred(Some("gzip, br;q=0.9"), &encodings),
+        Some("gzip")
+    );
+    assert_eq!(
+        Encoding.preferred(Some("deflate"), &encodings),
+        Some("identity")
+    );
+    assert_eq!(Encoding.preferred(Some(""), &encodings), Some("identity"));
+    assert_eq!(Encoding.preferred(Some("*;q=0"), &encodings), None);
+    assert_eq!(Encoding.preferred(None, &encodings), Some("br"));
+}
+
+#[test]
+fn should_add_negotiated_header_to_vary_when_negotiating() {
+    use crate::web::negotiation::Negotiable;
+    let request = HttpRequest::from("GET / HTTP/1.1\r\nAccept-Language: de\r\n\r\n");
+    let response = request.negotiate(Negotiable::Language, &["en", "de"], |language| {
+        let mut response = HttpResponse::text(language);
+        response.headers.insert("vary".into(), "Origin".into());
+        response
+    });
+    assert_eq!(response.body, Body::from("de"));
+    assert_eq!(response.headers["Vary"], "Origin, Accept-Language");
+    let response = request.negotiate(Negotiable::Language, &["en"], HttpResponse::text);
+    assert_eq!(response.status_code, StatusCode::NotAcceptable);
+    assert_eq!(response.headers["Vary"], "Accept-Language");
+    let response = request.negotiate(Negotiable::MediaType, &["text/plain"], |_| {
+        let mut response = HttpResponse::text("plain");
+        response.headers.insert("Vary".into(), "accept".into());
+        response
+    });
+    assert_eq!(response.headers["Vary"], "accept");
+}
+
+#[test]
+fn should_parse_typed_headers_when_present() {
+    let request = HttpRequest::from(
+        "POST / HTTP/1.1\r\nhost:  example.com \r\ncontent-type: TEXT/HTML;charset=utf-8\r\n\
+        Content-Length: 2\r\n\r\nhi",
+    );
+    assert_eq!(request.content_type(), Some(Mime::TextHtml));
+    assert_eq!(request.content_length(), Some(2));
+    assert_eq!(request.host(), Some("example.com"));
+    assert_eq!(request.authorization(), None);
+    let mut request = HttpRequest::from(
+        "GET http://user@proxied.org:81?q HTTP/1.1\r\nContent-Type: x/unknown\r\n\r\n",
+    );
+    request
+        .headers
+        .get_or_insert_with(HashMap::new)
+        .insert("Content-Length".into(), "+2".into());
+    assert_eq!(request.content_type(), None);
+    assert_eq!(request.content_length(), None);
+    assert_eq!(request.host(), Some("proxied.org:81"));
+    assert_eq!(HttpRequest::from("GET / HTTP/1.1\r\n\r\n").host(), None);
+}
+
+#[test]
+fn should_parse_authorization_when_of_any_scheme() {
+    assert_eq!(
+        Authorization::parse("bearer  abc.def "),
+        Some(Authorization::Bearer("abc.def"))
+    );
+    assert_eq!(Authorization::parse("Bearer"), None);
+    assert_eq!(
+        Authorization::parse("Basic dXNlcjpwYTpzcw=="),
+        Some(Authorization::Basic {
+            user: "user".into(),
+            password: "pa:ss".into(),
+        })
+    );
+    assert_eq!(Authorization::parse("Basic dXNlcg=="), None);
+    assert_eq!(Authorization::parse("Basic !!"), None);
+    let digest = Authorization::parse("Digest username=\"user\", nonce=\"n\"");
+    assert_eq!(
+        digest,
+        Some(Authorization::Other {
+            scheme: "Digest",
+            credentials: "username=\"user\", nonce=\"n\"",
+        })
+    );
+    assert_eq!(
+        digest.unwrap().to_string(),
+        "Digest username=\"user\", nonce=\"n\""
+    );
+    assert_eq!(Authorization::Bearer("t").to_string(), "Bearer t");
+    assert_eq!(Authorization::parse(""), None);
+}
+
+#[test]
+fn should_replace_header_in_any_case_when_set_on_response() {
+    use std::time::UNIX_EPOCH;
+    let mut response = HttpResponse::ok();
+    response.headers.insert("location".into(), "/old".into());
+    response.headers.insert("etag".into(), "\"old\"".into());
+    response.set_location("/new");
+    response.set_etag("abc", false);
+    response.set_expires(UNIX_EPOCH);
+    assert_eq!(response.headers.len(), 3);
+    assert_eq!(response.headers["Location"], "/new");
+    assert_eq!(response.header("ETAG"), Some("\"abc\""));
+    assert_eq!(response.headers["Expires"], "Thu, 01 Jan 1970 00:00:00 GMT");
+}
+
+#[test]
+#[should_panic]
+fn should_panic_when_etag_has_quote() {
+    HttpResponse::ok().set_etag("a\"b", false);
 }
@@ -0,0 +1,127 @@
+//! The target of a request, split into its path, query and fragment.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use super::QueryParams;
+
+/// The uri of a request as it was received, with its path, query and fragment
+/// available separately. Nothing is decoded, see [`encoding`] for that.
+///
+/// # Examples:
+/// ```
+/// use martian::web::Uri;
+/// let uri = Uri::from("/search?q=rust#results");
+/// assert_eq!(uri.path(), "/search");
+/// assert_eq!(uri.query(), Some("q=rust"));
+/// assert_eq!(uri.fragment(), Some("results"));
+/// assert_eq!(uri.as_str(), "/search?q=rust#results");
+/// ```
+///
+/// [`encoding`]: ./encoding/index.html
+#[derive(Clone, Default)]
+pub struct Uri {
+    raw: String,
+    path_end: usize,
+    query_end: usize,
+    /// The query decoded, once it is first asked for.
+    params: OnceLock<QueryParams>,
+}
+
+impl Uri {
+    /// The uri in full, exactly as received.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Everything before the query or fragment.
+    pub fn path(&self) -> &str {
+        &self.raw[..self.path_end]
+    }
+
+    /// Everything between the `?` and the fragment, if there is a `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.raw
+            .get(self.path_end..self.query_end)?
+            .strip_prefix('?')
+    }
+
+    /// Everything after the `#`, if there is one. Clients do not usually
+    /// send it.
+    pub fn fragment(&self) -> Option<&str> {
+        self.raw.get(self.query_end..)?.strip_prefix('#')
+    }
+
+    /// The query decoded, parsed only the first time.
+    pub(super) fn params(&self) -> &QueryParams {
+        self.params.get_or_init(|| QueryParams::parse(&self.raw))
+    }
+}
+
+impl From<String> for Uri {
+    fn from(raw: String) -> Uri {
+        let query_end = raw.find('#').unwrap_or(raw.len());
+        let path_end = raw[..query_end].find('?').unwrap_or(query_end);
+        Uri {
+            raw,
+            path_end,
+            query_end,
+            params: OnceLock::new(),
+        }
+    }
+}
+
+impl From<&str> for Uri {
+    fn from(raw: &str) -> Uri {
+        Uri::from(raw.to_string())
+    }
+}
+
+impl PartialEq for Uri {
+    fn eq(&self, other: &Uri) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl Eq for Uri {}
+
+impl Hash for Uri {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.raw.hash(state);
+    }
+}
+
+impl PartialEq<str> for Uri {
+    fn eq(&self, other: &str) -> bool {
+        self.raw == other
+    }
+}
+
+impl PartialEq<&str> for Uri {
+    fn eq(&self, other: &&str) -> bool {
+        self.raw == *other
+    }
+}
+
+impl AsRef<str> for Uri {
+    fn as_ref(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl fmt::Debug for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Uri")
+            .field("raw", &self.raw)
+            .field("path_end", &self.path_end)
+            .field("query_end", &self.query_end)
+            .finish()
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
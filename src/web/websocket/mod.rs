@@ -0,0 +1,427 @@
+//! WebSockets, see RFC 6455. A request asks for one through its `Upgrade`
+//! header and is answered with a 101, after which both sides exchange
+//! messages over the same connection, each sent in one or more frames.
+//! More documentation
+//! [here](https://developer.mozilla.org/en-US/docs/Web/API/WebSockets_API).
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use super::encoding::base64_encode;
+use super::{HttpMethod, HttpRequest, HttpResponse, HttpVersion, MartianError, StatusCode};
+
+/// Appended to the key of a handshake, proving the server understood it.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// The `Sec-WebSocket-Accept` answering the `Sec-WebSocket-Key` of a
+/// handshake.
+///
+/// # Examples:
+/// ```
+/// use martian::web::websocket::accept_key;
+/// assert_eq!(
+///     accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+///     "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+/// );
+/// ```
+pub fn accept_key(key: &str) -> String {
+    base64_encode(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// The 101 accepting the handshake of the request, see
+/// [`HttpRequest::upgrade_websocket`].
+///
+/// [`HttpRequest::upgrade_websocket`]: ../struct.HttpRequest.html#method.upgrade_websocket
+pub(super) fn accept<F>(request: &HttpRequest, on_upgrade: F) -> Result<HttpResponse, MartianError>
+where
+    F: FnOnce(WebSocket<'_>) + Send + 'static,
+{
+    let has_token = |header: &str, token: &str| {
+        request.header(header).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|listed| listed.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    if request.http_method != HttpMethod::Get
+        || request.http_version != HttpVersion::Http1_1
+        || !has_token("Upgrade", "websocket")
+        || !has_token("Connection", "Upgrade")
+    {
+        return Err(MartianError::bad_request("not a WebSocket handshake"));
+    }
+    let key = request
+        .header("Sec-WebSocket-Key")
+        .ok_or_else(|| MartianError::bad_request("missing Sec-WebSocket-Key"))?;
+    if request.header("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        return Err(MartianError::new(
+            StatusCode::UpgradeRequired,
+            "only Sec-WebSocket-Version 13 is supported",
+        ));
+    }
+    let mut response = HttpResponse::new(StatusCode::SwitchingProtocols);
+    let headers = &mut response.headers;
+    headers.insert("Upgrade".into(), "websocket".into());
+    headers.insert("Connection".into(), "Upgrade".into());
+    headers.insert("Sec-WebSocket-Accept".into(), accept_key(key));
+    response.body = super::Body::Upgrade(Upgrade(Box::new(on_upgrade)));
+    Ok(response)
+}
+
+/// What the connection is handed over to once the 101 of
+/// [`HttpRequest::upgrade_websocket`] is written, carried as the
+/// [`Body::Upgrade`] of the response.
+///
+/// [`HttpRequest::upgrade_websocket`]: ../struct.HttpRequest.html#method.upgrade_websocket
+/// [`Body::Upgrade`]: ../enum.Body.html#variant.Upgrade
+pub struct Upgrade(Box<dyn FnOnce(WebSocket<'_>) + Send>);
+
+impl Upgrade {
+    pub(crate) fn run(self, websocket: WebSocket<'_>) {
+        (self.0)(websocket)
+    }
+}
+
+impl fmt::Debug for Upgrade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Upgrade(..)")
+    }
+}
+
+/// A message either side of a [`WebSocket`] sends. A `Close` carries the
+/// status code and reason it was closed with, when given.
+///
+/// [`WebSocket`]: ./struct.WebSocket.html
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close(Option<(u16, String)>),
+}
+
+/// Anything a [`WebSocket`] can be spoken over.
+///
+/// [`WebSocket`]: ./struct.WebSocket.html
+trait Stream: Read + Write {}
+
+impl<S: Read + Write> Stream for S {}
+
+/// The server side of a WebSocket connection, sending and receiving whole
+/// [`Message`]s. Pings are answered with a pong, and a close with a close,
+/// as they are received.
+///
+/// # Examples:
+/// ```
+/// use martian::web::websocket::{Message, WebSocket};
+/// use std::io::Cursor;
+/// // "Hello" in a single masked frame, as a client sends it.
+/// let frame = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+/// let mut connection = Cursor::new(frame.to_vec());
+/// let mut websocket = WebSocket::new(&mut connection);
+/// assert_eq!(websocket.receive().unwrap(), Message::Text("Hello".into()));
+/// ```
+///
+/// [`Message`]: ./enum.Message.html
+pub struct WebSocket<'a> {
+    stream: Box<dyn Stream + 'a>,
+    /// Kept for setting the timeout of reads, when the `Server` hands over
+    /// the connection.
+    socket: Option<&'a TcpStream>,
+    max_message_size: usize,
+    /// The opcode and payload of a message split across frames, while the
+    /// rest of it is still coming in.
+    fragmented: Option<(u8, Vec<u8>)>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl<'a> WebSocket<'a> {
+    /// Speaks over a connection which has already been upgraded, expecting
+    /// the frames read off of it to be masked as a client masks them.
+    pub fn new<S: Read + Write + 'a>(stream: S) -> WebSocket<'a> {
+        WebSocket {
+            stream: Box::new(stream),
+            socket: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            fragmented: None,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    pub(crate) fn with_socket(mut self, socket: Option<&'a TcpStream>) -> WebSocket<'a> {
+        self.socket = socket;
+        self
+    }
+
+    /// Limits the size of a single message, 16 MiB unless set. A larger one
+    /// closes the connection with a 1009.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// How long [`receive`] waits for the client, forever unless set. Has
+    /// no effect on a `WebSocket` made through [`new`].
+    ///
+    /// # Returns:
+    /// An `Err` if the timeout is zero.
+    ///
+    /// [`receive`]: ./struct.WebSocket.html#method.receive
+    /// [`new`]: ./struct.WebSocket.html#method.new
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self.socket {
+            Some(socket) => socket.set_read_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the next message, waiting for all of it. A frame breaking the
+    /// protocol, or text which is not UTF-8, closes the connection with the
+    /// status code fitting it.
+    ///
+    /// # Returns:
+    /// An `Err` once the connection is closed, or if it breaks.
+    pub fn receive(&mut self) -> io::Result<Message> {
+        if self.close_received {
+            return Err(io::Error::new(
+                ErrorKind::NotConnected,
+                "WebSocket is closed",
+            ));
+        }
+        loop {
+            let (fin, opcode, payload) = self.read_frame()?;
+            let message = match opcode {
+                OP_PING => {
+                    if !self.close_sent {
+                        self.send(Message::Pong(payload.clone()))?;
+                    }
+                    Message::Ping(payload)
+                }
+                OP_PONG => Message::Pong(payload),
+                OP_CLOSE => self.on_close(payload)?,
+                OP_TEXT | OP_BINARY if self.fragmented.is_none() => {
+                    self.fragmented = Some((opcode, payload));
+                    match fin {
+                        true => self.take_fragmented()?,
+                        false => continue,
+                    }
+                }
+                OP_CONTINUATION if self.fragmented.is_some() => {
+                    let (opcode, mut message) = self.fragmented.take().unwrap_or_default();
+                    if message.len() + payload.len() > self.max_message_size {
+                        return Err(self.fail(CLOSE_TOO_BIG, "message too big"));
+                    }
+                    message.extend_from_slice(&payload);
+                    self.fragmented = Some((opcode, message));
+                    match fin {
+                        true => self.take_fragmented()?,
+                        false => continue,
+                    }
+                }
+                _ => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "unexpected frame")),
+            };
+            return Ok(message);
+        }
+    }
+
+    /// Sends the message in a single frame. Once a `Close` is sent no other
+    /// message is, and the client is expected to close in turn.
+    ///
+    /// # Returns:
+    /// An `Err` if the payload of a ping, pong or close is over 125 bytes,
+    /// or if the connection breaks.
+    pub fn send(&mut self, message: Message) -> io::Result<()> {
+        if self.close_sent {
+            return Err(io::Error::new(
+                ErrorKind::NotConnected,
+                "WebSocket is closed",
+            ));
+        }
+        let (opcode, payload) = match message {
+            Message::Text(text) => (OP_TEXT, text.into_bytes()),
+            Message::Binary(bytes) => (OP_BINARY, bytes),
+            Message::Ping(bytes) => (OP_PING, bytes),
+            Message::Pong(bytes) => (OP_PONG, bytes),
+            Message::Close(None) => (OP_CLOSE, Vec::new()),
+            Message::Close(Some((code, reason))) => {
+                let mut payload = code.to_be_bytes().to_vec();
+                payload.extend_from_slice(reason.as_bytes());
+                (OP_CLOSE, payload)
+            }
+        };
+        if opcode >= OP_CLOSE && payload.len() > 125 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "control frame payload over 125 bytes",
+            ));
+        }
+        self.close_sent = opcode == OP_CLOSE;
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            len @ 0..=125 => frame.push(len as u8),
+            len @ 126..=0xffff => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&payload);
+        self.stream.write_all(&frame)?;
+        self.stream.flush()
+    }
+
+    /// The fin bit, opcode and unmasked payload of the next frame.
+    fn read_frame(&mut self) -> io::Result<(bool, u8, Vec<u8>)> {
+        let mut header = [0; 2];
+        self.stream.read_exact(&mut header)?;
+        let (fin, opcode) = (header[0] & 0x80 != 0, header[0] & 0x0f);
+        if header[0] & 0x70 != 0 {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "reserved bits set"));
+        }
+        if header[1] & 0x80 == 0 {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "frame not masked"));
+        }
+        let len = match header[1] & 0x7f {
+            126 => {
+                let mut len = [0; 2];
+                self.stream.read_exact(&mut len)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0; 8];
+                self.stream.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        if opcode >= OP_CLOSE && (!fin || len > 125) {
+            return Err(self.fail(CLOSE_PROTOCOL_ERROR, "malformed control frame"));
+        }
+        if len > self.max_message_size as u64 {
+            return Err(self.fail(CLOSE_TOO_BIG, "message too big"));
+        }
+        let mut mask = [0; 4];
+        self.stream.read_exact(&mut mask)?;
+        let mut payload = vec![0; len as usize];
+        self.stream.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Ok((fin, opcode, payload))
+    }
+
+    fn take_fragmented(&mut self) -> io::Result<Message> {
+        match self.fragmented.take() {
+            Some((OP_TEXT, payload)) => match String::from_utf8(payload) {
+                Ok(text) => Ok(Message::Text(text)),
+                Err(_) => Err(self.fail(CLOSE_INVALID_DATA, "text not UTF-8")),
+            },
+            Some((_, payload)) => Ok(Message::Binary(payload)),
+            None => Err(self.fail(CLOSE_PROTOCOL_ERROR, "no message started")),
+        }
+    }
+
+    /// Answers the close with one of the same status code, unless already
+    /// closed from this side.
+    fn on_close(&mut self, payload: Vec<u8>) -> io::Result<Message> {
+        let close = match payload[..] {
+            [] => None,
+            [a, b, ref reason @ ..] => match String::from_utf8(reason.to_vec()) {
+                Ok(reason) => Some((u16::from_be_bytes([a, b]), reason)),
+                Err(_) => return Err(self.fail(CLOSE_INVALID_DATA, "reason not UTF-8")),
+            },
+            [_] => return Err(self.fail(CLOSE_PROTOCOL_ERROR, "malformed close")),
+        };
+        self.close_received = true;
+        if !self.close_sent {
+            let code = close.as_ref().map(|(code, _)| (*code, String::new()));
+            self.send(Message::Close(code))?;
+        }
+        Ok(Message::Close(close))
+    }
+
+    /// Closes the connection with the status code, for breaking the
+    /// protocol, handing back the error to return.
+    fn fail(&mut self, code: u16, reason: &str) -> io::Error {
+        if !self.close_sent {
+            // The connection is given up on either way.
+            let _ = self.send(Message::Close(Some((code, reason.into()))));
+        }
+        self.close_received = true;
+        io::Error::new(ErrorKind::InvalidData, reason)
+    }
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests;
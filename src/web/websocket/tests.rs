@@ -0,0 +1,190 @@
+use crate::web::websocket::{accept_key, Message, WebSocket};
+use crate::web::{HttpRequest, StatusCode};
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+
+/// The client side of a connection, reading the frames it sent and
+/// collecting those written back.
+struct TestConnection {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl TestConnection {
+    fn of(frames: &[Vec<u8>]) -> TestConnection {
+        TestConnection {
+            input: Cursor::new(frames.concat()),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Read for TestConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for TestConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A frame as a client sends it, masked.
+fn masked(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![first_byte];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    frame
+}
+
+fn handshake(version: &str) -> HttpRequest {
+    HttpRequest::from(&format!(
+        "GET /chat HTTP/1.1\r\n\
+        Host: example.com\r\n\
+        Upgrade: websocket\r\n\
+        Connection: keep-alive, Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Version: {}\r\n\r\n",
+        version
+    ))
+}
+
+#[test]
+fn should_answer_key_of_rfc_6455_with_its_accept_key() {
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[test]
+fn should_write_handshake_response_when_request_asks_for_websocket() {
+    let response = handshake("13").upgrade_websocket(|_| {}).unwrap();
+    let raw_response = String::from_utf8(response.to_bytes().unwrap()).unwrap();
+    assert!(raw_response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(raw_response.contains("Upgrade: websocket\r\n"));
+    assert!(raw_response.contains("Connection: Upgrade\r\n"));
+    assert!(raw_response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    assert!(!raw_response.contains("Content-Length"));
+}
+
+#[test]
+fn should_have_an_error_result_when_request_is_not_a_handshake() {
+    let request = HttpRequest::from("GET /chat HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    let error = request.upgrade_websocket(|_| {}).unwrap_err();
+    assert_eq!(error.status_code, StatusCode::BadRequest);
+}
+
+#[test]
+fn should_have_an_error_result_of_426_when_version_is_not_13() {
+    let error = handshake("8").upgrade_websocket(|_| {}).unwrap_err();
+    assert_eq!(error.status_code, StatusCode::UpgradeRequired);
+}
+
+#[test]
+fn should_receive_text_and_binary_messages() {
+    let mut connection = TestConnection::of(&[masked(0x81, b"hello"), masked(0x82, &[0, 1, 2])]);
+    let mut websocket = WebSocket::new(&mut connection);
+    assert_eq!(websocket.receive().unwrap(), Message::Text("hello".into()));
+    assert_eq!(websocket.receive().unwrap(), Message::Binary(vec![0, 1, 2]));
+}
+
+#[test]
+fn should_join_fragments_and_answer_ping_in_between() {
+    let mut connection = TestConnection::of(&[
+        masked(0x01, b"hel"),
+        masked(0x89, b"are you there"),
+        masked(0x80, b"lo"),
+    ]);
+    let mut websocket = WebSocket::new(&mut connection);
+    assert_eq!(
+        websocket.receive().unwrap(),
+        Message::Ping(b"are you there".to_vec())
+    );
+    assert_eq!(websocket.receive().unwrap(), Message::Text("hello".into()));
+    drop(websocket);
+    assert_eq!(connection.output, b"\x8a\x0dare you there");
+}
+
+#[test]
+fn should_write_unmasked_frames_when_sending() {
+    let mut connection = TestConnection::of(&[]);
+    let mut websocket = WebSocket::new(&mut connection);
+    websocket.send(Message::Text("hi".into())).unwrap();
+    websocket.send(Message::Binary(vec![7; 200])).unwrap();
+    drop(websocket);
+    assert_eq!(connection.output[..4], *b"\x81\x02hi");
+    assert_eq!(connection.output[4..8], [0x82, 126, 0, 200]);
+    assert_eq!(connection.output.len(), 8 + 200);
+}
+
+#[test]
+fn should_answer_close_with_close_of_same_code() {
+    let mut close = 1000u16.to_be_bytes().to_vec();
+    close.extend_from_slice(b"bye");
+    let mut connection = TestConnection::of(&[masked(0x88, &close)]);
+    let mut websocket = WebSocket::new(&mut connection);
+    assert_eq!(
+        websocket.receive().unwrap(),
+        Message::Close(Some((1000, "bye".into())))
+    );
+    let error = websocket.receive().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotConnected);
+    drop(websocket);
+    assert_eq!(connection.output, [0x88, 0x02, 0x03, 0xe8]);
+}
+
+#[test]
+fn should_close_with_1002_when_frame_is_not_masked() {
+    let mut connection = TestConnection::of(&[b"\x81\x02hi".to_vec()]);
+    let mut websocket = WebSocket::new(&mut connection);
+    let error = websocket.receive().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    drop(websocket);
+    assert_eq!(connection.output[..4], [0x88, 18, 0x03, 0xea]);
+}
+
+#[test]
+fn should_close_with_1007_when_text_is_not_utf8() {
+    let mut connection = TestConnection::of(&[masked(0x81, &[0xff, 0xfe])]);
+    let mut websocket = WebSocket::new(&mut connection);
+    assert!(websocket.receive().is_err());
+    drop(websocket);
+    assert_eq!(connection.output[2..4], 1007u16.to_be_bytes());
+}
+
+#[test]
+fn should_close_with_1009_when_message_is_over_max_size() {
+    let mut connection = TestConnection::of(&[masked(0x01, b"1234"), masked(0x80, b"5678")]);
+    let mut websocket = WebSocket::new(&mut connection);
+    websocket.set_max_message_size(6);
+    assert!(websocket.receive().is_err());
+    drop(websocket);
+    assert_eq!(connection.output[2..4], 1009u16.to_be_bytes());
+}
+
+#[test]
+fn should_have_an_error_result_when_control_frame_is_over_125_bytes() {
+    let mut connection = TestConnection::of(&[]);
+    let mut websocket = WebSocket::new(&mut connection);
+    let error = websocket.send(Message::Ping(vec![0; 126])).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "macros")]
+
+use martian::server::{get, post, routes, Server};
+use martian::test::TestClient;
+use martian::web::{HttpRequest, StatusCode};
+
+#[get("/users/{id}", name = "user_detail")]
+fn user_detail(request: HttpRequest) -> String {
+    format!("user {}", request.path_param("id").unwrap_or_default())
+}
+
+#[post("/users")]
+fn create_user(_: HttpRequest) -> (StatusCode, &'static str) {
+    (StatusCode::Created, "created")
+}
+
+mod health {
+    use martian::server::get;
+    use martian::web::{HttpRequest, HttpResponse};
+
+    #[get("/health")]
+    pub fn check(_: HttpRequest) -> HttpResponse {
+        HttpResponse::ok()
+    }
+}
+
+#[test]
+fn should_route_to_annotated_handlers_when_bound_through_routes() {
+    let mut server = Server::default();
+    server
+        .route(routes![user_detail, create_user, health::check])
+        .unwrap();
+    assert_eq!(
+        server.url_for("user_detail", &[("id", "9")]),
+        Some("/users/9".into())
+    );
+    let client = TestClient::new(server);
+    client.get("/users/7").assert_body("user 7");
+    client.post("/users", "").assert_status(StatusCode::Created);
+    client.get("/health").assert_status(StatusCode::Ok);
+}
+
+#[test]
+fn should_keep_handler_callable_when_annotated() {
+    let response = health::check(HttpRequest::from("GET /health HTTP/1.1\r\n\r\n"));
+    assert_eq!(response.status_code, StatusCode::Ok);
+}
@@ -0,0 +1,69 @@
+use martian::client::HttpClient;
+use martian::server::{HttpServer, Route, Server};
+use martian::web::{HttpMethod, HttpRequest, HttpResponse, StatusCode};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+fn hello(_: HttpRequest) -> HttpResponse {
+    HttpResponse::text("hello")
+}
+
+fn echo(request: HttpRequest) -> HttpResponse {
+    HttpResponse::text(request.body_text().unwrap_or_default())
+}
+
+#[test]
+fn should_serve_requests_over_tcp_when_started() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    thread::spawn(move || {
+        let mut server = Server::default();
+        server
+            .route(|| Route::bind(HttpMethod::Get).to("/hello", hello))
+            .unwrap();
+        server
+            .route(|| Route::bind(HttpMethod::Post).to("/echo", echo))
+            .unwrap();
+        HttpServer::of_port(port, server).start().unwrap();
+    });
+    let client = HttpClient::default();
+    let base_url = format!("http://127.0.0.1:{}", port);
+    let response = (0..50)
+        .find_map(|_| {
+            thread::sleep(Duration::from_millis(10));
+            client.get(&format!("{}/hello", base_url)).ok()
+        })
+        .unwrap();
+    assert_eq!(response.status_code, StatusCode::Ok);
+    assert_eq!(response.body, "hello".into());
+    let response = client.post(&format!("{}/echo", base_url), "ping").unwrap();
+    assert_eq!(response.body, "ping".into());
+    let response = client.get(&format!("{}/missing", base_url)).unwrap();
+    assert_eq!(response.status_code, StatusCode::NotFound);
+}
+
+#[test]
+fn should_serve_every_bound_address_until_shut_down() {
+    let http_server = HttpServer::new(Server::default())
+        .bind("127.0.0.1:0")
+        .unwrap()
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let addrs = http_server.addrs();
+    assert_eq!(addrs.len(), 2);
+    assert!(addrs.iter().all(|addr| addr.port() != 0));
+    let shutdown = http_server.shutdown();
+    let started = thread::spawn(move || http_server.start());
+    let client = HttpClient::default();
+    for addr in &addrs {
+        let response = client.get(&format!("http://{}/", addr)).unwrap();
+        assert_eq!(response.status_code, StatusCode::NotFound);
+    }
+    shutdown.trigger();
+    started.join().unwrap().unwrap();
+    assert!(client.get(&format!("http://{}/", addrs[0])).is_err());
+}